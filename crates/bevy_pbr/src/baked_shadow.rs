@@ -0,0 +1,220 @@
+//! Loads the shadow maps of static point lights from images instead of
+//! rendering them every frame.
+
+use bevy_asset::Handle;
+use bevy_ecs::{
+    component::Component,
+    entity::{Entity, EntityHashSet},
+    query::QueryItem,
+    reflect::ReflectComponent,
+    system::{lifetimeless::Read, Commands, Local, Query, Res},
+    world::World,
+};
+use bevy_image::Image;
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+use bevy_render::{
+    render_asset::RenderAssets,
+    render_graph::{NodeRunError, RenderGraphContext, ViewNode},
+    render_resource::{
+        Extent3d, Origin3d, TexelCopyTextureInfo, Texture, TextureAspect, TextureFormat,
+        TextureUsages,
+    },
+    renderer::RenderContext,
+    texture::GpuImage,
+};
+use bevy_utils::once;
+use tracing::warn;
+
+use crate::{ExtractedPointLight, LightEntity, ShadowView, ViewLightEntities, ViewShadowBindings};
+
+/// Add this component to a [`PointLight`](crate::PointLight) whose shadows
+/// never change to load its shadow map from [`cubemap`](Self::cubemap)
+/// instead of rendering it every frame.
+///
+/// The light needs [`shadows_enabled`](crate::PointLight::shadows_enabled),
+/// but no shadow casters are gathered for it and its shadow map isn't
+/// rendered. The cube map is copied into the shadow map texture every frame
+/// instead, which is much cheaper. The light always uses cube map shadows,
+/// whatever its [`shadow_technique`](crate::PointLight::shadow_technique).
+///
+/// The cube map must be a depth image in the format of the shadow maps, which
+/// is `Depth32Float` unless a [`ShadowDepthFormat`](crate::ShadowDepthFormat)
+/// selects another, with six array layers and the
+/// [`TextureUsages::COPY_SRC`] usage, and faces the size of the
+/// [`PointLightShadowMap`](crate::PointLightShadowMap). Otherwise, a warning
+/// is logged and nothing is copied. The faces must be in the order and
+/// orientation that Bevy renders point light shadows in, and store depth in
+/// the same way: reverse Z, with an infinite perspective projection whose
+/// near plane is the light's
+/// [`shadow_map_near_z`](crate::PointLight::shadow_map_near_z). The simplest
+/// way to bake a cube map is to render the light once with an
+/// [`ExportShadowMap`](crate::ExportShadowMap) for each face and save the
+/// results as the layers of one image.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct BakedPointShadow {
+    /// The baked cube map of shadow depths.
+    pub cubemap: Handle<Image>,
+}
+
+/// The baked shadow maps that the [`BakedPointShadowNode`] copies for a view.
+#[derive(Component, Default)]
+pub struct ViewBakedPointShadows(Vec<BakedPointShadowCopy>);
+
+/// A copy of a baked cube map into the layers of the point light shadow map
+/// texture.
+struct BakedPointShadowCopy {
+    source: Texture,
+    target: Texture,
+    target_layer: u32,
+    size: Extent3d,
+}
+
+/// The render graph node that copies the cube maps of [`BakedPointShadow`]s
+/// into the point light shadow map texture.
+#[derive(Default)]
+pub struct BakedPointShadowNode;
+
+/// Finds the point lights with a [`BakedPointShadow`] and prepares the copies
+/// of their cube maps.
+///
+/// Point light shadow maps are shared between views, so each light is copied
+/// at most once, by the first view that has it.
+pub fn prepare_baked_point_shadows(
+    mut commands: Commands,
+    images: Res<RenderAssets<GpuImage>>,
+    views: Query<(Entity, &ViewLightEntities, &ViewShadowBindings)>,
+    view_lights: Query<(&LightEntity, &ShadowView)>,
+    point_lights: Query<&ExtractedPointLight>,
+    mut copied_lights: Local<EntityHashSet>,
+) {
+    copied_lights.clear();
+
+    for (view_entity, view_light_entities, shadow_bindings) in &views {
+        let mut view_copies = Vec::new();
+
+        for &view_light in &view_light_entities.lights {
+            // The first face of the cube map starts at the array layer of the first face view.
+            let Ok((
+                &LightEntity::Point {
+                    light_entity,
+                    face_index: 0,
+                },
+                shadow_view,
+            )) = view_lights.get(view_light)
+            else {
+                continue;
+            };
+            let Some(cubemap) = point_lights
+                .get(light_entity)
+                .ok()
+                .and_then(|light| light.baked_shadow_map)
+            else {
+                continue;
+            };
+            if !copied_lights.insert(light_entity) {
+                continue;
+            }
+            let Some(cubemap) = images.get(cubemap) else {
+                continue;
+            };
+
+            let target = &shadow_bindings.point_light_depth_texture;
+            let size = Extent3d {
+                width: target.width(),
+                height: target.height(),
+                depth_or_array_layers: 6,
+            };
+            if let Some(error) = baked_point_shadow_error(
+                cubemap.texture_format,
+                cubemap.texture.usage(),
+                cubemap.size,
+                target.format(),
+                size,
+            ) {
+                once!(warn!("Can't use a baked point light shadow map: {error}"));
+                continue;
+            }
+
+            view_copies.push(BakedPointShadowCopy {
+                source: cubemap.texture.clone(),
+                target: target.clone(),
+                target_layer: shadow_view.array_layer,
+                size,
+            });
+        }
+
+        if view_copies.is_empty() {
+            commands
+                .entity(view_entity)
+                .remove::<ViewBakedPointShadows>();
+        } else {
+            commands
+                .entity(view_entity)
+                .insert(ViewBakedPointShadows(view_copies));
+        }
+    }
+}
+
+/// Returns why an image with the given format, usage and size can't be used
+/// as a baked cube map for shadow maps with the format `shadow_map_format` and
+/// size `shadow_map_size`, or [`None`] if it can.
+fn baked_point_shadow_error(
+    format: TextureFormat,
+    usage: TextureUsages,
+    size: Extent3d,
+    shadow_map_format: TextureFormat,
+    shadow_map_size: Extent3d,
+) -> Option<String> {
+    if format != shadow_map_format {
+        Some(format!(
+            "the cube map has the format {format:?} instead of {shadow_map_format:?}"
+        ))
+    } else if !usage.contains(TextureUsages::COPY_SRC) {
+        Some("the cube map doesn't have the `COPY_SRC` usage".into())
+    } else if size != shadow_map_size {
+        Some(format!(
+            "the cube map is {}x{}x{} instead of {}x{}x{}",
+            size.width,
+            size.height,
+            size.depth_or_array_layers,
+            shadow_map_size.width,
+            shadow_map_size.height,
+            shadow_map_size.depth_or_array_layers
+        ))
+    } else {
+        None
+    }
+}
+
+impl ViewNode for BakedPointShadowNode {
+    type ViewQuery = Read<ViewBakedPointShadows>;
+
+    fn run<'w>(
+        &self,
+        _: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        baked_shadows: QueryItem<'w, '_, Self::ViewQuery>,
+        _: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let command_encoder = render_context.command_encoder();
+        for copy in &baked_shadows.0 {
+            command_encoder.copy_texture_to_texture(
+                copy.source.as_image_copy(),
+                TexelCopyTextureInfo {
+                    texture: &copy.target,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: copy.target_layer,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                copy.size,
+            );
+        }
+
+        Ok(())
+    }
+}
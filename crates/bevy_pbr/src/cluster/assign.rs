@@ -14,7 +14,7 @@ use bevy_render::{
     primitives::{Aabb, Frustum, HalfSpace, Sphere},
     render_resource::BufferBindingType,
     renderer::{RenderAdapter, RenderDevice},
-    view::{RenderLayers, ViewVisibility},
+    view::{InheritedVisibility, RenderLayers, ViewVisibility},
 };
 use bevy_transform::components::GlobalTransform;
 use bevy_utils::prelude::default;
@@ -23,10 +23,10 @@ use tracing::warn;
 use crate::{
     decal::{self, clustered::ClusteredDecal},
     prelude::EnvironmentMapLight,
-    ClusterConfig, ClusterFarZMode, Clusters, ExtractedPointLight, GlobalVisibleClusterableObjects,
-    LightProbe, PointLight, SpotLight, ViewClusterBindings, VisibleClusterableObjects,
-    VolumetricLight, CLUSTERED_FORWARD_STORAGE_BUFFER_COUNT,
-    MAX_UNIFORM_BUFFER_CLUSTERABLE_OBJECTS,
+    scaled_light_range, ClusterConfig, ClusterFarZMode, Clusters, ExtractedPointLight,
+    GlobalVisibleClusterableObjects, LightProbe, LocalAmbient, PointLight, ScaleAffectsRange,
+    SpotLight, ViewClusterBindings, VisibleClusterableObjects, VolumetricLight,
+    CLUSTERED_FORWARD_STORAGE_BUFFER_COUNT, MAX_UNIFORM_BUFFER_CLUSTERABLE_OBJECTS,
 };
 
 const NDC_MIN: Vec2 = Vec2::NEG_ONE;
@@ -97,6 +97,9 @@ pub(crate) enum ClusterableObjectType {
 
     /// Marks that the clusterable object is a decal.
     Decal,
+
+    /// Marks that the clusterable object is a local ambient volume.
+    LocalAmbient,
 }
 
 impl ClusterableObjectType {
@@ -121,6 +124,7 @@ impl ClusterableObjectType {
             ClusterableObjectType::ReflectionProbe => (2, false, false),
             ClusterableObjectType::IrradianceVolume => (3, false, false),
             ClusterableObjectType::Decal => (4, false, false),
+            ClusterableObjectType::LocalAmbient => (5, false, false),
         }
     }
 
@@ -129,8 +133,8 @@ impl ClusterableObjectType {
         point_light: &ExtractedPointLight,
     ) -> ClusterableObjectType {
         match point_light.spot_light_angles {
-            Some((_, outer_angle)) => ClusterableObjectType::SpotLight {
-                outer_angle,
+            Some((_, outer_angle_x, outer_angle_y)) => ClusterableObjectType::SpotLight {
+                outer_angle: outer_angle_x.max(outer_angle_y),
                 shadows_enabled: point_light.shadows_enabled,
                 volumetric: point_light.volumetric,
             },
@@ -163,6 +167,8 @@ pub(crate) fn assign_objects_to_clusters(
         Option<&RenderLayers>,
         Option<&VolumetricLight>,
         &ViewVisibility,
+        &InheritedVisibility,
+        Has<ScaleAffectsRange>,
     )>,
     spot_lights_query: Query<(
         Entity,
@@ -171,12 +177,15 @@ pub(crate) fn assign_objects_to_clusters(
         Option<&RenderLayers>,
         Option<&VolumetricLight>,
         &ViewVisibility,
+        &InheritedVisibility,
+        Has<ScaleAffectsRange>,
     )>,
     light_probes_query: Query<
         (Entity, &GlobalTransform, Has<EnvironmentMapLight>),
         With<LightProbe>,
     >,
     decals_query: Query<(Entity, &GlobalTransform), With<ClusteredDecal>>,
+    local_ambients_query: Query<(Entity, &LocalAmbient)>,
     mut clusterable_objects: Local<Vec<ClusterableObjectAssignmentData>>,
     mut cluster_aabb_spheres: Local<Vec<Option<Sphere>>>,
     mut max_clusterable_objects_warning_emitted: Local<bool>,
@@ -192,13 +201,28 @@ pub(crate) fn assign_objects_to_clusters(
     clusterable_objects.extend(
         point_lights_query
             .iter()
-            .filter(|(.., visibility)| visibility.get())
+            // Lights whose parent is hidden are hidden too.
+            .filter(|(.., visibility, inherited_visibility, _)| {
+                visibility.get() && inherited_visibility.get()
+            })
             .map(
-                |(entity, transform, point_light, maybe_layers, volumetric, _visibility)| {
+                |(
+                    entity,
+                    transform,
+                    point_light,
+                    maybe_layers,
+                    volumetric,
+                    ..,
+                    scale_affects_range,
+                )| {
                     ClusterableObjectAssignmentData {
                         entity,
                         transform: GlobalTransform::from_translation(transform.translation()),
-                        range: point_light.range,
+                        range: scaled_light_range(
+                            point_light.range,
+                            transform,
+                            scale_affects_range,
+                        ),
                         object_type: ClusterableObjectType::PointLight {
                             shadows_enabled: point_light.shadows_enabled,
                             volumetric: volumetric.is_some(),
@@ -211,15 +235,26 @@ pub(crate) fn assign_objects_to_clusters(
     clusterable_objects.extend(
         spot_lights_query
             .iter()
-            .filter(|(.., visibility)| visibility.get())
+            // Lights whose parent is hidden are hidden too.
+            .filter(|(.., visibility, inherited_visibility, _)| {
+                visibility.get() && inherited_visibility.get()
+            })
             .map(
-                |(entity, transform, spot_light, maybe_layers, volumetric, _visibility)| {
+                |(
+                    entity,
+                    transform,
+                    spot_light,
+                    maybe_layers,
+                    volumetric,
+                    ..,
+                    scale_affects_range,
+                )| {
                     ClusterableObjectAssignmentData {
                         entity,
                         transform: *transform,
-                        range: spot_light.range,
+                        range: scaled_light_range(spot_light.range, transform, scale_affects_range),
                         object_type: ClusterableObjectType::SpotLight {
-                            outer_angle: spot_light.outer_angle,
+                            outer_angle: spot_light.max_outer_angle(),
                             shadows_enabled: spot_light.shadows_enabled,
                             volumetric: volumetric.is_some(),
                         },
@@ -271,6 +306,20 @@ pub(crate) fn assign_objects_to_clusters(
         }));
     }
 
+    // Gather up local ambient volumes, which, like light probes, only fit in
+    // the cluster offsets and counts on platforms with storage buffers.
+    if supports_storage_buffers {
+        clusterable_objects.extend(local_ambients_query.iter().map(|(entity, local_ambient)| {
+            ClusterableObjectAssignmentData {
+                entity,
+                transform: GlobalTransform::from_translation(local_ambient.bounds.center.into()),
+                range: local_ambient.bounds.half_extents.length(),
+                object_type: ClusterableObjectType::LocalAmbient,
+                render_layers: RenderLayers::default(),
+            }
+        }));
+    }
+
     if clusterable_objects.len() > MAX_UNIFORM_BUFFER_CLUSTERABLE_OBJECTS
         && !supports_storage_buffers
     {
@@ -636,7 +685,8 @@ pub(crate) fn assign_objects_to_clusters(
                     }
                     ClusterableObjectType::PointLight { .. }
                     | ClusterableObjectType::ReflectionProbe
-                    | ClusterableObjectType::IrradianceVolume => None,
+                    | ClusterableObjectType::IrradianceVolume
+                    | ClusterableObjectType::LocalAmbient => None,
                 };
                 let clusterable_object_center_clip =
                     camera.clip_from_view() * view_clusterable_object_sphere.center.extend(1.0);
@@ -859,6 +909,20 @@ pub(crate) fn assign_objects_to_clusters(
                                     cluster_index += clusters.dimensions.z as usize;
                                 }
                             }
+
+                            ClusterableObjectType::LocalAmbient => {
+                                // Local ambient volumes affect all clusters in
+                                // the bounding sphere of their bounds.
+                                for _ in min_x..=max_x {
+                                    clusters.clusterable_objects[cluster_index]
+                                        .entities
+                                        .push(clusterable_object.entity);
+                                    clusters.clusterable_objects[cluster_index]
+                                        .counts
+                                        .local_ambients += 1;
+                                    cluster_index += clusters.dimensions.z as usize;
+                                }
+                            }
                         }
                     }
                 }
@@ -153,6 +153,7 @@ pub struct GlobalClusterableObjectMeta {
 pub struct GpuClusterableObject {
     // For point lights: the lower-right 2x2 values of the projection matrix [2][2] [2][3] [3][2] [3][3]
     // For spot lights: 2 components of the direction (x,z), spot_scale and spot_offset
+    // For local ambients: the half extents of the bounds
     pub(crate) light_custom_data: Vec4,
     pub(crate) color_inverse_square_range: Vec4,
     pub(crate) position_radius: Vec4,
@@ -160,10 +161,12 @@ pub struct GpuClusterableObject {
     pub(crate) shadow_depth_bias: f32,
     pub(crate) shadow_normal_bias: f32,
     pub(crate) spot_light_tan_angle: f32,
+    // Positive for the soft shadow size, or the negated world-space shadow blur radius
     pub(crate) soft_shadow_size: f32,
     pub(crate) shadow_map_near_z: f32,
     pub(crate) decal_index: u32,
-    pub(crate) pad: f32,
+    // The shadow tint, packed as RGBA8 in linear space
+    pub(crate) shadow_tint: u32,
 }
 
 pub enum GpuClusterableObjects {
@@ -193,8 +196,9 @@ pub struct ExtractedClusterConfig {
 
 /// Stores the number of each type of clusterable object in a single cluster.
 ///
-/// Note that `reflection_probes` and `irradiance_volumes` won't be clustered if
-/// fewer than 3 SSBOs are available, which usually means on WebGL 2.
+/// Note that `reflection_probes`, `irradiance_volumes`, and `local_ambients`
+/// won't be clustered if fewer than 3 SSBOs are available, which usually means
+/// on WebGL 2.
 #[derive(Clone, Copy, Default, Debug)]
 struct ClusterableObjectCounts {
     /// The number of point lights in the cluster.
@@ -207,6 +211,8 @@ struct ClusterableObjectCounts {
     irradiance_volumes: u32,
     /// The number of decals in the cluster.
     decals: u32,
+    /// The number of local ambient volumes in the cluster.
+    local_ambients: u32,
 }
 
 enum ExtractedClusterableObjectElement {
@@ -233,8 +239,9 @@ struct GpuClusterableObjectIndexListsStorage {
 #[derive(ShaderType, Default)]
 struct GpuClusterOffsetsAndCountsStorage {
     /// The starting offset, followed by the number of point lights, spot
-    /// lights, reflection probes, and irradiance volumes in each cluster, in
-    /// that order. The remaining fields are filled with zeroes.
+    /// lights, reflection probes, irradiance volumes, decals, and local
+    /// ambient volumes in each cluster, in that order. The remaining fields are
+    /// filled with zeroes.
     #[size(runtime)]
     data: Vec<[UVec4; 2]>,
 }
@@ -676,7 +683,12 @@ impl ViewClusterBindings {
                         counts.spot_lights,
                         counts.reflection_probes,
                     ),
-                    uvec4(counts.irradiance_volumes, counts.decals, 0, 0),
+                    uvec4(
+                        counts.irradiance_volumes,
+                        counts.decals,
+                        counts.local_ambients,
+                        0,
+                    ),
                 ]);
             }
         }
@@ -17,13 +17,13 @@
 use core::{num::NonZero, ops::Deref};
 
 use bevy_app::{App, Plugin};
-use bevy_asset::{AssetId, Handle};
+use bevy_asset::{AssetId, Assets, Handle};
 use bevy_derive::{Deref, DerefMut};
 use bevy_ecs::{
     component::Component,
     entity::{Entity, EntityHashMap},
     prelude::ReflectComponent,
-    query::With,
+    query::{Or, With},
     resource::Resource,
     schedule::IntoScheduleConfigs as _,
     system::{Query, Res, ResMut},
@@ -51,7 +51,7 @@ use bytemuck::{Pod, Zeroable};
 
 use crate::{
     binding_arrays_are_usable, prepare_lights, DirectionalLight, GlobalClusterableObjectMeta,
-    LightVisibilityClass, PointLight, SpotLight,
+    IesLightProfile, IesProfile, LightVisibilityClass, PointLight, SpotLight, StandardMaterial,
 };
 
 /// The maximum number of decals that can be present in a view.
@@ -147,15 +147,66 @@ pub struct PointLightTexture {
 /// Add to a [`SpotLight`] to add a light texture effect.
 /// A texture mask is applied to the light source to modulate its intensity,  
 /// simulating patterns like window shadows, gobo/cookie effects, or soft falloffs.
+///
+/// The texture is projected through the cone of the light: the whole image covers the square
+/// around the cone of the light's [`outer_angle`](SpotLight::outer_angle), with the top of the
+/// image toward the light's local +Y axis and its right edge toward +X. Nothing outside of the
+/// outer cone is lit, so only the circle inscribed in the image is visible. Elliptical spot
+/// lights that are wider along Y than along X sample beyond the top and bottom edges of the
+/// image.
+///
+/// Parts of the light beyond the edges of the image are clamped or repeated according to the
+/// address mode of the image's [`ImageSampler`](bevy_image::ImageSampler). All clustered decals
+/// and light textures share one sampler, so when several light textures are visible, they should
+/// all use the same sampler.
+///
+/// Like other light textures, this requires the `pbr_light_textures` feature and a platform that
+/// supports clustered decals.
 #[derive(Clone, Component, Debug, Reflect)]
 #[reflect(Component, Debug)]
 #[require(SpotLight)]
 pub struct SpotLightTexture {
-    /// The texture image. Only the R channel is read.
+    /// The texture image, also known as a cookie or gobo.
+    ///
+    /// The light is scaled by the R channel of the image, multiplied by its alpha channel, so
+    /// both grayscale masks and cutouts with transparency work.
     /// Note the border of the image should be entirely black to avoid leaking light.
     pub image: Handle<Image>,
 }
 
+/// Add to a [`SpotLight`] to project the textures of a material onto surfaces within its cone,
+/// like a slide projector.
+///
+/// Unlike a [`SpotLightTexture`], which only modulates the light's intensity, this changes the
+/// surface itself: the projected base color, normal map, and metallic/roughness textures replace
+/// the surface's own properties, weighted by the alpha of the base color and by the spot's cone
+/// falloff. Only the textures of the material are projected; properties without a texture are
+/// left unchanged.
+///
+/// Like light textures, this requires the `pbr_light_textures` feature and a platform that
+/// supports clustered decals. A spot light can't have both a [`SpotLightTexture`] and a
+/// [`SpotProjector`]. If both are present, the projector is used.
+#[derive(Clone, Component, Debug, Reflect)]
+#[reflect(Component, Debug)]
+#[require(SpotLight)]
+pub struct SpotProjector {
+    /// The material whose textures are projected.
+    pub material: Handle<StandardMaterial>,
+}
+
+/// The tag of the decals that [`SpotProjector`]s create, which the shader uses to tell them apart
+/// from [`SpotLightTexture`]s.
+pub const SPOT_PROJECTOR_DECAL_TAG: u32 = 1;
+
+/// The tag of the decals that [`IesLightProfile`]s create, which the shader uses to tell them apart
+/// from light textures.
+///
+/// This is distinct from every [`CubemapLayout`], which point light textures use as their tag.
+pub const IES_PROFILE_DECAL_TAG: u32 = 16;
+
+/// The value of a texture index in [`RenderClusteredDecal`] that has no texture.
+const NO_DECAL_TEXTURE: u32 = u32::MAX;
+
 /// Add to a [`DirectionalLight`] to add a light texture effect.
 /// A texture mask is applied to the light source to modulate its intensity,  
 /// simulating patterns like window shadows, gobo/cookie effects, or soft falloffs.
@@ -210,12 +261,37 @@ impl RenderClusteredDecals {
             local_from_world,
             image_index,
             tag,
-            pad_a: 0,
-            pad_b: 0,
+            normal_map_index: NO_DECAL_TEXTURE,
+            metallic_roughness_index: NO_DECAL_TEXTURE,
         });
         self.entity_to_decal_index.insert(entity, decal_index);
     }
 
+    /// Adds the decal for a [`SpotProjector`] on the spot light `entity`, projecting the given
+    /// textures.
+    pub fn insert_spot_projector(
+        &mut self,
+        entity: Entity,
+        base_color: Option<&AssetId<Image>>,
+        normal_map: Option<&AssetId<Image>>,
+        metallic_roughness: Option<&AssetId<Image>>,
+        local_from_world: Mat4,
+    ) {
+        let mut image_index = |image: Option<&AssetId<Image>>| {
+            image.map_or(NO_DECAL_TEXTURE, |image| self.get_or_insert_image(image))
+        };
+        let decal = RenderClusteredDecal {
+            local_from_world,
+            image_index: image_index(base_color),
+            tag: SPOT_PROJECTOR_DECAL_TAG,
+            normal_map_index: image_index(normal_map),
+            metallic_roughness_index: image_index(metallic_roughness),
+        };
+        let decal_index = self.decals.len();
+        self.decals.push(decal);
+        self.entity_to_decal_index.insert(entity, decal_index);
+    }
+
     pub fn get(&self, entity: Entity) -> Option<usize> {
         self.entity_to_decal_index.get(&entity).copied()
     }
@@ -250,7 +326,8 @@ impl Plugin for ClusteredDecalPlugin {
         load_shader_library!(app, "clustered.wgsl");
 
         app.add_plugins(ExtractComponentPlugin::<ClusteredDecal>::default())
-            .register_type::<ClusteredDecal>();
+            .register_type::<ClusteredDecal>()
+            .register_type::<SpotProjector>();
 
         let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
             return;
@@ -286,10 +363,12 @@ pub struct RenderClusteredDecal {
     image_index: u32,
     /// A custom tag available for application-defined purposes.
     tag: u32,
-    /// Padding.
-    pad_a: u32,
-    /// Padding.
-    pad_b: u32,
+    /// The index of the normal map texture in the binding array, used by
+    /// [`SpotProjector`]s.
+    normal_map_index: u32,
+    /// The index of the metallic/roughness texture in the binding array, used
+    /// by [`SpotProjector`]s.
+    metallic_roughness_index: u32,
 }
 
 /// Extracts decals from the main world into the render world.
@@ -326,6 +405,27 @@ pub fn extract_decals(
             &ViewVisibility,
         )>,
     >,
+    spot_projectors: Extract<
+        Query<(
+            RenderEntity,
+            &SpotProjector,
+            &GlobalTransform,
+            &ViewVisibility,
+        )>,
+    >,
+    ies_light_profiles: Extract<
+        Query<
+            (
+                RenderEntity,
+                &IesLightProfile,
+                &GlobalTransform,
+                &ViewVisibility,
+            ),
+            Or<(With<PointLight>, With<SpotLight>)>,
+        >,
+    >,
+    materials: Extract<Res<Assets<StandardMaterial>>>,
+    ies_profiles: Extract<Res<Assets<IesProfile>>>,
     mut render_decals: ResMut<RenderClusteredDecals>,
 ) {
     // Clear out the `RenderDecals` in preparation for a new frame.
@@ -387,6 +487,56 @@ pub fn extract_decals(
             if texture.tiled { 1 } else { 0 },
         );
     }
+
+    // IES profiles go after light textures so that they replace them on the same light.
+    for (decal_entity, ies_light_profile, global_transform, view_visibility) in &ies_light_profiles
+    {
+        // If the light is invisible, skip it.
+        if !view_visibility.get() {
+            continue;
+        }
+        let Some(profile) = ies_profiles.get(&ies_light_profile.profile) else {
+            continue;
+        };
+
+        render_decals.insert_decal(
+            decal_entity,
+            &profile.texture.id(),
+            global_transform.affine().inverse().into(),
+            IES_PROFILE_DECAL_TAG,
+        );
+    }
+
+    // Projectors go last so that they replace any `SpotLightTexture` on the same light.
+    for (decal_entity, projector, global_transform, view_visibility) in &spot_projectors {
+        // If the decal is invisible, skip it.
+        if !view_visibility.get() {
+            continue;
+        }
+        let Some(material) = materials.get(&projector.material) else {
+            continue;
+        };
+
+        render_decals.insert_spot_projector(
+            decal_entity,
+            material
+                .base_color_texture
+                .as_ref()
+                .map(Handle::id)
+                .as_ref(),
+            material
+                .normal_map_texture
+                .as_ref()
+                .map(Handle::id)
+                .as_ref(),
+            material
+                .metallic_roughness_texture
+                .as_ref()
+                .map(Handle::id)
+                .as_ref(),
+            global_transform.affine().inverse().into(),
+        );
+    }
 }
 
 /// Adds all decals in the scene to the [`GlobalClusterableObjectMeta`] table.
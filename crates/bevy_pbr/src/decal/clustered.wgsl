@@ -31,6 +31,8 @@
 #import bevy_pbr::clustered_forward
 #import bevy_pbr::clustered_forward::ClusterableObjectIndexRanges
 #import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_view_types::SPOT_PROJECTOR_DECAL_TAG
+#import bevy_pbr::pbr_types::PbrInput
 #import bevy_render::maths
 
 // An object that allows stepping through all clustered decals that affect a
@@ -77,7 +79,7 @@ fn clustered_decal_iterator_new(
         // We subtract 1 because the first thing `decal_iterator_next` does is
         // add 1.
         i32((*clusterable_object_index_ranges).first_decal_offset) - 1,
-        i32((*clusterable_object_index_ranges).last_clusterable_object_index_offset),
+        i32((*clusterable_object_index_ranges).first_local_ambient_offset),
         world_position,
     );
 }
@@ -181,3 +183,108 @@ fn apply_decal_base_color(
     return base_color;
 }
 
+// Modifies the surface properties in `pbr_input` to account for spot projectors,
+// which project the textures of a material within the cone of a spot light.
+//
+// The projected properties are weighted by the alpha of the projected base
+// color and by the cone falloff of the spot light.
+fn apply_spot_projectors(frag_coord: vec2<f32>, pbr_input: ptr<function, PbrInput>) {
+#ifdef LIGHT_TEXTURES
+    let world_position = (*pbr_input).world_position.xyz;
+    let view_z = get_view_z(world_position);
+    let is_orthographic = view_is_orthographic();
+
+    let cluster_index =
+        clustered_forward::fragment_cluster_index(frag_coord, view_z, is_orthographic);
+    let clusterable_object_index_ranges =
+        clustered_forward::unpack_clusterable_object_index_ranges(cluster_index);
+
+    for (var i: u32 = clusterable_object_index_ranges.first_spot_light_index_offset;
+            i < clusterable_object_index_ranges.first_reflection_probe_index_offset;
+            i = i + 1u) {
+        let light_id = clustered_forward::get_clusterable_object_id(i);
+        let light = &mesh_view_bindings::clusterable_objects.data[light_id];
+        let decal_index = (*light).decal_index;
+        if (decal_index == 0xFFFFFFFFu ||
+                mesh_view_bindings::clustered_decals.decals[decal_index].tag !=
+                SPOT_PROJECTOR_DECAL_TAG) {
+            continue;
+        }
+        let decal = &mesh_view_bindings::clustered_decals.decals[decal_index];
+
+        // Project the fragment with the spot light's perspective, as is done
+        // for spot light textures.
+        let local_position = ((*decal).local_from_world * vec4(world_position, 1.0)).xyz;
+        if (local_position.z >= 0.0 ||
+                dot(local_position, local_position) *
+                (*light).color_inverse_square_range.w >= 1.0) {
+            continue;
+        }
+        let uv = (local_position.xy / (local_position.z * (*light).spot_light_tan_angle)) *
+            vec2(-0.5, 0.5) + 0.5;
+        if (any(uv < vec2(0.0)) || any(uv > vec2(1.0))) {
+            continue;
+        }
+
+        // Reuse the cone falloff of the spot light.
+        let cos_angle = -local_position.z / length(local_position);
+        let cone = saturate(
+            cos_angle * (*light).light_custom_data.z + (*light).light_custom_data.w
+        );
+        var weight = cone * cone;
+
+        if ((*decal).image_index != -1) {
+            let base_color = textureSampleLevel(
+                mesh_view_bindings::clustered_decal_textures[(*decal).image_index],
+                mesh_view_bindings::clustered_decal_sampler,
+                uv,
+                0.0
+            );
+            weight *= base_color.a;
+            (*pbr_input).material.base_color = vec4(
+                mix((*pbr_input).material.base_color.rgb, base_color.rgb, weight),
+                (*pbr_input).material.base_color.a
+            );
+        }
+
+        if ((*decal).normal_map_index != 0xFFFFFFFFu) {
+            let tangent_normal = textureSampleLevel(
+                mesh_view_bindings::clustered_decal_textures[(*decal).normal_map_index],
+                mesh_view_bindings::clustered_decal_sampler,
+                uv,
+                0.0
+            ).rgb * 2.0 - 1.0;
+
+            // The projected image's X axis is the spot light's local X axis,
+            // which we project onto the surface to form a tangent frame.
+            let N = (*pbr_input).N;
+            let projector_x = normalize(vec3(
+                (*decal).local_from_world[0].x,
+                (*decal).local_from_world[1].x,
+                (*decal).local_from_world[2].x
+            ));
+            let T = normalize(projector_x - N * dot(projector_x, N));
+            let B = cross(N, T);
+            let projected_N = normalize(
+                tangent_normal.x * T + tangent_normal.y * B + tangent_normal.z * N
+            );
+            (*pbr_input).N = normalize(mix(N, projected_N, weight));
+        }
+
+        if ((*decal).metallic_roughness_index != 0xFFFFFFFFu) {
+            let metallic_roughness = textureSampleLevel(
+                mesh_view_bindings::clustered_decal_textures[(*decal).metallic_roughness_index],
+                mesh_view_bindings::clustered_decal_sampler,
+                uv,
+                0.0
+            );
+            // Roughness is stored in the green channel and metallic in the
+            // blue channel, as in glTF.
+            (*pbr_input).material.perceptual_roughness =
+                mix((*pbr_input).material.perceptual_roughness, metallic_roughness.g, weight);
+            (*pbr_input).material.metallic =
+                mix((*pbr_input).material.metallic, metallic_roughness.b, weight);
+        }
+    }
+#endif  // LIGHT_TEXTURES
+}
@@ -338,6 +338,8 @@ impl SpecializedRenderPipeline for DeferredLightingLayout {
             shader_defs.push("SHADOW_FILTER_METHOD_GAUSSIAN".into());
         } else if shadow_filter_method == MeshPipelineKey::SHADOW_FILTER_METHOD_TEMPORAL {
             shader_defs.push("SHADOW_FILTER_METHOD_TEMPORAL".into());
+        } else if shadow_filter_method == MeshPipelineKey::SHADOW_FILTER_METHOD_VOGEL {
+            shader_defs.push("SHADOW_FILTER_METHOD_VOGEL".into());
         }
         if self.mesh_pipeline.binding_arrays_are_usable {
             shader_defs.push("MULTIPLE_LIGHT_PROBES_IN_ARRAY".into());
@@ -547,6 +549,9 @@ pub fn prepare_deferred_lighting_pipelines(
             ShadowFilteringMethod::Temporal => {
                 view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_TEMPORAL;
             }
+            ShadowFilteringMethod::Vogel { .. } => {
+                view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_VOGEL;
+            }
         }
 
         let pipeline_id =
@@ -1,7 +1,10 @@
 #define_import_path bevy_pbr::pbr_deferred_types
 
 #import bevy_pbr::{
-    mesh_types::MESH_FLAGS_SHADOW_RECEIVER_BIT,
+    mesh_types::{
+        MESH_FLAGS_NO_AMBIENT_LIGHT_BIT, MESH_FLAGS_RECEIVER_LAYERS_SHIFT,
+        MESH_FLAGS_SHADOW_RECEIVER_BIT,
+    },
     pbr_types::{STANDARD_MATERIAL_FLAGS_FOG_ENABLED_BIT, STANDARD_MATERIAL_FLAGS_UNLIT_BIT},
 }
 
@@ -9,10 +12,12 @@
 const DEFERRED_FLAGS_UNLIT_BIT: u32                 = 1u << 0u;
 const DEFERRED_FLAGS_FOG_ENABLED_BIT: u32           = 1u << 1u;
 const DEFERRED_MESH_FLAGS_SHADOW_RECEIVER_BIT: u32  = 1u << 2u;
+const DEFERRED_MESH_FLAGS_NO_AMBIENT_LIGHT_BIT: u32 = 1u << 3u;
 
 fn deferred_flags_from_mesh_material_flags(mesh_flags: u32, mat_flags: u32) -> u32 {
     var flags = 0u;
     flags |= u32((mesh_flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u) * DEFERRED_MESH_FLAGS_SHADOW_RECEIVER_BIT;
+    flags |= u32((mesh_flags & MESH_FLAGS_NO_AMBIENT_LIGHT_BIT) != 0u) * DEFERRED_MESH_FLAGS_NO_AMBIENT_LIGHT_BIT;
     flags |= u32((mat_flags & STANDARD_MATERIAL_FLAGS_FOG_ENABLED_BIT) != 0u) * DEFERRED_FLAGS_FOG_ENABLED_BIT;
     flags |= u32((mat_flags & STANDARD_MATERIAL_FLAGS_UNLIT_BIT) != 0u) * DEFERRED_FLAGS_UNLIT_BIT;
     return flags;
@@ -22,6 +27,10 @@ fn mesh_material_flags_from_deferred_flags(deferred_flags: u32) -> vec2<u32> {
     var mat_flags = 0u;
     var mesh_flags = 0u;
     mesh_flags |= u32((deferred_flags & DEFERRED_MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u) * MESH_FLAGS_SHADOW_RECEIVER_BIT;
+    mesh_flags |= u32((deferred_flags & DEFERRED_MESH_FLAGS_NO_AMBIENT_LIGHT_BIT) != 0u) * MESH_FLAGS_NO_AMBIENT_LIGHT_BIT;
+    // The render layers don't fit in the G-buffer, so deferred meshes receive shadows on every
+    // layer.
+    mesh_flags |= 0xffu << MESH_FLAGS_RECEIVER_LAYERS_SHIFT;
     mat_flags |= u32((deferred_flags & DEFERRED_FLAGS_FOG_ENABLED_BIT) != 0u) * STANDARD_MATERIAL_FLAGS_FOG_ENABLED_BIT;
     mat_flags |= u32((deferred_flags & DEFERRED_FLAGS_UNLIT_BIT) != 0u) * STANDARD_MATERIAL_FLAGS_UNLIT_BIT;
     return vec2(mesh_flags, mat_flags);
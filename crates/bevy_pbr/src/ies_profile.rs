@@ -0,0 +1,451 @@
+//! Loads IES photometric profiles, which shape the light of point and spot
+//! lights like the light distribution of a real luminaire.
+
+use core::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use bevy_asset::{io::Reader, Asset, AssetLoader, Handle, LoadContext, RenderAssetUsages};
+use bevy_ecs::{component::Component, reflect::ReflectComponent};
+use bevy_image::Image;
+use bevy_math::ops;
+use bevy_reflect::{std_traits::ReflectDefault, Reflect, TypePath};
+use bevy_render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use thiserror::Error;
+
+/// Add to a [`PointLight`](crate::PointLight) or [`SpotLight`](crate::SpotLight)
+/// to shape its light with an [`IesProfile`].
+///
+/// The profile is aimed along the light's forward direction: a vertical angle
+/// of 0° points along the light's local -Z axis, and 180° along +Z. Horizontal
+/// angles start at the light's local +X axis and turn toward +Y. Point lights
+/// can be aimed just like spot lights, for example with
+/// [`Transform::looking_at`](bevy_transform::components::Transform::looking_at).
+///
+/// The light's intensity is the intensity in the brightest direction of the
+/// profile, and every other direction is dimmed according to the profile. To
+/// keep the total light output of a light without a profile, divide its
+/// intensity by [`IesProfile::average_intensity`].
+///
+/// Like light textures, this requires the `pbr_light_textures` feature and a
+/// platform that supports clustered decals. Otherwise, the light isn't shaped.
+/// A light can't have both a light texture and an IES profile. If both are
+/// present, the profile is used, unless the light also has a
+/// [`SpotProjector`](crate::decal::clustered::SpotProjector), which replaces
+/// both.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct IesLightProfile {
+    /// The profile that shapes the light.
+    pub profile: Handle<IesProfile>,
+}
+
+/// The width of [`IesProfile`] textures, covering horizontal angles from 0° to
+/// 360°.
+const IES_TEXTURE_WIDTH: u32 = 128;
+
+/// The height of [`IesProfile`] textures, covering vertical angles from 0° to
+/// 180°.
+const IES_TEXTURE_HEIGHT: u32 = 64;
+
+/// The tolerance, in radians, for angles just outside of the angles measured
+/// by an [`IesProfile`].
+const ANGLE_EPSILON: f32 = 1e-4;
+
+/// The angular distribution of the light of a luminaire, from an IES LM-63
+/// photometric data file.
+///
+/// Load `.ies` files with the [`AssetServer`](bevy_asset::AssetServer), and add
+/// the profile to lights with an [`IesLightProfile`]. Only type C photometry,
+/// which is used by almost all architectural luminaires, is supported, and
+/// tilt data is ignored.
+///
+/// The candela values of the profile are normalized so that the brightest
+/// direction has an intensity of 1, and interpolated bilinearly between the
+/// measured angles. Profiles measured over a quadrant or half of the horizontal
+/// angles are mirrored to cover the rest, as LM-63 specifies.
+#[derive(Asset, TypePath, Clone, Debug)]
+pub struct IesProfile {
+    /// The measured vertical angles, in ascending order, in radians.
+    vertical_angles: Vec<f32>,
+    /// The measured horizontal angles, in ascending order, in radians.
+    horizontal_angles: Vec<f32>,
+    /// The normalized intensities, for each horizontal angle, at each vertical
+    /// angle.
+    intensities: Vec<f32>,
+    /// The texture of the intensities of the profile that the shader samples,
+    /// as made by [`IesProfile::to_image`].
+    ///
+    /// The [`IesProfileLoader`] sets this to the `texture` labeled asset of the
+    /// profile. Profiles made with [`IesProfile::from_lm63`] have no texture
+    /// until one is added to the [`Image`] assets and set here.
+    pub texture: Handle<Image>,
+}
+
+/// Possible errors that can be produced when parsing an [`IesProfile`].
+#[derive(Debug, Error, Clone, PartialEq)]
+pub enum IesProfileError {
+    /// The file has no `TILT=` line, which ends the header.
+    #[error("the IES file has no `TILT=` line")]
+    MissingTilt,
+    /// The file ends before all of the photometric data.
+    #[error("the IES file ends before all of the photometric data")]
+    UnexpectedEnd,
+    /// A value of the photometric data isn't a number.
+    #[error("the IES file has an invalid number `{0}`")]
+    InvalidNumber(String),
+    /// The photometry isn't type C.
+    #[error("the IES file uses photometric type {0}, but only type C (1) is supported")]
+    UnsupportedPhotometricType(u32),
+    /// The vertical or horizontal angles are missing, or aren't in ascending
+    /// order.
+    #[error("the IES file has no angles or its angles aren't in ascending order")]
+    InvalidAngles,
+}
+
+impl IesProfile {
+    /// Parses a profile from the text of an IES LM-63 photometric data file.
+    ///
+    /// The returned profile has no [`texture`](Self::texture).
+    pub fn from_lm63(text: &str) -> Result<Self, IesProfileError> {
+        let mut lines = text.lines();
+        // The header is made of keyword lines, up to the `TILT=` line.
+        let tilt = loop {
+            let line = lines.next().ok_or(IesProfileError::MissingTilt)?;
+            if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                break tilt.trim();
+            }
+        };
+
+        let mut numbers = lines
+            .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ','))
+            .filter(|token| !token.is_empty())
+            .map(|token| {
+                token
+                    .parse::<f32>()
+                    .map_err(|_| IesProfileError::InvalidNumber(token.into()))
+            });
+        let mut next = || {
+            numbers
+                .next()
+                .unwrap_or(Err(IesProfileError::UnexpectedEnd))
+        };
+
+        if tilt == "INCLUDE" {
+            // Skip the lamp-to-luminaire geometry, and the tilt angles and
+            // their multiplying factors.
+            next()?;
+            let tilt_angle_count = next()? as usize;
+            for _ in 0..2 * tilt_angle_count {
+                next()?;
+            }
+        }
+
+        // The number of lamps, lumens per lamp and candela multiplier only
+        // scale the candela values, which are normalized anyway.
+        for _ in 0..3 {
+            next()?;
+        }
+        let vertical_angle_count = next()? as usize;
+        let horizontal_angle_count = next()? as usize;
+        let photometric_type = next()?;
+        // Skip the units, the luminous opening's width, length and height, the
+        // ballast factor, a reserved value and the input watts.
+        for _ in 0..7 {
+            next()?;
+        }
+        if photometric_type != 1.0 {
+            return Err(IesProfileError::UnsupportedPhotometricType(
+                photometric_type as u32,
+            ));
+        }
+
+        let mut angles = |count| {
+            (0..count)
+                .map(|_| next().map(f32::to_radians))
+                .collect::<Result<Vec<_>, _>>()
+        };
+        let vertical_angles = angles(vertical_angle_count)?;
+        let horizontal_angles = angles(horizontal_angle_count)?;
+        let is_ascending =
+            |angles: &[f32]| !angles.is_empty() && angles.windows(2).all(|pair| pair[0] < pair[1]);
+        if !is_ascending(&vertical_angles) || !is_ascending(&horizontal_angles) {
+            return Err(IesProfileError::InvalidAngles);
+        }
+
+        let mut intensities = (0..vertical_angle_count.saturating_mul(horizontal_angle_count))
+            .map(|_| next())
+            .collect::<Result<Vec<_>, _>>()?;
+        let max_intensity = intensities.iter().copied().fold(0.0, f32::max);
+        if max_intensity > 0.0 {
+            for intensity in &mut intensities {
+                *intensity = intensity.max(0.0) / max_intensity;
+            }
+        }
+
+        Ok(Self {
+            vertical_angles,
+            horizontal_angles,
+            intensities,
+            texture: Handle::default(),
+        })
+    }
+
+    /// Returns the relative intensity of the profile, from 0 to 1, in the
+    /// direction with the given vertical and horizontal angles, in radians.
+    ///
+    /// Directions outside of the measured vertical angles have no light.
+    pub fn intensity(&self, vertical_angle: f32, horizontal_angle: f32) -> f32 {
+        let Some((v0, v1, vertical_t)) = angle_interpolation(&self.vertical_angles, vertical_angle)
+        else {
+            return 0.0;
+        };
+        let (h0, h1, horizontal_t) = self.horizontal_interpolation(horizontal_angle);
+
+        let vertical_angle_count = self.vertical_angles.len();
+        let at = |h: usize, v: usize| self.intensities[h * vertical_angle_count + v];
+        let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+        lerp(
+            lerp(at(h0, v0), at(h0, v1), vertical_t),
+            lerp(at(h1, v0), at(h1, v1), vertical_t),
+            horizontal_t,
+        )
+    }
+
+    /// Returns the average relative intensity of the profile over all
+    /// directions.
+    ///
+    /// This is the fraction of the light of a light without a profile that a
+    /// light with this profile emits, so dividing the light's intensity by it
+    /// keeps its total light output.
+    pub fn average_intensity(&self) -> f32 {
+        let row_height = PI / IES_TEXTURE_HEIGHT as f32;
+        let column_width = TAU / IES_TEXTURE_WIDTH as f32;
+        let mut total = 0.0;
+        for (vertical_angle, horizontal_angle) in texel_angles() {
+            // Each texel covers a solid angle proportional to the sine of its
+            // vertical angle.
+            total += self.intensity(vertical_angle, horizontal_angle)
+                * ops::sin(vertical_angle)
+                * row_height
+                * column_width;
+        }
+        total / (4.0 * PI)
+    }
+
+    /// Returns the texture of the intensities of the profile that the shader
+    /// samples.
+    ///
+    /// The horizontal angle goes from 0° to 360° across the width of the
+    /// texture, and the vertical angle from 0° to 180° down its height.
+    pub fn to_image(&self) -> Image {
+        let data = texel_angles()
+            .map(|(vertical_angle, horizontal_angle)| {
+                let intensity = self.intensity(vertical_angle, horizontal_angle);
+                (intensity.clamp(0.0, 1.0) * 255.0).round() as u8
+            })
+            .collect();
+        Image::new(
+            Extent3d {
+                width: IES_TEXTURE_WIDTH,
+                height: IES_TEXTURE_HEIGHT,
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            data,
+            TextureFormat::R8Unorm,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+    }
+
+    /// Returns the indices of the measured horizontal angles around
+    /// `horizontal_angle`, and the position of the angle between them.
+    ///
+    /// Horizontal angles outside of the measured quadrant or half are mirrored
+    /// into it, and full measurements wrap around from the last angle to the
+    /// first.
+    fn horizontal_interpolation(&self, horizontal_angle: f32) -> (usize, usize, f32) {
+        let angles = &self.horizontal_angles;
+        let last_index = angles.len() - 1;
+        let (first, last) = (angles[0], angles[last_index]);
+        let angle = horizontal_angle.rem_euclid(TAU);
+        let half_angle = if angle > PI { TAU - angle } else { angle };
+        let angle = if last <= ANGLE_EPSILON {
+            // The profile is rotationally symmetric.
+            return (0, 0, 0.0);
+        } else if last <= FRAC_PI_2 + ANGLE_EPSILON {
+            // The profile is symmetric in each quadrant.
+            if half_angle > FRAC_PI_2 {
+                PI - half_angle
+            } else {
+                half_angle
+            }
+        } else if last <= PI + ANGLE_EPSILON {
+            // The profile is symmetric about the 0°-180° plane.
+            half_angle
+        } else {
+            angle
+        };
+
+        angle_interpolation(angles, angle).unwrap_or_else(|| {
+            // Wrap around between the last and first angles.
+            let wrapped_angle = if angle < first { angle + TAU } else { angle };
+            let t = (wrapped_angle - last) / (first + TAU - last);
+            (last_index, 0, t.clamp(0.0, 1.0))
+        })
+    }
+}
+
+/// Returns the indices of the angles around `angle` in the ascending `angles`,
+/// and the position of `angle` between them, or [`None`] if `angle` is outside
+/// of `angles`.
+fn angle_interpolation(angles: &[f32], angle: f32) -> Option<(usize, usize, f32)> {
+    let (&first, &last) = (angles.first()?, angles.last()?);
+    if angle < first - ANGLE_EPSILON || angle > last + ANGLE_EPSILON {
+        return None;
+    }
+    if angles.len() == 1 {
+        return Some((0, 0, 0.0));
+    }
+    let upper = angles
+        .partition_point(|&measured_angle| measured_angle < angle)
+        .clamp(1, angles.len() - 1);
+    let lower = upper - 1;
+    let t = (angle - angles[lower]) / (angles[upper] - angles[lower]);
+    Some((lower, upper, t.clamp(0.0, 1.0)))
+}
+
+/// Returns the vertical and horizontal angles at the centers of the texels of
+/// [`IesProfile`] textures, row by row.
+fn texel_angles() -> impl Iterator<Item = (f32, f32)> {
+    (0..IES_TEXTURE_HEIGHT).flat_map(|row| {
+        let vertical_angle = (row as f32 + 0.5) / IES_TEXTURE_HEIGHT as f32 * PI;
+        (0..IES_TEXTURE_WIDTH).map(move |column| {
+            let horizontal_angle = (column as f32 + 0.5) / IES_TEXTURE_WIDTH as f32 * TAU;
+            (vertical_angle, horizontal_angle)
+        })
+    })
+}
+
+/// An [`AssetLoader`] for [`IesProfile`]s, from IES LM-63 photometric data
+/// files with the `.ies` extension.
+///
+/// The texture of each profile is added as its `texture` labeled asset.
+#[derive(Default)]
+pub struct IesProfileLoader;
+
+/// Possible errors that can be produced by [`IesProfileLoader`].
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum IesProfileLoaderError {
+    /// An [IO](std::io) Error.
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    /// The file isn't a valid IES profile.
+    #[error(transparent)]
+    Parse(#[from] IesProfileError),
+}
+
+impl AssetLoader for IesProfileLoader {
+    type Asset = IesProfile;
+    type Settings = ();
+    type Error = IesProfileLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<IesProfile, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        // IES files are meant to be ASCII, but the keywords of some files use
+        // other encodings, which don't matter for the photometric data.
+        let mut profile = IesProfile::from_lm63(&String::from_utf8_lossy(&bytes))?;
+        profile.texture = load_context.add_labeled_asset("texture".into(), profile.to_image());
+        Ok(profile)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ies"]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A downlight measured over one quadrant, with horizontal angles of 0° and
+    /// 90° and vertical angles of 0°, 45° and 90°.
+    const QUADRANT_PROFILE: &str = "IESNA:LM-63-2002
+[TEST] quadrant
+[MANUFAC] Bevy
+TILT=NONE
+1 1000 1.0 3 2 1 2 0.0 0.0 0.0
+1.0 1.0 10
+0.0 45.0 90.0
+0.0 90.0
+200.0 100.0 0.0
+200.0 50.0 0.0
+";
+
+    #[test]
+    fn ies_profiles_interpolate_angle_grid() {
+        let profile = IesProfile::from_lm63(QUADRANT_PROFILE).unwrap();
+        let close = |a: f32, b: f32| (a - b).abs() < 1e-4;
+
+        // The candela values are normalized to the brightest direction.
+        assert!(close(profile.intensity(0.0, 0.0), 1.0));
+        assert!(close(profile.intensity(45f32.to_radians(), 0.0), 0.5));
+        assert!(close(
+            profile.intensity(45f32.to_radians(), FRAC_PI_2),
+            0.25
+        ));
+        // Both angles are interpolated.
+        assert!(close(
+            profile.intensity(22.5f32.to_radians(), 45f32.to_radians()),
+            (0.75 + 0.625) / 2.0
+        ));
+        // The quadrant is mirrored into the rest of the horizontal angles.
+        assert!(close(
+            profile.intensity(45f32.to_radians(), 180f32.to_radians()),
+            0.5
+        ));
+        assert!(close(
+            profile.intensity(45f32.to_radians(), 270f32.to_radians()),
+            0.25
+        ));
+        // There's no light above the measured vertical angles.
+        assert_eq!(profile.intensity(135f32.to_radians(), 0.0), 0.0);
+
+        let average = profile.average_intensity();
+        assert!(average > 0.0 && average < 0.5);
+
+        let image = profile.to_image();
+        assert_eq!(image.texture_descriptor.format, TextureFormat::R8Unorm);
+        // The top row is nearly straight down, and the bottom row straight up.
+        let data = image.data.as_ref().unwrap();
+        assert!(data[0] > 240);
+        assert_eq!(data[data.len() - 1], 0);
+    }
+
+    #[test]
+    fn invalid_ies_profiles_are_rejected() {
+        assert_eq!(
+            IesProfile::from_lm63("IESNA:LM-63-2002\n1 2 3").unwrap_err(),
+            IesProfileError::MissingTilt
+        );
+        assert_eq!(
+            IesProfile::from_lm63(&QUADRANT_PROFILE.replace("200.0 50.0 0.0", "200.0"))
+                .unwrap_err(),
+            IesProfileError::UnexpectedEnd
+        );
+        assert_eq!(
+            IesProfile::from_lm63(&QUADRANT_PROFILE.replace("0.0 45.0 90.0", "0.0 90.0 45.0"))
+                .unwrap_err(),
+            IesProfileError::InvalidAngles
+        );
+        assert_eq!(
+            IesProfile::from_lm63(&QUADRANT_PROFILE.replace("3 2 1 2", "3 2 2 2")).unwrap_err(),
+            IesProfileError::UnsupportedPhotometricType(2)
+        );
+    }
+}
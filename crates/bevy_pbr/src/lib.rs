@@ -25,13 +25,16 @@ pub mod experimental {
 }
 
 mod atmosphere;
+mod baked_shadow;
 mod cluster;
 mod components;
 pub mod decal;
 pub mod deferred;
 mod extended_material;
 mod fog;
+mod ies_profile;
 mod light;
+mod light_influence_volumes;
 mod light_probe;
 mod lightmap;
 mod material;
@@ -41,6 +44,8 @@ mod parallax;
 mod pbr_material;
 mod prepass;
 mod render;
+mod shadow_debug;
+mod shadow_export;
 mod ssao;
 mod ssr;
 mod volumetric_fog;
@@ -48,12 +53,15 @@ mod volumetric_fog;
 use bevy_color::{Color, LinearRgba};
 
 pub use atmosphere::*;
+pub use baked_shadow::*;
 pub use cluster::*;
 pub use components::*;
 pub use decal::clustered::ClusteredDecalPlugin;
 pub use extended_material::*;
 pub use fog::*;
+pub use ies_profile::*;
 pub use light::*;
+pub use light_influence_volumes::*;
 pub use light_probe::*;
 pub use lightmap::*;
 pub use material::*;
@@ -63,6 +71,8 @@ pub use parallax::*;
 pub use pbr_material::*;
 pub use prepass::*;
 pub use render::*;
+pub use shadow_debug::*;
+pub use shadow_export::*;
 pub use ssao::*;
 pub use ssr::*;
 pub use volumetric_fog::{FogVolume, VolumetricFog, VolumetricFogPlugin, VolumetricLight};
@@ -119,6 +129,16 @@ pub mod graph {
         /// rendering pass, containing all meshes that are visible this frame.
         MainBuildIndirectParameters,
         ClearIndirectParametersMetadata,
+        /// Label for the node that draws the shadow map selected by
+        /// [`ShadowMapDebugView`](crate::ShadowMapDebugView) on screen.
+        ShadowMapDebug,
+        /// Label for the node that copies the cube maps of
+        /// [`BakedPointShadow`](crate::BakedPointShadow)s into the point light
+        /// shadow map texture.
+        BakedPointShadows,
+        /// Label for the node that copies shadow maps into the targets of
+        /// [`ExportShadowMap`](crate::ExportShadowMap) components.
+        ShadowMapExport,
     }
 }
 
@@ -134,7 +154,7 @@ use bevy_render::{
     extract_component::ExtractComponentPlugin,
     extract_resource::ExtractResourcePlugin,
     load_shader_library,
-    render_graph::RenderGraph,
+    render_graph::{RenderGraph, ViewNodeRunner},
     render_resource::ShaderRef,
     sync_component::SyncComponentPlugin,
     view::VisibilitySystems,
@@ -205,25 +225,70 @@ impl Plugin for PbrPlugin {
         load_shader_library!(app, "meshlet/dummy_visibility_buffer_resolve.wgsl");
 
         app.register_asset_reflect::<StandardMaterial>()
+            .init_asset::<IesProfile>()
+            .register_asset_loader(IesProfileLoader)
+            .register_type::<IesLightProfile>()
             .register_type::<AmbientLight>()
             .register_type::<CascadeShadowConfig>()
             .register_type::<Cascades>()
+            .register_type::<ComputeCascadesForInactiveView>()
+            .register_type::<ViewCascadeShadowConfig>()
+            .register_type::<ShadowMapState>()
+            .register_type::<ShadowCasterLights>()
+            .register_type::<ShadowCaster>()
+            .register_type::<ShadowCascadeRange>()
+            .register_type::<TemporalShadowSeed>()
+            .register_type::<ShadowCasters>()
+            .register_type::<AmbientLightTween>()
+            .register_type::<ExportShadowMap>()
+            .register_type::<CascadeSizeSmoothing>()
+            .register_type::<SharedCascades>()
+            .register_type::<SkipShadowRender>()
+            .register_type::<BakedPointShadow>()
+            .register_type::<ShadowLayers>()
+            .register_type::<DirectionalLightShadowWarningThreshold>()
+            .register_type::<ShadowConfigLinter>()
+            .register_type::<IntensitySmoothing>()
+            .register_type::<ScaleAffectsRange>()
+            .register_type::<ShadowDrawBudget>()
+            .register_type::<ForceSerialVisibility>()
+            .register_type::<ShadowFrustaTransform>()
+            .register_type::<ShadowFocus>()
+            .register_type::<ViewShadowMapSize>()
+            .register_type::<CascadeMinimumDistance>()
+            .register_type::<LightDebugName>()
+            .register_type::<ShadowLookAhead>()
+            .register_type::<ShadowCamera>()
+            .register_type::<ShadowPrewarm>()
+            .register_type::<LocalAmbient>()
             .register_type::<CascadesVisibleEntities>()
             .register_type::<VisibleMeshEntities>()
             .register_type::<ClusterConfig>()
             .register_type::<CubemapVisibleEntities>()
             .register_type::<DirectionalLight>()
+            .register_type::<AutoFillLight>()
             .register_type::<DirectionalLightShadowMap>()
+            .register_type::<ShadowBounds>()
+            .register_type::<TemporalShadowUpdate>()
             .register_type::<NotShadowCaster>()
             .register_type::<NotShadowReceiver>()
+            .register_type::<NoAmbientLight>()
             .register_type::<PointLight>()
+            .register_type::<PointShadowTechnique>()
             .register_type::<PointLightShadowMap>()
+            .register_type::<ShadowDepthFormat>()
             .register_type::<SpotLight>()
             .register_type::<ShadowFilteringMethod>()
             .init_resource::<AmbientLight>()
             .init_resource::<GlobalVisibleClusterableObjects>()
             .init_resource::<DirectionalLightShadowMap>()
+            .init_resource::<DirectionalLightShadowWarningThreshold>()
             .init_resource::<PointLightShadowMap>()
+            .init_resource::<ShadowDepthFormat>()
+            .register_type::<DominantDirectionalLight>()
+            .init_resource::<DominantDirectionalLight>()
+            .register_type::<ActiveCameraPositions>()
+            .init_resource::<ActiveCameraPositions>()
             .register_type::<DefaultOpaqueRendererMethod>()
             .init_resource::<DefaultOpaqueRendererMethod>()
             .add_plugins((
@@ -256,10 +321,17 @@ impl Plugin for PbrPlugin {
             ))
             .add_plugins((
                 decal::ForwardDecalPlugin,
+                ShadowMapDebugPlugin,
+                LightInfluenceVolumesPlugin,
                 SyncComponentPlugin::<DirectionalLight>::default(),
                 SyncComponentPlugin::<PointLight>::default(),
                 SyncComponentPlugin::<SpotLight>::default(),
                 ExtractComponentPlugin::<AmbientLight>::default(),
+                ExtractComponentPlugin::<ViewCascadeShadowConfig>::default(),
+                ExtractComponentPlugin::<LocalAmbient>::default(),
+                ExtractComponentPlugin::<ExportShadowMap>::default(),
+                ExtractResourcePlugin::<TemporalShadowSeed>::default(),
+                ExtractResourcePlugin::<ShadowDepthFormat>::default(),
             ))
             .add_plugins(AtmospherePlugin)
             .configure_sets(
@@ -326,6 +398,32 @@ impl Plugin for PbrPlugin {
                         // which would override any results from this otherwise
                         .after(VisibilitySystems::CheckVisibility)
                         .before(VisibilitySystems::MarkNewlyHiddenEntitiesInvisible),
+                    update_active_camera_positions
+                        .after(TransformSystems::Propagate)
+                        .after(CameraUpdateSystems)
+                        .before(SimulationLightSystems::CheckLightVisibility),
+                    apply_shadow_draw_budget
+                        .after(SimulationLightSystems::CheckLightVisibility)
+                        .before(VisibilitySystems::MarkNewlyHiddenEntitiesInvisible),
+                    update_shadow_casters.after(apply_shadow_draw_budget),
+                    tween_ambient_light,
+                    update_shadow_map_states
+                        .after(VisibilitySystems::MarkNewlyHiddenEntitiesInvisible),
+                    smooth_light_intensities
+                        .before(SimulationLightSystems::AssignLightsToClusters)
+                        .before(SimulationLightSystems::UpdateDirectionalLightCascades),
+                    warn_non_power_of_two_shadow_maps,
+                    sanitize_light_values
+                        .after(smooth_light_intensities)
+                        .before(SimulationLightSystems::AssignLightsToClusters)
+                        .before(SimulationLightSystems::UpdateDirectionalLightCascades),
+                    update_dominant_directional_light.after(sanitize_light_values),
+                    lint_cascade_shadow_configs.run_if(resource_exists::<ShadowConfigLinter>),
+                    update_auto_fill_lights
+                        .after(TransformSystems::Propagate)
+                        .after(sanitize_light_values)
+                        .before(SimulationLightSystems::UpdateDirectionalLightCascades)
+                        .before(update_dominant_directional_light),
                 ),
             );
 
@@ -355,6 +453,8 @@ impl Plugin for PbrPlugin {
                 (
                     extract_clusters,
                     extract_lights,
+                    extract_shadow_prewarm,
+                    extract_skip_shadow_render,
                     late_sweep_material_instances,
                 ),
             )
@@ -365,9 +465,12 @@ impl Plugin for PbrPlugin {
                         .in_set(RenderSystems::ManageViews)
                         .after(sort_cameras),
                     prepare_clusters.in_set(RenderSystems::PrepareResources),
+                    prepare_shadow_map_exports.in_set(RenderSystems::PrepareResources),
+                    prepare_baked_point_shadows.in_set(RenderSystems::PrepareResources),
                 ),
             )
             .init_resource::<LightMeta>()
+            .init_resource::<DirectionalLightShadowMapTexture>()
             .init_resource::<RenderMaterialBindings>();
 
         render_app.world_mut().add_observer(add_light_view_entities);
@@ -378,13 +481,21 @@ impl Plugin for PbrPlugin {
 
         let early_shadow_pass_node = EarlyShadowPassNode::from_world(render_app.world_mut());
         let late_shadow_pass_node = LateShadowPassNode::from_world(render_app.world_mut());
+        let baked_point_shadow_node =
+            ViewNodeRunner::<BakedPointShadowNode>::from_world(render_app.world_mut());
+        let shadow_map_export_node =
+            ViewNodeRunner::<ShadowMapExportNode>::from_world(render_app.world_mut());
         let mut graph = render_app.world_mut().resource_mut::<RenderGraph>();
         let draw_3d_graph = graph.get_sub_graph_mut(Core3d).unwrap();
         draw_3d_graph.add_node(NodePbr::EarlyShadowPass, early_shadow_pass_node);
         draw_3d_graph.add_node(NodePbr::LateShadowPass, late_shadow_pass_node);
+        draw_3d_graph.add_node(NodePbr::BakedPointShadows, baked_point_shadow_node);
+        draw_3d_graph.add_node(NodePbr::ShadowMapExport, shadow_map_export_node);
         draw_3d_graph.add_node_edges((
             NodePbr::EarlyShadowPass,
             NodePbr::LateShadowPass,
+            NodePbr::BakedPointShadows,
+            NodePbr::ShadowMapExport,
             Node3d::StartMainPass,
         ));
     }
@@ -1,3 +1,5 @@
+use bevy_color::{ColorToComponents, Mix};
+
 use super::*;
 
 /// An ambient light, which lights the entire scene equally.
@@ -37,6 +39,33 @@ pub struct AmbientLight {
     ///
     /// By default, this is set to true.
     pub affects_lightmapped_meshes: bool,
+
+    /// Whether the camera's exposure applies to this ambient light.
+    ///
+    /// Set this to false for a stylized, flat ambient fill that looks the same regardless of the
+    /// camera's [`Exposure`](bevy_render::camera::Exposure). The brightness is then relative to
+    /// the final image rather than in physical units.
+    ///
+    /// By default, this is set to true.
+    pub affected_by_exposure: bool,
+
+    /// The color of the ambient light reaching surfaces that face straight up, for hemisphere
+    /// ambient lighting.
+    ///
+    /// When this or [`ground_color`](Self::ground_color) is set, the ambient color of a surface is
+    /// blended between the two by how much its normal faces up, see
+    /// [`color_for_normal`](Self::color_for_normal). This is a cheap way to get convincing
+    /// outdoor ambient lighting, with a blue sky above and a darker ground below, without a light
+    /// probe. Both colors are scaled by [`brightness`](Self::brightness).
+    ///
+    /// By default, this is [`None`], which uses [`color`](Self::color).
+    pub sky_color: Option<Color>,
+
+    /// The color of the ambient light reaching surfaces that face straight down, for hemisphere
+    /// ambient lighting, see [`sky_color`](Self::sky_color).
+    ///
+    /// By default, this is [`None`], which uses [`color`](Self::color).
+    pub ground_color: Option<Color>,
 }
 
 impl Default for AmbientLight {
@@ -45,6 +74,9 @@ impl Default for AmbientLight {
             color: Color::WHITE,
             brightness: 80.0,
             affects_lightmapped_meshes: true,
+            affected_by_exposure: true,
+            sky_color: None,
+            ground_color: None,
         }
     }
 }
@@ -54,5 +86,212 @@ impl AmbientLight {
         color: Color::WHITE,
         brightness: 0.0,
         affects_lightmapped_meshes: true,
+        affected_by_exposure: true,
+        sky_color: None,
+        ground_color: None,
     };
+
+    /// Returns the color of the ambient light reaching a surface with the given world-space
+    /// `normal`, before it's scaled by the [`brightness`](Self::brightness).
+    ///
+    /// This blends from [`ground_color`](Self::ground_color) for a normal facing straight down to
+    /// [`sky_color`](Self::sky_color) for a normal facing straight up, in linear RGB. Without
+    /// either color, this is [`color`](Self::color) for every normal.
+    ///
+    /// This must be kept in sync with `ambient_color_at` in `pbr_ambient.wgsl`.
+    pub fn color_for_normal(&self, normal: Vec3) -> Color {
+        let sky_color = LinearRgba::from(self.sky_color.unwrap_or(self.color));
+        let ground_color = LinearRgba::from(self.ground_color.unwrap_or(self.color));
+        let sky_factor = normal.normalize_or_zero().y * 0.5 + 0.5;
+        ground_color.mix(&sky_color, sky_factor).into()
+    }
+
+    /// Returns the ambient color passed to the shader for surfaces facing up, in a view with the
+    /// given `exposure`.
+    pub(crate) fn shader_color(&self, exposure: f32) -> Vec4 {
+        self.exposed_shader_color(self.sky_color.unwrap_or(self.color), exposure)
+    }
+
+    /// Returns the ambient color passed to the shader for surfaces facing down, in a view with the
+    /// given `exposure`.
+    pub(crate) fn shader_ground_color(&self, exposure: f32) -> Vec4 {
+        self.exposed_shader_color(self.ground_color.unwrap_or(self.color), exposure)
+    }
+
+    /// Returns `color` scaled by the brightness for a view with the given `exposure`.
+    ///
+    /// The shader multiplies all lighting by the view's exposure, so if this light isn't
+    /// [affected by exposure](Self::affected_by_exposure), the color is divided by the exposure
+    /// here to cancel that out.
+    fn exposed_shader_color(&self, color: Color, exposure: f32) -> Vec4 {
+        let color = Vec4::from_slice(&LinearRgba::from(color).to_f32_array()) * self.brightness;
+        if self.affected_by_exposure || exposure <= 0.0 {
+            color
+        } else {
+            color / exposure
+        }
+    }
+}
+
+/// A resource that smoothly transitions the [`AmbientLight`] resource to a new color and
+/// brightness, for example to fade between the ambient lighting of two areas instead of switching
+/// instantly.
+///
+/// [`tween_ambient_light`] interpolates linearly, in linear RGB, from the values the
+/// [`AmbientLight`] had on the first frame of the transition, and removes this resource once the
+/// transition is over. Insert a new one to start another transition.
+///
+/// ```
+/// # use bevy_color::Color;
+/// # use bevy_ecs::system::Commands;
+/// # use bevy_pbr::AmbientLightTween;
+/// fn enter_cave(mut commands: Commands) {
+///     commands.insert_resource(AmbientLightTween::new(Color::srgb(0.2, 0.2, 0.4), 20.0, 2.0));
+/// }
+/// ```
+#[derive(Resource, Clone, Debug, Reflect)]
+#[reflect(Resource, Debug, Clone)]
+pub struct AmbientLightTween {
+    /// The color the [`AmbientLight`] ends up with.
+    pub target_color: Color,
+    /// The brightness the [`AmbientLight`] ends up with.
+    pub target_brightness: f32,
+    /// How long the transition takes, in seconds.
+    pub duration: f32,
+    /// How long the transition has been running, in seconds.
+    elapsed: f32,
+    /// The color and brightness of the [`AmbientLight`] when the transition started.
+    start: Option<(LinearRgba, f32)>,
+}
+
+impl AmbientLightTween {
+    /// Creates a transition to `target_color` and `target_brightness` over `duration` seconds.
+    pub fn new(target_color: Color, target_brightness: f32, duration: f32) -> Self {
+        Self {
+            target_color,
+            target_brightness,
+            duration,
+            elapsed: 0.0,
+            start: None,
+        }
+    }
+}
+
+/// Moves the [`AmbientLight`] resource toward the target of the [`AmbientLightTween`], if there is
+/// one.
+pub fn tween_ambient_light(
+    mut commands: Commands,
+    time: Option<Res<Time>>,
+    tween: Option<ResMut<AmbientLightTween>>,
+    ambient_light: Option<ResMut<AmbientLight>>,
+) {
+    let (Some(mut tween), Some(mut ambient_light)) = (tween, ambient_light) else {
+        return;
+    };
+
+    let (start_color, start_brightness) = *tween
+        .start
+        .get_or_insert_with(|| (ambient_light.color.into(), ambient_light.brightness));
+    tween.elapsed += time.map_or(0.0, |time| time.delta_secs());
+
+    if tween.elapsed >= tween.duration {
+        ambient_light.color = tween.target_color;
+        ambient_light.brightness = tween.target_brightness;
+        commands.remove_resource::<AmbientLightTween>();
+        return;
+    }
+
+    let t = tween.elapsed / tween.duration;
+    ambient_light.color = start_color.mix(&tween.target_color.into(), t).into();
+    ambient_light.brightness = start_brightness + (tween.target_brightness - start_brightness) * t;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ambient_light_unaffected_by_exposure_is_stable() {
+        let ambient_light = AmbientLight {
+            affected_by_exposure: false,
+            ..Default::default()
+        };
+        let exposed_color = |exposure| ambient_light.shader_color(exposure) * exposure;
+        assert!(exposed_color(0.5).abs_diff_eq(exposed_color(2.0), 1e-3));
+
+        let ambient_light = AmbientLight::default();
+        let exposed_color = |exposure| ambient_light.shader_color(exposure) * exposure;
+        assert_eq!(exposed_color(2.0), exposed_color(0.5) * 4.0);
+    }
+
+    #[test]
+    fn hemisphere_ambient_light_blends_sky_and_ground() {
+        let sky_color = Color::linear_rgb(0.2, 0.4, 1.0);
+        let ground_color = Color::linear_rgb(0.3, 0.2, 0.1);
+        let ambient_light = AmbientLight {
+            sky_color: Some(sky_color),
+            ground_color: Some(ground_color),
+            ..Default::default()
+        };
+        assert_eq!(ambient_light.color_for_normal(Vec3::Y), sky_color);
+        assert_eq!(ambient_light.color_for_normal(Vec3::NEG_Y), ground_color);
+        let side = LinearRgba::from(ambient_light.color_for_normal(Vec3::X));
+        assert!((side.red - 0.25).abs() < 1e-5 && (side.blue - 0.55).abs() < 1e-5);
+
+        // The shader gets the sky color as the regular ambient color.
+        assert_eq!(
+            ambient_light.shader_color(1.0),
+            Vec4::new(0.2, 0.4, 1.0, 1.0) * ambient_light.brightness
+        );
+
+        // Without sky and ground colors, every normal gets the same color.
+        let ambient_light = AmbientLight::default();
+        assert_eq!(
+            LinearRgba::from(ambient_light.color_for_normal(Vec3::NEG_Y)),
+            LinearRgba::from(ambient_light.color)
+        );
+        assert_eq!(
+            ambient_light.shader_color(1.0),
+            ambient_light.shader_ground_color(1.0)
+        );
+    }
+
+    #[test]
+    fn ambient_light_tween_ramps_toward_target() {
+        use bevy_ecs::system::RunSystemOnce;
+        use core::time::Duration;
+
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        world.insert_resource(AmbientLight {
+            color: Color::BLACK,
+            brightness: 0.0,
+            ..Default::default()
+        });
+        world.insert_resource(AmbientLightTween::new(Color::WHITE, 100.0, 1.0));
+
+        let mut previous_brightness = 0.0;
+        for _ in 0..4 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(200));
+            world.run_system_once(tween_ambient_light).unwrap();
+
+            let ambient_light = world.resource::<AmbientLight>();
+            assert!(ambient_light.brightness > previous_brightness);
+            assert!(ambient_light.brightness < 100.0);
+            previous_brightness = ambient_light.brightness;
+        }
+        let red = LinearRgba::from(world.resource::<AmbientLight>().color).red;
+        assert!((red - 0.8).abs() < 1e-5);
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_millis(300));
+        world.run_system_once(tween_ambient_light).unwrap();
+        let ambient_light = world.resource::<AmbientLight>();
+        assert_eq!(ambient_light.brightness, 100.0);
+        assert_eq!(ambient_light.color, Color::WHITE);
+        assert!(!world.contains_resource::<AmbientLightTween>());
+    }
 }
@@ -1,3 +1,5 @@
+use bevy_derive::{Deref, DerefMut};
+use bevy_math::{Rect, Vec2, Vec4Swizzles};
 use bevy_render::view::{self, Visibility};
 
 use super::*;
@@ -49,6 +51,7 @@ use super::*;
     CascadesFrusta,
     CascadeShadowConfig,
     CascadesVisibleEntities,
+    ShadowMapState,
     Transform,
     Visibility,
     VisibilityClass
@@ -114,12 +117,103 @@ pub struct DirectionalLight {
     /// objects incorrectly cast shadows on themselves, known as *shadow acne*.
     /// If set too high, shadows detach from the objects casting them and seem
     /// to "fly" off the objects, known as *Peter Panning*.
+    ///
+    /// This is the bias of the first cascade, in world units. Coarser cascades are biased
+    /// proportionally more, to avoid self-shadowing lines where cascades are blended. See
+    /// [`Cascade::world_depth_bias`](crate::Cascade::world_depth_bias).
     pub shadow_depth_bias: f32,
 
     /// A bias applied along the direction of the fragment's surface normal. It
     /// is scaled to the shadow map's texel size so that it is automatically
     /// adjusted to the orthographic projection.
+    ///
+    /// This is measured in texels rather than world units. Each cascade has its
+    /// own [`texel_size`](crate::Cascade::texel_size), so the same bias results
+    /// in a small offset in near cascades and a larger one in far cascades. See
+    /// [`Cascade::world_normal_bias`](crate::Cascade::world_normal_bias).
     pub shadow_normal_bias: f32,
+
+    /// The minimum distance, in world units, that fragments are offset toward
+    /// the light when sampling each cascade.
+    ///
+    /// On surfaces seen by the light at grazing angles, such as a floor under a
+    /// low sun, the normal bias barely moves fragments toward the light, so the
+    /// depth bias alone has to prevent acne there. The bias of each cascade,
+    /// [`shadow_depth_bias`](Self::shadow_depth_bias) scaled by the cascade's
+    /// texel size, is raised to this value if it's lower. This gives the near
+    /// cascades a minimum offset without also scaling up the bias of the far
+    /// cascades, which would detach their shadows from their casters.
+    ///
+    /// By default, this is 0.0, which leaves the bias of each cascade as is.
+    pub min_world_shadow_bias: f32,
+
+    /// The color that shadowed areas are tinted toward, for stylized colored shadows.
+    ///
+    /// In shadow, the light's contribution is multiplied by this color rather than removed
+    /// entirely, blending back to the full contribution as the surface leaves the shadow.
+    ///
+    /// By default, this is black, which makes shadows fully block the light.
+    pub shadow_tint: Color,
+
+    /// Whether this light's shadows fade out along with its
+    /// [`illuminance`](Self::illuminance), so that dimming the light also softens the
+    /// shadows it casts rather than leaving them at full strength until it turns off.
+    ///
+    /// When enabled, the shadow strength is the ratio of the illuminance to
+    /// [`full_shadow_illuminance`](Self::full_shadow_illuminance), clamped to 1.0. See
+    /// [`shadow_strength`](Self::shadow_strength).
+    ///
+    /// By default, this is false, and shadows are always at full strength.
+    pub fade_shadows_with_intensity: bool,
+
+    /// The illuminance at and above which shadows are at full strength, when
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity) is enabled.
+    ///
+    /// By default, this is [`light_consts::lux::AMBIENT_DAYLIGHT`], the default illuminance.
+    pub full_shadow_illuminance: f32,
+
+    /// The radius, in world units, over which shadow edges are blurred when filtering the shadow
+    /// map.
+    ///
+    /// By default, this is [`None`], and the blur radius is a fixed number of texels, so shadows
+    /// are softer in far cascades and change softness with the shadow map resolution. Setting this
+    /// converts the radius to texels using each cascade's
+    /// [`texel_size`](crate::Cascade::texel_size), keeping the softness the same across cascades
+    /// and resolutions. See
+    /// [`Cascade::shadow_blur_texel_radius`](crate::Cascade::shadow_blur_texel_radius).
+    ///
+    /// This only affects the [`Temporal`](crate::ShadowFilteringMethod::Temporal) and
+    /// [`Vogel`](crate::ShadowFilteringMethod::Vogel) filtering methods, and has no effect when
+    /// soft shadows are enabled.
+    pub shadow_blur_world_radius: Option<f32>,
+
+    /// Whether this light's shadows are filtered the same way on every frame.
+    ///
+    /// By default, this is `false`, and shadows are filtered with the camera's
+    /// [`ShadowFilteringMethod`](crate::ShadowFilteringMethod). The
+    /// [`Temporal`](crate::ShadowFilteringMethod::Temporal) and
+    /// [`Vogel`](crate::ShadowFilteringMethod::Vogel) methods change their sampling pattern from
+    /// frame to frame, relying on temporal antialiasing to smooth out the noise. Setting this makes
+    /// the light use a fixed pattern instead, such as the
+    /// [`Gaussian`](crate::ShadowFilteringMethod::Gaussian) filter, for crisp shadows that don't
+    /// flicker even when temporal antialiasing is in use.
+    pub stable_shadows: bool,
+
+    /// How strongly fragments close to a shadow caster are darkened, from 0.0 to 1.0, for subtle
+    /// contact shading where objects meet the ground.
+    ///
+    /// When shadows are sampled, the depth of the nearest occluder in the shadow map is compared
+    /// with the depth of the fragment. Fragments just below an occluder are darkened by up to this
+    /// factor, fading out as the occluder gets farther away. This approximates ambient occlusion
+    /// in the shadowed areas without a separate pass, but only sees occluders between the
+    /// fragment and the light.
+    ///
+    /// Values outside of 0.0 to 1.0 are clamped; see
+    /// [`clamped_shadow_contact_darkening`](Self::clamped_shadow_contact_darkening). This has no
+    /// effect on WebGL 2.
+    ///
+    /// By default, this is 0.0, which disables the darkening.
+    pub shadow_contact_darkening: f32,
 }
 
 impl Default for DirectionalLight {
@@ -130,6 +224,13 @@ impl Default for DirectionalLight {
             shadows_enabled: false,
             shadow_depth_bias: Self::DEFAULT_SHADOW_DEPTH_BIAS,
             shadow_normal_bias: Self::DEFAULT_SHADOW_NORMAL_BIAS,
+            min_world_shadow_bias: 0.0,
+            shadow_tint: Color::BLACK,
+            fade_shadows_with_intensity: false,
+            full_shadow_illuminance: light_consts::lux::AMBIENT_DAYLIGHT,
+            shadow_blur_world_radius: None,
+            stable_shadows: false,
+            shadow_contact_darkening: 0.0,
             affects_lightmapped_mesh_diffuse: true,
             #[cfg(feature = "experimental_pbr_pcss")]
             soft_shadow_size: None,
@@ -140,4 +241,402 @@ impl Default for DirectionalLight {
 impl DirectionalLight {
     pub const DEFAULT_SHADOW_DEPTH_BIAS: f32 = 0.02;
     pub const DEFAULT_SHADOW_NORMAL_BIAS: f32 = 1.8;
+
+    /// Returns a [`DirectionalLight`] with the default settings, and the
+    /// [`color`](Self::color) of a blackbody at the temperature `kelvin`, see
+    /// [`set_color_temperature`](Self::set_color_temperature).
+    ///
+    /// ```
+    /// # use bevy_pbr::DirectionalLight;
+    /// let overcast_sky = DirectionalLight::from_kelvin(6500.0);
+    /// ```
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let mut light = Self::default();
+        light.set_color_temperature(kelvin);
+        light
+    }
+
+    /// Sets the [`color`](Self::color) to that of a blackbody at the temperature `kelvin`, such as
+    /// about 3200 K for warm tungsten light, or 6500 K for overcast daylight.
+    ///
+    /// The temperature is clamped to 1000 K to 40000 K. The brightest channel of the color is
+    /// always 1.0, so this only changes the hue of the light.
+    pub fn set_color_temperature(&mut self, kelvin: f32) {
+        self.color = color_temperature_to_color(kelvin);
+    }
+
+    /// Returns [`shadow_contact_darkening`](Self::shadow_contact_darkening) clamped to the range
+    /// 0.0 to 1.0, with NaN treated as 0.0. This is the value used by the shader.
+    pub fn clamped_shadow_contact_darkening(&self) -> f32 {
+        if self.shadow_contact_darkening.is_nan() {
+            0.0
+        } else {
+            self.shadow_contact_darkening.clamp(0.0, 1.0)
+        }
+    }
+
+    /// Returns the strength of this light's shadows, from 0.0 for no shadows to 1.0 for full
+    /// shadows, as described in
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity).
+    pub fn shadow_strength(&self) -> f32 {
+        if self.fade_shadows_with_intensity {
+            intensity_shadow_strength(self.illuminance, self.full_shadow_illuminance)
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Limits the shadows of a [`DirectionalLight`] to a world-space box.
+///
+/// Shadow casters whose bounds lie entirely outside the box are culled, and
+/// cascades that don't overlap the box don't render any casters at all. This
+/// is useful for large outdoor scenes where shadows only matter within the
+/// playable area. Unlike [`CascadeShadowConfig::bounds`], which are relative to
+/// the camera, this box is fixed in the world.
+///
+/// When absent, the light's shadows aren't limited to any area.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect, Deref, DerefMut)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct ShadowBounds(pub Aabb);
+
+impl ShadowBounds {
+    /// Returns true if the given oriented bounding box, transformed into world
+    /// space by `world_from_local`, overlaps these bounds.
+    pub fn intersects_obb(&self, aabb: &Aabb, world_from_local: &Affine3A) -> bool {
+        let center = world_from_local.transform_point3a(aabb.center);
+        let matrix = world_from_local.matrix3;
+        let half_extents = matrix.x_axis.abs() * aabb.half_extents.x
+            + matrix.y_axis.abs() * aabb.half_extents.y
+            + matrix.z_axis.abs() * aabb.half_extents.z;
+        (center - self.center)
+            .abs()
+            .cmple(half_extents + self.half_extents)
+            .all()
+    }
+}
+
+/// Renders the shadow maps of a [`DirectionalLight`] only once every few
+/// frames, to save power.
+///
+/// On frames where the shadow maps aren't rendered, the light's [`Cascades`]
+/// aren't rebuilt either, and shading samples the shadow maps rendered on the
+/// last update. Shadows of moving objects, and shadows near the edges of the
+/// cascades when the camera moves, will lag behind by up to `interval - 1`
+/// frames. All shadow maps are rendered on frames where the shadow map
+/// texture is resized, such as when lights or cascades are added.
+///
+/// When absent, shadow maps are rendered every frame.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct TemporalShadowUpdate {
+    /// The number of frames between shadow map updates.
+    ///
+    /// A value of 1 (or 0) updates the shadow maps every frame, and 2 updates
+    /// them every other frame.
+    pub interval: u32,
+}
+
+impl Default for TemporalShadowUpdate {
+    fn default() -> Self {
+        Self { interval: 1 }
+    }
+}
+
+impl TemporalShadowUpdate {
+    /// Returns true if shadow maps should be rendered on the frame with the
+    /// given [`FrameCount`](bevy_diagnostic::FrameCount).
+    pub fn updates_on_frame(&self, frame_count: u32) -> bool {
+        self.interval <= 1 || frame_count.is_multiple_of(self.interval)
+    }
+}
+
+/// The shadow-casting [`DirectionalLight`] with the highest
+/// [`illuminance`](DirectionalLight::illuminance), usually the sun.
+///
+/// Sky, fog and other effects that follow the sun can read this instead of
+/// searching for the brightest light themselves. It's updated every frame in
+/// [`PostUpdate`](bevy_app::PostUpdate) by
+/// [`update_dominant_directional_light`], and is [`None`] if no directional
+/// light has [`shadows_enabled`](DirectionalLight::shadows_enabled).
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect, Deref, DerefMut)]
+#[reflect(Resource, Default, Debug, PartialEq, Clone)]
+pub struct DominantDirectionalLight(pub Option<Entity>);
+
+/// Points the [`DominantDirectionalLight`] to the brightest shadow-casting
+/// [`DirectionalLight`].
+pub fn update_dominant_directional_light(
+    mut dominant_light: ResMut<DominantDirectionalLight>,
+    directional_lights: Query<(Entity, &DirectionalLight)>,
+) {
+    let brightest = directional_lights
+        .iter()
+        .filter(|(_, light)| light.shadows_enabled)
+        .max_by(|(_, a), (_, b)| a.illuminance.total_cmp(&b.illuminance))
+        .map(|(entity, _)| entity);
+    dominant_light.set_if_neq(DominantDirectionalLight(brightest));
+}
+
+/// Makes this entity a dim [`DirectionalLight`] that fills in the shadowed side of a scene by
+/// shining in the opposite direction to a key light, such as the sun.
+///
+/// Every frame, [`update_auto_fill_lights`] turns the light to face away from the
+/// [`key`](Self::key) light, gives it the key light's color, and sets its illuminance to
+/// [`intensity_ratio`](Self::intensity_ratio) times the key light's illuminance, so the fill stays
+/// in sync as the key light rotates or dims. Unlike an [`AmbientLight`](crate::AmbientLight), the
+/// fill light doesn't brighten the side of the scene that the key light already lights.
+///
+/// The fill light's [`Transform`] is overwritten, so it shouldn't have a parent. Its other
+/// properties, such as [`shadows_enabled`](DirectionalLight::shadows_enabled), are left alone. If
+/// the key light doesn't exist or isn't a [`DirectionalLight`], the fill light is turned off.
+#[derive(Component, Clone, Copy, Debug, Reflect)]
+#[reflect(Component, Debug, Clone)]
+#[require(DirectionalLight)]
+pub struct AutoFillLight {
+    /// The directional light that this light fills in for.
+    pub key: Entity,
+    /// The illuminance of this light relative to the key light's illuminance.
+    ///
+    /// Values from `0.1` to `0.3` give a subtle fill.
+    pub intensity_ratio: f32,
+}
+
+/// Returns the rotation of an [`AutoFillLight`] whose key light has the given rotation, which
+/// shines in the opposite direction.
+fn auto_fill_light_rotation(key_rotation: Quat) -> Quat {
+    key_rotation * Quat::from_rotation_y(core::f32::consts::PI)
+}
+
+/// Points each [`AutoFillLight`] away from its key light and updates its color and illuminance.
+///
+/// This runs after transform propagation so that it follows the key light on the same frame, and
+/// updates the [`GlobalTransform`] of the fill light itself.
+pub fn update_auto_fill_lights(
+    mut fill_lights: Query<(
+        &AutoFillLight,
+        &mut DirectionalLight,
+        &mut Transform,
+        &mut GlobalTransform,
+    )>,
+    key_lights: Query<(&DirectionalLight, &GlobalTransform), Without<AutoFillLight>>,
+) {
+    for (fill, mut fill_light, mut transform, mut global_transform) in &mut fill_lights {
+        let Ok((key_light, key_transform)) = key_lights.get(fill.key) else {
+            if fill_light.illuminance != 0.0 {
+                fill_light.illuminance = 0.0;
+            }
+            continue;
+        };
+
+        let illuminance = key_light.illuminance * fill.intensity_ratio.max(0.0);
+        if fill_light.illuminance != illuminance || fill_light.color != key_light.color {
+            fill_light.illuminance = illuminance;
+            fill_light.color = key_light.color;
+        }
+
+        let fill_transform = Transform {
+            rotation: auto_fill_light_rotation(key_transform.rotation()),
+            ..*transform
+        };
+        transform.set_if_neq(fill_transform);
+        global_transform.set_if_neq(GlobalTransform::from(fill_transform));
+    }
+}
+
+/// Returns the position of the sun of a [`DirectionalLight`] in the viewport of a camera, in
+/// logical pixels, for effects such as lens flares and god rays.
+///
+/// The sun lies infinitely far away, in the opposite direction to the one the light shines in, so
+/// only the light's rotation matters. The returned position can be outside of the viewport when
+/// the sun is off-screen.
+///
+/// Returns [`None`] if the sun is behind the camera, if the camera has no viewport yet, or if the
+/// camera's projection is orthographic, as a direction has no position on screen there.
+///
+/// This uses the same conventions as [`Camera::world_to_viewport`].
+pub fn sun_screen_position(
+    light_transform: &GlobalTransform,
+    camera: &Camera,
+    camera_transform: &GlobalTransform,
+) -> Option<Vec2> {
+    direction_to_viewport(
+        light_transform.back().into(),
+        camera.clip_from_view(),
+        camera_transform,
+        camera.logical_viewport_rect()?,
+    )
+}
+
+/// Projects a world-space direction through a camera to a position in `viewport`, or returns
+/// [`None`] if the direction points behind the camera.
+pub(crate) fn direction_to_viewport(
+    direction: Vec3,
+    clip_from_view: Mat4,
+    camera_transform: &GlobalTransform,
+    viewport: Rect,
+) -> Option<Vec2> {
+    let view_direction = camera_transform
+        .affine()
+        .inverse()
+        .transform_vector3(direction);
+    let clip_position = clip_from_view * view_direction.extend(0.0);
+    if clip_position.w <= 0.0 {
+        return None;
+    }
+    let mut ndc_position = clip_position.xy() / clip_position.w;
+
+    // Flip the Y co-ordinate origin from the bottom to the top.
+    ndc_position.y = -ndc_position.y;
+    Some((ndc_position + Vec2::ONE) / 2.0 * viewport.size() + viewport.min)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sun_screen_position_projects_light_direction() {
+        use bevy_math::{Rect, Vec2};
+        use directional_light::direction_to_viewport;
+
+        let clip_from_view = PerspectiveProjection {
+            fov: core::f32::consts::FRAC_PI_2,
+            aspect_ratio: 2.0,
+            ..Default::default()
+        }
+        .get_clip_from_view();
+        let viewport = Rect::new(0.0, 0.0, 200.0, 100.0);
+        let camera_transform = GlobalTransform::IDENTITY;
+
+        // A sun straight ahead of the camera is in the center of the viewport.
+        let sun = |light_transform: Transform| {
+            direction_to_viewport(
+                GlobalTransform::from(light_transform).back().into(),
+                clip_from_view,
+                &camera_transform,
+                viewport,
+            )
+        };
+        let ahead = sun(Transform::IDENTITY.looking_to(Vec3::Z, Vec3::Y)).unwrap();
+        assert!(ahead.abs_diff_eq(Vec2::new(100.0, 50.0), 1e-4));
+
+        // With a 90° vertical field of view, a sun 45° up is at the top edge of the viewport, and
+        // a sun 45° up and to the right, in the vertical plane of the top right corner, is in the
+        // top right corner.
+        let up = sun(Transform::IDENTITY.looking_to(Vec3::new(0.0, -1.0, 1.0), Vec3::Y)).unwrap();
+        assert!(up.abs_diff_eq(Vec2::new(100.0, 0.0), 1e-4));
+        let corner = sun(Transform::IDENTITY.looking_to(Vec3::new(-2.0, -1.0, 1.0), Vec3::Y));
+        assert!(corner.unwrap().abs_diff_eq(Vec2::new(200.0, 0.0), 1e-4));
+
+        // A sun behind the camera has no screen position.
+        assert!(sun(Transform::IDENTITY.looking_to(Vec3::NEG_Z, Vec3::Y)).is_none());
+    }
+
+    #[test]
+    fn shadow_contact_darkening_is_clamped() {
+        let darkening = |shadow_contact_darkening| {
+            DirectionalLight {
+                shadow_contact_darkening,
+                ..Default::default()
+            }
+            .clamped_shadow_contact_darkening()
+        };
+
+        assert_eq!(
+            DirectionalLight::default().clamped_shadow_contact_darkening(),
+            0.0
+        );
+        assert_eq!(darkening(0.25), 0.25);
+        assert_eq!(darkening(-1.0), 0.0);
+        assert_eq!(darkening(3.0), 1.0);
+        assert_eq!(darkening(f32::INFINITY), 1.0);
+        assert_eq!(darkening(f32::NAN), 0.0);
+    }
+
+    #[test]
+    fn dominant_directional_light_is_brightest_shadow_caster() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.init_resource::<DominantDirectionalLight>();
+        let mut spawn_light = |illuminance, shadows_enabled| {
+            world
+                .spawn(DirectionalLight {
+                    illuminance,
+                    shadows_enabled,
+                    ..Default::default()
+                })
+                .id()
+        };
+        spawn_light(500.0, true);
+        let sun = spawn_light(10_000.0, true);
+        // Brighter, but without shadows.
+        spawn_light(50_000.0, false);
+        let runner_up = spawn_light(2_000.0, true);
+
+        world
+            .run_system_once(update_dominant_directional_light)
+            .unwrap();
+        assert_eq!(world.resource::<DominantDirectionalLight>().0, Some(sun));
+
+        world.despawn(sun);
+        world
+            .run_system_once(update_dominant_directional_light)
+            .unwrap();
+        assert_eq!(
+            world.resource::<DominantDirectionalLight>().0,
+            Some(runner_up)
+        );
+    }
+
+    #[test]
+    fn auto_fill_lights_oppose_key_light() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let key_transform = Transform::default().looking_to(Vec3::new(1.0, -2.0, 0.5), Vec3::Y);
+        let key = world
+            .spawn((
+                DirectionalLight {
+                    illuminance: 10_000.0,
+                    ..Default::default()
+                },
+                key_transform,
+                GlobalTransform::from(key_transform),
+            ))
+            .id();
+        let fill = world
+            .spawn(AutoFillLight {
+                key,
+                intensity_ratio: 0.2,
+            })
+            .id();
+
+        let check_fill = |world: &mut World| {
+            world.run_system_once(update_auto_fill_lights).unwrap();
+            let key_forward = world.get::<GlobalTransform>(key).unwrap().forward();
+            let fill_forward = world.get::<GlobalTransform>(fill).unwrap().forward();
+            assert!(fill_forward.dot(*key_forward) < -0.9999);
+            let fill_transform = world.get::<Transform>(fill).unwrap();
+            assert!(fill_transform.forward().dot(*fill_forward) > 0.9999);
+            let key_illuminance = world.get::<DirectionalLight>(key).unwrap().illuminance;
+            let fill_illuminance = world.get::<DirectionalLight>(fill).unwrap().illuminance;
+            assert!((fill_illuminance - key_illuminance * 0.2).abs() < 1e-2);
+        };
+        check_fill(&mut world);
+
+        // The fill light follows the key light as it rotates and dims.
+        let key_transform = Transform::default().looking_to(Vec3::new(-0.3, -0.5, -1.0), Vec3::Y);
+        *world.get_mut::<Transform>(key).unwrap() = key_transform;
+        *world.get_mut::<GlobalTransform>(key).unwrap() = GlobalTransform::from(key_transform);
+        world.get_mut::<DirectionalLight>(key).unwrap().illuminance = 500.0;
+        check_fill(&mut world);
+
+        world.despawn(key);
+        world.run_system_once(update_auto_fill_lights).unwrap();
+        assert_eq!(
+            world.get::<DirectionalLight>(fill).unwrap().illuminance,
+            0.0
+        );
+    }
 }
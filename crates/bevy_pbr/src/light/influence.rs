@@ -0,0 +1,249 @@
+use super::*;
+
+/// A light whose influence on the world can be tested against bounding boxes, see
+/// [`light_affects_aabb`].
+pub trait LightInfluence {
+    /// Returns true if this light, with the given global transform, may light some of the
+    /// world-space box `aabb`.
+    fn affects_aabb(&self, transform: &GlobalTransform, aabb: &Aabb) -> bool;
+}
+
+impl LightInfluence for PointLight {
+    fn affects_aabb(&self, transform: &GlobalTransform, aabb: &Aabb) -> bool {
+        sphere_intersects_aabb(&self.influence_sphere(transform), aabb)
+    }
+}
+
+impl LightInfluence for SpotLight {
+    fn affects_aabb(&self, transform: &GlobalTransform, aabb: &Aabb) -> bool {
+        let (sphere, cone) = self.influence_bounds(transform);
+        sphere_intersects_aabb(&sphere, aabb) && cone.intersects_obb(aabb, &Affine3A::IDENTITY)
+    }
+}
+
+impl LightInfluence for DirectionalLight {
+    /// Directional lights light the whole world, so this is always true.
+    fn affects_aabb(&self, _transform: &GlobalTransform, _aabb: &Aabb) -> bool {
+        true
+    }
+}
+
+/// [`Sphere::intersects_obb`] divides by the distance between the centers, so a sphere centered
+/// exactly on the box would otherwise never intersect it.
+fn sphere_intersects_aabb(sphere: &Sphere, aabb: &Aabb) -> bool {
+    sphere.center == aabb.center || sphere.intersects_obb(aabb, &Affine3A::IDENTITY)
+}
+
+/// Returns true if `light`, with the given global transform, may light some of the world-space
+/// box `aabb`, for gameplay queries such as whether an object is within range of a light.
+///
+/// This uses the same tests as the shadow caster culling of
+/// [`check_point_light_mesh_visibility`]: the [`influence_sphere`](PointLight::influence_sphere)
+/// of point lights, and the [`influence_bounds`](SpotLight::influence_bounds) of spot lights,
+/// which match the culling of spot lights with
+/// [`cone_shadow_culling`](SpotLight::cone_shadow_culling). Like those tests, it's conservative:
+/// a box that only lies near the edge of the light's influence may be reported as affected.
+///
+/// The light's range isn't scaled for lights with [`ScaleAffectsRange`].
+pub fn light_affects_aabb(
+    light: &impl LightInfluence,
+    transform: &GlobalTransform,
+    aabb: &Aabb,
+) -> bool {
+    light.affects_aabb(transform, aabb)
+}
+
+/// Returns every [`PointLight`], [`SpotLight`] and [`DirectionalLight`] in the world that may
+/// light some of the world-space box `aabb`, for example to find the lights illuminating a room.
+///
+/// Each light is tested with [`light_affects_aabb`], using its [`GlobalTransform`], or the
+/// identity transform if it has none. Directional lights are always included.
+///
+/// This visits every entity in the world, so it's meant for occasional queries rather than for
+/// use on many volumes every frame.
+pub fn lights_overlapping(world: &World, aabb: &Aabb) -> Vec<Entity> {
+    world
+        .iter_entities()
+        .filter(|entity| {
+            let transform = entity.get::<GlobalTransform>().copied().unwrap_or_default();
+            if let Some(light) = entity.get::<PointLight>() {
+                light.affects_aabb(&transform, aabb)
+            } else if let Some(light) = entity.get::<SpotLight>() {
+                light.affects_aabb(&transform, aabb)
+            } else if let Some(light) = entity.get::<DirectionalLight>() {
+                light.affects_aabb(&transform, aabb)
+            } else {
+                false
+            }
+        })
+        .map(|entity| entity.id())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn light_affects_aabb_matches_shadow_caster_culling() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        let point_light = PointLight {
+            shadows_enabled: true,
+            range: 5.0,
+            ..Default::default()
+        };
+        let point_transform = GlobalTransform::from_xyz(-20.0, 0.0, 0.0);
+        let spot_light = SpotLight {
+            shadows_enabled: true,
+            outer_angle: 0.3,
+            cone_shadow_culling: true,
+            ..Default::default()
+        };
+        let spot_transform = GlobalTransform::from_xyz(20.0, 0.0, 0.0);
+        let lights = [
+            world
+                .spawn((point_light, point_transform, InheritedVisibility::VISIBLE))
+                .id(),
+            world
+                .spawn((spot_light, spot_transform, InheritedVisibility::VISIBLE))
+                .id(),
+        ];
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.extend(lights);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.extend(lights);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+
+        // Casters inside, near the edge of, and outside of the range of the point light, and on
+        // the axis of, in the corner of the shadow frustum of, and behind the spot light.
+        let local_aabb = Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1));
+        let corner_offset = ops::tan(0.28) * 10.0;
+        let casters = [
+            Vec3::new(-18.0, 1.0, 0.0),
+            Vec3::new(-20.0, 0.0, 5.05),
+            Vec3::new(-20.0, 6.0, 0.0),
+            Vec3::new(20.0, 0.0, -10.0),
+            Vec3::new(20.0 + corner_offset, corner_offset, -10.0),
+            Vec3::new(20.0, 0.0, 3.0),
+        ]
+        .map(|position| {
+            let entity = world
+                .spawn((
+                    Mesh3d::default(),
+                    local_aabb,
+                    GlobalTransform::from_translation(position),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id();
+            let world_aabb = Aabb {
+                center: local_aabb.center + Vec3A::from(position),
+                half_extents: local_aabb.half_extents,
+            };
+            (entity, world_aabb)
+        });
+
+        world.run_system_once(update_point_light_frusta).unwrap();
+        world.run_system_once(update_spot_light_frusta).unwrap();
+        world
+            .run_system_once(check_point_light_mesh_visibility)
+            .unwrap();
+
+        let point_visible = world
+            .get::<CubemapVisibleEntities>(lights[0])
+            .unwrap()
+            .iter()
+            .flat_map(|visible_entities| visible_entities.entities.iter().copied())
+            .collect::<EntityHashSet>();
+        let spot_visible = world
+            .get::<VisibleMeshEntities>(lights[1])
+            .unwrap()
+            .entities
+            .iter()
+            .copied()
+            .collect::<EntityHashSet>();
+        for (entity, world_aabb) in casters {
+            assert_eq!(
+                light_affects_aabb(&point_light, &point_transform, &world_aabb),
+                point_visible.contains(&entity),
+            );
+            assert_eq!(
+                light_affects_aabb(&spot_light, &spot_transform, &world_aabb),
+                spot_visible.contains(&entity),
+            );
+        }
+        assert!(!point_visible.is_empty() && !spot_visible.is_empty());
+    }
+
+    #[test]
+    fn lights_overlapping_returns_lights_affecting_aabb() {
+        let mut world = World::new();
+        let room = Aabb::from_min_max(Vec3::splat(-2.0), Vec3::splat(2.0));
+
+        let point_light_inside = world
+            .spawn((PointLight::default(), GlobalTransform::IDENTITY))
+            .id();
+        let point_light_in_range = world
+            .spawn((
+                PointLight {
+                    range: 5.0,
+                    ..Default::default()
+                },
+                GlobalTransform::from_xyz(6.0, 0.0, 0.0),
+            ))
+            .id();
+        world.spawn((
+            PointLight {
+                range: 5.0,
+                ..Default::default()
+            },
+            GlobalTransform::from_xyz(20.0, 0.0, 0.0),
+        ));
+        // Spot lights pointing toward and away from the room.
+        let spot_light_toward = world
+            .spawn((
+                SpotLight {
+                    outer_angle: 0.3,
+                    ..Default::default()
+                },
+                GlobalTransform::from(
+                    Transform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::ZERO, Vec3::Y),
+                ),
+            ))
+            .id();
+        world.spawn((
+            SpotLight {
+                outer_angle: 0.3,
+                ..Default::default()
+            },
+            GlobalTransform::from(
+                Transform::from_xyz(0.0, 0.0, 10.0).looking_at(Vec3::Z * 20.0, Vec3::Y),
+            ),
+        ));
+        let directional_light = world
+            .spawn((
+                DirectionalLight::default(),
+                GlobalTransform::from_xyz(1000.0, 0.0, 0.0),
+            ))
+            .id();
+        world.spawn(GlobalTransform::IDENTITY);
+
+        let mut lights = lights_overlapping(&world, &room);
+        lights.sort();
+        let mut expected = vec![
+            point_light_inside,
+            point_light_in_range,
+            spot_light_toward,
+            directional_light,
+        ];
+        expected.sort();
+        assert_eq!(lights, expected);
+    }
+}
@@ -0,0 +1,213 @@
+use super::*;
+
+/// The type of a light described by a [`LightInfo`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Debug, PartialEq, Hash, Clone)]
+pub enum LightKind {
+    /// A [`PointLight`].
+    Point,
+    /// A [`SpotLight`].
+    Spot,
+    /// A [`DirectionalLight`].
+    Directional,
+}
+
+/// A common view of a [`PointLight`], [`SpotLight`] or [`DirectionalLight`], for tools that
+/// need to handle every light in the same way.
+///
+/// See [`iter_lights`].
+#[derive(Clone, Debug, PartialEq)]
+pub struct LightInfo {
+    /// The entity the light component is on.
+    pub entity: Entity,
+    /// The type of the light.
+    pub kind: LightKind,
+    /// The color of the light.
+    pub color: Color,
+    /// The brightness of the light.
+    ///
+    /// This is the [`illuminance`](DirectionalLight::illuminance) in lux for directional lights,
+    /// and the [`intensity`](PointLight::intensity) in lumens for point and spot lights.
+    pub intensity: f32,
+    /// The [`range`](PointLight::range) of point and spot lights, or [`None`] for directional
+    /// lights, which light the whole world.
+    pub range: Option<f32>,
+    /// Whether the light casts shadows.
+    pub shadows_enabled: bool,
+    /// The global transform of the light, or the identity transform if the light has no
+    /// [`GlobalTransform`].
+    pub transform: GlobalTransform,
+}
+
+impl LightInfo {
+    /// Returns the [`LightInfo`] of the light on `entity`, or [`None`] if it has no
+    /// [`PointLight`], [`SpotLight`] or [`DirectionalLight`] component.
+    pub fn from_entity(entity: EntityRef) -> Option<Self> {
+        let (kind, color, intensity, range, shadows_enabled) =
+            if let Some(light) = entity.get::<PointLight>() {
+                (
+                    LightKind::Point,
+                    light.color,
+                    light.intensity,
+                    Some(light.range),
+                    light.shadows_enabled,
+                )
+            } else if let Some(light) = entity.get::<SpotLight>() {
+                (
+                    LightKind::Spot,
+                    light.color,
+                    light.intensity,
+                    Some(light.range),
+                    light.shadows_enabled,
+                )
+            } else if let Some(light) = entity.get::<DirectionalLight>() {
+                (
+                    LightKind::Directional,
+                    light.color,
+                    light.illuminance,
+                    None,
+                    light.shadows_enabled,
+                )
+            } else {
+                return None;
+            };
+
+        Some(Self {
+            entity: entity.id(),
+            kind,
+            color,
+            intensity,
+            range,
+            shadows_enabled,
+            transform: entity.get::<GlobalTransform>().copied().unwrap_or_default(),
+        })
+    }
+
+    /// Returns a score of how much this light matters on screen for a camera at
+    /// `camera_position`, for prioritizing lights in shadow and streaming budgets. More important
+    /// lights have higher scores.
+    ///
+    /// Directional lights score their [`illuminance`](DirectionalLight::illuminance). Point and
+    /// spot lights score the illuminance they would give at the camera, treating distances under a
+    /// meter as one meter so that the score stays finite, weighted by how much of the view their
+    /// range covers: fully while the camera is within range, and falling off with the square of
+    /// the distance beyond it. This is a heuristic: it ignores the cone of spot lights, occlusion
+    /// and the camera's field of view. The light's range isn't scaled for lights with
+    /// [`ScaleAffectsRange`].
+    pub fn importance(&self, camera_position: Vec3) -> f32 {
+        let Some(range) = self.range else {
+            return self.intensity;
+        };
+        let distance_squared = self
+            .transform
+            .translation()
+            .distance_squared(camera_position);
+        let illuminance =
+            self.intensity / (4.0 * core::f32::consts::PI * distance_squared.max(1.0));
+        let coverage = (range * range / distance_squared.max(f32::EPSILON)).min(1.0);
+        illuminance * coverage
+    }
+}
+
+/// Returns a [`LightInfo`] for every [`PointLight`], [`SpotLight`] and [`DirectionalLight`] in
+/// the world.
+///
+/// This visits every entity in the world, so it's meant for editors and debugging tools rather
+/// than for use every frame.
+pub fn iter_lights(world: &World) -> impl Iterator<Item = LightInfo> + '_ {
+    world.iter_entities().filter_map(LightInfo::from_entity)
+}
+
+/// Returns a [`LightInfo`] for every shadow-casting [`PointLight`], [`SpotLight`] and
+/// [`DirectionalLight`] in the world, from the most to the least important for a camera at
+/// `camera_position`, as scored by [`LightInfo::importance`].
+///
+/// Like [`iter_lights`], this visits every entity in the world.
+pub fn shadow_casting_lights_by_importance(world: &World, camera_position: Vec3) -> Vec<LightInfo> {
+    let mut lights = iter_lights(world)
+        .filter(|light| light.shadows_enabled)
+        .map(|light| (light.importance(camera_position), light))
+        .collect::<Vec<_>>();
+    lights.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+    lights.into_iter().map(|(_, light)| light).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iter_lights_normalizes_each_light_type() {
+        let mut world = World::new();
+        let point = world
+            .spawn(PointLight {
+                intensity: 100.0,
+                shadows_enabled: true,
+                ..Default::default()
+            })
+            .id();
+        let spot = world
+            .spawn(SpotLight {
+                intensity: 200.0,
+                ..Default::default()
+            })
+            .id();
+        let directional = world
+            .spawn((
+                DirectionalLight {
+                    illuminance: 300.0,
+                    ..Default::default()
+                },
+                GlobalTransform::from_xyz(1.0, 2.0, 3.0),
+            ))
+            .id();
+        world.spawn(Transform::default());
+
+        let mut lights = iter_lights(&world).collect::<Vec<_>>();
+        lights.sort_by_key(|light| light.entity.index());
+        assert_eq!(lights.len(), 3);
+
+        assert_eq!(lights[0].entity, point);
+        assert_eq!(lights[0].kind, LightKind::Point);
+        assert_eq!(lights[0].intensity, 100.0);
+        assert!(lights[0].shadows_enabled);
+
+        assert_eq!(lights[1].entity, spot);
+        assert_eq!(lights[1].kind, LightKind::Spot);
+        assert_eq!(lights[1].intensity, 200.0);
+        assert!(!lights[1].shadows_enabled);
+
+        assert_eq!(lights[2].entity, directional);
+        assert_eq!(lights[2].kind, LightKind::Directional);
+        assert_eq!(lights[2].intensity, 300.0);
+        assert_eq!(lights[2].transform.translation(), Vec3::new(1.0, 2.0, 3.0));
+    }
+
+    #[test]
+    fn near_bright_lights_are_more_important() {
+        let mut world = World::new();
+        let spawn_light = |world: &mut World, intensity, distance: f32, shadows_enabled| {
+            world
+                .spawn((
+                    PointLight {
+                        intensity,
+                        range: 20.0,
+                        shadows_enabled,
+                        ..Default::default()
+                    },
+                    GlobalTransform::from_xyz(distance, 0.0, 0.0),
+                ))
+                .id()
+        };
+        let far_dim = spawn_light(&mut world, 1_000.0, 50.0, true);
+        let near_bright = spawn_light(&mut world, 100_000.0, 5.0, true);
+        let near_dim = spawn_light(&mut world, 1_000.0, 5.0, true);
+        spawn_light(&mut world, 1_000_000.0, 1.0, false);
+
+        let lights = shadow_casting_lights_by_importance(&world, Vec3::ZERO)
+            .into_iter()
+            .map(|light| light.entity)
+            .collect::<Vec<_>>();
+        assert_eq!(lights, vec![near_bright, near_dim, far_dim]);
+    }
+}
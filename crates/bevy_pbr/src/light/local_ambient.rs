@@ -0,0 +1,130 @@
+use bevy_color::ColorToComponents;
+use bevy_math::Vec3;
+
+use super::*;
+
+/// A box-shaped region with its own ambient light, for localized fill lighting, such as a cave
+/// entrance that should be darker than the surface around it.
+///
+/// Inside the central part of the [`bounds`](Self::bounds), this replaces the [`AmbientLight`].
+/// Toward the edges of the bounds, it fades out and blends with the [`AmbientLight`], and
+/// overlapping volumes blend with each other. This is a lightweight alternative to light probes,
+/// as it only affects the ambient term.
+///
+/// Like light probes, volumes are assigned to the clusters they overlap, so any number of them may
+/// be in view. This requires storage buffers, so local ambient volumes have no effect on platforms
+/// without them, such as WebGL 2.
+#[derive(Component, Clone, Debug, ExtractComponent, Reflect)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct LocalAmbient {
+    /// The world-space region affected by this volume.
+    pub bounds: Aabb,
+
+    /// The color of the ambient light in this volume.
+    pub color: Color,
+
+    /// A direct scale factor multiplied with `color` before being passed to the shader, in the
+    /// same units as [`AmbientLight::brightness`].
+    pub brightness: f32,
+}
+
+impl Default for LocalAmbient {
+    fn default() -> Self {
+        Self {
+            bounds: Aabb::default(),
+            color: Color::WHITE,
+            brightness: AmbientLight::default().brightness,
+        }
+    }
+}
+
+impl LocalAmbient {
+    /// Returns how strongly this volume affects the ambient light at `position`, from 0.0 outside
+    /// of the bounds to 1.0 in the central half of the bounds along each axis.
+    ///
+    /// This matches the weight used by the shader.
+    pub fn weight_at(&self, position: Vec3A) -> f32 {
+        let half_extents = self.bounds.half_extents.max(Vec3A::splat(f32::EPSILON));
+        let distance = ((position - self.bounds.center).abs() / half_extents).to_array();
+        distance
+            .iter()
+            .map(|&distance| 1.0 - smoothstep(0.5, 1.0, distance))
+            .product()
+    }
+
+    /// Returns the ambient color of this volume, premultiplied by its brightness.
+    pub(crate) fn shader_color(&self) -> Vec3 {
+        LinearRgba::from(self.color).to_vec3() * self.brightness
+    }
+}
+
+fn smoothstep(edge0: f32, edge1: f32, x: f32) -> f32 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Blends `global_color` with the colors of the local ambient volumes affecting a point, given
+    /// as `(weight, color)` pairs, in the same way as the shader.
+    ///
+    /// Where the weights sum to less than one, the remainder is made up by `global_color`.
+    /// Otherwise, the local colors are averaged by weight.
+    fn blend_local_ambients(
+        global_color: Vec3,
+        local_ambients: impl IntoIterator<Item = (f32, Vec3)>,
+    ) -> Vec3 {
+        let (total_weight, local_color) = local_ambients.into_iter().fold(
+            (0.0, Vec3::ZERO),
+            |(total_weight, local_color), (weight, color)| {
+                (total_weight + weight, local_color + color * weight)
+            },
+        );
+        (local_color + global_color * (1.0 - total_weight).max(0.0)) / total_weight.max(1.0)
+    }
+
+    #[test]
+    fn local_ambients_blend_smoothly() {
+        let global_color = Vec3::splat(1.0);
+        let dark = LocalAmbient {
+            bounds: Aabb::from_min_max(Vec3::new(-10.0, -5.0, -5.0), Vec3::new(2.0, 5.0, 5.0)),
+            color: Color::WHITE,
+            brightness: 0.0,
+        };
+        let bright = LocalAmbient {
+            bounds: Aabb::from_min_max(Vec3::new(-2.0, -5.0, -5.0), Vec3::new(10.0, 5.0, 5.0)),
+            color: Color::WHITE,
+            brightness: 4.0,
+        };
+        let color_at = |x: f32| {
+            let position = Vec3A::new(x, 0.0, 0.0);
+            blend_local_ambients(
+                global_color,
+                [&dark, &bright].map(|local_ambient| {
+                    (
+                        local_ambient.weight_at(position),
+                        local_ambient.shader_color(),
+                    )
+                }),
+            )
+        };
+
+        // Deep inside each volume, and outside of both, only one color applies.
+        assert_eq!(color_at(-6.0), Vec3::ZERO);
+        assert_eq!(color_at(6.0), Vec3::splat(4.0));
+        assert_eq!(color_at(20.0), global_color);
+
+        // In between, the color changes gradually.
+        let mut previous_color = color_at(-12.0);
+        for step in 1..=320 {
+            let color = color_at(-12.0 + step as f32 * 0.1);
+            assert!(
+                (color - previous_color).abs().max_element() < 0.25,
+                "ambient color jumped from {previous_color} to {color}"
+            );
+            previous_color = color;
+        }
+    }
+}
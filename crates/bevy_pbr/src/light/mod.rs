@@ -1,35 +1,56 @@
+use alloc::sync::Arc;
+use bevy_derive::Deref;
+use bevy_diagnostic::FrameCount;
 use bevy_ecs::{
     entity::{EntityHashMap, EntityHashSet},
     prelude::*,
+    query::{QueryData, QueryFilter, QueryItem},
 };
-use bevy_math::{ops, Mat4, Vec3A, Vec4};
+use bevy_math::{ops, Affine3A, Mat4, Quat, Vec3, Vec3A, Vec4};
 use bevy_reflect::prelude::*;
 use bevy_render::{
-    camera::{Camera, Projection},
+    camera::{Camera, CameraProjection, PerspectiveProjection, Projection},
     extract_component::ExtractComponent,
     extract_resource::ExtractResource,
     mesh::Mesh3d,
-    primitives::{Aabb, CascadesFrusta, CubemapFrusta, Frustum, Sphere},
+    primitives::{Aabb, CascadesFrusta, CubemapFrusta, Frustum, HalfSpace, Sphere},
+    render_resource::TextureFormat,
     view::{
         InheritedVisibility, NoFrustumCulling, PreviousVisibleEntities, RenderLayers,
         ViewVisibility, VisibilityClass, VisibilityRange, VisibleEntityRanges,
     },
 };
+use bevy_time::Time;
 use bevy_transform::components::{GlobalTransform, Transform};
-use bevy_utils::Parallel;
+use bevy_utils::{once, Parallel};
 use core::{marker::PhantomData, ops::DerefMut};
+use thiserror::Error;
+use tracing::warn;
 
 use crate::*;
 
 mod ambient_light;
-pub use ambient_light::AmbientLight;
+pub use ambient_light::{tween_ambient_light, AmbientLight, AmbientLightTween};
+mod local_ambient;
+pub use local_ambient::LocalAmbient;
 
 mod point_light;
-pub use point_light::PointLight;
+pub use point_light::{PointLight, PointLightShadowShape, PointShadowTechnique};
 mod spot_light;
-pub use spot_light::SpotLight;
+pub use spot_light::{SpotLight, SpotLightCone};
 mod directional_light;
-pub use directional_light::DirectionalLight;
+pub use directional_light::{
+    sun_screen_position, update_auto_fill_lights, update_dominant_directional_light, AutoFillLight,
+    DirectionalLight, DominantDirectionalLight, ShadowBounds, TemporalShadowUpdate,
+};
+mod influence;
+pub use influence::{light_affects_aabb, lights_overlapping, LightInfluence};
+mod light_info;
+pub use light_info::{iter_lights, shadow_casting_lights_by_importance, LightInfo, LightKind};
+mod shadow_caster_count;
+pub use shadow_caster_count::ShadowCasterCountDiagnosticsPlugin;
+mod shadow_prewarm;
+pub use shadow_prewarm::{ReservedShadowMaps, ShadowPrewarm};
 
 /// Constants for operating with the light units: lumens, and lux.
 pub mod light_consts {
@@ -48,6 +69,47 @@ pub mod light_consts {
         pub const LUMENS_PER_LED_WATTS: f32 = 90.0;
         pub const LUMENS_PER_INCANDESCENT_WATTS: f32 = 13.8;
         pub const LUMENS_PER_HALOGEN_WATTS: f32 = 19.8;
+
+        /// Returns the lumens emitted by an LED lamp of the given wattage.
+        ///
+        /// This is the unit of [`PointLight::intensity`](crate::PointLight::intensity) and
+        /// [`SpotLight::intensity`](crate::SpotLight::intensity), so it can be used directly:
+        ///
+        /// ```
+        /// # use bevy_pbr::{light_consts::lumens::from_led_watts, PointLight};
+        /// let light = PointLight {
+        ///     intensity: from_led_watts(10.0),
+        ///     ..Default::default()
+        /// };
+        /// ```
+        pub const fn from_led_watts(watts: f32) -> f32 {
+            watts * LUMENS_PER_LED_WATTS
+        }
+
+        /// Returns the lumens emitted by an incandescent lamp of the given wattage.
+        pub const fn from_incandescent_watts(watts: f32) -> f32 {
+            watts * LUMENS_PER_INCANDESCENT_WATTS
+        }
+
+        /// Returns the lumens emitted by a halogen lamp of the given wattage.
+        pub const fn from_halogen_watts(watts: f32) -> f32 {
+            watts * LUMENS_PER_HALOGEN_WATTS
+        }
+
+        /// Returns the wattage of an LED lamp emitting the given lumens.
+        pub const fn to_led_watts(lumens: f32) -> f32 {
+            lumens / LUMENS_PER_LED_WATTS
+        }
+
+        /// Returns the wattage of an incandescent lamp emitting the given lumens.
+        pub const fn to_incandescent_watts(lumens: f32) -> f32 {
+            lumens / LUMENS_PER_INCANDESCENT_WATTS
+        }
+
+        /// Returns the wattage of a halogen lamp emitting the given lumens.
+        pub const fn to_halogen_watts(lumens: f32) -> f32 {
+            lumens / LUMENS_PER_HALOGEN_WATTS
+        }
     }
 
     /// Predefined for lux values in several locations.
@@ -106,20 +168,49 @@ impl<M: Material> Default for ShadowsEnabled<M> {
 /// # use bevy_app::prelude::*;
 /// # use bevy_pbr::PointLightShadowMap;
 /// App::new()
-///     .insert_resource(PointLightShadowMap { size: 2048 });
+///     .insert_resource(PointLightShadowMap {
+///         size: 2048,
+///         ..Default::default()
+///     });
 /// ```
 #[derive(Resource, Clone, Debug, Reflect)]
 #[reflect(Resource, Debug, Default, Clone)]
 pub struct PointLightShadowMap {
     /// The width and height of each of the 6 faces of the cubemap.
     ///
+    /// Each face is a perspective projection from the light's position, so a shadow texel always
+    /// covers the same angle, and its world-space size grows linearly with the distance from the
+    /// light. Unlike directional light cascades, splitting a face's depth range into slices
+    /// wouldn't help: every slice would cover the same field of view at the same resolution. For
+    /// lights with a very large [`range`](PointLight::range), raise this size, or use
+    /// [`SpotLight`]s aimed at the areas that need detailed shadows.
+    ///
     /// Defaults to `1024`.
     pub size: usize,
+    /// Extra field of view, in radians, added to the 90° field of view of each cubemap face.
+    ///
+    /// With a positive padding, adjacent faces overlap slightly. Sampling still uses the face that
+    /// the fragment lies in, but filtering near the edge of a face reads from the overlapping
+    /// border instead of running off the edge, which hides seams between faces. The cost is that
+    /// each face covers a slightly larger area at the same resolution.
+    ///
+    /// Defaults to `0.0`.
+    pub cube_face_fov_padding: f32,
 }
 
 impl Default for PointLightShadowMap {
     fn default() -> Self {
-        Self { size: 1024 }
+        Self {
+            size: 1024,
+            cube_face_fov_padding: 0.0,
+        }
+    }
+}
+
+impl PointLightShadowMap {
+    /// Returns the field of view, in radians, that each cubemap face is rendered with.
+    pub fn cube_face_fov(&self) -> f32 {
+        core::f32::consts::FRAC_PI_2 + self.cube_face_fov_padding
     }
 }
 
@@ -150,9 +241,123 @@ impl Default for DirectionalLightShadowMap {
     }
 }
 
+/// A resource that selects the depth format of all shadow maps, trading depth precision for memory
+/// and bandwidth.
+///
+/// The shadow maps of all point lights share one texture, as do those of all spot and directional
+/// lights, so the format applies to every light. [`Depth16`](Self::Depth16) halves the memory of
+/// shadow maps and the bandwidth of rendering and sampling them, which is often worth it on
+/// memory-constrained platforms such as mobile.
+///
+/// [`BakedPointShadow`](crate::BakedPointShadow) cube maps and
+/// [`ExportShadowMap`](crate::ExportShadowMap) targets must use the same format as the shadow
+/// maps. Meshlet meshes only render into [`Depth32F`](Self::Depth32F) shadow maps.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, Hash, ExtractResource, Reflect)]
+#[reflect(Resource, Debug, Default, PartialEq, Clone)]
+pub enum ShadowDepthFormat {
+    /// 16-bit normalized depth.
+    ///
+    /// This is precise enough for most lights, but the depth range of a shadow map is split into
+    /// only 65536 steps, so lights with a long depth range, such as large directional light
+    /// cascades, may need a higher [`shadow_depth_bias`](DirectionalLight::shadow_depth_bias) to
+    /// avoid shadow acne.
+    Depth16,
+    /// 32-bit floating-point depth, the most precise format.
+    #[default]
+    Depth32F,
+}
+
+impl ShadowDepthFormat {
+    /// Returns the format of shadow map textures with this depth format.
+    pub fn texture_format(self) -> TextureFormat {
+        match self {
+            ShadowDepthFormat::Depth16 => TextureFormat::Depth16Unorm,
+            ShadowDepthFormat::Depth32F => TextureFormat::Depth32Float,
+        }
+    }
+}
+
+/// The vertical field of view assumed when sizing cascades without knowing the camera's projection.
+const ASSUMED_CASCADE_FOV: f32 = core::f32::consts::FRAC_PI_4;
+/// The aspect ratio assumed when sizing cascades without knowing the camera's projection.
+const ASSUMED_CASCADE_ASPECT_RATIO: f32 = 16.0 / 9.0;
+
+/// Returns the smallest power-of-two [`DirectionalLightShadowMap::size`] at which the shadow map
+/// texels of the first cascade of `config` are at most `target_texel_size` meters wide.
+///
+/// Like [`CascadeShadowConfigBuilder::from_texel_density`], this assumes the 45° vertical field of
+/// view of the default [`PerspectiveProjection`] and a 16:9 aspect ratio, as the area covered by
+/// each cascade depends on the camera's projection. Wider views will get larger texels.
+pub fn directional_shadow_size_for(config: &CascadeShadowConfig, target_texel_size: f32) -> usize {
+    let projection = PerspectiveProjection {
+        fov: ASSUMED_CASCADE_FOV,
+        aspect_ratio: ASSUMED_CASCADE_ASPECT_RATIO,
+        ..Default::default()
+    };
+    let minimum_distance = clamped_minimum_distance(config, projection.near);
+    let corners = projection.get_frustum_corners(-minimum_distance, -config.bounds[0]);
+
+    // This inverts `cascade_texel_size = cascade_diameter / cascade_texture_size` from
+    // `calculate_focused_cascade`.
+    let size = (cascade_diameter(&corners) / target_texel_size).ceil() as usize;
+    size.next_power_of_two()
+}
+
+/// The number of shadow-casting [`DirectionalLight`]s above which a warning is logged.
+///
+/// Each shadow-casting directional light renders a full set of shadow cascades for every view,
+/// so having many of them, for example from a duplicated sun in an instanced scene, is costly.
+/// The warning is logged once each time the number of lights rises above this threshold.
+///
+/// ```
+/// # use bevy_app::prelude::*;
+/// # use bevy_pbr::DirectionalLightShadowWarningThreshold;
+/// App::new()
+///     .insert_resource(DirectionalLightShadowWarningThreshold(8));
+/// ```
+#[derive(Resource, Clone, Copy, Debug, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct DirectionalLightShadowWarningThreshold(pub usize);
+
+impl Default for DirectionalLightShadowWarningThreshold {
+    fn default() -> Self {
+        Self(4)
+    }
+}
+
+/// Insert this resource to log suggestions for [`CascadeShadowConfig`]s that are valid, but
+/// likely to give poor shadows.
+///
+/// Each config is inspected when it's added or changed, and when this resource is inserted. The
+/// linter looks for a first cascade that covers more than half of the shadow distance, large
+/// cascade overlaps, and a single cascade covering a long distance.
+///
+/// ```
+/// # use bevy_app::prelude::*;
+/// # use bevy_pbr::ShadowConfigLinter;
+/// App::new()
+///     .insert_resource(ShadowConfigLinter);
+/// ```
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct ShadowConfigLinter;
+
+/// The overlap proportion above which [`ShadowConfigLinter`] suggests a smaller overlap.
+const LINTED_MAX_OVERLAP_PROPORTION: f32 = 0.5;
+
+/// The shadow distance above which [`ShadowConfigLinter`] suggests using more than one cascade.
+const LINTED_MAX_SINGLE_CASCADE_DISTANCE: f32 = 50.0;
+
 /// Controls how cascaded shadow mapping works.
 /// Prefer using [`CascadeShadowConfigBuilder`] to construct an instance.
 ///
+/// Changing this component rebuilds the light's cascades and shadow maps on that frame, even if
+/// the camera didn't move and the light's [`TemporalShadowUpdate`] would otherwise skip the
+/// frame. Mutating it, or calling [`DetectChangesMut::set_changed`] on it, is the supported way
+/// to invalidate the cascades.
+///
+/// [`DetectChangesMut::set_changed`]: bevy_ecs::change_detection::DetectChangesMut::set_changed
+///
 /// ```
 /// # use bevy_pbr::CascadeShadowConfig;
 /// # use bevy_pbr::CascadeShadowConfigBuilder;
@@ -170,8 +375,36 @@ pub struct CascadeShadowConfig {
     pub bounds: Vec<f32>,
     /// The proportion of overlap each cascade has with the previous cascade.
     pub overlap_proportion: f32,
-    /// The (positive) distance to the near boundary of the first cascade.
-    pub minimum_distance: f32,
+    /// The overlap proportion of each boundary between two cascades, overriding
+    /// [`overlap_proportion`](Self::overlap_proportion).
+    ///
+    /// The value at index `i` is the proportion of overlap between cascade `i + 1` and cascade
+    /// `i`. More overlap gives a smoother transition between the two cascades, at the cost of
+    /// spreading the next cascade's resolution over a larger depth range. Transitions are usually
+    /// most visible between the near cascades, so these can be given more overlap than the far
+    /// ones. Boundaries without a value use [`overlap_proportion`](Self::overlap_proportion).
+    ///
+    /// By default, this is empty, and every boundary uses the same overlap.
+    pub boundary_overlap_proportions: Vec<f32>,
+    /// The distance to the near boundary of the first cascade.
+    pub minimum_distance: CascadeMinimumDistance,
+    /// Disables snapping each cascade's position to whole shadow map texels.
+    ///
+    /// Snapping keeps shadow edges from shimmering as the camera moves, so this is only meant for
+    /// debugging: if shimmer remains with snapping enabled, it has some other cause.
+    pub disable_cascade_snapping: bool,
+    /// The number of cascades, counting back from the farthest one, whose shadow maps store depth
+    /// with a logarithmic rather than linear distribution.
+    ///
+    /// A far cascade covering a large distance spreads the depth precision of its shadow map
+    /// thinly. A logarithmic distribution gives more precision to casters near the top of the
+    /// cascade, as seen from the light, which can reduce acne and light leaking on distant
+    /// geometry. The depth is remapped per fragment when rendering and sampling the shadow map,
+    /// so these cascades can't use early depth testing, which makes them more expensive to render.
+    /// See [`Cascade::logarithmic_depth`].
+    ///
+    /// By default, this is 0, and all cascades use linear depth.
+    pub logarithmic_depth_cascades: usize,
 }
 
 impl Default for CascadeShadowConfig {
@@ -180,6 +413,151 @@ impl Default for CascadeShadowConfig {
     }
 }
 
+impl CascadeShadowConfig {
+    /// Returns a config that draws shadows up to `maximum_distance` away from the camera, and
+    /// uses the defaults of [`CascadeShadowConfigBuilder`] for everything else.
+    ///
+    /// The first cascade covers the same proportion of the distance as by default, where it ends
+    /// at 10 of the 150 meters. If that would end it before the default minimum distance, a single
+    /// cascade is used.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `maximum_distance` isn't greater than the default minimum distance of 0.1 meters.
+    ///
+    /// ```
+    /// # use bevy_pbr::CascadeShadowConfig;
+    /// let config = CascadeShadowConfig::with_max_distance(500.0);
+    /// let far_bound = *config.bounds.last().unwrap();
+    /// assert!((far_bound - 500.0).abs() < 0.01);
+    /// ```
+    pub fn with_max_distance(maximum_distance: f32) -> Self {
+        let defaults = CascadeShadowConfigBuilder::default();
+        let first_cascade_far_bound =
+            maximum_distance * defaults.first_cascade_far_bound / defaults.maximum_distance;
+        let num_cascades = if first_cascade_far_bound > DEFAULT_CASCADE_MINIMUM_DISTANCE {
+            defaults.num_cascades
+        } else {
+            1
+        };
+        CascadeShadowConfigBuilder {
+            num_cascades,
+            maximum_distance,
+            first_cascade_far_bound,
+            ..defaults
+        }
+        .build()
+    }
+
+    /// Changes the number of cascades to `num_cascades`, recomputing [`bounds`](Self::bounds) in
+    /// place as [`CascadeShadowConfigBuilder`] does, so that the first cascade ends at
+    /// `first_cascade_far_bound` and the last one at `maximum_distance`.
+    ///
+    /// This is meant for changing the shadow quality at runtime, for example on hardware that
+    /// turns out to be slower than expected. Everything else, such as the
+    /// [`overlap_proportion`](Self::overlap_proportion) and the
+    /// [`minimum_distance`](Self::minimum_distance), is kept.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `num_cascades` is 0.
+    ///
+    /// ```
+    /// # use bevy_pbr::CascadeShadowConfig;
+    /// let mut config = CascadeShadowConfig::default();
+    /// config.set_num_cascades(2, 100.0, 10.0);
+    /// assert_eq!(config.bounds.len(), 2);
+    /// assert!((config.bounds[1] - 100.0).abs() < 0.01);
+    /// ```
+    pub fn set_num_cascades(
+        &mut self,
+        num_cascades: usize,
+        maximum_distance: f32,
+        first_cascade_far_bound: f32,
+    ) {
+        assert!(
+            num_cascades > 0,
+            "num_cascades must be positive, but was {num_cascades}"
+        );
+        self.bounds =
+            calculate_cascade_bounds(num_cascades, first_cascade_far_bound, maximum_distance);
+    }
+
+    /// Returns the proportion of overlap between cascade `boundary + 1` and cascade `boundary`.
+    ///
+    /// See [`boundary_overlap_proportions`](Self::boundary_overlap_proportions).
+    pub fn boundary_overlap_proportion(&self, boundary: usize) -> f32 {
+        self.boundary_overlap_proportions
+            .get(boundary)
+            .copied()
+            .unwrap_or(self.overlap_proportion)
+    }
+}
+
+/// The default [`CascadeMinimumDistance::Absolute`] distance.
+const DEFAULT_CASCADE_MINIMUM_DISTANCE: f32 = 0.1;
+
+/// The distance from the camera to the near boundary of the first shadow cascade, see
+/// [`CascadeShadowConfigBuilder::minimum_distance`].
+#[derive(Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Default, Debug, PartialEq, Clone)]
+pub enum CascadeMinimumDistance {
+    /// A fixed, positive distance from the camera.
+    Absolute(f32),
+    /// The distance to the near plane of the camera's [`Projection`], so that shadows start right
+    /// in front of the camera. This is useful for first-person views with close-up shadows.
+    CameraNear,
+}
+
+impl Default for CascadeMinimumDistance {
+    fn default() -> Self {
+        Self::Absolute(DEFAULT_CASCADE_MINIMUM_DISTANCE)
+    }
+}
+
+impl From<f32> for CascadeMinimumDistance {
+    fn from(distance: f32) -> Self {
+        Self::Absolute(distance)
+    }
+}
+
+impl CascadeMinimumDistance {
+    /// Returns the distance from the camera, for a camera whose near plane is `camera_near` away.
+    pub fn distance(self, camera_near: f32) -> f32 {
+        match self {
+            Self::Absolute(distance) => distance,
+            Self::CameraNear => camera_near.max(0.0),
+        }
+    }
+}
+
+/// Returns the corners of the frustum slice of a single cascade ending at `far_bound`, in the
+/// same order as [`CascadeFrustumSlices::update`] computes them for several cascades.
+fn single_cascade_frustum_corners(
+    projection: &Projection,
+    minimum_distance: f32,
+    far_bound: f32,
+) -> [Vec3A; 8] {
+    // Negate bounds as -z is camera forward direction.
+    projection.get_frustum_corners(-minimum_distance, -far_bound)
+}
+
+/// Returns the distance from the camera to the near plane of `projection`.
+fn projection_near(projection: &Projection) -> f32 {
+    match projection {
+        Projection::Perspective(perspective) => perspective.near,
+        Projection::Orthographic(orthographic) => orthographic.near,
+        Projection::Custom(_) => {
+            // Solve for the view-space depth that maps to the near plane, at NDC depth 1 with
+            // reversed Z.
+            let clip_from_view = projection.get_clip_from_view();
+            let (a, b) = (clip_from_view.z_axis.z, clip_from_view.w_axis.z);
+            let (c, d) = (clip_from_view.z_axis.w, clip_from_view.w_axis.w);
+            (b - d) / (a - c)
+        }
+    }
+}
+
 fn calculate_cascade_bounds(
     num_cascades: usize,
     nearest_bound: f32,
@@ -220,7 +598,10 @@ pub struct CascadeShadowConfigBuilder {
     /// texel resolution of the first cascade is dominated by the width / height of the view frustum plane
     /// at `first_cascade_far_bound` rather than the depth of the frustum from `minimum_distance` to
     /// `first_cascade_far_bound`.
-    pub minimum_distance: f32,
+    ///
+    /// [`CascadeMinimumDistance::CameraNear`] starts the first cascade at the near plane of each
+    /// view's projection instead of at a fixed distance.
+    pub minimum_distance: CascadeMinimumDistance,
     /// The maximum shadow distance.
     /// Areas further from the camera than this will likely receive no shadows.
     pub maximum_distance: f32,
@@ -232,9 +613,149 @@ pub struct CascadeShadowConfigBuilder {
     /// The overlap is used to make the transition from one cascade's shadow map to the next
     /// less abrupt by blending between both shadow maps.
     pub overlap_proportion: f32,
+    /// Overrides how the far bounds of the cascades are spaced.
+    ///
+    /// The function is called with the number of cascades, the far bound of the first cascade and
+    /// the maximum shadow distance, and must return the far bound of each cascade. The returned
+    /// bounds must be positive and strictly increasing.
+    ///
+    /// If this is [`None`], the in-between cascades are spaced exponentially.
+    pub split_fn: Option<CascadeSplitFn>,
 }
 
+/// A function computing the far bounds of the cascades for
+/// [`CascadeShadowConfigBuilder::split_fn`].
+pub type CascadeSplitFn = Arc<dyn Fn(usize, f32, f32) -> Vec<f32> + Send + Sync>;
+
 impl CascadeShadowConfigBuilder {
+    /// A low quality preset: a single cascade covering 50 meters.
+    ///
+    /// This renders only one shadow map per view, so it's the cheapest option, but shadows near the
+    /// camera are blocky because the whole range shares a single
+    /// [`DirectionalLightShadowMap`] texture.
+    pub fn low() -> Self {
+        Self {
+            num_cascades: 1,
+            maximum_distance: 50.0,
+            first_cascade_far_bound: 50.0,
+            ..Default::default()
+        }
+    }
+
+    /// A medium quality preset: two cascades covering 100 meters.
+    ///
+    /// The first cascade gives shadows within 10 meters of the camera a shadow map of their own.
+    pub fn medium() -> Self {
+        Self {
+            num_cascades: 2.min(MAX_CASCADES_PER_LIGHT),
+            maximum_distance: 100.0,
+            first_cascade_far_bound: 10.0,
+            ..Default::default()
+        }
+    }
+
+    /// A high quality preset: four cascades covering 150 meters.
+    ///
+    /// This is the same as the default configuration.
+    pub fn high() -> Self {
+        Self {
+            num_cascades: 4.min(MAX_CASCADES_PER_LIGHT),
+            maximum_distance: 150.0,
+            first_cascade_far_bound: 10.0,
+            ..Default::default()
+        }
+    }
+
+    /// An ultra quality preset: four cascades covering 300 meters.
+    ///
+    /// The first cascade only covers 6 meters, so shadows close to the camera are sharper than with
+    /// [`high`](Self::high), and shadows are drawn twice as far away. Each further cascade covers a
+    /// larger area, so distant shadows are blurrier unless the
+    /// [`DirectionalLightShadowMap`] size is increased as well.
+    pub fn ultra() -> Self {
+        Self {
+            num_cascades: 4.min(MAX_CASCADES_PER_LIGHT),
+            maximum_distance: 300.0,
+            first_cascade_far_bound: 6.0,
+            ..Default::default()
+        }
+    }
+
+    /// Returns a builder whose first cascade has approximately `near_density` shadow map texels per
+    /// meter, for shadows drawn up to `max_distance` with a [`DirectionalLightShadowMap::size`] of
+    /// `shadow_map_size`.
+    ///
+    /// The area covered by each cascade depends on the camera's field of view and aspect ratio,
+    /// which aren't known here. This assumes the 45° vertical field of view of the default
+    /// [`PerspectiveProjection`](bevy_render::camera::PerspectiveProjection) and a 16:9 aspect
+    /// ratio. Wider views will get a lower density.
+    ///
+    /// Cascades are added until each one ends at most four times as far away as the previous one,
+    /// up to [`MAX_CASCADES_PER_LIGHT`]. If even the whole `max_distance` fits in a single cascade
+    /// at the requested density, a single cascade is used.
+    pub fn from_texel_density(
+        near_density: f32,
+        max_distance: f32,
+        shadow_map_size: usize,
+    ) -> Self {
+        const FOV: f32 = ASSUMED_CASCADE_FOV;
+        const ASPECT_RATIO: f32 = ASSUMED_CASCADE_ASPECT_RATIO;
+        const MAX_CASCADE_RATIO: f32 = 4.0;
+
+        let defaults = Self::default();
+
+        // The half-diagonal of the view frustum at a distance of one meter.
+        // `calculate_focused_cascade` sizes each cascade by the larger of the far plane diagonal
+        // and the body diagonal of its slice of the frustum, which for a slice starting close to
+        // the camera is about:
+        let half_diagonal = ops::tan(FOV * 0.5) * ops::sqrt(1.0 + ASPECT_RATIO * ASPECT_RATIO);
+        let diameter_per_meter =
+            (2.0 * half_diagonal).max(ops::sqrt(half_diagonal * half_diagonal + 1.0));
+
+        // `calculate_focused_cascade` rounds the diameter up to a whole number of meters.
+        let max_diameter = (shadow_map_size as f32 / near_density).floor().max(1.0);
+        let first_cascade_far_bound =
+            (max_diameter / diameter_per_meter).max(2.0 * DEFAULT_CASCADE_MINIMUM_DISTANCE);
+
+        if first_cascade_far_bound >= max_distance || MAX_CASCADES_PER_LIGHT == 1 {
+            return Self {
+                num_cascades: 1,
+                maximum_distance: max_distance,
+                first_cascade_far_bound: max_distance,
+                ..defaults
+            };
+        }
+
+        let num_cascades = 1
+            + (ops::ln(max_distance / first_cascade_far_bound) / ops::ln(MAX_CASCADE_RATIO)).ceil()
+                as usize;
+
+        Self {
+            num_cascades: num_cascades.min(MAX_CASCADES_PER_LIGHT),
+            maximum_distance: max_distance,
+            first_cascade_far_bound,
+            ..defaults
+        }
+    }
+
+    /// Checks that [`num_cascades`](Self::num_cascades) is supported by a render device whose
+    /// shadow map textures have up to `max_texture_array_layers` layers, as reported by the
+    /// `max_texture_array_layers` of the [`RenderDevice`](bevy_render::renderer::RenderDevice)
+    /// limits.
+    ///
+    /// Cascades beyond the limit aren't rendered, so checking this up front gives a descriptive
+    /// error instead. See [`max_shadow_cascades`].
+    pub fn validate(&self, max_texture_array_layers: u32) -> Result<(), TooManyCascadesError> {
+        let max_cascades = max_shadow_cascades(max_texture_array_layers);
+        if self.num_cascades > max_cascades {
+            return Err(TooManyCascadesError {
+                num_cascades: self.num_cascades,
+                max_cascades,
+            });
+        }
+        Ok(())
+    }
+
     /// Returns the cascade config as specified by this builder.
     pub fn build(&self) -> CascadeShadowConfig {
         assert!(
@@ -242,34 +763,59 @@ impl CascadeShadowConfigBuilder {
             "num_cascades must be positive, but was {}",
             self.num_cascades
         );
-        assert!(
-            self.minimum_distance >= 0.0,
-            "maximum_distance must be non-negative, but was {}",
-            self.minimum_distance
-        );
-        assert!(
-            self.num_cascades == 1 || self.minimum_distance < self.first_cascade_far_bound,
-            "minimum_distance must be less than first_cascade_far_bound, but was {}",
-            self.minimum_distance
-        );
-        assert!(
-            self.maximum_distance > self.minimum_distance,
-            "maximum_distance must be greater than minimum_distance, but was {}",
-            self.maximum_distance
-        );
+        // The near plane of the camera isn't known here, so it's clamped when the cascades are
+        // built instead.
+        if let CascadeMinimumDistance::Absolute(minimum_distance) = self.minimum_distance {
+            assert!(
+                minimum_distance >= 0.0,
+                "maximum_distance must be non-negative, but was {minimum_distance}"
+            );
+            assert!(
+                self.num_cascades == 1 || minimum_distance < self.first_cascade_far_bound,
+                "minimum_distance must be less than first_cascade_far_bound, but was \
+                {minimum_distance}"
+            );
+            assert!(
+                self.maximum_distance > minimum_distance,
+                "maximum_distance must be greater than minimum_distance, but was {}",
+                self.maximum_distance
+            );
+        }
         assert!(
             (0.0..1.0).contains(&self.overlap_proportion),
             "overlap_proportion must be in [0.0, 1.0) but was {}",
             self.overlap_proportion
         );
-        CascadeShadowConfig {
-            bounds: calculate_cascade_bounds(
+        let bounds = match &self.split_fn {
+            Some(split_fn) => split_fn(
                 self.num_cascades,
                 self.first_cascade_far_bound,
                 self.maximum_distance,
             ),
+            None => calculate_cascade_bounds(
+                self.num_cascades,
+                self.first_cascade_far_bound,
+                self.maximum_distance,
+            ),
+        };
+        assert!(
+            bounds.len() == self.num_cascades,
+            "split_fn must return one bound per cascade, but returned {} for {} cascades",
+            bounds.len(),
+            self.num_cascades
+        );
+        assert!(
+            bounds.first().is_some_and(|&bound| bound > 0.0)
+                && bounds.windows(2).all(|pair| pair[0] < pair[1]),
+            "cascade bounds must be positive and strictly increasing, but were {bounds:?}"
+        );
+        CascadeShadowConfig {
+            bounds,
             overlap_proportion: self.overlap_proportion,
+            boundary_overlap_proportions: Vec::new(),
             minimum_distance: self.minimum_distance,
+            disable_cascade_snapping: false,
+            logarithmic_depth_cascades: 0,
         }
     }
 }
@@ -291,14 +837,37 @@ impl Default for CascadeShadowConfigBuilder {
             } else {
                 4
             },
-            minimum_distance: 0.1,
+            minimum_distance: CascadeMinimumDistance::default(),
             maximum_distance: 150.0,
             first_cascade_far_bound: 10.0,
             overlap_proportion: 0.2,
+            split_fn: None,
         }
     }
 }
 
+/// Returns the maximum number of cascades per [`DirectionalLight`] on a render device whose shadow
+/// map textures have up to `max_texture_array_layers` layers.
+///
+/// This is at most [`MAX_CASCADES_PER_LIGHT`], which is 1 on WebGL 2.
+pub fn max_shadow_cascades(max_texture_array_layers: u32) -> usize {
+    MAX_CASCADES_PER_LIGHT.min(max_texture_array_layers as usize)
+}
+
+/// An error returned by [`CascadeShadowConfigBuilder::validate`] when more cascades are requested
+/// than the render device supports.
+#[derive(Error, Debug, Clone, Copy, PartialEq, Eq)]
+#[error(
+    "{num_cascades} shadow cascades were requested, but at most {max_cascades} are supported per \
+    directional light on this device; the extra cascades won't be rendered"
+)]
+pub struct TooManyCascadesError {
+    /// The requested number of cascades.
+    pub num_cascades: usize,
+    /// The maximum number of cascades supported.
+    pub max_cascades: usize,
+}
+
 impl From<CascadeShadowConfigBuilder> for CascadeShadowConfig {
     fn from(builder: CascadeShadowConfigBuilder) -> Self {
         builder.build()
@@ -310,6 +879,107 @@ impl From<CascadeShadowConfigBuilder> for CascadeShadowConfig {
 pub struct Cascades {
     /// Map from a view to the configuration of each of its [`Cascade`]s.
     pub cascades: EntityHashMap<Vec<Cascade>>,
+    /// Map from a view to the frustum slices covered by its cascades, kept from the last update.
+    #[reflect(ignore, clone)]
+    frustum_slices: EntityHashMap<CascadeFrustumSlices>,
+}
+
+/// The slices of a view's frustum that its [`Cascade`]s cover, in the view space of the camera.
+///
+/// The slices only depend on the view's projection and the bounds of the cascades, not on the
+/// rotation of the light or the position of the view, so they're kept between updates of the
+/// [`Cascades`] and only recomputed when their inputs change.
+#[derive(Clone, Debug, Default)]
+struct CascadeFrustumSlices {
+    /// The projection of the view that the slices were computed for.
+    clip_from_view: Mat4,
+    /// The near bound of each slice.
+    near_bounds: Vec<f32>,
+    /// The far bound of each slice, from [`CascadeShadowConfig::bounds`].
+    far_bounds: Vec<f32>,
+    /// The corners of each slice, in the order described in [`calculate_focused_cascade`].
+    corners: Vec<[Vec3A; 8]>,
+}
+
+impl CascadeFrustumSlices {
+    /// Updates the slices to span from `near_bounds` to `far_bounds` in `projection`.
+    ///
+    /// When only the near bounds changed, for example because the
+    /// [`minimum_distance`](CascadeShadowConfig::minimum_distance) or the
+    /// [`overlap_proportion`](CascadeShadowConfig::overlap_proportion) did, the far planes derived
+    /// from the [`bounds`](CascadeShadowConfig::bounds) are kept, and only the near planes are
+    /// recomputed. Projections compute each plane of the frustum independently, so this gives the
+    /// same slices as computing them from scratch.
+    fn update(&mut self, projection: &Projection, near_bounds: &[f32], far_bounds: &[f32]) {
+        let clip_from_view = projection.get_clip_from_view();
+        let recompute_far_planes =
+            clip_from_view != self.clip_from_view || far_bounds != self.far_bounds.as_slice();
+        if !recompute_far_planes && near_bounds == self.near_bounds.as_slice() {
+            return;
+        }
+        if recompute_far_planes {
+            // Negate bounds as -z is camera forward direction.
+            self.corners = near_bounds
+                .iter()
+                .zip(far_bounds)
+                .map(|(&near_bound, &far_bound)| {
+                    projection.get_frustum_corners(-near_bound, -far_bound)
+                })
+                .collect();
+        } else {
+            for ((corners, &near_bound), &previous_near_bound) in self
+                .corners
+                .iter_mut()
+                .zip(near_bounds)
+                .zip(&self.near_bounds)
+            {
+                if near_bound != previous_near_bound {
+                    let near_corners = projection.get_frustum_corners(-near_bound, -near_bound);
+                    corners[..4].copy_from_slice(&near_corners[..4]);
+                }
+            }
+        }
+
+        self.clip_from_view = clip_from_view;
+        self.near_bounds = near_bounds.to_vec();
+        self.far_bounds = far_bounds.to_vec();
+    }
+}
+
+impl Cascades {
+    /// Returns the nearest (and thus highest resolution) cascade of `view` whose shadow map covers
+    /// `world_position`, or [`None`] if the view has no cascades or none of them covers it.
+    pub fn cascade_at(&self, view: Entity, world_position: Vec3) -> Option<&Cascade> {
+        self.cascades.get(&view)?.iter().find(|cascade| {
+            let clip_position = cascade.clip_from_world.project_point3(world_position);
+            clip_position.x.abs() <= 1.0
+                && clip_position.y.abs() <= 1.0
+                && (0.0..=1.0).contains(&clip_position.z)
+        })
+    }
+
+    /// Returns the world-space size of a shadow map texel at `world_position` in the shadows of
+    /// `view`, using the cascade returned by [`cascade_at`](Self::cascade_at).
+    ///
+    /// This is useful for matching detail to shadow resolution, for example by passing the
+    /// camera's focus point each frame.
+    pub fn texel_size_at(&self, view: Entity, world_position: Vec3) -> Option<f32> {
+        self.cascade_at(view, world_position)
+            .map(|cascade| cascade.texel_size)
+    }
+
+    /// Returns every cascade of every view, along with the view entity and the index of the
+    /// cascade within that view, starting from 0 for the nearest cascade.
+    ///
+    /// The views are visited in no particular order, but the cascades of each view are in order.
+    pub fn iter_all(&self) -> impl Iterator<Item = (Entity, usize, &Cascade)> + '_ {
+        self.cascades.iter().flat_map(|(&view, cascades)| {
+            cascades
+                .iter()
+                .enumerate()
+                .map(move |(index, cascade)| (view, index, cascade))
+        })
+    }
 }
 
 #[derive(Clone, Debug, Default, Reflect)]
@@ -325,794 +995,5053 @@ pub struct Cascade {
     pub clip_from_world: Mat4,
     /// Size of each shadow map texel in world units.
     pub texel_size: f32,
+    /// The minimum corner of the bounding box of this cascade's view frustum slice, in the light's
+    /// view space (i.e. the rotation of the light, with no translation).
+    pub light_space_min: Vec3A,
+    /// The maximum corner of the bounding box of this cascade's view frustum slice, in the light's
+    /// view space (i.e. the rotation of the light, with no translation).
+    pub light_space_max: Vec3A,
+    /// A world-space sphere that bounds this cascade's view frustum slice, for sphere-based culling.
+    #[reflect(ignore, clone)]
+    pub world_bounding_sphere: Sphere,
+    /// The world-space corners of the box that this cascade's orthographic projection covers,
+    /// which is useful for drawing the cascade, for example with gizmos.
+    ///
+    /// The corners are in the same order as frustum corners: the bottom right, top right, top
+    /// left and bottom left corners of the near plane, nearest to the light, then the same for the
+    /// far plane. Unlike [`light_space_min`](Self::light_space_min) and
+    /// [`light_space_max`](Self::light_space_max), this is the box after it was snapped to texels
+    /// and resized to the cascade's diameter.
+    pub bounds_world: [Vec3A; 8],
+    /// Whether the shadow map stores depth with a logarithmic distribution, see
+    /// [`CascadeShadowConfig::logarithmic_depth_cascades`].
+    ///
+    /// The depth row of [`clip_from_cascade`](Self::clip_from_cascade) is linear either way, as a
+    /// projection matrix can't express a logarithm. The linear depth is remapped with
+    /// [`shadow_map_depth`](Self::shadow_map_depth) afterward.
+    pub logarithmic_depth: bool,
 }
 
-pub fn clear_directional_light_cascades(mut lights: Query<(&DirectionalLight, &mut Cascades)>) {
-    for (directional_light, mut cascades) in lights.iter_mut() {
-        if !directional_light.shadows_enabled {
-            continue;
-        }
-        cascades.cascades.clear();
-    }
-}
+/// Add this component to a [`Camera`] to compute [`Cascades`] for it even while it isn't
+/// [active](Camera::is_active).
+///
+/// By default, directional light cascades are only computed for active cameras. This is useful for
+/// cameras that are kept inactive and only rendered on demand, such as a render-to-texture minimap,
+/// so that their shadows are correct whenever they're rendered.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct ComputeCascadesForInactiveView;
 
-pub fn build_directional_light_cascades(
-    directional_light_shadow_map: Res<DirectionalLightShadowMap>,
-    views: Query<(Entity, &GlobalTransform, &Projection, &Camera)>,
-    mut lights: Query<(
-        &GlobalTransform,
-        &DirectionalLight,
-        &CascadeShadowConfig,
-        &mut Cascades,
-    )>,
-) {
-    let views = views
-        .iter()
-        .filter_map(|(entity, transform, projection, camera)| {
-            if camera.is_active {
-                Some((entity, projection, transform.to_matrix()))
-            } else {
-                None
-            }
-        })
-        .collect::<Vec<_>>();
+/// Add this component to a [`Camera`] to override the [`CascadeShadowConfig`] of every
+/// [`DirectionalLight`] for that view.
+///
+/// This allows views to have independent shadow quality, for example to give one viewport of a
+/// split-screen game fewer cascades or a shorter shadow distance. Views without this component
+/// use the [`CascadeShadowConfig`] of each light.
+#[derive(Component, Clone, Debug, Default, Reflect, ExtractComponent)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct ViewCascadeShadowConfig(pub CascadeShadowConfig);
 
-    for (transform, directional_light, cascades_config, mut cascades) in &mut lights {
-        if !directional_light.shadows_enabled {
-            continue;
-        }
+/// Add this component to a [`Camera`] to compute the [`Cascades`] of every [`DirectionalLight`]
+/// for that view as if the shadow map had a different size than [`DirectionalLightShadowMap`].
+///
+/// This is meant for views that render at a much lower resolution than the main view, such as a
+/// reflection probe. The cascades of the view are snapped to texels of this size, and their
+/// [`texel_size`](Cascade::texel_size), which scales the normal bias, matches it.
+///
+/// The shadow maps of all views share one texture, so they're still rendered at
+/// [`DirectionalLightShadowMap::size`]. A smaller size here doesn't save memory.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component, Debug, PartialEq, Clone)]
+pub struct ViewShadowMapSize(pub usize);
 
-        // It is very important to the numerical and thus visual stability of shadows that
-        // light_to_world has orthogonal upper-left 3x3 and zero translation.
-        // Even though only the direction (i.e. rotation) of the light matters, we don't constrain
-        // users to not change any other aspects of the transform - there's no guarantee
-        // `transform.to_matrix()` will give us a matrix with our desired properties.
-        // Instead, we directly create a good matrix from just the rotation.
-        let world_from_light = Mat4::from_quat(transform.compute_transform().rotation);
-        let light_to_world_inverse = world_from_light.inverse();
+/// Add this component to a [`Camera`] to concentrate the resolution of the first
+/// [`DirectionalLight`] shadow cascade around a point, such as the gaze point for foveated
+/// rendering in VR.
+///
+/// The first cascade only covers the part of its depth range within [`radius`](Self::radius) of
+/// [`point`](Self::point), which gives it finer texels, and the second cascade is extended to
+/// cover the rest of that depth range at its usual, coarser resolution. This has no effect on
+/// lights with a single cascade, or if the focus is outside of the first cascade's depth range.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component, Debug, PartialEq, Clone)]
+pub struct ShadowFocus {
+    /// The world-space point to concentrate shadow resolution around.
+    pub point: Vec3,
+    /// The world-space radius around [`point`](Self::point) covered by the first cascade.
+    pub radius: f32,
+}
 
-        for (view_entity, projection, view_to_world) in views.iter().copied() {
-            let camera_to_light_view = light_to_world_inverse * view_to_world;
-            let view_cascades = cascades_config
-                .bounds
-                .iter()
-                .enumerate()
-                .map(|(idx, far_bound)| {
-                    // Negate bounds as -z is camera forward direction.
-                    let z_near = if idx > 0 {
-                        (1.0 - cascades_config.overlap_proportion)
-                            * -cascades_config.bounds[idx - 1]
-                    } else {
-                        -cascades_config.minimum_distance
-                    };
-                    let z_far = -far_bound;
+/// A resource that skips rendering the shadow maps of all lights, for profiling and debugging.
+///
+/// The shadow cascades, frusta and shadow caster visibility of lights are still computed as usual,
+/// so they can be inspected or drawn with gizmos, but the shadow passes don't draw anything, and
+/// every light is shaded as if nothing cast shadows. Comparing frame times with and without this
+/// resource isolates the cost of rendering the shadow maps, unlike disabling
+/// [`shadows_enabled`](PointLight::shadows_enabled), which also skips the work on the CPU.
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct SkipShadowRender;
 
-                    let corners = projection.get_frustum_corners(z_near, z_far);
+/// A resource that makes all views share one set of [`DirectionalLight`] shadow cascades, instead
+/// of each view having its own.
+///
+/// Normally, the cascades are fitted to the frustum of each view and rendered once per view, so
+/// shadows cost as much as the number of views, as in a split-screen game. While this resource
+/// exists, each cascade instead covers the union of the matching slices of all of the view
+/// frusta. The shadow maps are then only rendered once per frame, and sampled by every view. In
+/// exchange, the cascades are larger, so shadows are blurrier, especially when the views look in
+/// different directions or are far apart.
+///
+/// Shared cascades use the [`CascadeShadowConfig`] of each light and the
+/// [`DirectionalLightShadowMap`] size, so the per-view [`ViewCascadeShadowConfig`],
+/// [`ViewShadowMapSize`], [`ShadowFocus`], [`ShadowLookAhead`] and [`CascadeSizeSmoothing`]
+/// components have no effect on them.
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct SharedCascades;
 
-                    calculate_cascade(
-                        corners,
-                        directional_light_shadow_map.size as f32,
-                        world_from_light,
-                        camera_to_light_view,
-                    )
-                })
-                .collect();
-            cascades.cascades.insert(view_entity, view_cascades);
-        }
-    }
+/// Add this component to a [`Camera`] to ease the size of its [`DirectionalLight`] shadow cascades
+/// toward their new size when the view frustum changes, such as while zooming, instead of snapping
+/// to it.
+///
+/// The size of each cascade follows the field of view, so zooming changes the shadow resolution,
+/// which is visible as a pop on the frame it happens. With this component, the size, and so the
+/// resolution, changes gradually instead. Until it catches up, a growing cascade is slightly too
+/// small to cover its slice of the view frustum, so shadows may be cut off at the edges of the view
+/// for a few frames. The cascades are still snapped to texels of the eased size.
+///
+/// Views without this component resize their cascades immediately.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component, Debug, PartialEq, Clone)]
+pub struct CascadeSizeSmoothing {
+    /// How quickly the cascade sizes approach their new size, per second.
+    ///
+    /// Each second, the remaining difference between the size of a cascade and its new size is
+    /// scaled by `exp(-rate)`, so higher values give faster transitions.
+    pub rate: f32,
 }
 
-/// Returns a [`Cascade`] for the frustum defined by `frustum_corners`.
+/// Add this component to a [`Camera`] to compute its [`DirectionalLight`] shadow cascades as if the
+/// camera were moved by [`offset`](Self::offset), to concentrate shadow resolution ahead of it.
 ///
-/// The corner vertices should be specified in the following order:
-/// first the bottom right, top right, top left, bottom left for the near plane, then similar for the far plane.
-fn calculate_cascade(
-    frustum_corners: [Vec3A; 8],
-    cascade_texture_size: f32,
-    world_from_light: Mat4,
-    light_from_camera: Mat4,
-) -> Cascade {
-    let mut min = Vec3A::splat(f32::MAX);
-    let mut max = Vec3A::splat(f32::MIN);
-    for corner_camera_view in frustum_corners {
-        let corner_light_view = light_from_camera.transform_point3a(corner_camera_view);
-        min = min.min(corner_light_view);
-        max = max.max(corner_light_view);
-    }
-
-    // NOTE: Use the larger of the frustum slice far plane diagonal and body diagonal lengths as this
-    //       will be the maximum possible projection size. Use the ceiling to get an integer which is
-    //       very important for floating point stability later. It is also important that these are
-    //       calculated using the original camera space corner positions for floating point precision
-    //       as even though the lengths using corner_light_view above should be the same, precision can
-    //       introduce small but significant differences.
-    // NOTE: The size remains the same unless the view frustum or cascade configuration is modified.
-    let cascade_diameter = (frustum_corners[0] - frustum_corners[6])
-        .length()
-        .max((frustum_corners[4] - frustum_corners[6]).length())
-        .ceil();
+/// This is meant for cameras that trail behind what the player is looking at, such as the chase
+/// camera of a racing game, where the offset can follow the camera's velocity. The cascades
+/// are still snapped to texels, in the shifted frame.
+///
+/// The cascades still cover the same range of depths from the actual camera, so parts of the view
+/// behind the shifted frustum may fall outside of their cascade and go unshadowed. Keep the offset
+/// small compared to the [`CascadeShadowConfig`] bounds.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct ShadowLookAhead {
+    /// The world-space offset to shift the view by.
+    pub offset: Vec3,
+}
 
-    // NOTE: If we ensure that cascade_texture_size is a power of 2, then as we made cascade_diameter an
-    //       integer, cascade_texel_size is then an integer multiple of a power of 2 and can be
-    //       exactly represented in a floating point value.
-    let cascade_texel_size = cascade_diameter / cascade_texture_size;
-    // NOTE: For shadow stability it is very important that the near_plane_center is at integer
-    //       multiples of the texel size to be exactly representable in a floating point value.
-    let near_plane_center = Vec3A::new(
-        (0.5 * (min.x + max.x) / cascade_texel_size).floor() * cascade_texel_size,
-        (0.5 * (min.y + max.y) / cascade_texel_size).floor() * cascade_texel_size,
-        // NOTE: max.z is the near plane for right-handed y-up
-        max.z,
-    );
+/// Add this component to a [`Camera`] to compute its [`DirectionalLight`] shadow cascades from
+/// this projection and transform instead of the camera's own.
+///
+/// This decouples the framing of the shadows from the visible camera, for example to keep
+/// shadows stable while the camera shakes: give the shadow camera the transform of the camera
+/// without the shake. [`ShadowLookAhead`] and [`ShadowFocus`] apply to the shadow camera.
+///
+/// When shading, cascades are still picked by the depth of each fragment from the actual camera,
+/// so parts of the view that the shadow camera doesn't see may go unshadowed. Keep the shadow
+/// camera close to the actual camera, and copy the camera's [`Projection`] into
+/// [`projection`](Self::projection), as its aspect ratio isn't updated with the window size.
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct ShadowCamera {
+    /// The projection that the cascades are fitted to.
+    pub projection: Projection,
+    /// The world-space transform of the shadow camera.
+    pub transform: Transform,
+}
 
-    // It is critical for `world_to_cascade` to be stable. So rather than forming `cascade_to_world`
-    // and inverting it, which risks instability due to numerical precision, we directly form
-    // `world_to_cascade` as the reference material suggests.
-    let light_to_world_transpose = world_from_light.transpose();
-    let cascade_from_world = Mat4::from_cols(
-        light_to_world_transpose.x_axis,
-        light_to_world_transpose.y_axis,
-        light_to_world_transpose.z_axis,
-        (-near_plane_center).extend(1.0),
-    );
+/// A human-readable name for a light, included alongside its [`Entity`] in warnings from the
+/// light systems, so that they point at the right light.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_pbr::{LightDebugName, PointLight};
+/// # let mut world = World::new();
+/// world.spawn((PointLight::default(), LightDebugName("Lobby lamp".into())));
+/// ```
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct LightDebugName(pub String);
 
-    // Right-handed orthographic projection, centered at `near_plane_center`.
-    // NOTE: This is different from the reference material, as we use reverse Z.
-    let r = (max.z - min.z).recip();
-    let clip_from_cascade = Mat4::from_cols(
-        Vec4::new(2.0 / cascade_diameter, 0.0, 0.0, 0.0),
-        Vec4::new(0.0, 2.0 / cascade_diameter, 0.0, 0.0),
-        Vec4::new(0.0, 0.0, r, 0.0),
-        Vec4::new(0.0, 0.0, 1.0, 1.0),
-    );
+/// Formats a light for warnings, as its [`LightDebugName`] followed by its entity if it has one,
+/// or else as its entity alone.
+pub(crate) struct LightLabel<'a> {
+    pub(crate) entity: Entity,
+    pub(crate) debug_name: Option<&'a LightDebugName>,
+}
 
-    let clip_from_world = clip_from_cascade * cascade_from_world;
-    Cascade {
-        world_from_cascade: cascade_from_world.inverse(),
-        clip_from_cascade,
-        clip_from_world,
-        texel_size: cascade_texel_size,
+impl core::fmt::Display for LightLabel<'_> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self.debug_name {
+            Some(LightDebugName(name)) => write!(f, "\"{name}\" ({})", self.entity),
+            None => write!(f, "{}", self.entity),
+        }
     }
 }
-/// Add this component to make a [`Mesh3d`] not cast shadows.
-#[derive(Debug, Component, Reflect, Default)]
-#[reflect(Component, Default, Debug)]
-pub struct NotShadowCaster;
-/// Add this component to make a [`Mesh3d`] not receive shadows.
-///
-/// **Note:** If you're using diffuse transmission, setting [`NotShadowReceiver`] will
-/// cause both “regular” shadows as well as diffusely transmitted shadows to be disabled,
-/// even when [`TransmittedShadowReceiver`] is being used.
-#[derive(Debug, Component, Reflect, Default)]
-#[reflect(Component, Default, Debug)]
-pub struct NotShadowReceiver;
-/// Add this component to make a [`Mesh3d`] using a PBR material with [`diffuse_transmission`](crate::pbr_material::StandardMaterial::diffuse_transmission)`> 0.0`
-/// receive shadows on its diffuse transmission lobe. (i.e. its “backside”)
-///
-/// Not enabled by default, as it requires carefully setting up [`thickness`](crate::pbr_material::StandardMaterial::thickness)
-/// (and potentially even baking a thickness texture!) to match the geometry of the mesh, in order to avoid self-shadow artifacts.
-///
-/// **Note:** Using [`NotShadowReceiver`] overrides this component.
-#[derive(Debug, Component, Reflect, Default)]
-#[reflect(Component, Default, Debug)]
-pub struct TransmittedShadowReceiver;
 
-/// Add this component to a [`Camera3d`](bevy_core_pipeline::core_3d::Camera3d)
-/// to control how to anti-alias shadow edges.
-///
-/// The different modes use different approaches to
-/// [Percentage Closer Filtering](https://developer.nvidia.com/gpugems/gpugems/part-ii-lighting-and-shadows/chapter-11-shadow-map-antialiasing).
-#[derive(Debug, Component, ExtractComponent, Reflect, Clone, Copy, PartialEq, Eq, Default)]
-#[reflect(Component, Default, Debug, PartialEq, Clone)]
-pub enum ShadowFilteringMethod {
-    /// Hardware 2x2.
-    ///
-    /// Fast but poor quality.
-    Hardware2x2,
-    /// Approximates a fixed Gaussian blur, good when TAA isn't in use.
+impl Cascade {
+    /// Returns the radius in shadow map texels that shadows are blurred over when sampling this
+    /// cascade, given a [`DirectionalLight::shadow_blur_world_radius`] of `world_radius`.
     ///
-    /// Good quality, good performance.
-    ///
-    /// For directional and spot lights, this uses a [method by Ignacio Castaño
-    /// for *The Witness*] using 9 samples and smart filtering to achieve the same
-    /// as a regular 5x5 filter kernel.
+    /// This shrinks as the cascade's [`texel_size`](Self::texel_size) grows, so that the blur
+    /// covers the same distance in the world in every cascade and at every shadow map
+    /// resolution. It matches the conversion done by the shadow sampling shader.
+    pub fn shadow_blur_texel_radius(&self, world_radius: f32) -> f32 {
+        world_radius / self.texel_size
+    }
+
+    /// Returns the world-space distance that fragments are offset along their normal when sampling
+    /// this cascade, given a [`DirectionalLight::shadow_normal_bias`] of `normal_bias` texels.
     ///
-    /// [method by Ignacio Castaño for *The Witness*]: https://web.archive.org/web/20230210095515/http://the-witness.net/news/2013/09/shadow-mapping-summary-part-1/
-    #[default]
-    Gaussian,
-    /// A randomized filter that varies over time, good when TAA is in use.
+    /// This grows with the cascade's [`texel_size`](Self::texel_size), so that far cascades are
+    /// biased more than near ones. It matches the offset applied by the shadow sampling shader,
+    /// including the factor of `SQRT_2` for the worst-case diagonal offset within a texel.
+    pub fn world_normal_bias(&self, normal_bias: f32) -> f32 {
+        normal_bias * core::f32::consts::SQRT_2 * self.texel_size
+    }
+
+    /// Returns the world-space distance that fragments are offset toward the light when sampling
+    /// this cascade, given a [`DirectionalLight::shadow_depth_bias`] of `depth_bias` and the
+    /// [`texel_size`](Self::texel_size) of the light's first cascade.
     ///
-    /// Good quality when used with `TemporalAntiAliasing`
-    /// and good performance.
+    /// Coarser cascades quantize the depth of casters more coarsely, so where adjacent cascades
+    /// are blended, a bias that suits the finer cascade leaves self-shadowing lines in the coarser
+    /// one. The bias is scaled by the ratio of this cascade's texel size to the first cascade's,
+    /// and is never less than `depth_bias`. It matches the offset applied by the shadow sampling
+    /// shader, unless it's below the light's
+    /// [`min_world_shadow_bias`](DirectionalLight::min_world_shadow_bias).
+    pub fn world_depth_bias(&self, depth_bias: f32, first_cascade_texel_size: f32) -> f32 {
+        depth_bias * (self.texel_size / first_cascade_texel_size).max(1.0)
+    }
+
+    /// Returns the depth stored in the shadow map for a point at the reverse-Z depth `clip_depth`
+    /// in this cascade's clip space, from 0.0 at the far plane to 1.0 at the near plane.
     ///
-    /// For directional and spot lights, this uses a [method by Jorge Jimenez for
-    /// *Call of Duty: Advanced Warfare*] using 8 samples in spiral pattern,
-    /// randomly-rotated by interleaved gradient noise with spatial variation.
+    /// This is `clip_depth` itself, unless the cascade has
+    /// [`logarithmic_depth`](Self::logarithmic_depth). It matches the depth written by the shadow
+    /// pass and compared by the shadow sampling shader.
+    pub fn shadow_map_depth(&self, clip_depth: f32) -> f32 {
+        if self.logarithmic_depth {
+            logarithmic_shadow_depth(clip_depth)
+        } else {
+            clip_depth
+        }
+    }
+
+    /// Returns the matrix converting light clip space back into world space, i.e. the inverse of
+    /// [`clip_from_world`](Self::clip_from_world).
     ///
-    /// [method by Jorge Jimenez for *Call of Duty: Advanced Warfare*]: https://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare/
-    Temporal,
+    /// This is useful for unprojecting shadow map samples, for example to visualize where shadow
+    /// map texels land in the world.
+    pub fn world_from_clip(&self) -> Mat4 {
+        // The orthographic projection is cheaper and more stable to invert than the full
+        // view-projection matrix.
+        self.world_from_cascade * self.clip_from_cascade.inverse()
+    }
 }
 
-/// The [`VisibilityClass`] used for all lights (point, directional, and spot).
-pub struct LightVisibilityClass;
+/// How strongly [`logarithmic_shadow_depth`] concentrates precision near the top of a cascade.
+///
+/// NOTE: This must match `LOGARITHMIC_SHADOW_DEPTH_SCALE` in `utils.wgsl`.
+const LOGARITHMIC_SHADOW_DEPTH_SCALE: f32 = 1000.0;
 
-/// System sets used to run light-related systems.
-#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
-pub enum SimulationLightSystems {
-    AddClusters,
-    AssignLightsToClusters,
-    /// System order ambiguities between systems in this set are ignored:
-    /// each [`build_directional_light_cascades`] system is independent of the others,
-    /// and should operate on distinct sets of entities.
-    UpdateDirectionalLightCascades,
-    UpdateLightFrusta,
-    /// System order ambiguities between systems in this set are ignored:
-    /// the order of systems within this set is irrelevant, as the various visibility-checking systems
-    /// assumes that their operations are irreversible during the frame.
-    CheckLightVisibility,
+/// Remaps a linear reverse-Z cascade depth to a logarithmic distribution that keeps 0.0 and 1.0
+/// in place, for [`Cascade::logarithmic_depth`].
+fn logarithmic_shadow_depth(linear_depth: f32) -> f32 {
+    1.0 - ops::log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE * (1.0 - linear_depth).max(0.0))
+        / ops::log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE)
 }
 
-pub fn update_directional_light_frusta(
-    mut views: Query<
-        (
-            &Cascades,
-            &DirectionalLight,
-            &ViewVisibility,
-            &mut CascadesFrusta,
-        ),
-        (
-            // Prevents this query from conflicting with camera queries.
-            Without<Camera>,
-        ),
-    >,
-) {
-    for (cascades, directional_light, visibility, mut frusta) in &mut views {
-        // The frustum is used for culling meshes to the light for shadow mapping
-        // so if shadow mapping is disabled for this light, then the frustum is
-        // not needed.
-        if !directional_light.shadows_enabled || !visibility.get() {
-            continue;
+/// Returns true if the shadow maps of a light should be rendered on this frame, according to its
+/// [`TemporalShadowUpdate`].
+fn shadow_map_updates_this_frame(
+    temporal_shadow_update: Option<&TemporalShadowUpdate>,
+    frame_count: Option<&FrameCount>,
+) -> bool {
+    match (temporal_shadow_update, frame_count) {
+        (Some(temporal_shadow_update), Some(frame_count)) => {
+            temporal_shadow_update.updates_on_frame(frame_count.0)
         }
-
-        frusta.frusta = cascades
-            .cascades
-            .iter()
-            .map(|(view, cascades)| {
-                (
-                    *view,
-                    cascades
-                        .iter()
-                        .map(|c| Frustum::from_clip_from_world(&c.clip_from_world))
-                        .collect::<Vec<_>>(),
-                )
-            })
-            .collect();
+        _ => true,
     }
 }
 
-// NOTE: Run this after assign_lights_to_clusters!
-pub fn update_point_light_frusta(
-    global_lights: Res<GlobalVisibleClusterableObjects>,
-    mut views: Query<(Entity, &GlobalTransform, &PointLight, &mut CubemapFrusta)>,
-    changed_lights: Query<
-        Entity,
-        (
-            With<PointLight>,
-            Or<(Changed<GlobalTransform>, Changed<PointLight>)>,
-        ),
-    >,
-) {
-    let view_rotations = CUBE_MAP_FACES
-        .iter()
-        .map(|CubeMapFace { target, up }| Transform::IDENTITY.looking_at(*target, *up))
-        .collect::<Vec<_>>();
-
-    for (entity, transform, point_light, mut cubemap_frusta) in &mut views {
-        // If this light hasn't changed, and neither has the set of global_lights,
-        // then we can skip this calculation.
-        if !global_lights.is_changed() && !changed_lights.contains(entity) {
-            continue;
-        }
+/// Returns true if the cascades and shadow maps of a directional light should be updated this
+/// frame, either because of its [`TemporalShadowUpdate`] or because its [`CascadeShadowConfig`]
+/// changed.
+fn directional_shadow_map_updates_this_frame(
+    cascades_config: &Ref<CascadeShadowConfig>,
+    temporal_shadow_update: Option<&TemporalShadowUpdate>,
+    frame_count: Option<&FrameCount>,
+) -> bool {
+    cascades_config.is_changed()
+        || shadow_map_updates_this_frame(temporal_shadow_update, frame_count)
+}
 
-        // The frusta are used for culling meshes to the light for shadow mapping
-        // so if shadow mapping is disabled for this light, then the frusta are
-        // not needed.
-        // Also, if the light is not relevant for any cluster, it will not be in the
-        // global lights set and so there is no need to update its frusta.
-        if !point_light.shadows_enabled || !global_lights.entities.contains(&entity) {
-            continue;
-        }
+/// Tracks whether a light's shadow map is rendered this frame.
+///
+/// This is added to every [`PointLight`], [`SpotLight`] and [`DirectionalLight`], and kept up to
+/// date by [`update_shadow_map_states`], so that systems such as LOD managers and profilers can
+/// poll it instead of reacting to events.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct ShadowMapState {
+    /// Whether the light's shadow map is rendered this frame.
+    pub rendered_this_frame: bool,
+    /// The [`FrameCount`] of the last frame on which the light's shadow map was rendered, or 0 if
+    /// it never has been.
+    pub last_rendered_frame: u64,
+}
 
-        let clip_from_view = Mat4::perspective_infinite_reverse_rh(
-            core::f32::consts::FRAC_PI_2,
-            1.0,
-            point_light.shadow_map_near_z,
-        );
+/// The [`GlobalTransform`] that the shadow frusta of a [`PointLight`] or [`SpotLight`] were last
+/// computed from.
+///
+/// [`update_point_light_frusta`] and [`update_spot_light_frusta`] write this whenever they update
+/// a light's frusta, and the light is rendered from it while it casts shadows. This keeps shadow
+/// caster culling, the shadow projection and the lighting in agreement within a frame, even if
+/// the light's [`GlobalTransform`] is modified after the frusta were updated, as can happen with
+/// lights attached to moving platforms.
+#[derive(Component, Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct ShadowFrustaTransform(pub GlobalTransform);
 
-        // ignore scale because we don't want to effectively scale light radius and range
-        // by applying those as a view transform to shadow map rendering of objects
-        // and ignore rotation because we want the shadow map projections to align with the axes
-        let view_translation = Transform::from_translation(transform.translation());
-        let view_backward = transform.back();
+/// Updates the [`ShadowMapState`] of each light.
+///
+/// A light's shadow map is rendered if it has shadows enabled, is visible, and (for lights with a
+/// [`TemporalShadowUpdate`]) is due for an update this frame or, for directional lights, has a
+/// changed [`CascadeShadowConfig`].
+pub fn update_shadow_map_states(
+    frame_count: Option<Res<FrameCount>>,
+    camera_positions: Res<ActiveCameraPositions>,
+    mut lights: Query<(
+        &mut ShadowMapState,
+        AnyOf<(&PointLight, &SpotLight, &DirectionalLight)>,
+        &GlobalTransform,
+        &ViewVisibility,
+        Option<&TemporalShadowUpdate>,
+        Option<Ref<CascadeShadowConfig>>,
+    )>,
+) {
+    for (
+        mut shadow_map_state,
+        (point_light, spot_light, directional_light),
+        transform,
+        view_visibility,
+        temporal_shadow_update,
+        cascades_config,
+    ) in &mut lights
+    {
+        let (shadows_enabled, shadow_cutoff_distance) =
+            match (point_light, spot_light, directional_light) {
+                (Some(light), _, _) => (light.shadows_enabled, light.shadow_cutoff_distance),
+                (_, Some(light), _) => (light.shadows_enabled, light.shadow_cutoff_distance),
+                (_, _, Some(light)) => (light.shadows_enabled, None),
+                (None, None, None) => (false, None),
+            };
+        let rendered_this_frame = shadows_enabled
+            && view_visibility.get()
+            && camera_positions.within_shadow_cutoff_distance(
+                shadow_cutoff_distance,
+                transform.translation_vec3a(),
+            )
+            && match (directional_light, cascades_config) {
+                (Some(_), Some(cascades_config)) => directional_shadow_map_updates_this_frame(
+                    &cascades_config,
+                    temporal_shadow_update,
+                    frame_count.as_deref(),
+                ),
+                _ => shadow_map_updates_this_frame(temporal_shadow_update, frame_count.as_deref()),
+            };
 
-        for (view_rotation, frustum) in view_rotations.iter().zip(cubemap_frusta.iter_mut()) {
-            let world_from_view = view_translation * *view_rotation;
-            let clip_from_world = clip_from_view * world_from_view.to_matrix().inverse();
+        let last_rendered_frame = match frame_count.as_deref() {
+            Some(frame_count) if rendered_this_frame => u64::from(frame_count.0),
+            _ => shadow_map_state.last_rendered_frame,
+        };
 
-            *frustum = Frustum::from_clip_from_world_custom_far(
-                &clip_from_world,
-                &transform.translation(),
-                &view_backward,
-                point_light.range,
-            );
-        }
+        shadow_map_state.set_if_neq(ShadowMapState {
+            rendered_this_frame,
+            last_rendered_frame,
+        });
     }
 }
 
-pub fn update_spot_light_frusta(
-    global_lights: Res<GlobalVisibleClusterableObjects>,
-    mut views: Query<
-        (Entity, &GlobalTransform, &SpotLight, &mut Frustum),
-        Or<(Changed<GlobalTransform>, Changed<SpotLight>)>,
-    >,
+/// Add this component to a [`PointLight`], [`SpotLight`] or [`DirectionalLight`] to smoothly
+/// move its intensity toward [`target`](Self::target) over time, instead of changing it instantly.
+///
+/// This avoids the whole frame flashing as auto-exposure reacts to a sudden change in brightness,
+/// such as a light being switched on. Set [`target`](Self::target) rather than the light's
+/// intensity (or illuminance, for directional lights) directly, and [`smooth_light_intensities`]
+/// will update the light's intensity every frame until it reaches the target.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Reflect)]
+#[reflect(Component, Debug, PartialEq, Clone)]
+pub struct IntensitySmoothing {
+    /// How quickly the intensity approaches the target, per second.
+    ///
+    /// Each second, the remaining difference between the intensity and the target is scaled by
+    /// `exp(-rate)`, so higher values give faster transitions.
+    pub rate: f32,
+    /// The intensity the light is moving toward, in the same units as the light's intensity.
+    pub target: f32,
+}
+
+/// Moves the intensity of each light with an [`IntensitySmoothing`] toward its target.
+pub fn smooth_light_intensities(
+    time: Option<Res<Time>>,
+    mut lights: Query<(
+        &IntensitySmoothing,
+        AnyOf<(&mut PointLight, &mut SpotLight, &mut DirectionalLight)>,
+    )>,
 ) {
-    for (entity, transform, spot_light, mut frustum) in &mut views {
-        // The frusta are used for culling meshes to the light for shadow mapping
-        // so if shadow mapping is disabled for this light, then the frusta are
-        // not needed.
-        // Also, if the light is not relevant for any cluster, it will not be in the
-        // global lights set and so there is no need to update its frusta.
-        if !spot_light.shadows_enabled || !global_lights.entities.contains(&entity) {
+    let delta_secs = time.map_or(0.0, |time| time.delta_secs());
+
+    for (smoothing, (point_light, spot_light, directional_light)) in &mut lights {
+        let intensity = match (point_light, spot_light, directional_light) {
+            (Some(light), _, _) => light.map_unchanged(|light| &mut light.intensity),
+            (_, Some(light), _) => light.map_unchanged(|light| &mut light.intensity),
+            (_, _, Some(light)) => light.map_unchanged(|light| &mut light.illuminance),
+            (None, None, None) => continue,
+        };
+        smooth_intensity(intensity, smoothing, delta_secs);
+    }
+}
+
+fn smooth_intensity(mut intensity: Mut<f32>, smoothing: &IntensitySmoothing, delta_secs: f32) {
+    // Snap to the target once the remaining difference is imperceptible, so that the light stops
+    // being marked as changed.
+    let remaining = (*intensity - smoothing.target) * ops::exp(-smoothing.rate * delta_secs);
+    let new_intensity = if remaining.abs() <= smoothing.target.abs().max(1.0) * 1.0e-3 {
+        smoothing.target
+    } else {
+        smoothing.target + remaining
+    };
+    intensity.set_if_neq(new_intensity);
+}
+
+/// Replaces non-finite or negative color channels and intensities of lights with zero, so that bad
+/// data, such as a NaN color in a deserialized scene, can't corrupt the lighting of a whole view.
+///
+/// This applies to the `color` and `intensity` of [`PointLight`]s and [`SpotLight`]s, and to the
+/// `color` and `illuminance` of [`DirectionalLight`]s. Valid values are left untouched. A warning
+/// naming the light is logged the first time each light is fixed up.
+pub fn sanitize_light_values(
+    mut lights: Query<(
+        Entity,
+        AnyOf<(&mut PointLight, &mut SpotLight, &mut DirectionalLight)>,
+        Option<&LightDebugName>,
+    )>,
+    mut warned_lights: Local<EntityHashSet>,
+) {
+    for (entity, (point_light, spot_light, directional_light), debug_name) in &mut lights {
+        match (point_light, spot_light, directional_light) {
+            (Some(mut light), _, _) => {
+                let Some((color, intensity)) = sanitized_light_values(light.color, light.intensity)
+                else {
+                    continue;
+                };
+                light.color = color;
+                light.intensity = intensity;
+            }
+            (_, Some(mut light), _) => {
+                let Some((color, intensity)) = sanitized_light_values(light.color, light.intensity)
+                else {
+                    continue;
+                };
+                light.color = color;
+                light.intensity = intensity;
+            }
+            (_, _, Some(mut light)) => {
+                let Some((color, illuminance)) =
+                    sanitized_light_values(light.color, light.illuminance)
+                else {
+                    continue;
+                };
+                light.color = color;
+                light.illuminance = illuminance;
+            }
+            (None, None, None) => continue,
+        }
+
+        if warned_lights.insert(entity) {
+            warn!(
+                "{}",
+                invalid_light_values_warning(&LightLabel { entity, debug_name })
+            );
+        }
+    }
+}
+
+fn invalid_light_values_warning(light: &LightLabel) -> String {
+    format!(
+        "Light {light} has a non-finite or negative color or intensity, which has been clamped \
+        to zero."
+    )
+}
+
+/// Returns `color` and `intensity` with non-finite or negative values replaced with zero, or
+/// [`None`] if they're already valid.
+fn sanitized_light_values(color: Color, intensity: f32) -> Option<(Color, f32)> {
+    let sanitize = |value: f32| {
+        if value.is_finite() {
+            value.max(0.0)
+        } else {
+            0.0
+        }
+    };
+
+    let linear = LinearRgba::from(color);
+    let sanitized_color = LinearRgba::new(
+        sanitize(linear.red),
+        sanitize(linear.green),
+        sanitize(linear.blue),
+        sanitize(linear.alpha),
+    );
+    let sanitized_intensity = sanitize(intensity);
+    if sanitized_color == linear && sanitized_intensity == intensity {
+        return None;
+    }
+    Some((sanitized_color.into(), sanitized_intensity))
+}
+
+/// Add this marker to a [`PointLight`] or [`SpotLight`] to scale its range by the scale of its
+/// [`GlobalTransform`].
+///
+/// Light ranges normally ignore scale. With this marker, clustering, shadow caster culling and
+/// light attenuation use the light's `range` multiplied by the largest axis of its scale, which
+/// is convenient for light prefabs that are scaled as a whole, such as a large torch. The shadow
+/// map projection still ignores scale, so shadows remain stable.
+#[derive(Component, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct ScaleAffectsRange;
+
+/// Returns the range of a light with the given `range` and `transform`, taking
+/// [`ScaleAffectsRange`] into account.
+pub(crate) fn scaled_light_range(
+    range: f32,
+    transform: &GlobalTransform,
+    scale_affects_range: bool,
+) -> f32 {
+    if scale_affects_range {
+        range * transform.scale().abs().max_element()
+    } else {
+        range
+    }
+}
+
+/// The positions of the active cameras, which [`PointLight::shadow_cutoff_distance`] and
+/// [`SpotLight::shadow_cutoff_distance`] are measured from.
+///
+/// It's updated every frame in [`PostUpdate`](bevy_app::PostUpdate) by
+/// [`update_active_camera_positions`], so that the systems that check the shadow cutoff distance
+/// of every light, and light extraction, don't each gather the cameras again.
+#[derive(Resource, Clone, Debug, Default, PartialEq, Reflect, Deref)]
+#[reflect(Resource, Default, Debug, PartialEq, Clone)]
+pub struct ActiveCameraPositions(pub Vec<Vec3A>);
+
+impl ActiveCameraPositions {
+    /// Returns true if a light at `light_position` is within its `shadow_cutoff_distance` of any
+    /// active camera, or if it has no cutoff distance.
+    pub fn within_shadow_cutoff_distance(
+        &self,
+        shadow_cutoff_distance: Option<f32>,
+        light_position: Vec3A,
+    ) -> bool {
+        shadow_cutoff_distance.is_none_or(|shadow_cutoff_distance| {
+            self.iter().any(|camera_position| {
+                camera_position.distance_squared(light_position)
+                    <= shadow_cutoff_distance * shadow_cutoff_distance
+            })
+        })
+    }
+}
+
+/// Gathers the [`ActiveCameraPositions`].
+pub fn update_active_camera_positions(
+    mut active_camera_positions: ResMut<ActiveCameraPositions>,
+    cameras: Query<(&GlobalTransform, &Camera)>,
+) {
+    active_camera_positions.0.clear();
+    active_camera_positions.0.extend(
+        cameras
+            .iter()
+            .filter(|(_, camera)| camera.is_active)
+            .map(|(transform, _)| transform.translation_vec3a()),
+    );
+}
+
+pub fn clear_directional_light_cascades(
+    frame_count: Option<Res<FrameCount>>,
+    mut lights: Query<(
+        &DirectionalLight,
+        Ref<CascadeShadowConfig>,
+        &mut Cascades,
+        Option<&TemporalShadowUpdate>,
+    )>,
+) {
+    for (directional_light, cascades_config, mut cascades, temporal_shadow_update) in
+        lights.iter_mut()
+    {
+        if !directional_light.shadows_enabled
+            || !directional_shadow_map_updates_this_frame(
+                &cascades_config,
+                temporal_shadow_update,
+                frame_count.as_deref(),
+            )
+        {
+            continue;
+        }
+        cascades.cascades.clear();
+    }
+}
+
+/// Computes the [`Cascades`] of every shadow-casting [`DirectionalLight`] for every active view.
+///
+/// Cascades are rebuilt whenever the light's shadow map updates, and also on any frame where its
+/// [`CascadeShadowConfig`] changed. The slices of each view's frustum that the cascades cover are
+/// kept from the last update, so moving the view or rotating the light doesn't recompute them, and
+/// changing only the near bounds of the cascades, through the
+/// [`minimum_distance`](CascadeShadowConfig::minimum_distance) or the overlap proportions, keeps
+/// their far planes.
+pub fn build_directional_light_cascades(
+    directional_light_shadow_map: Res<DirectionalLightShadowMap>,
+    warning_threshold: Res<DirectionalLightShadowWarningThreshold>,
+    mut above_warning_threshold: Local<bool>,
+    frame_count: Option<Res<FrameCount>>,
+    time: Option<Res<Time>>,
+    shared_cascades: Option<Res<SharedCascades>>,
+    views: Query<(
+        Entity,
+        &GlobalTransform,
+        &Projection,
+        &Camera,
+        Has<ComputeCascadesForInactiveView>,
+        Option<&ViewCascadeShadowConfig>,
+        Option<&ShadowFocus>,
+        Option<&ViewShadowMapSize>,
+        Option<&ShadowLookAhead>,
+        Option<&CascadeSizeSmoothing>,
+        Option<&ShadowCamera>,
+    )>,
+    mut lights: Query<(
+        Entity,
+        &GlobalTransform,
+        &DirectionalLight,
+        Ref<CascadeShadowConfig>,
+        &mut Cascades,
+        Option<&TemporalShadowUpdate>,
+        &InheritedVisibility,
+        Option<&LightDebugName>,
+    )>,
+) {
+    let delta_secs = time.map_or(0.0, |time| time.delta_secs());
+    let views = views
+        .iter()
+        .filter_map(
+            |(
+                entity,
+                transform,
+                projection,
+                camera,
+                compute_when_inactive,
+                view_config,
+                focus,
+                shadow_map_size,
+                look_ahead,
+                size_smoothing,
+                shadow_camera,
+            )| {
+                if !camera.is_active && !compute_when_inactive {
+                    return None;
+                }
+                let (projection, transform) = match shadow_camera {
+                    Some(shadow_camera) => (
+                        &shadow_camera.projection,
+                        shadow_camera.transform.compute_affine(),
+                    ),
+                    None => (projection, transform.affine()),
+                };
+                let view_to_world = match look_ahead {
+                    Some(look_ahead) => Affine3A::from_translation(look_ahead.offset) * transform,
+                    None => transform,
+                };
+                // The cascades are computed in the view space of the camera.
+                let focus = focus.map(|focus| Sphere {
+                    center: view_to_world
+                        .inverse()
+                        .transform_point3a(focus.point.into()),
+                    radius: focus.radius,
+                });
+                Some((
+                    entity,
+                    projection,
+                    Mat4::from(view_to_world),
+                    view_config.map(|view_config| &view_config.0),
+                    focus,
+                    shadow_map_size.map_or(directional_light_shadow_map.size, |size| size.0),
+                    // The fraction of the remaining size difference kept this frame.
+                    size_smoothing.map(|smoothing| ops::exp(-smoothing.rate * delta_secs)),
+                ))
+            },
+        )
+        .collect::<Vec<_>>();
+    let shared_views = shared_cascades.is_some().then(|| {
+        views
+            .iter()
+            .map(|(_, projection, view_to_world, ..)| (*projection, *view_to_world))
+            .collect::<Vec<_>>()
+    });
+
+    let shadowed_light_count = lights
+        .iter()
+        .filter(|(_, _, directional_light, ..)| directional_light.shadows_enabled)
+        .count();
+    if crossed_warning_threshold(
+        shadowed_light_count,
+        warning_threshold.0,
+        &mut above_warning_threshold,
+    ) {
+        warn!(
+            "{} shadow-casting directional lights exist, which is more than the warning \
+            threshold of {}. Each one renders shadow cascades for every view, which is costly; \
+            consider disabling shadows on some of them, or raise the threshold with \
+            `DirectionalLightShadowWarningThreshold`.",
+            shadowed_light_count, warning_threshold.0
+        );
+    }
+
+    // Each light only writes its own `Cascades`, so the lights can be processed in parallel.
+    lights.par_iter_mut().for_each(
+        |(
+            entity,
+            transform,
+            directional_light,
+            cascades_config,
+            mut cascades,
+            temporal_shadow_update,
+            inherited_visibility,
+            debug_name,
+        )| {
+            // Keep the cascades from the last update, so that they match the shadow maps that
+            // will be sampled this frame.
+            if !directional_light.shadows_enabled
+                || !inherited_visibility.get()
+                || !directional_shadow_map_updates_this_frame(
+                    &cascades_config,
+                    temporal_shadow_update,
+                    frame_count.as_deref(),
+                )
+            {
+                return;
+            }
+
+            // It is very important to the numerical and thus visual stability of shadows that
+            // light_to_world has orthogonal upper-left 3x3 and zero translation.
+            // Even though only the direction (i.e. rotation) of the light matters, we don't
+            // constrain users to not change any other aspects of the transform - there's no
+            // guarantee `transform.to_matrix()` will give us a matrix with our desired properties.
+            // Instead, we directly create a good matrix from just the rotation.
+            let world_from_light = Mat4::from_quat(directional_light_rotation(
+                transform,
+                &LightLabel { entity, debug_name },
+            ));
+
+            if let Some(shared_views) = &shared_views {
+                if shared_views.is_empty() {
+                    return;
+                }
+                let shared_cascades = calculate_shared_cascades(
+                    &cascades_config,
+                    shared_views,
+                    world_from_light,
+                    directional_light_shadow_map.size as f32,
+                );
+                for (view_entity, ..) in &views {
+                    cascades
+                        .cascades
+                        .insert(*view_entity, shared_cascades.clone());
+                }
+                return;
+            }
+
+            for (
+                view_entity,
+                projection,
+                view_to_world,
+                view_config,
+                focus,
+                shadow_map_size,
+                size_smoothing,
+            ) in &views
+            {
+                let Cascades {
+                    cascades,
+                    frustum_slices,
+                } = &mut *cascades;
+                let previous_cascades = size_smoothing.and_then(|retained| {
+                    let previous_cascades = cascades.get(view_entity)?;
+                    Some((previous_cascades.as_slice(), retained))
+                });
+                let view_cascades = calculate_view_cascades(
+                    view_config.unwrap_or(&cascades_config),
+                    projection,
+                    world_from_light,
+                    *view_to_world,
+                    *shadow_map_size as f32,
+                    focus.as_ref(),
+                    previous_cascades,
+                    frustum_slices.entry(*view_entity).or_default(),
+                );
+                cascades.insert(*view_entity, view_cascades);
+            }
+            cascades
+                .frustum_slices
+                .retain(|view_entity, _| views.iter().any(|(entity, ..)| entity == view_entity));
+        },
+    );
+}
+
+/// Returns the [`Cascade`]s described by `cascades_config` for the view with the given
+/// `projection` and `view_to_world` transform.
+///
+/// If there's a `focus`, a sphere in the view space of the camera, the first cascade is
+/// restricted to it as described in [`ShadowFocus`].
+///
+/// If there are `previous_cascades`, the cascades computed for the view on the previous update
+/// along with the fraction of the size difference to keep, the size of each cascade is eased from
+/// the size of the previous one as described in [`CascadeSizeSmoothing`].
+///
+/// The slices of the view's frustum covered by the cascades are taken from `frustum_slices`,
+/// which is updated first, so that only the slices whose bounds changed are recomputed.
+#[expect(
+    clippy::too_many_arguments,
+    reason = "the cascades depend on the view, the light and the previous update"
+)]
+fn calculate_view_cascades(
+    cascades_config: &CascadeShadowConfig,
+    projection: &Projection,
+    world_from_light: Mat4,
+    view_to_world: Mat4,
+    cascade_texture_size: f32,
+    focus: Option<&Sphere>,
+    previous_cascades: Option<(&[Cascade], f32)>,
+    frustum_slices: &mut CascadeFrustumSlices,
+) -> Vec<Cascade> {
+    // The builder ensures that an absolute `minimum_distance` is less than the first cascade's
+    // far bound, but the config may have been mutated directly since, and the camera's near plane
+    // may be farther away.
+    let camera_near = projection_near(projection);
+    let minimum_distance = clamped_minimum_distance(cascades_config, camera_near);
+    if minimum_distance != cascades_config.minimum_distance.distance(camera_near) {
+        once!(warn!(
+            "CascadeShadowConfig::minimum_distance ({}) must be less than the first cascade \
+            bound ({}); clamping it to avoid an inverted cascade frustum",
+            cascades_config.minimum_distance.distance(camera_near),
+            cascades_config.bounds[0]
+        ));
+    }
+
+    let camera_to_light_view = world_from_light.inverse() * view_to_world;
+    let snap_to_texels = !cascades_config.disable_cascade_snapping;
+    let diameter_smoothing = |idx: usize| {
+        let (previous_cascades, retained) = previous_cascades?;
+        let previous_diameter = previous_cascades.get(idx)?.texel_size * cascade_texture_size;
+        Some((previous_diameter, retained))
+    };
+
+    // Single cascades are common on low-end targets. The only cascade spans the whole shadow
+    // distance and has no overlap, so skip computing the near bounds of the cascades and caching
+    // their frustum slices.
+    let single_cascade_corners;
+    let corners = match cascades_config.bounds[..] {
+        [far_bound] => {
+            single_cascade_corners = [single_cascade_frustum_corners(
+                projection,
+                minimum_distance,
+                far_bound,
+            )];
+            &single_cascade_corners[..]
+        }
+        _ => {
+            let near_bounds = (0..cascades_config.bounds.len())
+                .map(|idx| {
+                    cascade_near_bound(cascades_config, idx, minimum_distance, focus.is_some())
+                })
+                .collect::<Vec<_>>();
+            frustum_slices.update(projection, &near_bounds, &cascades_config.bounds);
+            &frustum_slices.corners[..]
+        }
+    };
+
+    let mut cascades = corners
+        .iter()
+        .enumerate()
+        .map(|(idx, &corners)| {
+            calculate_focused_cascade(
+                corners,
+                cascade_texture_size,
+                world_from_light,
+                camera_to_light_view,
+                snap_to_texels,
+                focus.filter(|_| idx == 0),
+                diameter_smoothing(idx),
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let first_logarithmic = cascades
+        .len()
+        .saturating_sub(cascades_config.logarithmic_depth_cascades);
+    for cascade in &mut cascades[first_logarithmic..] {
+        cascade.logarithmic_depth = true;
+    }
+    cascades
+}
+
+/// Returns the [`Cascade`]s described by `cascades_config` for a group of views sharing their
+/// cascades, given by their `projection` and `view_to_world` transform, as described in
+/// [`SharedCascades`].
+///
+/// Each cascade covers the union of the matching slices of the view frusta.
+fn calculate_shared_cascades(
+    cascades_config: &CascadeShadowConfig,
+    views: &[(&Projection, Mat4)],
+    world_from_light: Mat4,
+    cascade_texture_size: f32,
+) -> Vec<Cascade> {
+    let light_from_world = world_from_light.inverse();
+    let snap_to_texels = !cascades_config.disable_cascade_snapping;
+
+    let mut cascades = cascades_config
+        .bounds
+        .iter()
+        .enumerate()
+        .map(|(idx, far_bound)| {
+            let corners_light_view = views
+                .iter()
+                .flat_map(|&(projection, view_to_world)| {
+                    let minimum_distance =
+                        clamped_minimum_distance(cascades_config, projection_near(projection));
+                    // Negate bounds as -z is camera forward direction.
+                    let z_near = -cascade_near_bound(cascades_config, idx, minimum_distance, false);
+                    let light_from_camera = light_from_world * view_to_world;
+                    projection
+                        .get_frustum_corners(z_near, -far_bound)
+                        .map(|corner| light_from_camera.transform_point3a(corner))
+                })
+                .collect::<Vec<_>>();
+
+            let mut min = Vec3A::splat(f32::MAX);
+            let mut max = Vec3A::splat(f32::MIN);
+            for corner_light_view in &corners_light_view {
+                min = min.min(*corner_light_view);
+                max = max.max(*corner_light_view);
+            }
+
+            // Like `cascade_diameter`, this is the longest distance between two corners, which
+            // doesn't depend on the orientation of the light, and is rounded up to a whole
+            // number.
+            let cascade_diameter = corners_light_view
+                .iter()
+                .flat_map(|a| corners_light_view.iter().map(|b| a.distance(*b)))
+                .fold(0.0, f32::max)
+                .ceil();
+
+            fit_cascade(
+                &corners_light_view,
+                min,
+                max,
+                cascade_diameter,
+                cascade_texture_size,
+                world_from_light,
+                snap_to_texels,
+            )
+        })
+        .collect::<Vec<_>>();
+
+    let first_logarithmic = cascades
+        .len()
+        .saturating_sub(cascades_config.logarithmic_depth_cascades);
+    for cascade in &mut cascades[first_logarithmic..] {
+        cascade.logarithmic_depth = true;
+    }
+    cascades
+}
+
+/// Returns the shadow strength of a light with
+/// [`fade_shadows_with_intensity`](PointLight::fade_shadows_with_intensity) enabled: the ratio of
+/// `intensity` to `full_shadow_intensity`, clamped to 0.0 to 1.0.
+///
+/// A non-positive `full_shadow_intensity` always gives full shadows.
+pub(crate) fn intensity_shadow_strength(intensity: f32, full_shadow_intensity: f32) -> f32 {
+    if full_shadow_intensity > 0.0 {
+        (intensity / full_shadow_intensity).clamp(0.0, 1.0)
+    } else {
+        1.0
+    }
+}
+
+/// The range of color temperatures, in kelvin, that [`color_temperature_to_color`] supports.
+const COLOR_TEMPERATURE_RANGE: core::ops::RangeInclusive<f32> = 1000.0..=40000.0;
+
+/// Returns the color of a blackbody at the temperature `kelvin`, clamped to 1000 K to 40000 K.
+///
+/// This uses Tanner Helland's fit of the Planckian locus in sRGB, which is accurate to within a
+/// few percent over that range. The brightest channel is always 1.0, so the color only sets the
+/// hue of a light, and its intensity still sets the brightness.
+pub(crate) fn color_temperature_to_color(kelvin: f32) -> Color {
+    let kelvin = if kelvin.is_nan() {
+        *COLOR_TEMPERATURE_RANGE.start()
+    } else {
+        kelvin.clamp(
+            *COLOR_TEMPERATURE_RANGE.start(),
+            *COLOR_TEMPERATURE_RANGE.end(),
+        )
+    };
+    // The fit works in hundreds of kelvin and 8-bit channels.
+    let temperature = kelvin / 100.0;
+    let red = if temperature <= 66.0 {
+        255.0
+    } else {
+        329.69873 * ops::powf(temperature - 60.0, -0.13320476)
+    };
+    let green = if temperature <= 66.0 {
+        99.4708 * ops::ln(temperature) - 161.11957
+    } else {
+        288.12216 * ops::powf(temperature - 60.0, -0.075514846)
+    };
+    let blue = if temperature >= 66.0 {
+        255.0
+    } else if temperature <= 19.0 {
+        0.0
+    } else {
+        138.51773 * ops::ln(temperature - 10.0) - 305.0448
+    };
+    Color::srgb(
+        (red / 255.0).clamp(0.0, 1.0),
+        (green / 255.0).clamp(0.0, 1.0),
+        (blue / 255.0).clamp(0.0, 1.0),
+    )
+}
+
+/// Logs a warning whenever the [`DirectionalLightShadowMap`] or [`PointLightShadowMap`] size is
+/// set to a value that isn't a power of two.
+pub fn warn_non_power_of_two_shadow_maps(
+    directional_light_shadow_map: Res<DirectionalLightShadowMap>,
+    point_light_shadow_map: Res<PointLightShadowMap>,
+) {
+    let sizes = [
+        (
+            "DirectionalLightShadowMap",
+            directional_light_shadow_map.is_changed(),
+            directional_light_shadow_map.size,
+        ),
+        (
+            "PointLightShadowMap",
+            point_light_shadow_map.is_changed(),
+            point_light_shadow_map.size,
+        ),
+    ];
+    for (resource, changed, size) in sizes {
+        if !changed {
+            continue;
+        }
+        if let Some(warning) = non_power_of_two_shadow_map_warning(resource, size) {
+            warn!("{warning}");
+        }
+    }
+}
+
+/// Returns the warning to log if a shadow map `size`, set on the `resource` with the given name,
+/// isn't a power of two.
+fn non_power_of_two_shadow_map_warning(resource: &str, size: usize) -> Option<String> {
+    (!size.is_power_of_two()).then(|| {
+        format!(
+            "{resource}::size ({size}) isn't a power of two. Shadow texel sizes are then not \
+            exactly representable as floats, so cascades can't be snapped exactly to texels and \
+            shadow edges may shimmer as the camera moves; consider using a power of two such as \
+            {}.",
+            size.checked_next_power_of_two().unwrap_or(size)
+        )
+    })
+}
+
+/// Logs the suggestions of the [`ShadowConfigLinter`] for each [`CascadeShadowConfig`] that was
+/// added or changed, or for every config when the linter was just inserted.
+pub fn lint_cascade_shadow_configs(
+    linter: Res<ShadowConfigLinter>,
+    configs: Query<(Entity, Ref<CascadeShadowConfig>)>,
+) {
+    for (entity, config) in &configs {
+        if !linter.is_added() && !config.is_changed() {
             continue;
         }
+        for suggestion in cascade_shadow_config_suggestions(&config) {
+            warn!("The CascadeShadowConfig of {entity} may give poor shadows: {suggestion}");
+        }
+    }
+}
+
+/// Returns the suggestions of the [`ShadowConfigLinter`] for `config`.
+fn cascade_shadow_config_suggestions(config: &CascadeShadowConfig) -> Vec<String> {
+    let mut suggestions = Vec::new();
+    let (Some(&first_far_bound), Some(&maximum_distance)) =
+        (config.bounds.first(), config.bounds.last())
+    else {
+        return suggestions;
+    };
+
+    if config.bounds.len() > 1 && first_far_bound > maximum_distance / 2.0 {
+        suggestions.push(format!(
+            "the first cascade ends at {first_far_bound}, more than half of the maximum distance \
+            of {maximum_distance}, so the other cascades share little of the distance; consider \
+            lowering `first_cascade_far_bound` to around {}.",
+            maximum_distance / 15.0
+        ));
+    }
+
+    let max_overlap_proportion = (0..config.bounds.len().saturating_sub(1))
+        .map(|index| config.boundary_overlap_proportion(index))
+        .fold(0.0, f32::max);
+    if max_overlap_proportion > LINTED_MAX_OVERLAP_PROPORTION {
+        suggestions.push(format!(
+            "an overlap proportion of {max_overlap_proportion} blends cascades over a large part \
+            of their range, which spreads their resolution thinly; consider an overlap of \
+            {LINTED_MAX_OVERLAP_PROPORTION} or less."
+        ));
+    }
+
+    if config.bounds.len() == 1 && maximum_distance > LINTED_MAX_SINGLE_CASCADE_DISTANCE {
+        suggestions.push(format!(
+            "a single cascade covering {maximum_distance} makes nearby shadows very blocky; \
+            consider using several cascades with `CascadeShadowConfigBuilder::num_cascades`."
+        ));
+    }
+
+    suggestions
+}
+
+/// Returns the (positive) distance to the near boundary of the cascade at `index`, which overlaps
+/// the previous cascade by its [`CascadeShadowConfig::boundary_overlap_proportion`].
+fn cascade_near_bound(
+    config: &CascadeShadowConfig,
+    index: usize,
+    minimum_distance: f32,
+    focused: bool,
+) -> f32 {
+    match index {
+        0 => minimum_distance,
+        // A focused first cascade only covers part of its depth range, so the second cascade
+        // covers the rest of it too.
+        1 if focused => minimum_distance,
+        _ => (1.0 - config.boundary_overlap_proportion(index - 1)) * config.bounds[index - 1],
+    }
+}
+
+/// Returns true if `count` has just risen above `threshold`, tracking whether it was above the
+/// threshold on the previous call in `above_threshold`.
+fn crossed_warning_threshold(count: usize, threshold: usize, above_threshold: &mut bool) -> bool {
+    let was_above_threshold = core::mem::replace(above_threshold, count > threshold);
+    *above_threshold && !was_above_threshold
+}
+
+/// Returns the rotation of a directional light's `transform` as a unit quaternion.
+///
+/// Manually constructed transforms may have a non-unit rotation, which would give a non-orthogonal
+/// (or, for a zero-length rotation, NaN) light matrix and thus broken cascades.
+fn directional_light_rotation(transform: &GlobalTransform, light: &LightLabel) -> Quat {
+    let rotation = transform.compute_transform().rotation;
+    if rotation.is_normalized() {
+        return rotation;
+    }
+
+    once!(warn!(
+        "DirectionalLight {light} has a non-unit rotation ({:?}); normalizing it to build its \
+        shadow cascades",
+        rotation
+    ));
+    Vec4::from(rotation)
+        .try_normalize()
+        .map_or(Quat::IDENTITY, Quat::from_vec4)
+}
+
+/// Returns the `minimum_distance` of `config` for a camera whose near plane is `camera_near` away,
+/// clamped to just below the far bound of the first cascade so that the first cascade's frustum is
+/// never inverted.
+fn clamped_minimum_distance(config: &CascadeShadowConfig, camera_near: f32) -> f32 {
+    let minimum_distance = config.minimum_distance.distance(camera_near);
+    match config.bounds.first() {
+        Some(&first_bound) if minimum_distance >= first_bound => first_bound.next_down().max(0.0),
+        _ => minimum_distance,
+    }
+}
+
+/// Returns the diameter of the orthographic projection of a cascade covering the frustum defined by
+/// `frustum_corners`, in the same order as for [`calculate_focused_cascade`].
+fn cascade_diameter(frustum_corners: &[Vec3A; 8]) -> f32 {
+    // NOTE: Use the larger of the frustum slice far plane diagonal and body diagonal lengths as this
+    //       will be the maximum possible projection size. Use the ceiling to get an integer which is
+    //       very important for floating point stability later. It is also important that these are
+    //       calculated using the original camera space corner positions for floating point precision
+    //       as even though the lengths in light view space should be the same, precision can
+    //       introduce small but significant differences.
+    // NOTE: The size remains the same unless the view frustum or cascade configuration is modified.
+    (frustum_corners[0] - frustum_corners[6])
+        .length()
+        .max((frustum_corners[4] - frustum_corners[6]).length())
+        .ceil()
+}
+
+/// Returns the diameter of a cascade moving from `previous_diameter` toward `target_diameter`,
+/// keeping the fraction `retained` of the difference between them.
+///
+/// The result is rounded up to a whole number for the same reason as [`cascade_diameter`], and
+/// snaps to the target once less than one unit away.
+fn smoothed_cascade_diameter(previous_diameter: f32, target_diameter: f32, retained: f32) -> f32 {
+    let remaining = (previous_diameter - target_diameter) * retained;
+    if remaining.abs() < 1.0 {
+        target_diameter
+    } else {
+        (target_diameter + remaining).ceil()
+    }
+}
+
+/// Returns a [`Cascade`] for the frustum defined by `frustum_corners`.
+///
+/// The corner vertices should be specified in the following order:
+/// first the bottom right, top right, top left, bottom left for the near plane, then similar for the far plane.
+///
+/// If `snap_to_texels` is false, the cascade isn't snapped to whole texels, see
+/// [`CascadeShadowConfig::disable_cascade_snapping`].
+///
+/// If there's a `focus`, a sphere in the same space as the corners, the cascade only covers the
+/// part of the frustum within it, as seen from the light.
+///
+/// If there's a `diameter_smoothing`, the diameter of the previous cascade and the fraction of the
+/// difference to keep, the diameter is eased from the previous one, see
+/// [`smoothed_cascade_diameter`].
+fn calculate_focused_cascade(
+    frustum_corners: [Vec3A; 8],
+    cascade_texture_size: f32,
+    world_from_light: Mat4,
+    light_from_camera: Mat4,
+    snap_to_texels: bool,
+    focus: Option<&Sphere>,
+    diameter_smoothing: Option<(f32, f32)>,
+) -> Cascade {
+    let corners_light_view =
+        frustum_corners.map(|corner| light_from_camera.transform_point3a(corner));
+    let mut min = Vec3A::splat(f32::MAX);
+    let mut max = Vec3A::splat(f32::MIN);
+    for corner_light_view in corners_light_view {
+        min = min.min(corner_light_view);
+        max = max.max(corner_light_view);
+    }
+
+    let mut cascade_diameter = cascade_diameter(&frustum_corners);
+    if let Some(focus) = focus {
+        let center = light_from_camera.transform_point3a(focus.center);
+        let focus_min = (center - focus.radius).max(min);
+        let focus_max = (center + focus.radius).min(max);
+        // Only the X and Y bounds are restricted, so that casters along the whole depth range
+        // are still captured. A focus that misses the frustum is ignored.
+        if focus_min.x < focus_max.x && focus_min.y < focus_max.y {
+            min = Vec3A::new(focus_min.x, focus_min.y, min.z);
+            max = Vec3A::new(focus_max.x, focus_max.y, max.z);
+            // Keep the diameter a whole number, for the same reason as `cascade_diameter`.
+            cascade_diameter = cascade_diameter.min((2.0 * focus.radius).ceil().max(1.0));
+        }
+    }
+    if let Some((previous_diameter, retained)) = diameter_smoothing {
+        cascade_diameter = smoothed_cascade_diameter(previous_diameter, cascade_diameter, retained);
+    }
+
+    fit_cascade(
+        &corners_light_view,
+        min,
+        max,
+        cascade_diameter,
+        cascade_texture_size,
+        world_from_light,
+        snap_to_texels,
+    )
+}
+
+/// Returns a [`Cascade`] whose orthographic projection is `cascade_diameter` wide and covers the
+/// box from `min` to `max` in the light's view space, which bounds `corners_light_view`.
+fn fit_cascade(
+    corners_light_view: &[Vec3A],
+    min: Vec3A,
+    max: Vec3A,
+    cascade_diameter: f32,
+    cascade_texture_size: f32,
+    world_from_light: Mat4,
+    snap_to_texels: bool,
+) -> Cascade {
+    // The light's view space is only rotated relative to world space, so distances are the same in
+    // both and the sphere can be fitted to the light space corners.
+    let sphere_center_light_view = 0.5 * (min + max);
+    let world_bounding_sphere = Sphere {
+        center: world_from_light.transform_point3a(sphere_center_light_view),
+        radius: corners_light_view
+            .iter()
+            .map(|corner| corner.distance(sphere_center_light_view))
+            .fold(0.0, f32::max),
+    };
+
+    // NOTE: If we ensure that cascade_texture_size is a power of 2, then as we made cascade_diameter an
+    //       integer, cascade_texel_size is then an integer multiple of a power of 2 and can be
+    //       exactly represented in a floating point value.
+    let cascade_texel_size = cascade_diameter / cascade_texture_size;
+    // NOTE: For shadow stability it is very important that the near_plane_center is at integer
+    //       multiples of the texel size to be exactly representable in a floating point value.
+    let snap = |coordinate: f32| {
+        if snap_to_texels {
+            (coordinate / cascade_texel_size).floor() * cascade_texel_size
+        } else {
+            coordinate
+        }
+    };
+    let near_plane_center = Vec3A::new(
+        snap(0.5 * (min.x + max.x)),
+        snap(0.5 * (min.y + max.y)),
+        // NOTE: max.z is the near plane for right-handed y-up
+        max.z,
+    );
+
+    // It is critical for `world_to_cascade` to be stable. So rather than forming `cascade_to_world`
+    // and inverting it, which risks instability due to numerical precision, we directly form
+    // `world_to_cascade` as the reference material suggests.
+    let light_to_world_transpose = world_from_light.transpose();
+    let cascade_from_world = Mat4::from_cols(
+        light_to_world_transpose.x_axis,
+        light_to_world_transpose.y_axis,
+        light_to_world_transpose.z_axis,
+        (-near_plane_center).extend(1.0),
+    );
+
+    // Right-handed orthographic projection, centered at `near_plane_center`.
+    // NOTE: This is different from the reference material, as we use reverse Z.
+    let r = (max.z - min.z).recip();
+    let clip_from_cascade = Mat4::from_cols(
+        Vec4::new(2.0 / cascade_diameter, 0.0, 0.0, 0.0),
+        Vec4::new(0.0, 2.0 / cascade_diameter, 0.0, 0.0),
+        Vec4::new(0.0, 0.0, r, 0.0),
+        Vec4::new(0.0, 0.0, 1.0, 1.0),
+    );
+
+    let clip_from_world = clip_from_cascade * cascade_from_world;
+    let world_from_cascade = cascade_from_world.inverse();
+    // The cascade's view space is centered on the near plane, with the far plane along -Z.
+    let half_diameter = 0.5 * cascade_diameter;
+    let bounds_world = core::array::from_fn(|index| {
+        let (x, y) = [(1.0, -1.0), (1.0, 1.0), (-1.0, 1.0), (-1.0, -1.0)][index % 4];
+        let z = if index < 4 { 0.0 } else { min.z - max.z };
+        world_from_cascade.transform_point3a(Vec3A::new(x * half_diameter, y * half_diameter, z))
+    });
+    Cascade {
+        world_from_cascade,
+        clip_from_cascade,
+        clip_from_world,
+        texel_size: cascade_texel_size,
+        light_space_min: min,
+        light_space_max: max,
+        world_bounding_sphere,
+        bounds_world,
+        logarithmic_depth: false,
+    }
+}
+/// Add this component to make a [`Mesh3d`] not cast shadows.
+///
+/// To toggle shadows often, use [`ShadowCaster`] instead.
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component, Default, Debug)]
+pub struct NotShadowCaster;
+
+/// Controls whether a [`Mesh3d`] casts shadows, without adding or removing components.
+///
+/// Adding or removing [`NotShadowCaster`] moves the entity to a different archetype, which is
+/// costly for meshes whose shadows are toggled often, such as a destructible object that stops
+/// casting shadows when broken. Toggling [`enabled`](Self::enabled) instead only changes a value.
+///
+/// A mesh with [`NotShadowCaster`] never casts shadows, whatever the value of this component.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub struct ShadowCaster {
+    /// Whether the mesh casts shadows.
+    pub enabled: bool,
+}
+
+impl Default for ShadowCaster {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Returns true if a mesh with the given [`ShadowCaster`] and [`ShadowCasterLights`] components
+/// casts shadows from `light`.
+fn casts_shadows_from(
+    shadow_caster: Option<&ShadowCaster>,
+    shadow_caster_lights: Option<&ShadowCasterLights>,
+    light: Entity,
+) -> bool {
+    shadow_caster.is_none_or(|shadow_caster| shadow_caster.enabled)
+        && shadow_caster_lights
+            .is_none_or(|shadow_caster_lights| shadow_caster_lights.casts_shadows_from(light))
+}
+
+/// Add this component to make a [`Mesh3d`] cast shadows only from the given lights.
+///
+/// This is a finer-grained version of [`NotShadowCaster`]: a mesh without this component casts
+/// shadows from every light.
+///
+/// ```
+/// # use bevy_ecs::prelude::*;
+/// # use bevy_pbr::ShadowCasterLights;
+/// # let mut world = World::new();
+/// # let sun = world.spawn_empty().id();
+/// # let torch = world.spawn_empty().id();
+/// // Cast shadows from the sun, but not from torches.
+/// let shadow_caster_lights = ShadowCasterLights::from_iter([sun]);
+/// assert!(shadow_caster_lights.casts_shadows_from(sun));
+/// assert!(!shadow_caster_lights.casts_shadows_from(torch));
+/// ```
+#[derive(Component, Clone, Debug, Default, Reflect)]
+#[reflect(Component, Default, Debug, Clone)]
+pub struct ShadowCasterLights {
+    /// The lights that the mesh casts shadows from.
+    pub only: EntityHashSet,
+}
+
+impl ShadowCasterLights {
+    /// Returns true if the mesh casts shadows from the light on `light`.
+    pub fn casts_shadows_from(&self, light: Entity) -> bool {
+        self.only.contains(&light)
+    }
+}
+
+impl FromIterator<Entity> for ShadowCasterLights {
+    fn from_iter<T: IntoIterator<Item = Entity>>(iter: T) -> Self {
+        Self {
+            only: iter.into_iter().collect(),
+        }
+    }
+}
+
+/// Add this component to a [`Mesh3d`] to make it cast shadows only into the [`DirectionalLight`]
+/// cascades with indices from [`min`](Self::min) to [`max`](Self::max), inclusive.
+///
+/// Cascade 0 is the one nearest to the camera. Large meshes that only matter in the distance,
+/// such as terrain, can be kept out of the near cascades so that they don't cost draws there.
+/// Point and spot light shadows aren't affected. A mesh without this component casts shadows into
+/// every cascade.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Component, Debug, PartialEq, Clone)]
+pub struct ShadowCascadeRange {
+    /// The index of the nearest cascade that the mesh casts shadows into.
+    pub min: usize,
+    /// The index of the farthest cascade that the mesh casts shadows into.
+    pub max: usize,
+}
+
+impl ShadowCascadeRange {
+    /// Returns true if the mesh casts shadows into the cascade at `cascade_index`.
+    pub fn contains(&self, cascade_index: usize) -> bool {
+        (self.min..=self.max).contains(&cascade_index)
+    }
+}
+
+/// Add this component to a [`PointLight`], [`SpotLight`] or [`DirectionalLight`] to only show its
+/// shadows on meshes in the given render layers.
+///
+/// This is separate from the light's own [`RenderLayers`]: meshes on other layers are still lit by
+/// the light, they just don't receive its shadows. A light without this component shadows meshes
+/// on every layer. A mesh without [`RenderLayers`] is on layer 0.
+///
+/// Only the first [`ShadowLayers::MAX_LAYERS`] render layers are told apart. If the layers include
+/// all of them, meshes on every layer receive the shadows. Otherwise, meshes that are only on
+/// higher layers don't. The layers of meshes aren't stored in the G-buffer, so meshes rendered with
+/// the deferred renderer receive the shadows on every layer.
+#[derive(Component, Clone, Debug, Default, PartialEq, Reflect)]
+#[reflect(Component, Debug, Default, PartialEq, Clone)]
+pub struct ShadowLayers(pub RenderLayers);
+
+impl ShadowLayers {
+    /// The number of render layers, starting from layer 0, that shadow layers can tell apart.
+    pub const MAX_LAYERS: usize = 8;
+}
+
+/// Add this component to make a [`Mesh3d`] not receive shadows.
+///
+/// This also applies to transparent materials, such as ones using
+/// [`AlphaMode::Blend`](bevy_render::alpha::AlphaMode::Blend), which receive shadows in their
+/// forward pass just like opaque materials.
+///
+/// **Note:** If you're using diffuse transmission, setting [`NotShadowReceiver`] will
+/// cause both “regular” shadows as well as diffusely transmitted shadows to be disabled,
+/// even when [`TransmittedShadowReceiver`] is being used.
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component, Default, Debug)]
+pub struct NotShadowReceiver;
+/// Add this component to make a [`Mesh3d`] using a PBR material with [`diffuse_transmission`](crate::pbr_material::StandardMaterial::diffuse_transmission)`> 0.0`
+/// receive shadows on its diffuse transmission lobe. (i.e. its “backside”)
+///
+/// Not enabled by default, as it requires carefully setting up [`thickness`](crate::pbr_material::StandardMaterial::thickness)
+/// (and potentially even baking a thickness texture!) to match the geometry of the mesh, in order to avoid self-shadow artifacts.
+///
+/// **Note:** Using [`NotShadowReceiver`] overrides this component.
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component, Default, Debug)]
+pub struct TransmittedShadowReceiver;
+
+/// Add this component to make a [`Mesh3d`] not receive any [`AmbientLight`], for example to keep
+/// an emissive in-world UI panel from being washed out.
+///
+/// This only removes the ambient term. Direct lights, environment maps, irradiance volumes and
+/// lightmaps still apply.
+#[derive(Debug, Component, Reflect, Default)]
+#[reflect(Component, Default, Debug)]
+pub struct NoAmbientLight;
+
+/// Add this component to a [`Camera3d`](bevy_core_pipeline::core_3d::Camera3d)
+/// to control how to anti-alias shadow edges.
+///
+/// The different modes use different approaches to
+/// [Percentage Closer Filtering](https://developer.nvidia.com/gpugems/gpugems/part-ii-lighting-and-shadows/chapter-11-shadow-map-antialiasing).
+#[derive(Debug, Component, ExtractComponent, Reflect, Clone, Copy, PartialEq, Eq, Default)]
+#[reflect(Component, Default, Debug, PartialEq, Clone)]
+pub enum ShadowFilteringMethod {
+    /// Hardware 2x2.
+    ///
+    /// Fast but poor quality.
+    Hardware2x2,
+    /// Approximates a fixed Gaussian blur, good when TAA isn't in use.
+    ///
+    /// Good quality, good performance.
+    ///
+    /// For directional and spot lights, this uses a [method by Ignacio Castaño
+    /// for *The Witness*] using 9 samples and smart filtering to achieve the same
+    /// as a regular 5x5 filter kernel.
+    ///
+    /// [method by Ignacio Castaño for *The Witness*]: https://web.archive.org/web/20230210095515/http://the-witness.net/news/2013/09/shadow-mapping-summary-part-1/
+    #[default]
+    Gaussian,
+    /// A randomized filter that varies over time, good when TAA is in use.
+    ///
+    /// Good quality when used with `TemporalAntiAliasing`
+    /// and good performance.
+    ///
+    /// For directional and spot lights, this uses a [method by Jorge Jimenez for
+    /// *Call of Duty: Advanced Warfare*] using 8 samples in spiral pattern,
+    /// randomly-rotated by interleaved gradient noise with spatial variation.
+    ///
+    /// [method by Jorge Jimenez for *Call of Duty: Advanced Warfare*]: https://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare/
+    Temporal,
+    /// A filter that distributes a configurable number of samples on a [Vogel disk], randomly
+    /// rotated per pixel and per frame by interleaved gradient noise.
+    ///
+    /// The spiral covers the disk more evenly than a fixed grid, so it gives smoother edges than
+    /// [`ShadowFilteringMethod::Gaussian`] for the same number of samples. Like
+    /// [`ShadowFilteringMethod::Temporal`], the remaining noise is best resolved by
+    /// `TemporalAntiAliasing`; without it, use more samples.
+    ///
+    /// ```
+    /// # use bevy_pbr::ShadowFilteringMethod;
+    /// let shadow_filtering_method = ShadowFilteringMethod::Vogel { samples: 16 };
+    /// ```
+    ///
+    /// [Vogel disk]: https://www.gamedev.net/tutorials/programming/graphics/contact-hardening-soft-shadows-made-fast-r4906/
+    Vogel {
+        /// The number of shadow map samples taken per fragment.
+        ///
+        /// This is clamped to at least 1.
+        samples: u32,
+    },
+}
+
+/// A resource that fixes the noise used by [`ShadowFilteringMethod::Temporal`] and
+/// [`ShadowFilteringMethod::Vogel`], so that their shadows render reproducibly.
+///
+/// The noise that rotates the filter samples normally changes with the [`FrameCount`], so the same
+/// scene renders slightly differently depending on how many frames have elapsed since startup.
+/// While this resource exists, the noise uses [`frame`](Self::frame) instead. Golden-image tests
+/// can leave it fixed, and deterministic replays can set it to the replay's own frame index every
+/// frame to keep the variation over time that temporal anti-aliasing relies on.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq, ExtractResource, Reflect)]
+#[reflect(Resource, Debug, Default, PartialEq, Clone)]
+pub struct TemporalShadowSeed {
+    /// The frame index that the shadow filtering noise is computed for.
+    pub frame: u32,
+}
+
+/// Returns the frame index that the shadow filtering noise is computed for: the
+/// [`TemporalShadowSeed`] if there is one, or the [`FrameCount`] otherwise.
+pub(crate) fn temporal_shadow_frame(seed: Option<&TemporalShadowSeed>, frame_count: u32) -> u32 {
+    seed.map_or(frame_count, |seed| seed.frame)
+}
+
+/// The [`VisibilityClass`] used for all lights (point, directional, and spot).
+pub struct LightVisibilityClass;
+
+/// System sets used to run light-related systems.
+#[derive(Debug, Hash, PartialEq, Eq, Clone, SystemSet)]
+pub enum SimulationLightSystems {
+    AddClusters,
+    AssignLightsToClusters,
+    /// System order ambiguities between systems in this set are ignored:
+    /// each [`build_directional_light_cascades`] system is independent of the others,
+    /// and should operate on distinct sets of entities.
+    UpdateDirectionalLightCascades,
+    UpdateLightFrusta,
+    /// System order ambiguities between systems in this set are ignored:
+    /// the order of systems within this set is irrelevant, as the various visibility-checking systems
+    /// assumes that their operations are irreversible during the frame.
+    CheckLightVisibility,
+}
+
+pub fn update_directional_light_frusta(
+    mut views: Query<
+        (
+            &Cascades,
+            &DirectionalLight,
+            &ViewVisibility,
+            &mut CascadesFrusta,
+        ),
+        (
+            // Prevents this query from conflicting with camera queries.
+            Without<Camera>,
+        ),
+    >,
+) {
+    for (cascades, directional_light, visibility, mut frusta) in &mut views {
+        // The frustum is used for culling meshes to the light for shadow mapping
+        // so if shadow mapping is disabled for this light, then the frustum is
+        // not needed.
+        if !directional_light.shadows_enabled || !visibility.get() {
+            continue;
+        }
+
+        frusta.frusta = cascades
+            .cascades
+            .iter()
+            .map(|(view, cascades)| {
+                (
+                    *view,
+                    cascades
+                        .iter()
+                        .map(|c| Frustum::from_clip_from_world(&c.clip_from_world))
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+    }
+}
+
+// NOTE: Run this after assign_lights_to_clusters!
+pub fn update_point_light_frusta(
+    global_lights: Res<GlobalVisibleClusterableObjects>,
+    point_light_shadow_map: Res<PointLightShadowMap>,
+    mut views: Query<(
+        Entity,
+        &GlobalTransform,
+        &PointLight,
+        &mut CubemapFrusta,
+        &mut ShadowFrustaTransform,
+        Has<ScaleAffectsRange>,
+    )>,
+    changed_lights: Query<
+        Entity,
+        (
+            With<PointLight>,
+            Or<(
+                Changed<GlobalTransform>,
+                Changed<PointLight>,
+                Changed<ScaleAffectsRange>,
+            )>,
+        ),
+    >,
+) {
+    let view_rotations = CUBE_MAP_FACES
+        .iter()
+        .map(|CubeMapFace { target, up }| Transform::IDENTITY.looking_at(*target, *up))
+        .collect::<Vec<_>>();
+
+    for (
+        entity,
+        transform,
+        point_light,
+        mut cubemap_frusta,
+        mut frusta_transform,
+        scale_affects_range,
+    ) in &mut views
+    {
+        // If this light hasn't changed, and neither has the set of global_lights,
+        // then we can skip this calculation.
+        if !global_lights.is_changed()
+            && !point_light_shadow_map.is_changed()
+            && !changed_lights.contains(entity)
+        {
+            continue;
+        }
+
+        // The frusta are used for culling meshes to the light for shadow mapping
+        // so if shadow mapping is disabled for this light, then the frusta are
+        // not needed.
+        // Also, if the light is not relevant for any cluster, it will not be in the
+        // global lights set and so there is no need to update its frusta.
+        if !point_light.shadows_enabled || !global_lights.entities.contains(&entity) {
+            continue;
+        }
+
+        frusta_transform.0 = *transform;
+
+        let clip_from_view = Mat4::perspective_infinite_reverse_rh(
+            point_light_shadow_map.cube_face_fov(),
+            1.0,
+            point_light.shadow_map_near_z,
+        );
+
+        // ignore scale because we don't want to effectively scale light radius and range
+        // by applying those as a view transform to shadow map rendering of objects
+        // and ignore rotation because we want the shadow map projections to align with the axes
+        let view_translation = Transform::from_translation(transform.translation());
+        let view_backward = transform.back();
+        let range = scaled_light_range(point_light.range, transform, scale_affects_range);
+        let hemisphere_direction = match point_light.shadow_shape {
+            PointLightShadowShape::FullCube => None,
+            PointLightShadowShape::Hemisphere { direction } => {
+                Some(transform.rotation() * direction.normalize_or(Vec3::NEG_Z))
+            }
+        };
+        let face_sees_hemisphere = |face_index| {
+            hemisphere_direction.is_none_or(|direction| {
+                shadow_face_sees_hemisphere(
+                    face_index,
+                    point_light.shadow_technique,
+                    point_light_shadow_map.cube_face_fov(),
+                    direction,
+                )
+            })
+        };
+
+        if point_light.shadow_technique == PointShadowTechnique::DualParaboloid {
+            for (face_index, frustum) in cubemap_frusta.iter_mut().enumerate() {
+                *frustum = if face_sees_hemisphere(face_index) {
+                    dual_paraboloid_frustum(face_index, transform.translation(), range)
+                } else {
+                    empty_frustum()
+                };
+            }
+            continue;
+        }
+
+        for (face_index, (view_rotation, frustum)) in view_rotations
+            .iter()
+            .zip(cubemap_frusta.iter_mut())
+            .enumerate()
+        {
+            // Faces that don't see the hemisphere gather no casters, and so stay empty.
+            if !face_sees_hemisphere(face_index) {
+                *frustum = empty_frustum();
+                continue;
+            }
+
+            let world_from_view = view_translation * *view_rotation;
+            let clip_from_world = clip_from_view * world_from_view.to_matrix().inverse();
+
+            *frustum = Frustum::from_clip_from_world_custom_far(
+                &clip_from_world,
+                &transform.translation(),
+                &view_backward,
+                range,
+            );
+        }
+    }
+}
+
+/// Returns a frustum that contains nothing, for shadow map faces that aren't rendered.
+fn empty_frustum() -> Frustum {
+    Frustum {
+        half_spaces: [HalfSpace::new(Vec3::X.extend(f32::NEG_INFINITY)); 6],
+    }
+}
+
+/// Returns true if the shadow map face `face_index` of a point light with `shadow_technique`, whose
+/// cube map faces have a field of view of `cube_face_fov`, sees some of the hemisphere around the
+/// world-space unit vector `direction`, see [`PointLightShadowShape::Hemisphere`].
+fn shadow_face_sees_hemisphere(
+    face_index: usize,
+    shadow_technique: PointShadowTechnique,
+    cube_face_fov: f32,
+    direction: Vec3,
+) -> bool {
+    // Directions on the edge of the hemisphere don't need any shadows.
+    const EPSILON: f32 = 1e-4;
+
+    let CubeMapFace { target, up } = &CUBE_MAP_FACES[face_index];
+    let (target, up) = (*target, *up);
+    match shadow_technique {
+        // Each face covers the whole hemisphere around its target, which only misses the
+        // hemisphere around the opposite direction.
+        PointShadowTechnique::DualParaboloid => target.dot(direction) > EPSILON - 1.0,
+        // The face sees some of the hemisphere if one of the corners of its frustum does.
+        PointShadowTechnique::Cubemap => {
+            let half_size = ops::tan(cube_face_fov * 0.5);
+            let right = target.cross(up);
+            [(-1.0, -1.0), (-1.0, 1.0), (1.0, -1.0), (1.0, 1.0)]
+                .into_iter()
+                .any(|(x, y)| {
+                    let corner = target + (right * x + up * y) * half_size;
+                    corner.dot(direction) > EPSILON
+                })
+        }
+    }
+}
+
+/// The faces of a point light's cube map that hold the hemispheres of
+/// [`PointShadowTechnique::DualParaboloid`] shadows, looking along -Z and +Z respectively.
+pub(crate) const DUAL_PARABOLOID_FACES: [usize; 2] = [4, 5];
+
+/// Returns the frustum of the cube map face `face_index` of a point light with
+/// [`PointShadowTechnique::DualParaboloid`] shadows.
+///
+/// The faces in [`DUAL_PARABOLOID_FACES`] cover the hemisphere around their target direction, up
+/// to `range` along it, and the other faces aren't rendered, so their frustum contains nothing.
+fn dual_paraboloid_frustum(face_index: usize, light_position: Vec3, range: f32) -> Frustum {
+    if !DUAL_PARABOLOID_FACES.contains(&face_index) {
+        return empty_frustum();
+    }
+
+    let target = CUBE_MAP_FACES[face_index].target;
+    let mut half_spaces = [HalfSpace::new(Vec3::X.extend(f32::INFINITY)); 6];
+    half_spaces[4] = HalfSpace::new(target.extend(-target.dot(light_position)));
+    half_spaces[5] = HalfSpace::new((-target).extend(target.dot(light_position) + range));
+    Frustum { half_spaces }
+}
+
+// NOTE: Run this after assign_lights_to_clusters!
+pub fn update_spot_light_frusta(
+    global_lights: Res<GlobalVisibleClusterableObjects>,
+    mut views: Query<(
+        Entity,
+        &GlobalTransform,
+        &SpotLight,
+        &mut Frustum,
+        &mut ShadowFrustaTransform,
+        Has<ScaleAffectsRange>,
+    )>,
+    changed_lights: Query<
+        Entity,
+        (
+            With<SpotLight>,
+            Or<(
+                Changed<GlobalTransform>,
+                Changed<SpotLight>,
+                Changed<ScaleAffectsRange>,
+            )>,
+        ),
+    >,
+) {
+    for (entity, transform, spot_light, mut frustum, mut frusta_transform, scale_affects_range) in
+        &mut views
+    {
+        // If this light hasn't changed, and neither has the set of global_lights, then we can
+        // skip this calculation. A light that moved while it wasn't in the set is updated once
+        // it's back.
+        if !global_lights.is_changed() && !changed_lights.contains(entity) {
+            continue;
+        }
+
+        // The frusta are used for culling meshes to the light for shadow mapping
+        // so if shadow mapping is disabled for this light, then the frusta are
+        // not needed.
+        // Also, if the light is not relevant for any cluster, it will not be in the
+        // global lights set and so there is no need to update its frusta.
+        if !spot_light.shadows_enabled || !global_lights.entities.contains(&entity) {
+            continue;
+        }
+
+        frusta_transform.0 = *transform;
+
+        // ignore scale because we don't want to effectively scale light radius and range
+        // by applying those as a view transform to shadow map rendering of objects
+        let view_backward = transform.back();
+
+        let spot_world_from_view = spot_light_world_from_view(transform);
+        let (angle_x, angle_y) = spot_light.outer_angles();
+        let spot_clip_from_view =
+            spot_light_clip_from_view(angle_x, angle_y, spot_light.shadow_map_near_z);
+        let clip_from_world = spot_clip_from_view * spot_world_from_view.inverse();
+
+        *frustum = Frustum::from_clip_from_world_custom_far(
+            &clip_from_world,
+            &transform.translation(),
+            &view_backward,
+            scaled_light_range(spot_light.range, transform, scale_affects_range),
+        );
+    }
+}
+
+fn shrink_entities(visible_entities: &mut Vec<Entity>) {
+    // Check that visible entities capacity() is no more than two times greater than len()
+    let capacity = visible_entities.capacity();
+    let reserved = capacity
+        .checked_div(visible_entities.len())
+        .map_or(0, |reserve| {
+            if reserve > 2 {
+                capacity / (reserve / 2)
+            } else {
+                capacity
+            }
+        });
+
+    visible_entities.shrink_to(reserved);
+}
+
+/// A resource that makes the shadow caster visibility systems, [`check_dir_light_mesh_visibility`]
+/// and [`check_point_light_mesh_visibility`], check every mesh on the system's thread, in query
+/// order, instead of in parallel.
+///
+/// The results are the same as with the parallel path, but the order of the visible entities is
+/// deterministic. This is slower, and meant for debugging nondeterministic visibility bugs and for
+/// comparing profiles against the parallel path.
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct ForceSerialVisibility;
+
+/// Runs `func` on every item of `query` like [`QueryParIter::for_each_init`], or, if `serial` is
+/// true, on the calling thread in query order, with a single value from `init`.
+///
+/// [`QueryParIter::for_each_init`]: bevy_ecs::query::QueryParIter::for_each_init
+fn for_each_init_maybe_serial<'a, 's, D: QueryData, F: QueryFilter, T>(
+    query: &'a mut Query<'_, 's, D, F>,
+    serial: bool,
+    init: impl Fn() -> T + Send + Sync + Clone,
+    func: impl Fn(&mut T, QueryItem<'a, 's, D>) + Send + Sync + Clone,
+) {
+    if serial {
+        let mut local = init();
+        query.iter_mut().for_each(|item| func(&mut local, item));
+    } else {
+        query.par_iter_mut().for_each_init(init, func);
+    }
+}
+
+pub fn check_dir_light_mesh_visibility(
+    mut commands: Commands,
+    mut directional_lights: Query<
+        (
+            Entity,
+            &DirectionalLight,
+            &CascadesFrusta,
+            &mut CascadesVisibleEntities,
+            Option<&RenderLayers>,
+            &ViewVisibility,
+            Option<&ShadowBounds>,
+        ),
+        Without<SpotLight>,
+    >,
+    mut visible_entity_query: Query<
+        (
+            Entity,
+            &InheritedVisibility,
+            Option<&RenderLayers>,
+            Option<&Aabb>,
+            Option<&GlobalTransform>,
+            Has<VisibilityRange>,
+            Has<NoFrustumCulling>,
+            Option<&ShadowCasterLights>,
+            Option<&ShadowCaster>,
+            Option<&ShadowCascadeRange>,
+        ),
+        (
+            Without<NotShadowCaster>,
+            Without<DirectionalLight>,
+            With<Mesh3d>,
+        ),
+    >,
+    visible_entity_ranges: Option<Res<VisibleEntityRanges>>,
+    force_serial_visibility: Option<Res<ForceSerialVisibility>>,
+    mut defer_visible_entities_queue: Local<Parallel<Vec<Entity>>>,
+    mut view_visible_entities_queue: Local<Parallel<Vec<Vec<Entity>>>>,
+) {
+    let visible_entity_ranges = visible_entity_ranges.as_deref();
+    let serial = force_serial_visibility.is_some();
+
+    for (
+        light_entity,
+        directional_light,
+        frusta,
+        mut visible_entities,
+        maybe_view_mask,
+        light_view_visibility,
+        shadow_bounds,
+    ) in &mut directional_lights
+    {
+        let mut views_to_remove = Vec::new();
+        for (view, cascade_view_entities) in &mut visible_entities.entities {
+            match frusta.frusta.get(view) {
+                Some(view_frusta) => {
+                    cascade_view_entities.resize(view_frusta.len(), Default::default());
+                    cascade_view_entities.iter_mut().for_each(|x| x.clear());
+                }
+                None => views_to_remove.push(*view),
+            };
+        }
+        for (view, frusta) in &frusta.frusta {
+            visible_entities
+                .entities
+                .entry(*view)
+                .or_insert_with(|| vec![VisibleMeshEntities::default(); frusta.len()]);
+        }
+
+        for v in views_to_remove {
+            visible_entities.entities.remove(&v);
+        }
+
+        // NOTE: If shadow mapping is disabled for the light then it must have no visible entities
+        if !directional_light.shadows_enabled || !light_view_visibility.get() {
+            continue;
+        }
+
+        let view_mask = maybe_view_mask.unwrap_or_default();
+
+        for (view, view_frusta) in &frusta.frusta {
+            // Cascades that don't overlap the shadow bounds can't cast any shadows into them.
+            let cascades_in_bounds = view_frusta
+                .iter()
+                .map(|frustum| {
+                    shadow_bounds.is_none_or(|shadow_bounds| {
+                        frustum.intersects_obb(&shadow_bounds.0, &Affine3A::IDENTITY, false, true)
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            for_each_init_maybe_serial(
+                &mut visible_entity_query,
+                serial,
+                || {
+                    let mut entities = view_visible_entities_queue.borrow_local_mut();
+                    entities.resize(view_frusta.len(), Vec::default());
+                    (defer_visible_entities_queue.borrow_local_mut(), entities)
+                },
+                |(defer_visible_entities_local_queue, view_visible_entities_local_queue),
+                 (
+                    entity,
+                    inherited_visibility,
+                    maybe_entity_mask,
+                    maybe_aabb,
+                    maybe_transform,
+                    has_visibility_range,
+                    has_no_frustum_culling,
+                    maybe_shadow_caster_lights,
+                    maybe_shadow_caster,
+                    maybe_cascade_range,
+                )| {
+                    if !inherited_visibility.get() {
+                        return;
+                    }
+
+                    let in_cascade_range = |cascade_index| {
+                        maybe_cascade_range.is_none_or(|range| range.contains(cascade_index))
+                    };
+
+                    if !casts_shadows_from(
+                        maybe_shadow_caster,
+                        maybe_shadow_caster_lights,
+                        light_entity,
+                    ) {
+                        return;
+                    }
+
+                    let entity_mask = maybe_entity_mask.unwrap_or_default();
+                    if !view_mask.intersects(entity_mask) {
+                        return;
+                    }
+
+                    // Check visibility ranges.
+                    if has_visibility_range
+                        && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
+                            !visible_entity_ranges.entity_is_in_range_of_view(entity, *view)
+                        })
+                    {
+                        return;
+                    }
+
+                    if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
+                        if shadow_bounds.is_some_and(|shadow_bounds| {
+                            !shadow_bounds.intersects_obb(aabb, &transform.affine())
+                        }) {
+                            return;
+                        }
+
+                        let mut visible = false;
+                        for (cascade_index, ((frustum, frustum_visible_entities), in_bounds)) in
+                            view_frusta
+                                .iter()
+                                .zip(view_visible_entities_local_queue.iter_mut())
+                                .zip(cascades_in_bounds.iter())
+                                .enumerate()
+                        {
+                            if !in_bounds || !in_cascade_range(cascade_index) {
+                                continue;
+                            }
+
+                            // Disable near-plane culling, as a shadow caster could lie before the near plane.
+                            if !has_no_frustum_culling
+                                && !frustum.intersects_obb(aabb, &transform.affine(), false, true)
+                            {
+                                continue;
+                            }
+                            visible = true;
+
+                            frustum_visible_entities.push(entity);
+                        }
+                        if visible {
+                            defer_visible_entities_local_queue.push(entity);
+                        }
+                    } else {
+                        defer_visible_entities_local_queue.push(entity);
+                        for (cascade_index, frustum_visible_entities) in
+                            view_visible_entities_local_queue.iter_mut().enumerate()
+                        {
+                            if in_cascade_range(cascade_index) {
+                                frustum_visible_entities.push(entity);
+                            }
+                        }
+                    }
+                },
+            );
+            // collect entities from parallel queue
+            for entities in view_visible_entities_queue.iter_mut() {
+                visible_entities
+                    .entities
+                    .get_mut(view)
+                    .unwrap()
+                    .iter_mut()
+                    .zip(entities.iter_mut())
+                    .for_each(|(dst, source)| {
+                        dst.append(source);
+                    });
+            }
+        }
+
+        for (_, cascade_view_entities) in &mut visible_entities.entities {
+            cascade_view_entities
+                .iter_mut()
+                .map(DerefMut::deref_mut)
+                .for_each(shrink_entities);
+        }
+    }
+
+    // Defer marking view visibility so this system can run in parallel with check_point_light_mesh_visibility
+    // TODO: use resource to avoid unnecessary memory alloc
+    let mut defer_queue = core::mem::take(defer_visible_entities_queue.deref_mut());
+    commands.queue(move |world: &mut World| {
+        world.resource_scope::<PreviousVisibleEntities, _>(
+            |world, mut previous_visible_entities| {
+                let mut query = world.query::<(Entity, &mut ViewVisibility)>();
+                for entities in defer_queue.iter_mut() {
+                    let mut iter = query.iter_many_mut(world, entities.iter());
+                    while let Some((entity, mut view_visibility)) = iter.fetch_next() {
+                        if !**view_visibility {
+                            view_visibility.set();
+                        }
+
+                        // Remove any entities that were discovered to be
+                        // visible from the `PreviousVisibleEntities` resource.
+                        previous_visible_entities.remove(&entity);
+                    }
+                }
+            },
+        );
+    });
+}
+
+pub fn check_point_light_mesh_visibility(
+    visible_point_lights: Query<&VisibleClusterableObjects>,
+    camera_positions: Res<ActiveCameraPositions>,
+    mut point_lights: Query<(
+        &PointLight,
+        &GlobalTransform,
+        &CubemapFrusta,
+        &mut CubemapVisibleEntities,
+        Option<&RenderLayers>,
+        &InheritedVisibility,
+        Has<ScaleAffectsRange>,
+        Has<BakedPointShadow>,
+    )>,
+    mut spot_lights: Query<(
+        &SpotLight,
+        &GlobalTransform,
+        &Frustum,
+        &mut VisibleMeshEntities,
+        Option<&RenderLayers>,
+        &InheritedVisibility,
+        Has<ScaleAffectsRange>,
+    )>,
+    mut visible_entity_query: Query<
+        (
+            Entity,
+            &InheritedVisibility,
+            &mut ViewVisibility,
+            Option<&RenderLayers>,
+            Option<&Aabb>,
+            Option<&GlobalTransform>,
+            Has<VisibilityRange>,
+            Has<NoFrustumCulling>,
+            Option<&ShadowCasterLights>,
+            Option<&ShadowCaster>,
+        ),
+        (
+            Without<NotShadowCaster>,
+            Without<DirectionalLight>,
+            With<Mesh3d>,
+        ),
+    >,
+    visible_entity_ranges: Option<Res<VisibleEntityRanges>>,
+    force_serial_visibility: Option<Res<ForceSerialVisibility>>,
+    mut previous_visible_entities: ResMut<PreviousVisibleEntities>,
+    mut cubemap_visible_entities_queue: Local<Parallel<[Vec<Entity>; 6]>>,
+    mut spot_visible_entities_queue: Local<Parallel<Vec<Entity>>>,
+    mut checked_lights: Local<EntityHashSet>,
+) {
+    checked_lights.clear();
+
+    let serial = force_serial_visibility.is_some();
+    let visible_entity_ranges = visible_entity_ranges.as_deref();
+    for visible_lights in &visible_point_lights {
+        for light_entity in visible_lights.entities.iter().copied() {
+            if !checked_lights.insert(light_entity) {
+                continue;
+            }
+
+            // Point lights
+            if let Ok((
+                point_light,
+                transform,
+                cubemap_frusta,
+                mut cubemap_visible_entities,
+                maybe_view_mask,
+                light_inherited_visibility,
+                scale_affects_range,
+                baked_shadow,
+            )) = point_lights.get_mut(light_entity)
+            {
+                for visible_entities in cubemap_visible_entities.iter_mut() {
+                    visible_entities.entities.clear();
+                }
+
+                // NOTE: If shadow mapping is disabled for the light, its shadows are baked, the
+                // light is hidden, or it's beyond its shadow cutoff distance, then it must have no
+                // visible entities
+                if !point_light.shadows_enabled
+                    || baked_shadow
+                    || !light_inherited_visibility.get()
+                    || !camera_positions.within_shadow_cutoff_distance(
+                        point_light.shadow_cutoff_distance,
+                        transform.translation_vec3a(),
+                    )
+                {
+                    continue;
+                }
+
+                let view_mask = maybe_view_mask.unwrap_or_default();
+                let light_sphere = Sphere {
+                    center: Vec3A::from(transform.translation()),
+                    radius: scaled_light_range(point_light.range, transform, scale_affects_range),
+                };
+
+                for_each_init_maybe_serial(
+                    &mut visible_entity_query,
+                    serial,
+                    || cubemap_visible_entities_queue.borrow_local_mut(),
+                    |cubemap_visible_entities_local_queue,
+                     (
+                        entity,
+                        inherited_visibility,
+                        mut view_visibility,
+                        maybe_entity_mask,
+                        maybe_aabb,
+                        maybe_transform,
+                        has_visibility_range,
+                        has_no_frustum_culling,
+                        maybe_shadow_caster_lights,
+                        maybe_shadow_caster,
+                    )| {
+                        if !inherited_visibility.get() {
+                            return;
+                        }
+                        if !casts_shadows_from(
+                            maybe_shadow_caster,
+                            maybe_shadow_caster_lights,
+                            light_entity,
+                        ) {
+                            return;
+                        }
+                        let entity_mask = maybe_entity_mask.unwrap_or_default();
+                        if !view_mask.intersects(entity_mask) {
+                            return;
+                        }
+                        if has_visibility_range
+                            && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
+                                !visible_entity_ranges.entity_is_in_range_of_any_view(entity)
+                            })
+                        {
+                            return;
+                        }
+
+                        // If we have an aabb and transform, do frustum culling
+                        if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
+                            let model_to_world = transform.affine();
+                            // Do a cheap sphere vs obb test to prune out most meshes outside the sphere of the light
+                            if !has_no_frustum_culling
+                                && !light_sphere.intersects_obb(aabb, &model_to_world)
+                            {
+                                return;
+                            }
+
+                            for (frustum, visible_entities) in cubemap_frusta
+                                .iter()
+                                .zip(cubemap_visible_entities_local_queue.iter_mut())
+                            {
+                                if has_no_frustum_culling
+                                    || frustum.intersects_obb(aabb, &model_to_world, true, true)
+                                {
+                                    if !**view_visibility {
+                                        view_visibility.set();
+                                    }
+                                    visible_entities.push(entity);
+                                }
+                            }
+                        } else {
+                            if !**view_visibility {
+                                view_visibility.set();
+                            }
+                            for visible_entities in cubemap_visible_entities_local_queue.iter_mut()
+                            {
+                                visible_entities.push(entity);
+                            }
+                        }
+                    },
+                );
+
+                for entities in cubemap_visible_entities_queue.iter_mut() {
+                    for (dst, source) in
+                        cubemap_visible_entities.iter_mut().zip(entities.iter_mut())
+                    {
+                        // Remove any entities that were discovered to be
+                        // visible from the `PreviousVisibleEntities` resource.
+                        for entity in source.iter() {
+                            previous_visible_entities.remove(entity);
+                        }
+
+                        dst.entities.append(source);
+                    }
+                }
+
+                for visible_entities in cubemap_visible_entities.iter_mut() {
+                    shrink_entities(visible_entities);
+                }
+            }
+
+            // Spot lights
+            if let Ok((
+                point_light,
+                transform,
+                frustum,
+                mut visible_entities,
+                maybe_view_mask,
+                light_inherited_visibility,
+                scale_affects_range,
+            )) = spot_lights.get_mut(light_entity)
+            {
+                visible_entities.clear();
+
+                // NOTE: If shadow mapping is disabled for the light, the light is hidden, or it's
+                // beyond its shadow cutoff distance, then it must have no visible entities
+                if !point_light.shadows_enabled
+                    || !light_inherited_visibility.get()
+                    || !camera_positions.within_shadow_cutoff_distance(
+                        point_light.shadow_cutoff_distance,
+                        transform.translation_vec3a(),
+                    )
+                {
+                    continue;
+                }
+
+                let view_mask = maybe_view_mask.unwrap_or_default();
+                let light_sphere = Sphere {
+                    center: Vec3A::from(transform.translation()),
+                    radius: scaled_light_range(point_light.range, transform, scale_affects_range),
+                };
+                let light_cone = point_light
+                    .cone_shadow_culling
+                    .then(|| SpotLightCone::new(transform, point_light.max_outer_angle()));
+
+                for_each_init_maybe_serial(
+                    &mut visible_entity_query,
+                    serial,
+                    || spot_visible_entities_queue.borrow_local_mut(),
+                    |spot_visible_entities_local_queue,
+                     (
+                        entity,
+                        inherited_visibility,
+                        mut view_visibility,
+                        maybe_entity_mask,
+                        maybe_aabb,
+                        maybe_transform,
+                        has_visibility_range,
+                        has_no_frustum_culling,
+                        maybe_shadow_caster_lights,
+                        maybe_shadow_caster,
+                    )| {
+                        if !inherited_visibility.get() {
+                            return;
+                        }
+                        if !casts_shadows_from(
+                            maybe_shadow_caster,
+                            maybe_shadow_caster_lights,
+                            light_entity,
+                        ) {
+                            return;
+                        }
+
+                        let entity_mask = maybe_entity_mask.unwrap_or_default();
+                        if !view_mask.intersects(entity_mask) {
+                            return;
+                        }
+                        // Check visibility ranges.
+                        if has_visibility_range
+                            && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
+                                !visible_entity_ranges.entity_is_in_range_of_any_view(entity)
+                            })
+                        {
+                            return;
+                        }
+
+                        if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
+                            let model_to_world = transform.affine();
+                            // Do a cheap sphere vs obb test to prune out most meshes outside the sphere of the light
+                            if !has_no_frustum_culling
+                                && !light_sphere.intersects_obb(aabb, &model_to_world)
+                            {
+                                return;
+                            }
+
+                            if has_no_frustum_culling
+                                || (frustum.intersects_obb(aabb, &model_to_world, true, true)
+                                    && light_cone.is_none_or(|light_cone| {
+                                        light_cone.intersects_obb(aabb, &model_to_world)
+                                    }))
+                            {
+                                if !**view_visibility {
+                                    view_visibility.set();
+                                }
+                                spot_visible_entities_local_queue.push(entity);
+                            }
+                        } else {
+                            if !**view_visibility {
+                                view_visibility.set();
+                            }
+                            spot_visible_entities_local_queue.push(entity);
+                        }
+                    },
+                );
+
+                for entities in spot_visible_entities_queue.iter_mut() {
+                    visible_entities.append(entities);
+
+                    // Remove any entities that were discovered to be visible
+                    // from the `PreviousVisibleEntities` resource.
+                    for entity in entities {
+                        previous_visible_entities.remove(entity);
+                    }
+                }
+
+                shrink_entities(visible_entities.deref_mut());
+            }
+        }
+    }
+}
+
+/// A resource that caps the number of shadow caster draws rendered each frame.
+///
+/// Every mesh visible to a shadow map face or cascade counts as one draw. When the total across
+/// all lights exceeds [`max_draws`](Self::max_draws), [`apply_shadow_draw_budget`] drops the
+/// shadows of the least important lights until the total fits: point and spot lights furthest
+/// from the active cameras go first, and directional lights go last. Lights that lose their
+/// shadows still light the scene.
+///
+/// Without this resource, there is no limit.
+#[derive(Resource, Clone, Copy, Debug, PartialEq, Eq, Reflect)]
+#[reflect(Resource, Debug, PartialEq, Clone)]
+pub struct ShadowDrawBudget {
+    /// The maximum number of shadow caster draws per frame.
+    pub max_draws: u32,
+}
+
+/// Returns the number of shadow caster draws of a point light, over all of its cube faces.
+fn cubemap_shadow_draws(visible_entities: &CubemapVisibleEntities) -> usize {
+    visible_entities.iter().map(|v| v.entities.len()).sum()
+}
+
+/// Returns the number of shadow caster draws of a directional light, over all of its cascades
+/// for every view.
+fn cascades_shadow_draws(visible_entities: &CascadesVisibleEntities) -> usize {
+    visible_entities
+        .entities
+        .values()
+        .flatten()
+        .map(|v| v.entities.len())
+        .sum()
+}
+
+/// Drops the shadows of the least important lights while the number of shadow caster draws exceeds
+/// the [`ShadowDrawBudget`].
+pub fn apply_shadow_draw_budget(
+    budget: Option<Res<ShadowDrawBudget>>,
+    camera_positions: Res<ActiveCameraPositions>,
+    mut point_lights: Query<(Entity, &GlobalTransform, &mut CubemapVisibleEntities)>,
+    mut spot_lights: Query<(Entity, &GlobalTransform, &mut VisibleMeshEntities), With<SpotLight>>,
+    mut directional_lights: Query<(Entity, &mut CascadesVisibleEntities)>,
+) {
+    let Some(budget) = budget else {
+        return;
+    };
+
+    let distance_to_cameras = |transform: &GlobalTransform| {
+        camera_positions
+            .iter()
+            .map(|position| position.distance(transform.translation_vec3a()))
+            .reduce(f32::min)
+            .unwrap_or(f32::INFINITY)
+    };
+
+    // The importance of each light's shadows, and how many draws they take. A lower importance
+    // means the light's shadows are dropped first.
+    let mut lights: Vec<(Entity, f32, usize)> = point_lights
+        .iter()
+        .map(|(entity, transform, visible_entities)| {
+            (
+                entity,
+                -distance_to_cameras(transform),
+                cubemap_shadow_draws(visible_entities),
+            )
+        })
+        .chain(
+            spot_lights
+                .iter()
+                .map(|(entity, transform, visible_entities)| {
+                    (
+                        entity,
+                        -distance_to_cameras(transform),
+                        visible_entities.len(),
+                    )
+                }),
+        )
+        .chain(directional_lights.iter().map(|(entity, visible_entities)| {
+            (
+                entity,
+                f32::INFINITY,
+                cascades_shadow_draws(visible_entities),
+            )
+        }))
+        .collect();
+
+    let mut total_draws: usize = lights.iter().map(|(.., draws)| draws).sum();
+    if total_draws <= budget.max_draws as usize {
+        return;
+    }
+    lights.sort_by(|(_, a, _), (_, b, _)| a.total_cmp(b));
+
+    for (entity, _, draws) in lights {
+        if total_draws <= budget.max_draws as usize {
+            break;
+        }
+        if draws == 0 {
+            continue;
+        }
+        total_draws -= draws;
+
+        if let Ok((.., mut visible_entities)) = point_lights.get_mut(entity) {
+            for visible_entities in visible_entities.iter_mut() {
+                visible_entities.entities.clear();
+            }
+        } else if let Ok((.., mut visible_entities)) = spot_lights.get_mut(entity) {
+            visible_entities.entities.clear();
+        } else if let Ok((_, mut visible_entities)) = directional_lights.get_mut(entity) {
+            for cascades in visible_entities.entities.values_mut() {
+                for visible_entities in cascades {
+                    visible_entities.entities.clear();
+                }
+            }
+        }
+    }
+}
+
+/// Add this component to a [`PointLight`], [`SpotLight`] or [`DirectionalLight`] to keep track
+/// of the meshes that currently cast shadows from it, for example to let gameplay react to what a
+/// light is illuminating.
+///
+/// [`update_shadow_casters`] fills it in every frame with the meshes visible from any of the
+/// light's shadow views, over every cube face, cascade and view. Maintaining the set has a cost
+/// for lights with many shadow casters, so it's only kept up to date on lights that have this
+/// component. It's empty for lights that don't cast shadows.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Component, Debug, Default, PartialEq, Clone)]
+pub struct ShadowCasters(pub EntityHashSet);
+
+/// Updates the [`ShadowCasters`] of each light from the meshes found by the shadow caster
+/// visibility systems.
+///
+/// The component is only marked as changed when the set of shadow casters changes.
+pub fn update_shadow_casters(
+    mut lights: Query<(
+        &mut ShadowCasters,
+        Option<&VisibleMeshEntities>,
+        Option<&CubemapVisibleEntities>,
+        Option<&CascadesVisibleEntities>,
+    )>,
+) {
+    for (
+        mut shadow_casters,
+        visible_entities,
+        cubemap_visible_entities,
+        cascades_visible_entities,
+    ) in &mut lights
+    {
+        let cubemap_visible_entities = cubemap_visible_entities
+            .into_iter()
+            .flat_map(CubemapVisibleEntities::iter);
+        let cascades_visible_entities = cascades_visible_entities
+            .into_iter()
+            .flat_map(|visible_entities| visible_entities.entities.values().flatten());
+        let casters = visible_entities
+            .into_iter()
+            .chain(cubemap_visible_entities)
+            .chain(cascades_visible_entities)
+            .flat_map(|visible_entities| visible_entities.entities.iter().copied())
+            .collect::<EntityHashSet>();
+        shadow_casters.set_if_neq(ShadowCasters(casters));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_math::{EulerRot, Quat, Vec3};
+    use bevy_render::camera::{CameraProjection, PerspectiveProjection};
+
+    #[test]
+    fn cascade_light_space_bounds_contain_frustum_corners() {
+        let frustum_corners = [
+            Vec3A::new(1.0, -1.0, -1.0),
+            Vec3A::new(1.0, 1.0, -1.0),
+            Vec3A::new(-1.0, 1.0, -1.0),
+            Vec3A::new(-1.0, -1.0, -1.0),
+            Vec3A::new(10.0, -10.0, -10.0),
+            Vec3A::new(10.0, 10.0, -10.0),
+            Vec3A::new(-10.0, 10.0, -10.0),
+            Vec3A::new(-10.0, -10.0, -10.0),
+        ];
+        let world_from_light = Mat4::from_quat(Quat::from_rotation_x(-1.0));
+        let world_from_view = Mat4::from_translation(Vec3::new(3.0, 2.0, 1.0));
+        let light_from_camera = world_from_light.inverse() * world_from_view;
+
+        let cascade = calculate_focused_cascade(
+            frustum_corners,
+            1024.0,
+            world_from_light,
+            light_from_camera,
+            true,
+            None,
+            None,
+        );
+
+        for corner in frustum_corners {
+            let corner_light_view = light_from_camera.transform_point3a(corner);
+            assert!(corner_light_view.cmpge(cascade.light_space_min).all());
+            assert!(corner_light_view.cmple(cascade.light_space_max).all());
+        }
+    }
+
+    #[test]
+    fn cascade_world_from_clip_inverts_clip_from_world() {
+        let frustum_corners = PerspectiveProjection::default().get_frustum_corners(-0.1, -20.0);
+        let world_from_light = Mat4::from_quat(Quat::from_rotation_x(-1.0));
+        let world_from_view = Mat4::from_translation(Vec3::new(3.0, 2.0, 1.0));
+        let cascade = calculate_focused_cascade(
+            frustum_corners,
+            1024.0,
+            world_from_light,
+            world_from_light.inverse() * world_from_view,
+            true,
+            None,
+            None,
+        );
+
+        assert!(
+            (cascade.world_from_clip() * cascade.clip_from_world).abs_diff_eq(Mat4::IDENTITY, 1e-4)
+        );
+    }
+
+    #[test]
+    fn disable_cascade_snapping_bypasses_texel_snapping() {
+        let frustum_corners = PerspectiveProjection::default().get_frustum_corners(-0.1, -20.0);
+        let offset = Vec3A::new(0.3, 0.7, 0.0);
+        let frustum_corners = frustum_corners.map(|corner| corner + offset);
+        let cascade_with_snapping = |snap_to_texels| {
+            calculate_focused_cascade(
+                frustum_corners,
+                1024.0,
+                Mat4::IDENTITY,
+                Mat4::IDENTITY,
+                snap_to_texels,
+                None,
+                None,
+            )
+        };
+
+        // With an identity light transform, the cascade's translation is its near plane center.
+        let snapped = cascade_with_snapping(true);
+        let unsnapped = cascade_with_snapping(false);
+        let snapped_center = snapped.world_from_cascade.w_axis.truncate();
+        let unsnapped_center = unsnapped.world_from_cascade.w_axis.truncate();
+
+        let snapped_texels = snapped_center.truncate() / snapped.texel_size;
+        assert!(snapped_texels.abs_diff_eq(snapped_texels.round(), 1e-3));
+        assert!(unsnapped_center
+            .truncate()
+            .abs_diff_eq(offset.truncate(), 1e-5));
+        assert_ne!(snapped_center, unsnapped_center);
+    }
+
+    #[test]
+    fn texel_size_at_selects_covering_cascade() {
+        let config = CascadeShadowConfigBuilder {
+            num_cascades: 2,
+            minimum_distance: CascadeMinimumDistance::Absolute(0.1),
+            first_cascade_far_bound: 5.0,
+            maximum_distance: 20.0,
+            ..Default::default()
+        }
+        .build();
+        // The light points straight down, and the camera looks along -Z from the origin.
+        let world_from_light = Mat4::from_rotation_x(-core::f32::consts::FRAC_PI_2);
+        let view_cascades = calculate_view_cascades(
+            &config,
+            &Projection::default(),
+            world_from_light,
+            Mat4::IDENTITY,
+            1024.0,
+            None,
+            None,
+            &mut CascadeFrustumSlices::default(),
+        );
+        assert!(view_cascades[0].texel_size < view_cascades[1].texel_size);
+
+        let view = Entity::from_raw_u32(1).unwrap();
+        let mut cascades = Cascades::default();
+        cascades.cascades.insert(view, view_cascades.clone());
+
+        let near = Vec3::new(0.0, 0.0, -2.0);
+        let far = Vec3::new(0.0, 0.0, -15.0);
+        let behind = Vec3::new(0.0, 0.0, 50.0);
+        assert_eq!(
+            cascades.texel_size_at(view, near),
+            Some(view_cascades[0].texel_size)
+        );
+        assert_eq!(
+            cascades.texel_size_at(view, far),
+            Some(view_cascades[1].texel_size)
+        );
+        assert_eq!(cascades.texel_size_at(view, behind), None);
+        assert_eq!(
+            cascades.texel_size_at(Entity::from_raw_u32(2).unwrap(), near),
+            None
+        );
+    }
+
+    #[test]
+    fn cascades_iter_all_visits_every_cascade() {
+        let views = [1, 2, 3].map(|index| Entity::from_raw_u32(index).unwrap());
+        let mut cascades = Cascades::default();
+        for (count, &view) in views.iter().enumerate() {
+            let view_cascades = (0..count + 2)
+                .map(|index| Cascade {
+                    texel_size: index as f32,
+                    ..Default::default()
+                })
+                .collect();
+            cascades.cascades.insert(view, view_cascades);
+        }
+
+        assert_eq!(cascades.iter_all().count(), 2 + 3 + 4);
+        for (view, index, cascade) in cascades.iter_all() {
+            assert_eq!(cascade.texel_size, index as f32);
+            assert!(index < cascades.cascades[&view].len());
+        }
+        for view in views {
+            let indices = cascades
+                .iter_all()
+                .filter(|(cascade_view, ..)| *cascade_view == view)
+                .map(|(_, index, _)| index)
+                .collect::<Vec<_>>();
+            assert_eq!(
+                indices,
+                (0..cascades.cascades[&view].len()).collect::<Vec<_>>()
+            );
+        }
+    }
+
+    #[test]
+    fn shadow_caster_can_be_toggled_in_place() {
+        let light = Entity::from_raw_u32(1).unwrap();
+        let other_light = Entity::from_raw_u32(2).unwrap();
+        let only_light = ShadowCasterLights::from_iter([light]);
+
+        assert!(casts_shadows_from(None, None, light));
+        assert!(casts_shadows_from(
+            Some(&ShadowCaster::default()),
+            None,
+            light
+        ));
+        assert!(!casts_shadows_from(
+            Some(&ShadowCaster { enabled: false }),
+            None,
+            light
+        ));
+
+        // Both components must allow the light.
+        let enabled = ShadowCaster { enabled: true };
+        assert!(casts_shadows_from(Some(&enabled), Some(&only_light), light));
+        assert!(!casts_shadows_from(
+            Some(&enabled),
+            Some(&only_light),
+            other_light
+        ));
+        assert!(!casts_shadows_from(
+            Some(&ShadowCaster { enabled: false }),
+            Some(&only_light),
+            light
+        ));
+    }
+
+    #[test]
+    fn shadow_caster_lights_limit_visible_casters() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        let point_light = world
+            .spawn((
+                PointLight {
+                    shadows_enabled: true,
+                    range: 20.0,
+                    ..Default::default()
+                },
+                GlobalTransform::IDENTITY,
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.insert(point_light);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.push(point_light);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+        // A directional light with a single cascade whose frustum contains everything.
+        let view = world.spawn_empty().id();
+        let mut frusta = CascadesFrusta::default();
+        frusta.frusta.insert(
+            view,
+            vec![Frustum {
+                half_spaces: [HalfSpace::new(Vec3::X.extend(f32::INFINITY)); 6],
+            }],
+        );
+        let mut light_view_visibility = ViewVisibility::default();
+        light_view_visibility.set();
+        let directional_light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                frusta,
+                light_view_visibility,
+            ))
+            .id();
+
+        let caster = (
+            Mesh3d::default(),
+            Aabb {
+                center: Vec3A::ZERO,
+                half_extents: Vec3A::splat(0.1),
+            },
+            GlobalTransform::from_xyz(0.0, 0.0, -5.0),
+            InheritedVisibility::VISIBLE,
+        );
+        let every_light = world.spawn(caster.clone()).id();
+        let point_light_only = world
+            .spawn((caster.clone(), ShadowCasterLights::from_iter([point_light])))
+            .id();
+        let directional_light_only = world
+            .spawn((caster, ShadowCasterLights::from_iter([directional_light])))
+            .id();
+        world.run_system_once(update_point_light_frusta).unwrap();
+        world
+            .run_system_once(check_point_light_mesh_visibility)
+            .unwrap();
+        world
+            .run_system_once(check_dir_light_mesh_visibility)
+            .unwrap();
+
+        let mut point_light_casters = world
+            .get::<CubemapVisibleEntities>(point_light)
+            .unwrap()
+            .iter()
+            .flat_map(|visible_entities| visible_entities.entities.iter().copied())
+            .collect::<Vec<_>>();
+        point_light_casters.sort();
+        let mut expected = vec![every_light, point_light_only];
+        expected.sort();
+        assert_eq!(point_light_casters, expected);
+
+        let mut directional_light_casters = world
+            .get::<CascadesVisibleEntities>(directional_light)
+            .unwrap()
+            .entities[&view][0]
+            .entities
+            .clone();
+        directional_light_casters.sort();
+        let mut expected = vec![every_light, directional_light_only];
+        expected.sort();
+        assert_eq!(directional_light_casters, expected);
+    }
+
+    #[test]
+    fn shadow_focus_tightens_first_cascade() {
+        let config = CascadeShadowConfigBuilder {
+            num_cascades: 2,
+            first_cascade_far_bound: 10.0,
+            maximum_distance: 40.0,
+            ..Default::default()
+        }
+        .build();
+        let projection = Projection::Perspective(PerspectiveProjection::default());
+        let world_from_light = Mat4::from_rotation_x(-1.0);
+        let view_cascades = |focus| {
+            calculate_view_cascades(
+                &config,
+                &projection,
+                world_from_light,
+                Mat4::IDENTITY,
+                1024.0,
+                focus,
+                None,
+                &mut CascadeFrustumSlices::default(),
+            )
+        };
+        let covers = |cascade: &Cascade, point: Vec3A| {
+            let clip = cascade.clip_from_world.project_point3a(point);
+            clip.x.abs() <= 1.0 && clip.y.abs() <= 1.0
+        };
+
+        let gaze = Vec3A::new(0.5, 0.0, -5.0);
+        let periphery = Vec3A::new(-1.9, -1.9, -5.0);
+        let focus = Sphere {
+            center: gaze,
+            radius: 1.0,
+        };
+        let unfocused = view_cascades(None);
+        let focused = view_cascades(Some(&focus));
+
+        // The first cascade has finer texels around the gaze point.
+        assert!(focused[0].texel_size < unfocused[0].texel_size);
+        assert!(covers(&focused[0], gaze));
+
+        // The periphery of the first cascade's depth range falls through to the second cascade.
+        assert!(covers(&unfocused[0], periphery));
+        assert!(!covers(&focused[0], periphery));
+        assert!(covers(&focused[1], periphery));
+    }
+
+    #[test]
+    fn minimum_distance_is_clamped_below_first_cascade_bound() {
+        let mut config = CascadeShadowConfigBuilder {
+            first_cascade_far_bound: 5.0,
+            ..Default::default()
+        }
+        .build();
+        config.minimum_distance = CascadeMinimumDistance::Absolute(10.0);
+
+        let minimum_distance = clamped_minimum_distance(&config, 0.1);
+        assert!(minimum_distance < config.bounds[0]);
+
+        // -z is forward, so the near plane must be in front of the far plane.
+        let (z_near, z_far) = (-minimum_distance, -config.bounds[0]);
+        assert!(z_near > z_far);
+    }
+
+    #[test]
+    fn camera_near_minimum_distance_starts_at_near_plane() {
+        let near = 0.02;
+        let perspective = PerspectiveProjection {
+            near,
+            ..Default::default()
+        };
+        let config = CascadeShadowConfigBuilder {
+            minimum_distance: CascadeMinimumDistance::CameraNear,
+            ..Default::default()
+        }
+        .build();
+
+        // The first cascade starts at the near plane of the projection, rather than at the
+        // default minimum distance. The near plane is also found for custom projections.
+        for projection in [
+            Projection::from(perspective.clone()),
+            Projection::custom(perspective),
+        ] {
+            let minimum_distance = clamped_minimum_distance(&config, projection_near(&projection));
+            assert!((minimum_distance - near).abs() < 1e-6, "{minimum_distance}");
+        }
+
+        // Cascades built with it match those with the same absolute minimum distance.
+        let projection = Projection::Perspective(PerspectiveProjection {
+            near,
+            ..Default::default()
+        });
+        let world_from_light = Mat4::from_quat(Quat::from_rotation_x(-1.0));
+        let absolute_config = CascadeShadowConfigBuilder {
+            minimum_distance: CascadeMinimumDistance::Absolute(near),
+            ..Default::default()
+        }
+        .build();
+        let [camera_near, absolute] = [&config, &absolute_config].map(|config| {
+            calculate_view_cascades(
+                config,
+                &projection,
+                world_from_light,
+                Mat4::IDENTITY,
+                1024.0,
+                None,
+                None,
+                &mut CascadeFrustumSlices::default(),
+            )
+        });
+        assert_eq!(camera_near[0].clip_from_world, absolute[0].clip_from_world);
+    }
+
+    #[test]
+    fn single_cascade_fast_path_matches_general_path() {
+        let projection = Projection::default();
+        let world_from_light = Mat4::from_quat(Quat::from_rotation_x(-1.0));
+        let view_to_world = Mat4::from_translation(Vec3::new(3.0, 2.0, 1.0));
+        let config = CascadeShadowConfig {
+            bounds: vec![20.0],
+            overlap_proportion: 0.2,
+            boundary_overlap_proportions: Vec::new(),
+            minimum_distance: CascadeMinimumDistance::Absolute(0.1),
+            disable_cascade_snapping: false,
+            logarithmic_depth_cascades: 0,
+        };
+
+        // The general path slices the view frustum at the near bound of each cascade.
+        let minimum_distance = clamped_minimum_distance(&config, projection_near(&projection));
+        let near_bounds = [cascade_near_bound(&config, 0, minimum_distance, false)];
+        let mut frustum_slices = CascadeFrustumSlices::default();
+        frustum_slices.update(&projection, &near_bounds, &config.bounds);
+        assert_eq!(
+            frustum_slices.corners,
+            [single_cascade_frustum_corners(
+                &projection,
+                minimum_distance,
+                20.0
+            )]
+        );
+
+        let focus = Sphere {
+            center: Vec3A::new(0.0, 0.0, -5.0),
+            radius: 2.0,
+        };
+        for focus in [None, Some(&focus)] {
+            let fast = calculate_view_cascades(
+                &config,
+                &projection,
+                world_from_light,
+                view_to_world,
+                1024.0,
+                focus,
+                None,
+                &mut CascadeFrustumSlices::default(),
+            );
+            let general = calculate_focused_cascade(
+                frustum_slices.corners[0],
+                1024.0,
+                world_from_light,
+                world_from_light.inverse() * view_to_world,
+                true,
+                focus,
+                None,
+            );
+            assert_eq!(fast.len(), 1);
+            assert_eq!(fast[0].clip_from_world, general.clip_from_world);
+            assert_eq!(fast[0].world_from_cascade, general.world_from_cascade);
+            assert_eq!(fast[0].texel_size, general.texel_size);
+        }
+    }
+
+    #[test]
+    fn non_unit_directional_light_rotation_gives_finite_cascades() {
+        let frustum_corners = PerspectiveProjection::default().get_frustum_corners(-0.1, -10.0);
+        for rotation in [
+            Quat::from_xyzw(1.0, 1.0, 0.0, 1.0),
+            Quat::from_xyzw(0.0, 0.0, 0.0, 0.0),
+        ] {
+            let transform = GlobalTransform::from(Transform::from_rotation(rotation));
+            let rotation = directional_light_rotation(
+                &transform,
+                &LightLabel {
+                    entity: Entity::PLACEHOLDER,
+                    debug_name: None,
+                },
+            );
+            assert!(rotation.is_normalized());
+
+            let world_from_light = Mat4::from_quat(rotation);
+            let cascade = calculate_focused_cascade(
+                frustum_corners,
+                1024.0,
+                world_from_light,
+                world_from_light.inverse(),
+                true,
+                None,
+                None,
+            );
+            assert!(cascade.clip_from_world.is_finite());
+            assert!(cascade.world_from_cascade.is_finite());
+        }
+    }
+
+    #[test]
+    fn view_cascade_shadow_config_overrides_cascade_count() {
+        let projection = Projection::default();
+        let light_config = CascadeShadowConfigBuilder {
+            num_cascades: 4,
+            ..Default::default()
+        }
+        .build();
+        let view_config = ViewCascadeShadowConfig(
+            CascadeShadowConfigBuilder {
+                num_cascades: 2,
+                maximum_distance: 50.0,
+                ..Default::default()
+            }
+            .build(),
+        );
+
+        // Each view uses its override if it has one, or else the light's config.
+        let cascade_counts = [None, Some(&view_config)].map(|view_config| {
+            calculate_view_cascades(
+                view_config.map_or(&light_config, |view_config| &view_config.0),
+                &projection,
+                Mat4::IDENTITY,
+                Mat4::IDENTITY,
+                1024.0,
+                None,
+                None,
+                &mut CascadeFrustumSlices::default(),
+            )
+            .len()
+        });
+        assert_eq!(cascade_counts, [4, 2]);
+    }
+
+    #[test]
+    fn view_shadow_map_size_overrides_texel_size() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.insert_resource(DirectionalLightShadowMap { size: 2048 });
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let main_view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        let reflection_view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+                ViewShadowMapSize(512),
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        // The views see the same frustum, so a shadow map a quarter of the size has texels four
+        // times as large.
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades;
+        let texel_size = |view| cascades[&view][0].texel_size;
+        let ratio = texel_size(reflection_view) / texel_size(main_view);
+        assert!((ratio - 4.0).abs() < 1e-3, "{ratio}");
+    }
+
+    #[test]
+    fn shadow_camera_frames_cascades() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let shadow_transform = Transform::from_xyz(50.0, 0.0, 0.0);
+        // A shaking camera whose shadows are framed by its steady transform, and a camera at the
+        // steady transform.
+        let shaking_view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::from_xyz(50.3, 0.2, 0.0),
+                ShadowCamera {
+                    projection: Projection::default(),
+                    transform: shadow_transform,
+                },
+            ))
+            .id();
+        let steady_view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::from(shadow_transform),
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades;
+        for (shaking, steady) in cascades[&shaking_view].iter().zip(&cascades[&steady_view]) {
+            assert!(shaking
+                .clip_from_world
+                .abs_diff_eq(steady.clip_from_world, 1e-4));
+        }
+    }
+
+    #[test]
+    fn cascade_world_bounds_match_projection() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::from_xyz(3.0, 2.0, 1.0),
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                GlobalTransform::from_rotation(Quat::from_euler(EulerRot::ZYX, 0.0, 0.7, -0.9)),
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        // The corners are the corners of the cascade's clip space box, near plane first.
+        let clip_corners = [
+            Vec3::new(1.0, -1.0, 1.0),
+            Vec3::new(1.0, 1.0, 1.0),
+            Vec3::new(-1.0, 1.0, 1.0),
+            Vec3::new(-1.0, -1.0, 1.0),
+            Vec3::new(1.0, -1.0, 0.0),
+            Vec3::new(1.0, 1.0, 0.0),
+            Vec3::new(-1.0, 1.0, 0.0),
+            Vec3::new(-1.0, -1.0, 0.0),
+        ];
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades[&view];
+        assert!(!cascades.is_empty());
+        for cascade in cascades {
+            for (corner, clip_corner) in cascade.bounds_world.iter().zip(clip_corners) {
+                let clip = cascade.clip_from_world.project_point3((*corner).into());
+                assert!(
+                    clip.abs_diff_eq(clip_corner, 1e-3),
+                    "{clip} != {clip_corner}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn shadow_look_ahead_shifts_cascades() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let offset = Vec3::new(0.0, 0.0, -5.0);
+        let [view, look_ahead_view] = [None, Some(ShadowLookAhead { offset })].map(|look_ahead| {
+            let mut view = world.spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ));
+            if let Some(look_ahead) = look_ahead {
+                view.insert(look_ahead);
+            }
+            view.id()
+        });
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                GlobalTransform::from(Transform::from_rotation(Quat::from_rotation_x(-1.0))),
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        // Every cascade moves forward with the view.
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades;
+        for (cascade, shifted) in cascades[&view].iter().zip(&cascades[&look_ahead_view]) {
+            let shift = shifted.world_bounding_sphere.center - cascade.world_bounding_sphere.center;
+            assert!(
+                shift.abs_diff_eq(offset.into(), 2.0 * shifted.texel_size),
+                "{shift}"
+            );
+        }
+    }
+
+    #[test]
+    fn inactive_views_only_get_cascades_if_marked() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let inactive_camera = Camera {
+            is_active: false,
+            ..Default::default()
+        };
+        let active_view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        let inactive_view = world
+            .spawn((
+                inactive_camera.clone(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        let marked_inactive_view = world
+            .spawn((
+                inactive_camera,
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+                ComputeCascadesForInactiveView,
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades;
+        assert!(cascades.contains_key(&active_view));
+        assert!(!cascades.contains_key(&inactive_view));
+        assert!(cascades.contains_key(&marked_inactive_view));
+    }
+
+    #[test]
+    fn shadow_map_state_tracks_rendered_frames() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.insert_resource(FrameCount(5));
+        let light = world
+            .spawn(DirectionalLight {
+                shadows_enabled: true,
+                ..Default::default()
+            })
+            .id();
+        world.get_mut::<ViewVisibility>(light).unwrap().set();
+
+        world.run_system_once(update_shadow_map_states).unwrap();
+        assert_eq!(
+            *world.get::<ShadowMapState>(light).unwrap(),
+            ShadowMapState {
+                rendered_this_frame: true,
+                last_rendered_frame: 5,
+            }
+        );
+
+        world.resource_mut::<FrameCount>().0 = 6;
+        world
+            .get_mut::<DirectionalLight>(light)
+            .unwrap()
+            .shadows_enabled = false;
+
+        world.run_system_once(update_shadow_map_states).unwrap();
+        assert_eq!(
+            *world.get::<ShadowMapState>(light).unwrap(),
+            ShadowMapState {
+                rendered_this_frame: false,
+                last_rendered_frame: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn temporal_shadow_update_renders_every_interval_frames() {
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.insert_resource(FrameCount(1));
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                TemporalShadowUpdate { interval: 3 },
+            ))
+            .id();
+        world.get_mut::<ViewVisibility>(light).unwrap().set();
+        let update = world.register_system(update_shadow_map_states);
+        let run_frame = |world: &mut World, frame| {
+            world.resource_mut::<FrameCount>().0 = frame;
+            world.run_system(update).unwrap();
+            *world.get::<ShadowMapState>(light).unwrap()
+        };
+
+        // The newly added cascade config renders the shadow maps straight away.
+        assert!(run_frame(&mut world, 1).rendered_this_frame);
+
+        let states = (2..=7).map(|frame| run_frame(&mut world, frame));
+        assert_eq!(
+            states
+                .map(|state| (state.rendered_this_frame, state.last_rendered_frame))
+                .collect::<Vec<_>>(),
+            [
+                (false, 1),
+                (true, 3),
+                (false, 3),
+                (false, 3),
+                (true, 6),
+                (false, 6),
+            ]
+        );
+
+        // Changing the cascade config renders the shadow maps on a frame that would be skipped.
+        world
+            .get_mut::<CascadeShadowConfig>(light)
+            .unwrap()
+            .overlap_proportion = 0.1;
+        assert!(run_frame(&mut world, 8).rendered_this_frame);
+    }
+
+    #[test]
+    fn changed_cascade_shadow_config_rebuilds_cascades() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        world.insert_resource(FrameCount(1));
+        world.spawn((
+            Camera::default(),
+            Projection::default(),
+            GlobalTransform::IDENTITY,
+        ));
+        // The light's shadow maps only update on even frames.
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                TemporalShadowUpdate { interval: 2 },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let build = world.register_system(build_directional_light_cascades);
+        let cascade_count = |world: &World| world.get::<Cascades>(light).unwrap().cascades.len();
+
+        // The newly added config counts as changed.
+        world.run_system(build).unwrap();
+        assert_eq!(cascade_count(&world), 1);
+
+        // On odd frames, unchanged cascades are kept as they are, even if they're out of date.
+        world.get_mut::<Cascades>(light).unwrap().cascades.clear();
+        world.resource_mut::<FrameCount>().0 = 3;
+        world.run_system(build).unwrap();
+        assert_eq!(cascade_count(&world), 0);
+
+        // Changing the config forces a rebuild on the same frame.
+        world
+            .get_mut::<CascadeShadowConfig>(light)
+            .unwrap()
+            .overlap_proportion = 0.1;
+        world.run_system(build).unwrap();
+        assert_eq!(cascade_count(&world), 1);
+    }
+
+    #[test]
+    fn serial_visibility_matches_parallel() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        let light = world
+            .spawn((
+                PointLight {
+                    shadows_enabled: true,
+                    range: 10.0,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.insert(light);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.push(light);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+
+        // A grid of meshes, some of which are out of the light's range.
+        for x in -8..=8 {
+            for z in -8..=8 {
+                world.spawn((
+                    Mesh3d::default(),
+                    Aabb::from_min_max(Vec3::splat(-0.5), Vec3::splat(0.5)),
+                    GlobalTransform::from_xyz(x as f32 * 2.0, 1.0, z as f32 * 2.0),
+                    InheritedVisibility::VISIBLE,
+                ));
+            }
+        }
+        world.run_system_once(update_point_light_frusta).unwrap();
+
+        let visible_per_face = |world: &mut World| {
+            world
+                .run_system_once(check_point_light_mesh_visibility)
+                .unwrap();
+            world
+                .get::<CubemapVisibleEntities>(light)
+                .unwrap()
+                .iter()
+                .map(|visible_entities| {
+                    let mut entities = visible_entities.entities.clone();
+                    entities.sort();
+                    entities
+                })
+                .collect::<Vec<_>>()
+        };
+        let parallel = visible_per_face(&mut world);
+        world.insert_resource(ForceSerialVisibility);
+        let serial = visible_per_face(&mut world);
+
+        let visible = parallel
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<EntityHashSet>();
+        assert!(!visible.is_empty() && visible.len() < 17 * 17);
+        assert_eq!(parallel, serial);
+    }
+
+    #[test]
+    fn shadow_frusta_transform_matches_frustum() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let light = world
+            .spawn((
+                SpotLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                GlobalTransform::from_xyz(0.0, 5.0, 0.0),
+            ))
+            .id();
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.insert(light);
+        world.insert_resource(global_lights);
+
+        let frustum_and_transform = |world: &World| {
+            let frustum = *world.get::<Frustum>(light).unwrap();
+            (
+                frustum,
+                world.get::<ShadowFrustaTransform>(light).unwrap().0,
+            )
+        };
+        let planes = |frustum: &Frustum| frustum.half_spaces.map(|plane| plane.normal_d());
+        let sphere_at = |y: f32| Sphere {
+            center: Vec3A::new(0.0, y, -2.0),
+            radius: 0.1,
+        };
+
+        // As the platform moves, the snapshot follows the frustum.
+        for height in [5.0, 6.0, 7.5] {
+            *world.get_mut::<GlobalTransform>(light).unwrap() =
+                GlobalTransform::from_xyz(0.0, height, 0.0);
+            world.run_system_once(update_spot_light_frusta).unwrap();
+            let (frustum, frusta_transform) = frustum_and_transform(&world);
+            assert_eq!(
+                frusta_transform,
+                GlobalTransform::from_xyz(0.0, height, 0.0)
+            );
+            assert!(frustum.intersects_sphere(&sphere_at(height), true));
+            assert!(!frustum.intersects_sphere(&sphere_at(height - 3.0), true));
+        }
+
+        // Moving the light after the frusta update leaves both the frustum and the transform that
+        // the shadow is rendered from unchanged for the rest of the frame.
+        let (frustum_before, transform_before) = frustum_and_transform(&world);
+        *world.get_mut::<GlobalTransform>(light).unwrap() =
+            GlobalTransform::from_xyz(0.0, 9.0, 0.0);
+        let (frustum_after, transform_after) = frustum_and_transform(&world);
+        assert_eq!(planes(&frustum_after), planes(&frustum_before));
+        assert_eq!(transform_after, transform_before);
+    }
+
+    #[test]
+    fn shadows_stop_beyond_shadow_cutoff_distance() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        world.init_resource::<ActiveCameraPositions>();
+        let lights = [
+            world
+                .spawn((
+                    PointLight {
+                        shadows_enabled: true,
+                        shadow_cutoff_distance: Some(10.0),
+                        ..Default::default()
+                    },
+                    GlobalTransform::from_xyz(5.0, 0.0, 0.0),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id(),
+            world
+                .spawn((
+                    SpotLight {
+                        shadows_enabled: true,
+                        shadow_cutoff_distance: Some(10.0),
+                        ..Default::default()
+                    },
+                    GlobalTransform::from_xyz(2.0, 0.0, 0.0),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id(),
+        ];
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.extend(lights);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.extend(lights);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+        for light in lights {
+            world.get_mut::<ViewVisibility>(light).unwrap().set();
+        }
+        // A caster in the -Z cube face of the point light and on the axis of the spot light.
+        world.spawn((
+            Mesh3d::default(),
+            Aabb {
+                center: Vec3A::ZERO,
+                half_extents: Vec3A::splat(0.1),
+            },
+            GlobalTransform::from_xyz(2.0, 0.0, -5.0),
+            InheritedVisibility::VISIBLE,
+        ));
+
+        let shadow_map_states = |world: &mut World| {
+            world
+                .run_system_once(update_active_camera_positions)
+                .unwrap();
+            world.run_system_once(update_point_light_frusta).unwrap();
+            world.run_system_once(update_spot_light_frusta).unwrap();
+            world
+                .run_system_once(check_point_light_mesh_visibility)
+                .unwrap();
+            world.run_system_once(update_shadow_map_states).unwrap();
+            lights.map(|light| {
+                let caster_count = match world.get::<CubemapVisibleEntities>(light) {
+                    Some(cubemap) => cubemap.iter().map(|visible| visible.entities.len()).sum(),
+                    None => world
+                        .get::<VisibleMeshEntities>(light)
+                        .unwrap()
+                        .entities
+                        .len(),
+                };
+                let state = world.get::<ShadowMapState>(light).unwrap();
+                (state.rendered_this_frame, caster_count > 0)
+            })
+        };
+        assert_eq!(shadow_map_states(&mut world), [(true, true); 2]);
+
+        // Past the cutoff, the lights stop rendering shadows and gathering casters, but they're
+        // still visible, shadow-enabled lights that light the scene.
+        for light in lights {
+            let position = world.get::<GlobalTransform>(light).unwrap().translation();
+            world
+                .entity_mut(light)
+                .insert(GlobalTransform::from_translation(position + 50.0 * Vec3::Z));
+        }
+        assert_eq!(shadow_map_states(&mut world), [(false, false); 2]);
+        assert!(world.get::<PointLight>(lights[0]).unwrap().shadows_enabled);
+        assert!(world.get::<SpotLight>(lights[1]).unwrap().shadows_enabled);
+        for light in lights {
+            assert!(world.get::<ViewVisibility>(light).unwrap().get());
+        }
+    }
+
+    #[test]
+    fn shadow_cutoff_distance_is_measured_from_nearest_active_camera() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY));
+        world.spawn((
+            Camera {
+                is_active: false,
+                ..Default::default()
+            },
+            GlobalTransform::from_xyz(100.0, 0.0, 0.0),
+        ));
+        world.spawn((Camera::default(), GlobalTransform::from_xyz(0.0, 0.0, 50.0)));
+        world
+            .run_system_once(update_active_camera_positions)
+            .unwrap();
+
+        let camera_positions = world.resource::<ActiveCameraPositions>();
+        assert_eq!(camera_positions.len(), 2);
+        let within = |light_position: Vec3| {
+            camera_positions.within_shadow_cutoff_distance(Some(10.0), light_position.into())
+        };
+        assert!(within(Vec3::new(8.0, 0.0, 0.0)));
+        assert!(within(Vec3::new(0.0, 0.0, 45.0)));
+        assert!(!within(Vec3::new(20.0, 0.0, 0.0)));
+        // The inactive camera doesn't count.
+        assert!(!within(Vec3::new(100.0, 0.0, 0.0)));
+        // Without a cutoff distance, shadows are rendered at any distance.
+        assert!(camera_positions.within_shadow_cutoff_distance(None, Vec3A::splat(1000.0)));
+    }
+
+    #[test]
+    fn directional_light_count_warning_fires_once_per_crossing() {
+        let mut above_threshold = false;
+        let warnings = [3, 5, 6, 5, 4, 5]
+            .map(|count| crossed_warning_threshold(count, 4, &mut above_threshold));
+        assert_eq!(warnings, [false, true, false, false, false, true]);
+    }
+
+    #[test]
+    fn normal_bias_scales_with_cascade_texel_size() {
+        let near = Cascade {
+            texel_size: 0.01,
+            ..Default::default()
+        };
+        let far = Cascade {
+            texel_size: 0.04,
+            ..Default::default()
+        };
+
+        let bias = DirectionalLight::DEFAULT_SHADOW_NORMAL_BIAS;
+        assert_eq!(near.world_normal_bias(0.0), 0.0);
+        assert!((far.world_normal_bias(bias) / near.world_normal_bias(bias) - 4.0).abs() < 1e-5);
+        assert!(
+            (near.world_normal_bias(bias) - bias * core::f32::consts::SQRT_2 * 0.01).abs() < 1e-7
+        );
+    }
+
+    #[test]
+    fn depth_bias_scales_with_cascade_texel_size() {
+        let near = Cascade {
+            texel_size: 0.01,
+            ..Default::default()
+        };
+        let far = Cascade {
+            texel_size: 0.04,
+            ..Default::default()
+        };
+
+        let bias = DirectionalLight::DEFAULT_SHADOW_DEPTH_BIAS;
+        // The first cascade keeps the light's bias, and coarser cascades are biased more so that
+        // they don't self-shadow where they're blended with finer ones.
+        assert_eq!(near.world_depth_bias(bias, near.texel_size), bias);
+        assert!((far.world_depth_bias(bias, near.texel_size) - bias * 4.0).abs() < 1e-6);
+        // The bias never drops below the light's bias.
+        assert_eq!(near.world_depth_bias(bias, far.texel_size), bias);
+    }
+
+    #[test]
+    fn cascade_shadow_presets_increase_in_quality() {
+        let presets = [
+            CascadeShadowConfigBuilder::low(),
+            CascadeShadowConfigBuilder::medium(),
+            CascadeShadowConfigBuilder::high(),
+            CascadeShadowConfigBuilder::ultra(),
+        ];
+
+        for pair in presets.windows(2) {
+            assert!(pair[0].num_cascades <= pair[1].num_cascades);
+            assert!(pair[0].maximum_distance < pair[1].maximum_distance);
+        }
+        for preset in presets {
+            let config = preset.build();
+            assert_eq!(config.bounds.len(), preset.num_cascades);
+        }
+    }
+
+    #[test]
+    fn cascade_config_from_texel_density_meets_near_density() {
+        let near_density = 100.0;
+        let shadow_map_size = 2048;
+        let config =
+            CascadeShadowConfigBuilder::from_texel_density(near_density, 150.0, shadow_map_size)
+                .build();
+
+        let projection = PerspectiveProjection {
+            aspect_ratio: 16.0 / 9.0,
+            ..Default::default()
+        };
+        let minimum_distance = config.minimum_distance.distance(projection.near);
+        let corners = projection.get_frustum_corners(-minimum_distance, -config.bounds[0]);
+        let cascade = calculate_focused_cascade(
+            corners,
+            shadow_map_size as f32,
+            Mat4::IDENTITY,
+            Mat4::IDENTITY,
+            true,
+            None,
+            None,
+        );
+
+        let density = cascade.texel_size.recip();
+        assert!(
+            density >= near_density * 0.95,
+            "expected at least {near_density} texels per meter, got {density}"
+        );
+        assert!(config.bounds.len() > 1);
+    }
+
+    #[test]
+    fn directional_shadow_size_for_meets_target_texel_size() {
+        let config = CascadeShadowConfigBuilder::default().build();
+        let projection = PerspectiveProjection {
+            aspect_ratio: 16.0 / 9.0,
+            ..Default::default()
+        };
+        let minimum_distance = config.minimum_distance.distance(projection.near);
+        let corners = projection.get_frustum_corners(-minimum_distance, -config.bounds[0]);
+        let texel_size_for = |size: usize| {
+            calculate_focused_cascade(
+                corners,
+                size as f32,
+                Mat4::IDENTITY,
+                Mat4::IDENTITY,
+                true,
+                None,
+                None,
+            )
+            .texel_size
+        };
+
+        for target_texel_size in [0.002, 0.01, 0.05] {
+            let size = directional_shadow_size_for(&config, target_texel_size);
+            assert!(size.is_power_of_two());
+            assert!(texel_size_for(size) <= target_texel_size);
+            // Half the size would miss the target, so this is the smallest such size.
+            assert!(texel_size_for(size / 2) > target_texel_size);
+        }
+    }
+
+    #[test]
+    fn cascade_config_builder_uses_split_fn() {
+        let config = CascadeShadowConfigBuilder {
+            num_cascades: 4,
+            maximum_distance: 100.0,
+            split_fn: Some(Arc::new(|num_cascades, _, maximum_distance| {
+                (1..=num_cascades)
+                    .map(|i| maximum_distance * i as f32 / num_cascades as f32)
+                    .collect()
+            })),
+            ..Default::default()
+        }
+        .build();
+
+        assert_eq!(config.bounds, vec![25.0, 50.0, 75.0, 100.0]);
+    }
+
+    #[test]
+    #[should_panic(expected = "strictly increasing")]
+    fn cascade_config_builder_rejects_invalid_split_fn() {
+        CascadeShadowConfigBuilder {
+            num_cascades: 2,
+            split_fn: Some(Arc::new(|_, _, maximum_distance| {
+                vec![maximum_distance, maximum_distance]
+            })),
+            ..Default::default()
+        }
+        .build();
+    }
+
+    #[test]
+    fn intensity_smoothing_ramps_toward_target() {
+        use bevy_ecs::system::RunSystemOnce;
+        use core::time::Duration;
+
+        let mut world = World::new();
+        world.insert_resource(Time::<()>::default());
+        let light = world
+            .spawn((
+                PointLight {
+                    intensity: 0.0,
+                    ..Default::default()
+                },
+                IntensitySmoothing {
+                    rate: 5.0,
+                    target: 1000.0,
+                },
+            ))
+            .id();
+
+        let mut previous_intensity = 0.0;
+        for _ in 0..5 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(100));
+            world.run_system_once(smooth_light_intensities).unwrap();
+
+            let intensity = world.get::<PointLight>(light).unwrap().intensity;
+            assert!(intensity > previous_intensity && intensity < 1000.0);
+            previous_intensity = intensity;
+        }
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(10));
+        world.run_system_once(smooth_light_intensities).unwrap();
+        assert_eq!(world.get::<PointLight>(light).unwrap().intensity, 1000.0);
+    }
+
+    #[test]
+    fn invalid_light_values_are_clamped() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let valid = PointLight {
+            color: Color::srgb(1.0, 0.5, 0.25),
+            intensity: 1000.0,
+            ..Default::default()
+        };
+        let valid_light = world.spawn(valid).id();
+        let nan_light = world
+            .spawn(SpotLight {
+                color: Color::linear_rgb(f32::NAN, 1.0, -1.0),
+                ..Default::default()
+            })
+            .id();
+        let infinite_light = world
+            .spawn(DirectionalLight {
+                illuminance: f32::INFINITY,
+                ..Default::default()
+            })
+            .id();
+
+        world.run_system_once(sanitize_light_values).unwrap();
+
+        let point_light = world.get::<PointLight>(valid_light).unwrap();
+        assert_eq!(point_light.color, valid.color);
+        assert_eq!(point_light.intensity, valid.intensity);
+        let spot_light = world.get::<SpotLight>(nan_light).unwrap();
+        assert_eq!(spot_light.color, Color::linear_rgb(0.0, 1.0, 0.0));
+        assert_eq!(spot_light.intensity, SpotLight::default().intensity);
+        let directional_light = world.get::<DirectionalLight>(infinite_light).unwrap();
+        assert_eq!(directional_light.illuminance, 0.0);
+    }
+
+    #[test]
+    fn scale_affects_range_is_opt_in() {
+        let transform = GlobalTransform::from(Transform::from_scale(Vec3::new(2.0, 3.0, 1.0)));
+
+        assert_eq!(scaled_light_range(10.0, &transform, false), 10.0);
+        assert_eq!(scaled_light_range(10.0, &transform, true), 30.0);
+    }
+
+    #[test]
+    fn shadow_draw_budget_drops_shadows_until_under_budget() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.insert_resource(ShadowDrawBudget { max_draws: 10 });
+        let casters: Vec<Entity> = (0..4).map(|_| world.spawn_empty().id()).collect();
+        let count_draws = |world: &mut World| {
+            let mut query = world.query::<&CubemapVisibleEntities>();
+            query
+                .iter(world)
+                .flat_map(CubemapVisibleEntities::iter)
+                .map(|v| v.entities.len())
+                .sum::<usize>()
+        };
+
+        for _ in 0..3 {
+            let mut visible_entities = CubemapVisibleEntities::default();
+            for face in 0..2 {
+                visible_entities.get_mut(face).entities = casters.clone();
+            }
+            world.spawn((GlobalTransform::default(), visible_entities));
+        }
+        assert_eq!(count_draws(&mut world), 24);
 
-        // ignore scale because we don't want to effectively scale light radius and range
-        // by applying those as a view transform to shadow map rendering of objects
-        let view_backward = transform.back();
+        world.run_system_once(apply_shadow_draw_budget).unwrap();
 
-        let spot_world_from_view = spot_light_world_from_view(transform);
-        let spot_clip_from_view =
-            spot_light_clip_from_view(spot_light.outer_angle, spot_light.shadow_map_near_z);
-        let clip_from_world = spot_clip_from_view * spot_world_from_view.inverse();
+        let draws = count_draws(&mut world);
+        assert!(
+            draws > 0 && draws <= 10,
+            "expected at most 10 draws, got {draws}"
+        );
+    }
 
-        *frustum = Frustum::from_clip_from_world_custom_far(
-            &clip_from_world,
-            &transform.translation(),
-            &view_backward,
-            spot_light.range,
+    #[test]
+    fn shadow_blur_texel_radius_scales_inversely_with_texel_size() {
+        let cascade = |texel_size| Cascade {
+            texel_size,
+            ..Default::default()
+        };
+
+        assert_eq!(cascade(0.25).shadow_blur_texel_radius(1.0), 4.0);
+        assert_eq!(cascade(0.5).shadow_blur_texel_radius(1.0), 2.0);
+    }
+
+    #[test]
+    fn cascade_world_bounding_sphere_contains_frustum_corners() {
+        let projection = PerspectiveProjection::default();
+        let frustum_corners = projection.get_frustum_corners(-1.0, -20.0);
+        let world_from_light = Mat4::from_quat(Quat::from_euler(EulerRot::XYZ, -0.8, 0.4, 0.0));
+        let world_from_view =
+            Mat4::from_rotation_translation(Quat::from_rotation_y(1.2), Vec3::new(5.0, 2.0, -3.0));
+        let light_from_camera = world_from_light.inverse() * world_from_view;
+
+        let cascade = calculate_focused_cascade(
+            frustum_corners,
+            1024.0,
+            world_from_light,
+            light_from_camera,
+            true,
+            None,
+            None,
         );
+
+        let sphere = &cascade.world_bounding_sphere;
+        for corner in frustum_corners {
+            let corner_world = world_from_view.transform_point3a(corner);
+            assert!(corner_world.distance(sphere.center) <= sphere.radius + 1e-3);
+        }
     }
-}
 
-fn shrink_entities(visible_entities: &mut Vec<Entity>) {
-    // Check that visible entities capacity() is no more than two times greater than len()
-    let capacity = visible_entities.capacity();
-    let reserved = capacity
-        .checked_div(visible_entities.len())
-        .map_or(0, |reserve| {
-            if reserve > 2 {
-                capacity / (reserve / 2)
-            } else {
-                capacity
-            }
+    #[test]
+    fn warnings_include_light_debug_name() {
+        let entity = Entity::from_raw_u32(7).unwrap();
+        let debug_name = LightDebugName("Lobby lamp".into());
+
+        let named = invalid_light_values_warning(&LightLabel {
+            entity,
+            debug_name: Some(&debug_name),
         });
+        assert!(named.contains("\"Lobby lamp\""), "{named}");
+        assert!(named.contains(&entity.to_string()), "{named}");
 
-    visible_entities.shrink_to(reserved);
-}
+        let unnamed = invalid_light_values_warning(&LightLabel {
+            entity,
+            debug_name: None,
+        });
+        assert!(unnamed.contains(&entity.to_string()), "{unnamed}");
+        assert!(!unnamed.contains('"'), "{unnamed}");
+    }
 
-pub fn check_dir_light_mesh_visibility(
-    mut commands: Commands,
-    mut directional_lights: Query<
-        (
-            &DirectionalLight,
-            &CascadesFrusta,
-            &mut CascadesVisibleEntities,
-            Option<&RenderLayers>,
-            &ViewVisibility,
-        ),
-        Without<SpotLight>,
-    >,
-    visible_entity_query: Query<
-        (
-            Entity,
-            &InheritedVisibility,
-            Option<&RenderLayers>,
-            Option<&Aabb>,
-            Option<&GlobalTransform>,
-            Has<VisibilityRange>,
-            Has<NoFrustumCulling>,
-        ),
-        (
-            Without<NotShadowCaster>,
-            Without<DirectionalLight>,
-            With<Mesh3d>,
-        ),
-    >,
-    visible_entity_ranges: Option<Res<VisibleEntityRanges>>,
-    mut defer_visible_entities_queue: Local<Parallel<Vec<Entity>>>,
-    mut view_visible_entities_queue: Local<Parallel<Vec<Vec<Entity>>>>,
-) {
-    let visible_entity_ranges = visible_entity_ranges.as_deref();
+    #[test]
+    fn cone_shadow_culling_culls_casters_outside_cone() {
+        use bevy_ecs::system::RunSystemOnce;
 
-    for (directional_light, frusta, mut visible_entities, maybe_view_mask, light_view_visibility) in
-        &mut directional_lights
-    {
-        let mut views_to_remove = Vec::new();
-        for (view, cascade_view_entities) in &mut visible_entities.entities {
-            match frusta.frusta.get(view) {
-                Some(view_frusta) => {
-                    cascade_view_entities.resize(view_frusta.len(), Default::default());
-                    cascade_view_entities.iter_mut().for_each(|x| x.clear());
-                }
-                None => views_to_remove.push(*view),
-            };
-        }
-        for (view, frusta) in &frusta.frusta {
-            visible_entities
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        let light = world
+            .spawn((
+                SpotLight {
+                    shadows_enabled: true,
+                    outer_angle: 0.3,
+                    ..Default::default()
+                },
+                GlobalTransform::IDENTITY,
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.insert(light);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.push(light);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+
+        let mut spawn_caster = |position: Vec3| {
+            world
+                .spawn((
+                    Mesh3d::default(),
+                    Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1)),
+                    GlobalTransform::from_translation(position),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id()
+        };
+        // One caster on the axis of the cone, and one in a corner of the shadow frustum, which is
+        // about 0.39 radians from the axis.
+        let on_axis = spawn_caster(Vec3::new(0.0, 0.0, -10.0));
+        let corner_offset = ops::tan(0.28) * 10.0;
+        let in_corner = spawn_caster(Vec3::new(corner_offset, corner_offset, -10.0));
+        world.run_system_once(update_spot_light_frusta).unwrap();
+
+        let visible_entities = |world: &mut World| {
+            world
+                .run_system_once(check_point_light_mesh_visibility)
+                .unwrap();
+            let mut entities = world
+                .get::<VisibleMeshEntities>(light)
+                .unwrap()
                 .entities
-                .entry(*view)
-                .or_insert_with(|| vec![VisibleMeshEntities::default(); frusta.len()]);
+                .clone();
+            entities.sort();
+            entities
+        };
+        let mut both = vec![on_axis, in_corner];
+        both.sort();
+        assert_eq!(visible_entities(&mut world), both);
+
+        world
+            .get_mut::<SpotLight>(light)
+            .unwrap()
+            .cone_shadow_culling = true;
+        assert_eq!(visible_entities(&mut world), vec![on_axis]);
+    }
+
+    #[test]
+    fn logarithmic_depth_cascades_only_affect_far_cascades() {
+        let config = CascadeShadowConfig {
+            logarithmic_depth_cascades: 1,
+            ..CascadeShadowConfigBuilder {
+                num_cascades: 3,
+                ..Default::default()
+            }
+            .build()
+        };
+        let cascades = calculate_view_cascades(
+            &config,
+            &Projection::default(),
+            Mat4::from_quat(Quat::from_rotation_x(-1.0)),
+            Mat4::IDENTITY,
+            1024.0,
+            None,
+            None,
+            &mut CascadeFrustumSlices::default(),
+        );
+        let [near, middle, far] = &cascades[..] else {
+            panic!("expected 3 cascades, got {}", cascades.len());
+        };
+
+        // Near cascades store the linear depth of the projection.
+        for cascade in [near, middle] {
+            assert!(!cascade.logarithmic_depth);
+            for depth in [0.0, 0.25, 0.5, 1.0] {
+                assert_eq!(cascade.shadow_map_depth(depth), depth);
+            }
         }
 
-        for v in views_to_remove {
-            visible_entities.entities.remove(&v);
+        // The far cascade keeps the ends of the depth range in place, but gives more of the range
+        // to depths near the top of the cascade.
+        assert!(far.logarithmic_depth);
+        assert!(far.shadow_map_depth(0.0).abs() < 1e-6);
+        assert!((far.shadow_map_depth(1.0) - 1.0).abs() < 1e-6);
+        let expected = 1.0
+            - ops::log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE * 0.5)
+                / ops::log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE);
+        assert!((far.shadow_map_depth(0.5) - expected).abs() < 1e-6);
+        assert!(far.shadow_map_depth(0.25) < far.shadow_map_depth(0.5));
+        assert!(far.shadow_map_depth(0.99) - far.shadow_map_depth(0.98) > 0.01);
+    }
+
+    #[test]
+    fn dual_paraboloid_shadows_only_use_two_faces() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        let light = world
+            .spawn((
+                PointLight {
+                    shadows_enabled: true,
+                    shadow_technique: PointShadowTechnique::DualParaboloid,
+                    ..Default::default()
+                },
+                GlobalTransform::IDENTITY,
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.insert(light);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.push(light);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+
+        let mut spawn_caster = |position: Vec3| {
+            world
+                .spawn((
+                    Mesh3d::default(),
+                    Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1)),
+                    GlobalTransform::from_translation(position),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id()
+        };
+        // Casters in the directions of every cube face.
+        let neg_z = spawn_caster(Vec3::new(0.0, 0.0, -5.0));
+        let pos_z = spawn_caster(Vec3::new(0.0, 0.0, 5.0));
+        spawn_caster(Vec3::new(5.0, 0.0, -0.5));
+        spawn_caster(Vec3::new(0.0, -5.0, 0.5));
+        world.run_system_once(update_point_light_frusta).unwrap();
+        world
+            .run_system_once(check_point_light_mesh_visibility)
+            .unwrap();
+
+        let cubemap_visible_entities = world.get::<CubemapVisibleEntities>(light).unwrap();
+        for face_index in 0..4 {
+            assert!(cubemap_visible_entities.get(face_index).entities.is_empty());
         }
+        let face_4 = &cubemap_visible_entities.get(4).entities;
+        let face_5 = &cubemap_visible_entities.get(5).entities;
+        assert_eq!(face_4.len() + face_5.len(), 4);
+        assert!(face_4.contains(&neg_z) && !face_4.contains(&pos_z));
+        assert!(face_5.contains(&pos_z) && !face_5.contains(&neg_z));
+    }
 
-        // NOTE: If shadow mapping is disabled for the light then it must have no visible entities
-        if !directional_light.shadows_enabled || !light_view_visibility.get() {
-            continue;
+    #[test]
+    fn non_power_of_two_shadow_map_sizes_warn() {
+        assert!(non_power_of_two_shadow_map_warning("DirectionalLightShadowMap", 1000).is_some());
+        assert!(non_power_of_two_shadow_map_warning("PointLightShadowMap", 0).is_some());
+        assert!(non_power_of_two_shadow_map_warning("DirectionalLightShadowMap", 2048).is_none());
+        assert!(non_power_of_two_shadow_map_warning("PointLightShadowMap", 1).is_none());
+    }
+
+    #[test]
+    fn poor_cascade_shadow_configs_get_suggestions() {
+        assert!(cascade_shadow_config_suggestions(&CascadeShadowConfig::default()).is_empty());
+
+        let wasteful = CascadeShadowConfigBuilder {
+            num_cascades: 4,
+            first_cascade_far_bound: 80.0,
+            maximum_distance: 100.0,
+            overlap_proportion: 0.8,
+            ..Default::default()
         }
+        .build();
+        let suggestions = cascade_shadow_config_suggestions(&wasteful);
+        assert_eq!(suggestions.len(), 2, "{suggestions:?}");
+        assert!(suggestions[0].contains("first_cascade_far_bound"));
+        assert!(suggestions[1].contains("overlap"));
 
-        let view_mask = maybe_view_mask.unwrap_or_default();
+        let blocky = CascadeShadowConfigBuilder {
+            num_cascades: 1,
+            maximum_distance: 500.0,
+            ..Default::default()
+        }
+        .build();
+        let suggestions = cascade_shadow_config_suggestions(&blocky);
+        assert_eq!(suggestions.len(), 1, "{suggestions:?}");
+        assert!(suggestions[0].contains("num_cascades"));
+    }
 
-        for (view, view_frusta) in &frusta.frusta {
-            visible_entity_query.par_iter().for_each_init(
-                || {
-                    let mut entities = view_visible_entities_queue.borrow_local_mut();
-                    entities.resize(view_frusta.len(), Vec::default());
-                    (defer_visible_entities_queue.borrow_local_mut(), entities)
-                },
-                |(defer_visible_entities_local_queue, view_visible_entities_local_queue),
-                 (
-                    entity,
-                    inherited_visibility,
-                    maybe_entity_mask,
-                    maybe_aabb,
-                    maybe_transform,
-                    has_visibility_range,
-                    has_no_frustum_culling,
-                )| {
-                    if !inherited_visibility.get() {
-                        return;
-                    }
+    #[test]
+    fn color_temperatures_follow_planckian_locus() {
+        let tungsten = PointLight::from_kelvin(3200.0).color.to_srgba();
+        assert_eq!(tungsten.red, 1.0);
+        assert!(tungsten.green < 0.8 && tungsten.blue < tungsten.green);
 
-                    let entity_mask = maybe_entity_mask.unwrap_or_default();
-                    if !view_mask.intersects(entity_mask) {
-                        return;
-                    }
+        // The fit is close to white around the temperature of daylight.
+        let daylight = DirectionalLight::from_kelvin(6500.0).color.to_srgba();
+        assert!(daylight.red == 1.0 && daylight.green > 0.95 && daylight.blue > 0.95);
 
-                    // Check visibility ranges.
-                    if has_visibility_range
-                        && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
-                            !visible_entity_ranges.entity_is_in_range_of_view(entity, *view)
-                        })
-                    {
-                        return;
-                    }
+        let sky = SpotLight::from_kelvin(20_000.0).color.to_srgba();
+        assert_eq!(sky.blue, 1.0);
+        assert!(sky.red < sky.green && sky.green < sky.blue);
 
-                    if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
-                        let mut visible = false;
-                        for (frustum, frustum_visible_entities) in view_frusta
-                            .iter()
-                            .zip(view_visible_entities_local_queue.iter_mut())
-                        {
-                            // Disable near-plane culling, as a shadow caster could lie before the near plane.
-                            if !has_no_frustum_culling
-                                && !frustum.intersects_obb(aabb, &transform.affine(), false, true)
-                            {
-                                continue;
-                            }
-                            visible = true;
+        // Temperatures are clamped to 1000 K to 40000 K.
+        let mut light = SpotLight::default();
+        light.set_color_temperature(100.0);
+        assert_eq!(light.color, SpotLight::from_kelvin(1000.0).color);
+        light.set_color_temperature(f32::INFINITY);
+        assert_eq!(light.color, SpotLight::from_kelvin(40_000.0).color);
+    }
 
-                            frustum_visible_entities.push(entity);
-                        }
-                        if visible {
-                            defer_visible_entities_local_queue.push(entity);
-                        }
-                    } else {
-                        defer_visible_entities_local_queue.push(entity);
-                        for frustum_visible_entities in view_visible_entities_local_queue.iter_mut()
-                        {
-                            frustum_visible_entities.push(entity);
-                        }
-                    }
-                },
-            );
-            // collect entities from parallel queue
-            for entities in view_visible_entities_queue.iter_mut() {
-                visible_entities
-                    .entities
-                    .get_mut(view)
-                    .unwrap()
-                    .iter_mut()
-                    .zip(entities.iter_mut())
-                    .for_each(|(dst, source)| {
-                        dst.append(source);
-                    });
-            }
+    #[test]
+    fn shadow_strength_fades_with_intensity() {
+        let mut light = PointLight {
+            intensity: 250_000.0,
+            ..Default::default()
+        };
+        assert_eq!(light.shadow_strength(), 1.0);
+
+        light.fade_shadows_with_intensity = true;
+        assert_eq!(light.shadow_strength(), 0.25);
+        light.intensity = 0.0;
+        assert_eq!(light.shadow_strength(), 0.0);
+        light.intensity = 2_000_000.0;
+        assert_eq!(light.shadow_strength(), 1.0);
+
+        let directional_light = DirectionalLight {
+            illuminance: light_consts::lux::AMBIENT_DAYLIGHT * 0.5,
+            fade_shadows_with_intensity: true,
+            ..Default::default()
+        };
+        assert_eq!(directional_light.shadow_strength(), 0.5);
+    }
+
+    #[test]
+    fn cascade_near_bounds_use_per_boundary_overlap() {
+        let config = CascadeShadowConfig {
+            bounds: vec![10.0, 20.0, 40.0, 80.0],
+            overlap_proportion: 0.2,
+            boundary_overlap_proportions: vec![0.5, 0.25],
+            ..Default::default()
+        };
+        let near_bounds = (0..4)
+            .map(|index| cascade_near_bound(&config, index, 0.1, false))
+            .collect::<Vec<_>>();
+        // The last boundary has no value of its own, so it uses `overlap_proportion`.
+        assert_eq!(near_bounds, [0.1, 5.0, 15.0, 32.0]);
+    }
+
+    #[test]
+    fn overlap_changes_only_recompute_cascade_near_planes() {
+        let mut config = CascadeShadowConfigBuilder {
+            num_cascades: 3,
+            first_cascade_far_bound: 10.0,
+            maximum_distance: 90.0,
+            ..Default::default()
         }
+        .build();
+        let projection = Projection::Perspective(PerspectiveProjection::default());
+        let world_from_light = Mat4::from_rotation_x(-1.0);
+        let view_cascades =
+            |config: &CascadeShadowConfig, frustum_slices: &mut CascadeFrustumSlices| {
+                calculate_view_cascades(
+                    config,
+                    &projection,
+                    world_from_light,
+                    Mat4::IDENTITY,
+                    1024.0,
+                    None,
+                    None,
+                    frustum_slices,
+                )
+            };
 
-        for (_, cascade_view_entities) in &mut visible_entities.entities {
-            cascade_view_entities
-                .iter_mut()
-                .map(DerefMut::deref_mut)
-                .for_each(shrink_entities);
+        let mut frustum_slices = CascadeFrustumSlices::default();
+        view_cascades(&config, &mut frustum_slices);
+        let previous_corners = frustum_slices.corners.clone();
+
+        config.overlap_proportion = 0.5;
+        let cascades = view_cascades(&config, &mut frustum_slices);
+        for (idx, (corners, previous_corners)) in frustum_slices
+            .corners
+            .iter()
+            .zip(&previous_corners)
+            .enumerate()
+        {
+            // The far planes are derived from the bounds, which didn't change.
+            assert_eq!(corners[4..], previous_corners[4..]);
+            // The first cascade starts at the minimum distance rather than overlapping another.
+            assert_eq!(corners[..4] == previous_corners[..4], idx == 0);
+        }
+
+        // The partial update gives the same cascades as a full rebuild.
+        let rebuilt = view_cascades(&config, &mut CascadeFrustumSlices::default());
+        assert_eq!(cascades.len(), rebuilt.len());
+        for (cascade, rebuilt) in cascades.iter().zip(&rebuilt) {
+            assert_eq!(cascade.clip_from_world, rebuilt.clip_from_world);
+            assert_eq!(cascade.texel_size, rebuilt.texel_size);
         }
     }
 
-    // Defer marking view visibility so this system can run in parallel with check_point_light_mesh_visibility
-    // TODO: use resource to avoid unnecessary memory alloc
-    let mut defer_queue = core::mem::take(defer_visible_entities_queue.deref_mut());
-    commands.queue(move |world: &mut World| {
-        world.resource_scope::<PreviousVisibleEntities, _>(
-            |world, mut previous_visible_entities| {
-                let mut query = world.query::<(Entity, &mut ViewVisibility)>();
-                for entities in defer_queue.iter_mut() {
-                    let mut iter = query.iter_many_mut(world, entities.iter());
-                    while let Some((entity, mut view_visibility)) = iter.fetch_next() {
-                        if !**view_visibility {
-                            view_visibility.set();
-                        }
+    #[test]
+    fn shadow_cascade_range_limits_cascades() {
+        use bevy_ecs::system::RunSystemOnce;
 
-                        // Remove any entities that were discovered to be
-                        // visible from the `PreviousVisibleEntities` resource.
-                        previous_visible_entities.remove(&entity);
-                    }
-                }
-            },
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<PreviousVisibleEntities>();
+        let view = world.spawn_empty().id();
+        let mut light_view_visibility = ViewVisibility::default();
+        light_view_visibility.set();
+        // Three cascades whose frusta contain everything.
+        let mut frusta = CascadesFrusta::default();
+        let frustum = Frustum {
+            half_spaces: [HalfSpace::new(Vec3::X.extend(f32::INFINITY)); 6],
+        };
+        frusta.frusta.insert(view, vec![frustum; 3]);
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                frusta,
+                light_view_visibility,
+            ))
+            .id();
+
+        let mut spawn_caster = |cascade_range: Option<ShadowCascadeRange>| {
+            let mut caster = world.spawn((
+                Mesh3d::default(),
+                Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1)),
+                GlobalTransform::IDENTITY,
+                InheritedVisibility::VISIBLE,
+            ));
+            if let Some(cascade_range) = cascade_range {
+                caster.insert(cascade_range);
+            }
+            caster.id()
+        };
+        let unlimited = spawn_caster(None);
+        let far_only = spawn_caster(Some(ShadowCascadeRange { min: 1, max: 2 }));
+        world
+            .run_system_once(check_dir_light_mesh_visibility)
+            .unwrap();
+
+        let cascades_visible_entities = world.get::<CascadesVisibleEntities>(light).unwrap();
+        let visible_entities = cascades_visible_entities.entities[&view]
+            .iter()
+            .map(|visible_entities| {
+                let mut entities = visible_entities.entities.clone();
+                entities.sort();
+                entities
+            })
+            .collect::<Vec<_>>();
+        let mut both = vec![unlimited, far_only];
+        both.sort();
+        assert_eq!(visible_entities, [vec![unlimited], both.clone(), both]);
+    }
+
+    #[test]
+    fn shadow_bounds_cull_casters_and_cascades() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<PreviousVisibleEntities>();
+        let view = world.spawn_empty().id();
+        let mut light_view_visibility = ViewVisibility::default();
+        light_view_visibility.set();
+        // A cascade whose frustum contains everything, and one that only contains `x > 100`.
+        let mut frusta = CascadesFrusta::default();
+        let everything = Frustum {
+            half_spaces: [HalfSpace::new(Vec3::X.extend(f32::INFINITY)); 6],
+        };
+        let mut far_side = everything;
+        far_side.half_spaces[0] = HalfSpace::new(Vec3::X.extend(-100.0));
+        frusta.frusta.insert(view, vec![everything, far_side]);
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                frusta,
+                light_view_visibility,
+                ShadowBounds(Aabb {
+                    center: Vec3A::ZERO,
+                    half_extents: Vec3A::splat(10.0),
+                }),
+            ))
+            .id();
+
+        // Casters inside, straddling the edge of, and outside of the bounds, and one in the far
+        // cascade.
+        let [inside, straddling, outside, far] = [
+            Vec3::new(0.0, 5.0, 0.0),
+            Vec3::new(10.05, 0.0, 0.0),
+            Vec3::new(20.0, 0.0, 0.0),
+            Vec3::new(120.0, 0.0, 0.0),
+        ]
+        .map(|position| {
+            world
+                .spawn((
+                    Mesh3d::default(),
+                    Aabb {
+                        center: Vec3A::ZERO,
+                        half_extents: Vec3A::splat(0.1),
+                    },
+                    GlobalTransform::from_translation(position),
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id()
+        });
+
+        let visible_entities = |world: &mut World| {
+            world
+                .run_system_once(check_dir_light_mesh_visibility)
+                .unwrap();
+            world
+                .get::<CascadesVisibleEntities>(light)
+                .unwrap()
+                .entities[&view]
+                .iter()
+                .map(|visible_entities| {
+                    let mut entities = visible_entities.entities.clone();
+                    entities.sort();
+                    entities
+                })
+                .collect::<Vec<_>>()
+        };
+        // The far cascade doesn't overlap the bounds, so it gathers no casters.
+        let mut expected = vec![inside, straddling];
+        expected.sort();
+        assert_eq!(visible_entities(&mut world), [expected, vec![]]);
+
+        world.entity_mut(light).remove::<ShadowBounds>();
+        let mut expected = vec![inside, straddling, outside, far];
+        expected.sort();
+        assert_eq!(visible_entities(&mut world), [expected, vec![far]]);
+    }
+
+    #[test]
+    fn temporal_shadow_seed_fixes_noise_frame() {
+        assert_eq!(temporal_shadow_frame(None, 7), 7);
+        assert_eq!(temporal_shadow_frame(None, 8), 8);
+
+        let seed = TemporalShadowSeed { frame: 42 };
+        assert_eq!(temporal_shadow_frame(Some(&seed), 7), 42);
+        assert_eq!(temporal_shadow_frame(Some(&seed), 8), 42);
+    }
+
+    #[test]
+    fn shadow_casters_are_union_of_visible_entities() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let casters = [0, 1, 2].map(|index| Entity::from_raw_u32(index + 10).unwrap());
+
+        let mut cubemap_visible_entities = CubemapVisibleEntities::default();
+        cubemap_visible_entities
+            .get_mut(0)
+            .entities
+            .extend([casters[0], casters[1]]);
+        cubemap_visible_entities
+            .get_mut(3)
+            .entities
+            .extend([casters[1], casters[2]]);
+        let point_light = world
+            .spawn((cubemap_visible_entities, ShadowCasters::default()))
+            .id();
+
+        let mut cascades_visible_entities = CascadesVisibleEntities::default();
+        let mut cascade = VisibleMeshEntities::default();
+        cascade.entities.push(casters[2]);
+        cascades_visible_entities.entities.insert(
+            Entity::from_raw_u32(1).unwrap(),
+            vec![VisibleMeshEntities::default(), cascade],
+        );
+        let directional_light = world
+            .spawn((cascades_visible_entities, ShadowCasters::default()))
+            .id();
+
+        world.run_system_once(update_shadow_casters).unwrap();
+        assert_eq!(
+            world.get::<ShadowCasters>(point_light).unwrap().0,
+            casters.into_iter().collect::<EntityHashSet>()
         );
-    });
-}
+        assert_eq!(
+            world.get::<ShadowCasters>(directional_light).unwrap().0,
+            [casters[2]].into_iter().collect::<EntityHashSet>()
+        );
+    }
 
-pub fn check_point_light_mesh_visibility(
-    visible_point_lights: Query<&VisibleClusterableObjects>,
-    mut point_lights: Query<(
-        &PointLight,
-        &GlobalTransform,
-        &CubemapFrusta,
-        &mut CubemapVisibleEntities,
-        Option<&RenderLayers>,
-    )>,
-    mut spot_lights: Query<(
-        &SpotLight,
-        &GlobalTransform,
-        &Frustum,
-        &mut VisibleMeshEntities,
-        Option<&RenderLayers>,
-    )>,
-    mut visible_entity_query: Query<
-        (
-            Entity,
-            &InheritedVisibility,
-            &mut ViewVisibility,
-            Option<&RenderLayers>,
-            Option<&Aabb>,
-            Option<&GlobalTransform>,
-            Has<VisibilityRange>,
-            Has<NoFrustumCulling>,
-        ),
-        (
-            Without<NotShadowCaster>,
-            Without<DirectionalLight>,
-            With<Mesh3d>,
-        ),
-    >,
-    visible_entity_ranges: Option<Res<VisibleEntityRanges>>,
-    mut previous_visible_entities: ResMut<PreviousVisibleEntities>,
-    mut cubemap_visible_entities_queue: Local<Parallel<[Vec<Entity>; 6]>>,
-    mut spot_visible_entities_queue: Local<Parallel<Vec<Entity>>>,
-    mut checked_lights: Local<EntityHashSet>,
-) {
-    checked_lights.clear();
+    #[test]
+    fn validate_rejects_too_many_cascades() {
+        let builder = |num_cascades| CascadeShadowConfigBuilder {
+            num_cascades,
+            ..Default::default()
+        };
+        assert_eq!(builder(MAX_CASCADES_PER_LIGHT).validate(256), Ok(()));
 
-    let visible_entity_ranges = visible_entity_ranges.as_deref();
-    for visible_lights in &visible_point_lights {
-        for light_entity in visible_lights.entities.iter().copied() {
-            if !checked_lights.insert(light_entity) {
-                continue;
+        let error = builder(MAX_CASCADES_PER_LIGHT + 4)
+            .validate(256)
+            .unwrap_err();
+        assert_eq!(
+            error,
+            TooManyCascadesError {
+                num_cascades: MAX_CASCADES_PER_LIGHT + 4,
+                max_cascades: MAX_CASCADES_PER_LIGHT,
             }
+        );
+        assert!(error
+            .to_string()
+            .contains(&format!("at most {MAX_CASCADES_PER_LIGHT} are supported")));
 
-            // Point lights
-            if let Ok((
-                point_light,
-                transform,
-                cubemap_frusta,
-                mut cubemap_visible_entities,
-                maybe_view_mask,
-            )) = point_lights.get_mut(light_entity)
-            {
-                for visible_entities in cubemap_visible_entities.iter_mut() {
-                    visible_entities.entities.clear();
-                }
+        // The device limit applies when it's lower than `MAX_CASCADES_PER_LIGHT`.
+        assert_eq!(
+            builder(2).validate(1),
+            Err(TooManyCascadesError {
+                num_cascades: 2,
+                max_cascades: 1,
+            })
+        );
+    }
 
-                // NOTE: If shadow mapping is disabled for the light then it must have no visible entities
-                if !point_light.shadows_enabled {
-                    continue;
+    #[test]
+    fn cascade_size_smoothing_eases_zoom() {
+        use core::time::Duration;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        world.insert_resource(Time::<()>::default());
+        let [view, smoothed_view] =
+            [None, Some(CascadeSizeSmoothing { rate: 5.0 })].map(|smoothing| {
+                let mut view = world.spawn((
+                    Camera::default(),
+                    Projection::default(),
+                    GlobalTransform::IDENTITY,
+                ));
+                if let Some(smoothing) = smoothing {
+                    view.insert(smoothing);
                 }
+                view.id()
+            });
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
 
-                let view_mask = maybe_view_mask.unwrap_or_default();
-                let light_sphere = Sphere {
-                    center: Vec3A::from(transform.translation()),
-                    radius: point_light.range,
-                };
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+        let texel_size = |world: &World, view| {
+            world.get::<Cascades>(light).unwrap().cascades[&view][0].texel_size
+        };
+        let initial_texel_size = texel_size(&world, view);
 
-                visible_entity_query.par_iter_mut().for_each_init(
-                    || cubemap_visible_entities_queue.borrow_local_mut(),
-                    |cubemap_visible_entities_local_queue,
-                     (
-                        entity,
-                        inherited_visibility,
-                        mut view_visibility,
-                        maybe_entity_mask,
-                        maybe_aabb,
-                        maybe_transform,
-                        has_visibility_range,
-                        has_no_frustum_culling,
-                    )| {
-                        if !inherited_visibility.get() {
-                            return;
-                        }
-                        let entity_mask = maybe_entity_mask.unwrap_or_default();
-                        if !view_mask.intersects(entity_mask) {
-                            return;
-                        }
-                        if has_visibility_range
-                            && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
-                                !visible_entity_ranges.entity_is_in_range_of_any_view(entity)
-                            })
-                        {
-                            return;
-                        }
+        // Zoom out.
+        for view in [view, smoothed_view] {
+            *world.get_mut::<Projection>(view).unwrap() =
+                Projection::Perspective(PerspectiveProjection {
+                    fov: 1.5,
+                    ..Default::default()
+                });
+        }
+        let mut previous_texel_size = initial_texel_size;
+        for _ in 0..3 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(100));
+            world.run_system(build).unwrap();
 
-                        // If we have an aabb and transform, do frustum culling
-                        if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
-                            let model_to_world = transform.affine();
-                            // Do a cheap sphere vs obb test to prune out most meshes outside the sphere of the light
-                            if !has_no_frustum_culling
-                                && !light_sphere.intersects_obb(aabb, &model_to_world)
-                            {
-                                return;
-                            }
+            let smoothed_texel_size = texel_size(&world, smoothed_view);
+            assert!(smoothed_texel_size > previous_texel_size);
+            assert!(smoothed_texel_size < texel_size(&world, view));
+            previous_texel_size = smoothed_texel_size;
+        }
 
-                            for (frustum, visible_entities) in cubemap_frusta
-                                .iter()
-                                .zip(cubemap_visible_entities_local_queue.iter_mut())
-                            {
-                                if has_no_frustum_culling
-                                    || frustum.intersects_obb(aabb, &model_to_world, true, true)
-                                {
-                                    if !**view_visibility {
-                                        view_visibility.set();
-                                    }
-                                    visible_entities.push(entity);
-                                }
-                            }
-                        } else {
-                            if !**view_visibility {
-                                view_visibility.set();
-                            }
-                            for visible_entities in cubemap_visible_entities_local_queue.iter_mut()
-                            {
-                                visible_entities.push(entity);
-                            }
-                        }
-                    },
-                );
+        for _ in 0..50 {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_millis(100));
+            world.run_system(build).unwrap();
+        }
+        assert_eq!(texel_size(&world, smoothed_view), texel_size(&world, view));
+    }
 
-                for entities in cubemap_visible_entities_queue.iter_mut() {
-                    for (dst, source) in
-                        cubemap_visible_entities.iter_mut().zip(entities.iter_mut())
-                    {
-                        // Remove any entities that were discovered to be
-                        // visible from the `PreviousVisibleEntities` resource.
-                        for entity in source.iter() {
-                            previous_visible_entities.remove(entity);
-                        }
+    #[test]
+    fn shared_cascades_cover_all_views() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
 
-                        dst.entities.append(source);
-                    }
-                }
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        world.insert_resource(SharedCascades);
+        let view_transforms = [
+            GlobalTransform::IDENTITY,
+            GlobalTransform::from(
+                Transform::from_xyz(30.0, 0.0, 0.0).with_rotation(Quat::from_rotation_y(2.0)),
+            ),
+        ];
+        let views = view_transforms.map(|transform| {
+            world
+                .spawn((Camera::default(), Projection::default(), transform))
+                .id()
+        });
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                GlobalTransform::from(Transform::from_rotation(Quat::from_rotation_x(-1.0))),
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
 
-                for visible_entities in cubemap_visible_entities.iter_mut() {
-                    shrink_entities(visible_entities);
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades;
+        let shared = &cascades[&views[0]];
+        assert_eq!(
+            shared.iter().map(|c| c.clip_from_world).collect::<Vec<_>>(),
+            cascades[&views[1]]
+                .iter()
+                .map(|c| c.clip_from_world)
+                .collect::<Vec<_>>()
+        );
+
+        // Every cascade contains its slice of the frustum of each view.
+        let config = CascadeShadowConfig::default();
+        let projection = Projection::default();
+        let minimum_distance = clamped_minimum_distance(&config, projection_near(&projection));
+        for (idx, cascade) in shared.iter().enumerate() {
+            let z_near = -cascade_near_bound(&config, idx, minimum_distance, false);
+            let corners = projection.get_frustum_corners(z_near, -config.bounds[idx]);
+            for transform in &view_transforms {
+                for corner in corners {
+                    let clip = cascade
+                        .clip_from_world
+                        .project_point3(transform.transform_point(corner.into()));
+                    assert!(
+                        clip.x.abs() <= 1.0 + 1e-4 && clip.y.abs() <= 1.0 + 1e-4,
+                        "{clip}"
+                    );
                 }
             }
+        }
+    }
 
-            // Spot lights
-            if let Ok((point_light, transform, frustum, mut visible_entities, maybe_view_mask)) =
-                spot_lights.get_mut(light_entity)
-            {
-                visible_entities.clear();
+    #[test]
+    fn skip_shadow_render_keeps_cascades() {
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
 
-                // NOTE: If shadow mapping is disabled for the light then it must have no visible entities
-                if !point_light.shadows_enabled {
-                    continue;
-                }
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        world.insert_resource(SkipShadowRender);
+        let view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
 
-                let view_mask = maybe_view_mask.unwrap_or_default();
-                let light_sphere = Sphere {
-                    center: Vec3A::from(transform.translation()),
-                    radius: point_light.range,
-                };
+        world.get_mut::<ViewVisibility>(light).unwrap().set();
 
-                visible_entity_query.par_iter_mut().for_each_init(
-                    || spot_visible_entities_queue.borrow_local_mut(),
-                    |spot_visible_entities_local_queue,
-                     (
-                        entity,
-                        inherited_visibility,
-                        mut view_visibility,
-                        maybe_entity_mask,
-                        maybe_aabb,
-                        maybe_transform,
-                        has_visibility_range,
-                        has_no_frustum_culling,
-                    )| {
-                        if !inherited_visibility.get() {
-                            return;
-                        }
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+        let update_frusta = world.register_system(update_directional_light_frusta);
+        world.run_system(update_frusta).unwrap();
 
-                        let entity_mask = maybe_entity_mask.unwrap_or_default();
-                        if !view_mask.intersects(entity_mask) {
-                            return;
-                        }
-                        // Check visibility ranges.
-                        if has_visibility_range
-                            && visible_entity_ranges.is_some_and(|visible_entity_ranges| {
-                                !visible_entity_ranges.entity_is_in_range_of_any_view(entity)
-                            })
-                        {
-                            return;
-                        }
+        let cascade_count = CascadeShadowConfig::default().bounds.len();
+        assert_eq!(
+            world.get::<Cascades>(light).unwrap().cascades[&view].len(),
+            cascade_count
+        );
+        assert_eq!(
+            world.get::<CascadesFrusta>(light).unwrap().frusta[&view].len(),
+            cascade_count
+        );
+    }
 
-                        if let (Some(aabb), Some(transform)) = (maybe_aabb, maybe_transform) {
-                            let model_to_world = transform.affine();
-                            // Do a cheap sphere vs obb test to prune out most meshes outside the sphere of the light
-                            if !has_no_frustum_culling
-                                && !light_sphere.intersects_obb(aabb, &model_to_world)
-                            {
-                                return;
-                            }
+    #[test]
+    fn baked_point_shadows_gather_no_casters() {
+        use bevy_ecs::system::RunSystemOnce;
 
-                            if has_no_frustum_culling
-                                || frustum.intersects_obb(aabb, &model_to_world, true, true)
-                            {
-                                if !**view_visibility {
-                                    view_visibility.set();
-                                }
-                                spot_visible_entities_local_queue.push(entity);
-                            }
-                        } else {
-                            if !**view_visibility {
-                                view_visibility.set();
-                            }
-                            spot_visible_entities_local_queue.push(entity);
-                        }
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<ActiveCameraPositions>();
+        world.init_resource::<PreviousVisibleEntities>();
+        world.init_resource::<PointLightShadowMap>();
+        let point_light = PointLight {
+            shadows_enabled: true,
+            ..Default::default()
+        };
+        let lights = [
+            world
+                .spawn((
+                    point_light,
+                    GlobalTransform::IDENTITY,
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id(),
+            world
+                .spawn((
+                    point_light,
+                    GlobalTransform::IDENTITY,
+                    InheritedVisibility::VISIBLE,
+                    BakedPointShadow::default(),
+                ))
+                .id(),
+        ];
+        let mut global_lights = GlobalVisibleClusterableObjects::default();
+        global_lights.entities.extend(lights);
+        world.insert_resource(global_lights);
+        let mut visible_lights = VisibleClusterableObjects::default();
+        visible_lights.entities.extend(lights);
+        world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
+        world.spawn((
+            Mesh3d::default(),
+            Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1)),
+            GlobalTransform::from_xyz(2.0, 0.0, 0.0),
+            InheritedVisibility::VISIBLE,
+        ));
+
+        world.run_system_once(update_point_light_frusta).unwrap();
+        world
+            .run_system_once(check_point_light_mesh_visibility)
+            .unwrap();
+
+        let caster_count = |light| {
+            world
+                .get::<CubemapVisibleEntities>(light)
+                .unwrap()
+                .iter()
+                .map(|visible_entities| visible_entities.entities.len())
+                .sum::<usize>()
+        };
+        assert!(caster_count(lights[0]) > 0);
+        assert_eq!(caster_count(lights[1]), 0);
+    }
+
+    #[test]
+    fn hemisphere_shadows_skip_back_faces() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let visible_faces = |shadow_technique, direction: Vec3| {
+            let mut world = World::new();
+            world.init_resource::<ActiveCameraPositions>();
+            world.init_resource::<PreviousVisibleEntities>();
+            world.init_resource::<PointLightShadowMap>();
+            let light = world
+                .spawn((
+                    PointLight {
+                        shadows_enabled: true,
+                        shadow_technique,
+                        shadow_shape: PointLightShadowShape::Hemisphere { direction },
+                        ..Default::default()
                     },
-                );
+                    GlobalTransform::IDENTITY,
+                    InheritedVisibility::VISIBLE,
+                ))
+                .id();
+            let mut global_lights = GlobalVisibleClusterableObjects::default();
+            global_lights.entities.insert(light);
+            world.insert_resource(global_lights);
+            let mut visible_lights = VisibleClusterableObjects::default();
+            visible_lights.entities.push(light);
+            world.spawn((Camera::default(), GlobalTransform::IDENTITY, visible_lights));
 
-                for entities in spot_visible_entities_queue.iter_mut() {
-                    visible_entities.append(entities);
+            // A caster in the direction of every cube face.
+            let casters = CUBE_MAP_FACES.map(|face| {
+                world
+                    .spawn((
+                        Mesh3d::default(),
+                        Aabb::from_min_max(Vec3::splat(-0.1), Vec3::splat(0.1)),
+                        GlobalTransform::from_translation(face.target * 5.0),
+                        InheritedVisibility::VISIBLE,
+                    ))
+                    .id()
+            });
+            world.run_system_once(update_point_light_frusta).unwrap();
+            world
+                .run_system_once(check_point_light_mesh_visibility)
+                .unwrap();
 
-                    // Remove any entities that were discovered to be visible
-                    // from the `PreviousVisibleEntities` resource.
-                    for entity in entities {
-                        previous_visible_entities.remove(entity);
-                    }
-                }
+            let cubemap_visible_entities = world.get::<CubemapVisibleEntities>(light).unwrap();
+            let face_has_casters = (0..6)
+                .map(|face_index| !cubemap_visible_entities.get(face_index).entities.is_empty())
+                .collect::<Vec<_>>();
+            let shadowed_casters = casters
+                .iter()
+                .map(|caster| {
+                    cubemap_visible_entities
+                        .iter()
+                        .any(|face| face.entities.contains(caster))
+                })
+                .collect::<Vec<_>>();
+            (face_has_casters, shadowed_casters)
+        };
 
-                shrink_entities(visible_entities.deref_mut());
-            }
-        }
+        // Facing +X, only the -X face is skipped, and the caster behind the light isn't gathered.
+        let (faces, casters) = visible_faces(PointShadowTechnique::Cubemap, Vec3::X);
+        assert_eq!(faces, [true, false, true, true, true, true]);
+        assert_eq!(casters, [true, false, true, true, true, true]);
+
+        // Facing +Z with dual-paraboloid shadows, only the +Z hemisphere is rendered.
+        let (faces, casters) = visible_faces(PointShadowTechnique::DualParaboloid, Vec3::Z);
+        assert_eq!(faces, [false, false, false, false, false, true]);
+        assert!(!casters[4] && casters[5]);
+    }
+
+    #[test]
+    fn cube_face_fov_padding_widens_face_frusta() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let neg_z_face = CUBE_MAP_FACES
+            .iter()
+            .position(|face| face.target == Vec3::NEG_Z)
+            .unwrap();
+        // Whether the -Z face sees points 47° and 52° away from its axis, which the unpadded 45°
+        // half-angle doesn't reach.
+        let face_sees = |cube_face_fov_padding| {
+            let mut world = World::new();
+            world.insert_resource(PointLightShadowMap {
+                cube_face_fov_padding,
+                ..Default::default()
+            });
+            let light = world
+                .spawn((
+                    PointLight {
+                        shadows_enabled: true,
+                        range: 20.0,
+                        ..Default::default()
+                    },
+                    GlobalTransform::IDENTITY,
+                ))
+                .id();
+            let mut global_lights = GlobalVisibleClusterableObjects::default();
+            global_lights.entities.insert(light);
+            world.insert_resource(global_lights);
+            world.run_system_once(update_point_light_frusta).unwrap();
+
+            let frustum = world.get::<CubemapFrusta>(light).unwrap().frusta[neg_z_face];
+            [47.0_f32, 52.0].map(|degrees| {
+                let angle = degrees.to_radians();
+                let sphere = Sphere {
+                    center: Vec3A::new(ops::sin(angle), 0.0, -ops::cos(angle)) * 10.0,
+                    radius: 0.01,
+                };
+                frustum.intersects_sphere(&sphere, true)
+            })
+        };
+
+        // Padding the field of view by 0.1 radians widens the half-angle to about 47.9°, and by
+        // 0.25 radians to about 52.2°.
+        assert_eq!(face_sees(0.0), [false, false]);
+        assert_eq!(face_sees(0.1), [true, false]);
+        assert_eq!(face_sees(0.25), [true, true]);
+    }
+
+    #[test]
+    fn lumen_conversions_round_trip() {
+        use light_consts::lumens::*;
+
+        const BULB: f32 = from_incandescent_watts(60.0);
+        assert_eq!(BULB, 60.0 * LUMENS_PER_INCANDESCENT_WATTS);
+        assert!((to_incandescent_watts(BULB) - 60.0).abs() < 1e-4);
+        assert!((to_led_watts(from_led_watts(8.5)) - 8.5).abs() < 1e-4);
+        assert!((to_halogen_watts(from_halogen_watts(42.0)) - 42.0).abs() < 1e-4);
+        // An LED lamp needs fewer watts than an incandescent one for the same light.
+        assert!(to_led_watts(BULB) < 60.0);
     }
 }
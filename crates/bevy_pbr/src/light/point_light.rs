@@ -24,12 +24,15 @@ use super::*;
 ///
 /// To enable shadows, set the `shadows_enabled` property to `true`.
 ///
-/// To control the resolution of the shadow maps, use the [`PointLightShadowMap`] resource.
+/// To control the resolution of the shadow maps, use the [`PointLightShadowMap`] resource, or
+/// [`PointLight::shadow_map_size`] for a single light.
 #[derive(Component, Debug, Clone, Copy, Reflect)]
 #[reflect(Component, Default, Debug, Clone)]
 #[require(
     CubemapFrusta,
     CubemapVisibleEntities,
+    ShadowFrustaTransform,
+    ShadowMapState,
     Transform,
     Visibility,
     VisibilityClass
@@ -101,6 +104,73 @@ pub struct PointLight {
     /// away.
     pub shadow_normal_bias: f32,
 
+    /// The minimum distance, in world units, that fragments are offset toward the light when
+    /// sampling the shadow map.
+    ///
+    /// On surfaces seen by the light at grazing angles, the normal bias barely moves fragments
+    /// toward the light, so the depth bias alone has to prevent acne there. The
+    /// [`shadow_depth_bias`](Self::shadow_depth_bias) is raised to this value if it's lower. This
+    /// matters most for [`DirectionalLight`](crate::DirectionalLight)s, whose depth bias varies
+    /// per cascade.
+    ///
+    /// By default, this is 0.0, which leaves the depth bias as is.
+    pub min_world_shadow_bias: f32,
+
+    /// The color that shadowed areas are tinted toward, for stylized colored shadows.
+    ///
+    /// In shadow, the light's contribution is multiplied by this color rather than removed
+    /// entirely, blending back to the full contribution as the surface leaves the shadow.
+    ///
+    /// By default, this is black, which makes shadows fully block the light.
+    pub shadow_tint: Color,
+
+    /// Whether this light's shadows fade out along with its
+    /// [`intensity`](Self::intensity), so that dimming the light also softens the
+    /// shadows it casts rather than leaving them at full strength until it turns off.
+    ///
+    /// When enabled, the shadow strength is the ratio of the intensity to
+    /// [`full_shadow_intensity`](Self::full_shadow_intensity), clamped to 1.0. See
+    /// [`shadow_strength`](Self::shadow_strength).
+    ///
+    /// By default, this is false, and shadows are always at full strength.
+    pub fade_shadows_with_intensity: bool,
+
+    /// The intensity at and above which shadows are at full strength, when
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity) is enabled.
+    ///
+    /// By default, this is the default intensity of 1,000,000 lumens.
+    pub full_shadow_intensity: f32,
+
+    /// The radius, in world units, over which shadow edges are blurred when filtering the shadow
+    /// map.
+    ///
+    /// By default, this is [`None`], and the blur radius is a fixed number of shadow map texels,
+    /// so changing the shadow map resolution changes how soft shadows look. Setting this keeps the
+    /// softness the same at any resolution.
+    ///
+    /// This has no effect with [`ShadowFilteringMethod::Hardware2x2`], or when soft shadows are
+    /// enabled, since the penumbra is then determined by the light's radius.
+    pub shadow_blur_world_radius: Option<f32>,
+
+    /// Whether this light's shadows are filtered the same way on every frame.
+    ///
+    /// By default, this is `false`, and shadows are filtered with the camera's
+    /// [`ShadowFilteringMethod`](crate::ShadowFilteringMethod). The
+    /// [`Temporal`](crate::ShadowFilteringMethod::Temporal) and
+    /// [`Vogel`](crate::ShadowFilteringMethod::Vogel) methods change their sampling pattern from
+    /// frame to frame, relying on temporal antialiasing to smooth out the noise. Setting this makes
+    /// the light use a fixed pattern instead, such as the
+    /// [`Gaussian`](crate::ShadowFilteringMethod::Gaussian) filter, for crisp shadows that don't
+    /// flicker even when temporal antialiasing is in use.
+    pub stable_shadows: bool,
+
+    /// The distance from the nearest active camera beyond which this light stops casting shadows,
+    /// while still lighting the scene.
+    ///
+    /// This saves the cost of rendering shadow maps for distant lights, whose shadows are less
+    /// noticeable. By default, this is [`None`], so shadows are rendered at any distance.
+    pub shadow_cutoff_distance: Option<f32>,
+
     /// The distance from the light to near Z plane in the shadow map.
     ///
     /// Objects closer than this distance to the light won't cast shadows.
@@ -108,6 +178,77 @@ pub struct PointLight {
     ///
     /// This only has an effect if shadows are enabled.
     pub shadow_map_near_z: f32,
+
+    /// How the shadow map of this light is rendered.
+    ///
+    /// By default, this is [`PointShadowTechnique::Cubemap`].
+    pub shadow_technique: PointShadowTechnique,
+
+    /// The part of the space around the light that casts shadows.
+    ///
+    /// By default, this is [`PointLightShadowShape::FullCube`].
+    pub shadow_shape: PointLightShadowShape,
+
+    /// The width and height of each face of this light's shadow cubemap, overriding
+    /// [`PointLightShadowMap::size`].
+    ///
+    /// The shadow maps of all point lights share one cubemap array texture, so its faces have
+    /// the largest size among the visible point lights with shadows, and every light is rendered
+    /// at that size. This lets an important light have sharper shadows than the rest, but a size
+    /// smaller than [`PointLightShadowMap::size`] has no effect: to save memory, lower the
+    /// resource's size and raise the size of the lights that need it.
+    ///
+    /// By default, this is [`None`], which uses [`PointLightShadowMap::size`].
+    pub shadow_map_size: Option<usize>,
+}
+
+/// How the shadow map of a [`PointLight`] is rendered, see [`PointLight::shadow_technique`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect)]
+#[reflect(Default, Debug, PartialEq, Hash, Clone)]
+pub enum PointShadowTechnique {
+    /// Renders the scene six times, once for each face of a cube around the light.
+    ///
+    /// This gives the most accurate shadows.
+    #[default]
+    Cubemap,
+    /// Renders the scene twice, once for each hemisphere around the light, each warped onto a
+    /// paraboloid. This is cheaper than [`Cubemap`](Self::Cubemap) in scenes with many
+    /// shadow-casting point lights.
+    ///
+    /// The warp is applied to the vertices of the shadow casters, and the triangles between them
+    /// are still rasterized as straight-edged. Large triangles near the light are distorted, so
+    /// shadows of coarse geometry may bend or detach from their casters, especially around the
+    /// horizon between the hemispheres, which is at the light's `Z = 0` plane in world space.
+    /// Finely tessellated casters avoid most of the distortion.
+    ///
+    /// The hemispheres are stored in two faces of the light's cube map, so this doesn't save any
+    /// memory. Custom materials with their own prepass vertex shader don't apply the warp.
+    DualParaboloid,
+}
+
+/// The part of the space around a [`PointLight`] that casts shadows, see
+/// [`PointLight::shadow_shape`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Reflect)]
+#[reflect(Default, Debug, PartialEq, Clone)]
+pub enum PointLightShadowShape {
+    /// Shadows are cast in every direction.
+    #[default]
+    FullCube,
+    /// Shadows are only cast in the hemisphere around `direction`, for lights that can only shine
+    /// on one side, such as a light mounted flush on a wall.
+    ///
+    /// The light still shines in every direction, but shadow casters are only gathered for the
+    /// shadow map faces that see some of the hemisphere, and the other faces are left empty, so
+    /// that surfaces behind the light are lit without shadows. The faces are aligned with the
+    /// world axes, so a hemisphere facing along an axis skips one face of the cube, and without
+    /// [`cube_face_fov_padding`](PointLightShadowMap::cube_face_fov_padding), one facing halfway
+    /// between two axes skips two. With [`PointShadowTechnique::DualParaboloid`], a
+    /// hemisphere facing along the world Z axis skips one of the two hemispheres, halving the cost
+    /// of the light's shadows.
+    Hemisphere {
+        /// The direction that the hemisphere faces, in the light's local space.
+        direction: Vec3,
+    },
 }
 
 impl Default for PointLight {
@@ -124,7 +265,17 @@ impl Default for PointLight {
             affects_lightmapped_mesh_diffuse: true,
             shadow_depth_bias: Self::DEFAULT_SHADOW_DEPTH_BIAS,
             shadow_normal_bias: Self::DEFAULT_SHADOW_NORMAL_BIAS,
+            min_world_shadow_bias: 0.0,
+            shadow_tint: Color::BLACK,
+            fade_shadows_with_intensity: false,
+            full_shadow_intensity: 1_000_000.0,
+            shadow_blur_world_radius: None,
+            stable_shadows: false,
+            shadow_cutoff_distance: None,
             shadow_map_near_z: Self::DEFAULT_SHADOW_MAP_NEAR_Z,
+            shadow_technique: PointShadowTechnique::Cubemap,
+            shadow_shape: PointLightShadowShape::FullCube,
+            shadow_map_size: None,
             #[cfg(feature = "experimental_pbr_pcss")]
             soft_shadows_enabled: false,
         }
@@ -135,4 +286,116 @@ impl PointLight {
     pub const DEFAULT_SHADOW_DEPTH_BIAS: f32 = 0.08;
     pub const DEFAULT_SHADOW_NORMAL_BIAS: f32 = 0.6;
     pub const DEFAULT_SHADOW_MAP_NEAR_Z: f32 = 0.1;
+
+    /// Returns a [`PointLight`] with the default settings, and the [`color`](Self::color) of a
+    /// blackbody at the temperature `kelvin`, see
+    /// [`set_color_temperature`](Self::set_color_temperature).
+    ///
+    /// ```
+    /// # use bevy_pbr::PointLight;
+    /// let tungsten = PointLight::from_kelvin(3200.0);
+    /// ```
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let mut light = Self::default();
+        light.set_color_temperature(kelvin);
+        light
+    }
+
+    /// Sets the [`color`](Self::color) to that of a blackbody at the temperature `kelvin`, such as
+    /// about 3200 K for warm tungsten light, or 6500 K for overcast daylight.
+    ///
+    /// The temperature is clamped to 1000 K to 40000 K. The brightest channel of the color is
+    /// always 1.0, so this only changes the hue of the light.
+    pub fn set_color_temperature(&mut self, kelvin: f32) {
+        self.color = color_temperature_to_color(kelvin);
+    }
+
+    /// Returns the strength of this light's shadows, from 0.0 for no shadows to 1.0 for full
+    /// shadows, as described in
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity).
+    pub fn shadow_strength(&self) -> f32 {
+        if self.fade_shadows_with_intensity {
+            intensity_shadow_strength(self.intensity, self.full_shadow_intensity)
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the world-space sphere lit by this light, for a light with the given global
+    /// transform.
+    ///
+    /// This is the sphere that shadow casters are culled against. It doesn't account for
+    /// [`ScaleAffectsRange`]; see [`light_affects_aabb`] to test a bounding box against it.
+    pub fn influence_sphere(&self, transform: &GlobalTransform) -> Sphere {
+        Sphere {
+            center: transform.translation_vec3a(),
+            radius: self.range,
+        }
+    }
+
+    /// Returns a [`SpotLight`] with a cone of half-angle `outer_angle` that emits the same total
+    /// amount of light as this point light, for turning a point light into a spot light without
+    /// the scene getting brighter or dimmer.
+    ///
+    /// The spot light only emits light within a solid angle of `2π(1 - cos(outer_angle))`
+    /// steradians out of the `4π` of a full sphere, so its intensity is scaled up by the inverse of
+    /// that fraction. This is the inverse of [`SpotLight::into_point`]. `outer_angle` must be
+    /// greater than zero.
+    ///
+    /// The color, range, radius and shadow settings carry over, except for the shadow biases,
+    /// which are tuned separately for each type of light and so take the spot light defaults.
+    pub fn into_spot(self, outer_angle: f32) -> SpotLight {
+        let intensity_scale = 2.0 / (1.0 - ops::cos(outer_angle));
+        SpotLight {
+            color: self.color,
+            intensity: self.intensity * intensity_scale,
+            range: self.range,
+            radius: self.radius,
+            shadows_enabled: self.shadows_enabled,
+            #[cfg(feature = "experimental_pbr_pcss")]
+            soft_shadows_enabled: self.soft_shadows_enabled,
+            affects_lightmapped_mesh_diffuse: self.affects_lightmapped_mesh_diffuse,
+            shadow_tint: self.shadow_tint,
+            fade_shadows_with_intensity: self.fade_shadows_with_intensity,
+            full_shadow_intensity: self.full_shadow_intensity * intensity_scale,
+            shadow_blur_world_radius: self.shadow_blur_world_radius,
+            stable_shadows: self.stable_shadows,
+            shadow_cutoff_distance: self.shadow_cutoff_distance,
+            shadow_map_near_z: self.shadow_map_near_z,
+            outer_angle,
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn point_spot_conversion_preserves_emitted_light() {
+        let point_light = PointLight {
+            color: Color::srgb(1.0, 0.5, 0.25),
+            intensity: 4000.0,
+            range: 15.0,
+            shadows_enabled: true,
+            shadow_map_near_z: 0.5,
+            ..Default::default()
+        };
+
+        let spot_light = point_light.into_spot(0.3);
+        assert_eq!(spot_light.outer_angle, 0.3);
+        assert_eq!(spot_light.color, point_light.color);
+        assert_eq!(spot_light.range, point_light.range);
+        assert!(spot_light.shadows_enabled);
+        assert_eq!(spot_light.shadow_map_near_z, point_light.shadow_map_near_z);
+        // The narrow cone concentrates the same light, so it must be brighter.
+        assert!(spot_light.intensity > point_light.intensity);
+
+        let round_trip = spot_light.into_point();
+        assert!((round_trip.intensity - point_light.intensity).abs() < 1.0e-2);
+        assert_eq!(round_trip.color, point_light.color);
+        assert_eq!(round_trip.range, point_light.range);
+        assert_eq!(round_trip.shadow_map_near_z, point_light.shadow_map_near_z);
+    }
 }
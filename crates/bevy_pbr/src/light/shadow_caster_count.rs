@@ -0,0 +1,107 @@
+use bevy_app::prelude::*;
+use bevy_diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+
+use super::*;
+
+/// Adds the "shadow caster count" diagnostic to an App: the total number of shadow caster draws
+/// across every directional light cascade, point light cube face and spot light each frame.
+///
+/// This is a headline number for the cost of shadow rendering. It's counted after
+/// [`apply_shadow_draw_budget`], so it never exceeds the [`ShadowDrawBudget`]. Without this
+/// plugin, nothing is counted.
+///
+/// # See also
+///
+/// [`LogDiagnosticsPlugin`](bevy_diagnostic::LogDiagnosticsPlugin) to output diagnostics to the
+/// console.
+#[derive(Default)]
+pub struct ShadowCasterCountDiagnosticsPlugin;
+
+impl Plugin for ShadowCasterCountDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(Self::SHADOW_CASTER_COUNT))
+            .add_systems(
+                PostUpdate,
+                Self::diagnostic_system
+                    .after(apply_shadow_draw_budget)
+                    .after(SimulationLightSystems::CheckLightVisibility),
+            );
+    }
+}
+
+impl ShadowCasterCountDiagnosticsPlugin {
+    /// Number of shadow caster draws this frame, summed over all lights.
+    pub const SHADOW_CASTER_COUNT: DiagnosticPath =
+        DiagnosticPath::const_new("shadow_caster_count");
+
+    /// Updates the shadow caster count measurement.
+    pub fn diagnostic_system(
+        mut diagnostics: Diagnostics,
+        point_lights: Query<&CubemapVisibleEntities>,
+        spot_lights: Query<&VisibleMeshEntities, With<SpotLight>>,
+        directional_lights: Query<&CascadesVisibleEntities>,
+    ) {
+        diagnostics.add_measurement(&Self::SHADOW_CASTER_COUNT, || {
+            let draws = point_lights.iter().map(cubemap_shadow_draws).sum::<usize>()
+                + spot_lights
+                    .iter()
+                    .map(|visible| visible.len())
+                    .sum::<usize>()
+                + directional_lights
+                    .iter()
+                    .map(cascades_shadow_draws)
+                    .sum::<usize>();
+            draws as f64
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_caster_count_sums_all_lights() {
+        use bevy_diagnostic::DiagnosticsStore;
+        use bevy_ecs::system::RunSystemOnce;
+
+        let path = ShadowCasterCountDiagnosticsPlugin::SHADOW_CASTER_COUNT;
+        let visible = |count: u32| VisibleMeshEntities {
+            entities: (0..count)
+                .map(|i| Entity::from_raw_u32(100 + i).unwrap())
+                .collect(),
+        };
+
+        let mut world = World::new();
+        let mut store = DiagnosticsStore::default();
+        store.add(Diagnostic::new(path.clone()));
+        world.insert_resource(store);
+
+        let point_light = world.spawn(PointLight::default()).id();
+        let mut cubemap = world
+            .get_mut::<CubemapVisibleEntities>(point_light)
+            .unwrap();
+        for (face, visible_entities) in cubemap.iter_mut().enumerate() {
+            *visible_entities = visible(face as u32);
+        }
+        world.spawn((SpotLight::default(), visible(4)));
+        let directional_light = world.spawn(DirectionalLight::default()).id();
+        world
+            .get_mut::<CascadesVisibleEntities>(directional_light)
+            .unwrap()
+            .entities
+            .insert(
+                Entity::from_raw_u32(1).unwrap(),
+                vec![visible(2), visible(3)],
+            );
+
+        world
+            .run_system_once(ShadowCasterCountDiagnosticsPlugin::diagnostic_system)
+            .unwrap();
+
+        // 0 + 1 + ... + 5 for the cube faces, 4 for the spot light and 2 + 3 for the cascades.
+        let store = world.resource::<DiagnosticsStore>();
+        let count = store.get_measurement(&path).unwrap().value;
+        assert_eq!(count, (15 + 4 + 5) as f64);
+    }
+}
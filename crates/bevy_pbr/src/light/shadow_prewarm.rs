@@ -0,0 +1,123 @@
+use super::*;
+
+/// A resource that reserves room in the shadow map textures for a set of lights before they
+/// become visible, to avoid a hitch on the frame when they first cast shadows.
+///
+/// The shadow map textures are sized for the shadow-casting lights that are currently visible, so
+/// a new shadow-casting light normally causes them to be reallocated. Inserting this resource
+/// during a loading screen sizes the textures for the [`lights`](Self::lights) as well, even while
+/// they aren't visible, so that the allocation happens up front. The textures then stay the same
+/// size when the lights appear.
+///
+/// Only lights with [`shadows_enabled`](PointLight::shadows_enabled) reserve room. Entities that
+/// aren't lights are ignored.
+#[derive(Resource, Clone, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct ShadowPrewarm {
+    /// The lights to reserve room for.
+    pub lights: Vec<Entity>,
+}
+
+/// The number of shadow maps reserved by a [`ShadowPrewarm`], by type of shadow map.
+#[derive(Resource, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ReservedShadowMaps {
+    /// The number of [`PointLight`] cube maps.
+    pub point_light_shadow_maps: usize,
+    /// The number of [`SpotLight`] shadow maps.
+    pub spot_light_shadow_maps: usize,
+    /// The number of [`DirectionalLight`] cascades, summed over all of the lights.
+    pub directional_cascades: usize,
+}
+
+impl ShadowPrewarm {
+    /// Returns the number of shadow maps needed by the shadow-casting lights in
+    /// [`lights`](Self::lights).
+    pub fn reserved_shadow_maps(
+        &self,
+        lights: &Query<(
+            AnyOf<(&PointLight, &SpotLight, &DirectionalLight)>,
+            Option<&CascadeShadowConfig>,
+        )>,
+    ) -> ReservedShadowMaps {
+        let mut reserved = ReservedShadowMaps::default();
+        for ((point_light, spot_light, directional_light), cascade_shadow_config) in
+            lights.iter_many(&self.lights)
+        {
+            if point_light.is_some_and(|light| light.shadows_enabled) {
+                reserved.point_light_shadow_maps += 1;
+            } else if spot_light.is_some_and(|light| light.shadows_enabled) {
+                reserved.spot_light_shadow_maps += 1;
+            } else if directional_light.is_some_and(|light| light.shadows_enabled) {
+                reserved.directional_cascades += cascade_shadow_config
+                    .map_or(1, |config| config.bounds.len())
+                    .min(MAX_CASCADES_PER_LIGHT);
+            }
+        }
+        reserved
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_prewarm_reserves_shadow_maps() {
+        use bevy_ecs::system::RunSystemOnce;
+
+        let mut world = World::new();
+        let point_light = world
+            .spawn(PointLight {
+                shadows_enabled: true,
+                ..Default::default()
+            })
+            .id();
+        let spot_light = world
+            .spawn(SpotLight {
+                shadows_enabled: true,
+                ..Default::default()
+            })
+            .id();
+        let directional_light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                CascadeShadowConfigBuilder {
+                    num_cascades: 3,
+                    ..Default::default()
+                }
+                .build(),
+            ))
+            .id();
+        let unshadowed_light = world.spawn(PointLight::default()).id();
+        let not_a_light = world.spawn_empty().id();
+
+        let shadow_prewarm = ShadowPrewarm {
+            lights: vec![
+                point_light,
+                spot_light,
+                directional_light,
+                unshadowed_light,
+                not_a_light,
+            ],
+        };
+        let reserved = world
+            .run_system_once(
+                move |lights: Query<(
+                    AnyOf<(&PointLight, &SpotLight, &DirectionalLight)>,
+                    Option<&CascadeShadowConfig>,
+                )>| shadow_prewarm.reserved_shadow_maps(&lights),
+            )
+            .unwrap();
+        assert_eq!(
+            reserved,
+            ReservedShadowMaps {
+                point_light_shadow_maps: 1,
+                spot_light_shadow_maps: 1,
+                directional_cascades: 3,
+            }
+        );
+    }
+}
@@ -7,9 +7,21 @@ use super::*;
 /// Behaves like a point light in a perfectly absorbent housing that
 /// shines light only in a given direction. The direction is taken from
 /// the transform, and can be specified with [`Transform::looking_at`](Transform::looking_at).
+///
+/// To project a texture through the light, such as a cookie or gobo that
+/// fakes window blinds or foliage, add a
+/// [`SpotLightTexture`](crate::decal::clustered::SpotLightTexture).
 #[derive(Component, Debug, Clone, Copy, Reflect)]
 #[reflect(Component, Default, Debug, Clone)]
-#[require(Frustum, VisibleMeshEntities, Transform, Visibility, VisibilityClass)]
+#[require(
+    Frustum,
+    VisibleMeshEntities,
+    ShadowFrustaTransform,
+    ShadowMapState,
+    Transform,
+    Visibility,
+    VisibilityClass
+)]
 #[component(on_add = view::add_visibility_class::<LightVisibilityClass>)]
 pub struct SpotLight {
     /// The color of the light.
@@ -89,6 +101,86 @@ pub struct SpotLight {
     /// away.
     pub shadow_normal_bias: f32,
 
+    /// The minimum distance, in world units, that fragments are offset toward the light when
+    /// sampling the shadow map.
+    ///
+    /// On surfaces seen by the light at grazing angles, the normal bias barely moves fragments
+    /// toward the light, so the depth bias alone has to prevent acne there. The
+    /// [`shadow_depth_bias`](Self::shadow_depth_bias) is raised to this value if it's lower. This
+    /// matters most for [`DirectionalLight`](crate::DirectionalLight)s, whose depth bias varies
+    /// per cascade.
+    ///
+    /// By default, this is 0.0, which leaves the depth bias as is.
+    pub min_world_shadow_bias: f32,
+
+    /// The color that shadowed areas are tinted toward, for stylized colored shadows.
+    ///
+    /// In shadow, the light's contribution is multiplied by this color rather than removed
+    /// entirely, blending back to the full contribution as the surface leaves the shadow.
+    ///
+    /// By default, this is black, which makes shadows fully block the light.
+    pub shadow_tint: Color,
+
+    /// Whether this light's shadows fade out along with its
+    /// [`intensity`](Self::intensity), so that dimming the light also softens the
+    /// shadows it casts rather than leaving them at full strength until it turns off.
+    ///
+    /// When enabled, the shadow strength is the ratio of the intensity to
+    /// [`full_shadow_intensity`](Self::full_shadow_intensity), clamped to 1.0. See
+    /// [`shadow_strength`](Self::shadow_strength).
+    ///
+    /// By default, this is false, and shadows are always at full strength.
+    pub fade_shadows_with_intensity: bool,
+
+    /// The intensity at and above which shadows are at full strength, when
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity) is enabled.
+    ///
+    /// By default, this is the default intensity of 1,000,000 lumens.
+    pub full_shadow_intensity: f32,
+
+    /// The radius, in world units, over which shadow edges are blurred when filtering the shadow
+    /// map.
+    ///
+    /// By default, this is [`None`], and the blur radius is a fixed number of shadow map texels,
+    /// so changing the shadow map resolution changes how soft shadows look. Setting this keeps the
+    /// softness the same at any resolution.
+    ///
+    /// This only affects the [`ShadowFilteringMethod::Temporal`] and
+    /// [`ShadowFilteringMethod::Vogel`] filtering methods, and has no effect when soft shadows are
+    /// enabled, since the penumbra is then determined by the light's radius.
+    pub shadow_blur_world_radius: Option<f32>,
+
+    /// Whether this light's shadows are filtered the same way on every frame.
+    ///
+    /// By default, this is `false`, and shadows are filtered with the camera's
+    /// [`ShadowFilteringMethod`](crate::ShadowFilteringMethod). The
+    /// [`Temporal`](crate::ShadowFilteringMethod::Temporal) and
+    /// [`Vogel`](crate::ShadowFilteringMethod::Vogel) methods change their sampling pattern from
+    /// frame to frame, relying on temporal antialiasing to smooth out the noise. Setting this makes
+    /// the light use a fixed pattern instead, such as the
+    /// [`Gaussian`](crate::ShadowFilteringMethod::Gaussian) filter, for crisp shadows that don't
+    /// flicker even when temporal antialiasing is in use.
+    pub stable_shadows: bool,
+
+    /// The distance from the nearest active camera beyond which this light stops casting shadows,
+    /// while still lighting the scene.
+    ///
+    /// This saves the cost of rendering shadow maps for distant lights, whose shadows are less
+    /// noticeable. By default, this is [`None`], so shadows are rendered at any distance.
+    pub shadow_cutoff_distance: Option<f32>,
+
+    /// Whether shadow casters are culled against the light's circular cone, rather than only
+    /// against the pyramid-shaped frustum of its shadow map.
+    ///
+    /// The frustum bounds the cone, so its corners include casters just outside of the lit
+    /// region. Those casters are rendered into the shadow map for nothing, which is most
+    /// noticeable for narrow spot lights. Enabling this skips them, at the cost of an additional
+    /// test for every caster inside the frustum. For an elliptical cone, the test uses the larger
+    /// of the [`outer_angles`](Self::outer_angles).
+    ///
+    /// By default, this is `false`.
+    pub cone_shadow_culling: bool,
+
     /// The distance from the light to the near Z plane in the shadow map.
     ///
     /// Objects closer than this distance to the light won't cast shadows.
@@ -102,8 +194,22 @@ pub struct SpotLight {
     /// `outer_angle` should be < `PI / 2.0`.
     /// `PI / 2.0` defines a hemispherical spot light, but shadows become very blocky as the angle
     /// approaches this limit.
+    ///
+    /// If [`outer_angle_y`](Self::outer_angle_y) is set, this is the angle along the light's X
+    /// axis only.
     pub outer_angle: f32,
 
+    /// Angle defining the distance from the spot light direction to the outer limit of the
+    /// light's cone of effect along its Y axis, for an elliptical cone such as a car headlight.
+    ///
+    /// If this is [`None`], the cone is circular, with `outer_angle` along both axes. Like
+    /// `outer_angle`, this should be < `PI / 2.0`.
+    ///
+    /// The X and Y axes are the axes of the light's shadow map, which are derived from the
+    /// light's direction alone. Rolling the light around its direction doesn't rotate the
+    /// ellipse.
+    pub outer_angle_y: Option<f32>,
+
     /// Angle defining the distance from the spot light direction to the inner limit
     /// of the light's cone of effect.
     /// Light is attenuated from `inner_angle` to `outer_angle` to give a smooth falloff.
@@ -115,6 +221,123 @@ impl SpotLight {
     pub const DEFAULT_SHADOW_DEPTH_BIAS: f32 = 0.02;
     pub const DEFAULT_SHADOW_NORMAL_BIAS: f32 = 1.8;
     pub const DEFAULT_SHADOW_MAP_NEAR_Z: f32 = 0.1;
+
+    /// Returns a [`SpotLight`] with the default settings, and the [`color`](Self::color) of a
+    /// blackbody at the temperature `kelvin`, see
+    /// [`set_color_temperature`](Self::set_color_temperature).
+    ///
+    /// ```
+    /// # use bevy_pbr::SpotLight;
+    /// let stage_light = SpotLight::from_kelvin(5600.0);
+    /// ```
+    pub fn from_kelvin(kelvin: f32) -> Self {
+        let mut light = Self::default();
+        light.set_color_temperature(kelvin);
+        light
+    }
+
+    /// Sets the [`color`](Self::color) to that of a blackbody at the temperature `kelvin`, such as
+    /// about 3200 K for warm tungsten light, or 6500 K for overcast daylight.
+    ///
+    /// The temperature is clamped to 1000 K to 40000 K. The brightest channel of the color is
+    /// always 1.0, so this only changes the hue of the light.
+    pub fn set_color_temperature(&mut self, kelvin: f32) {
+        self.color = color_temperature_to_color(kelvin);
+    }
+
+    /// Returns the strength of this light's shadows, from 0.0 for no shadows to 1.0 for full
+    /// shadows, as described in
+    /// [`fade_shadows_with_intensity`](Self::fade_shadows_with_intensity).
+    pub fn shadow_strength(&self) -> f32 {
+        if self.fade_shadows_with_intensity {
+            intensity_shadow_strength(self.intensity, self.full_shadow_intensity)
+        } else {
+            1.0
+        }
+    }
+
+    /// Returns the outer angles of the light's cone along its X and Y axes.
+    ///
+    /// See [`outer_angle_y`](Self::outer_angle_y).
+    pub fn outer_angles(&self) -> (f32, f32) {
+        (
+            self.outer_angle,
+            self.outer_angle_y.unwrap_or(self.outer_angle),
+        )
+    }
+
+    /// Returns the larger of the outer angles of the light's cone along its X and Y axes, which
+    /// gives a circular cone that contains the whole light.
+    pub fn max_outer_angle(&self) -> f32 {
+        let (outer_angle_x, outer_angle_y) = self.outer_angles();
+        outer_angle_x.max(outer_angle_y)
+    }
+
+    /// Returns the world-space sphere within the range of this light, and the cone that it lights,
+    /// for a light with the given global transform.
+    ///
+    /// Only the intersection of the two is lit. For an elliptical cone, the cone uses the larger
+    /// of the [`outer_angles`](Self::outer_angles). This doesn't account for
+    /// [`ScaleAffectsRange`]; see [`light_affects_aabb`] to test a bounding box against them.
+    pub fn influence_bounds(&self, transform: &GlobalTransform) -> (Sphere, SpotLightCone) {
+        (
+            Sphere {
+                center: transform.translation_vec3a(),
+                radius: self.range,
+            },
+            SpotLightCone::new(transform, self.max_outer_angle()),
+        )
+    }
+
+    /// Returns the [`PointLight::intensity`] that emits the same total amount of light as this
+    /// spot light, for swapping a distant spot light for a cheaper point light as a level of
+    /// detail.
+    ///
+    /// A spot light only emits light within its cone of half-angle `outer_angle`, which covers a
+    /// solid angle of `2π(1 - cos(outer_angle))` steradians out of the `4π` of a full sphere. The
+    /// returned intensity is `intensity` scaled by that fraction, so a narrow spot light converts to
+    /// a dimmer point light than a wide one.
+    ///
+    /// The swap is least noticeable once the camera is far enough away that the light's cone no
+    /// longer stands out on screen. A reasonable starting point is to swap once the camera is
+    /// farther from the light than twice its [`range`](Self::range).
+    pub fn equivalent_point_intensity(&self) -> f32 {
+        self.intensity * self.point_intensity_scale()
+    }
+
+    /// Returns the fraction of a full sphere covered by this light's cone, which scales its
+    /// intensity to the equivalent [`PointLight::intensity`].
+    fn point_intensity_scale(&self) -> f32 {
+        (1.0 - ops::cos(self.outer_angle)) * 0.5
+    }
+
+    /// Returns a [`PointLight`] that emits the same total amount of light as this spot light, with
+    /// the intensity given by [`equivalent_point_intensity`](Self::equivalent_point_intensity).
+    /// This is the inverse of [`PointLight::into_spot`].
+    ///
+    /// The color, range, radius and shadow settings carry over, except for the shadow biases,
+    /// which are tuned separately for each type of light and so take the point light defaults.
+    pub fn into_point(self) -> PointLight {
+        let intensity_scale = self.point_intensity_scale();
+        PointLight {
+            color: self.color,
+            intensity: self.equivalent_point_intensity(),
+            range: self.range,
+            radius: self.radius,
+            shadows_enabled: self.shadows_enabled,
+            #[cfg(feature = "experimental_pbr_pcss")]
+            soft_shadows_enabled: self.soft_shadows_enabled,
+            affects_lightmapped_mesh_diffuse: self.affects_lightmapped_mesh_diffuse,
+            shadow_tint: self.shadow_tint,
+            fade_shadows_with_intensity: self.fade_shadows_with_intensity,
+            full_shadow_intensity: self.full_shadow_intensity * intensity_scale,
+            shadow_blur_world_radius: self.shadow_blur_world_radius,
+            stable_shadows: self.stable_shadows,
+            shadow_cutoff_distance: self.shadow_cutoff_distance,
+            shadow_map_near_z: self.shadow_map_near_z,
+            ..Default::default()
+        }
+    }
 }
 
 impl Default for SpotLight {
@@ -132,11 +355,89 @@ impl Default for SpotLight {
             affects_lightmapped_mesh_diffuse: true,
             shadow_depth_bias: Self::DEFAULT_SHADOW_DEPTH_BIAS,
             shadow_normal_bias: Self::DEFAULT_SHADOW_NORMAL_BIAS,
+            min_world_shadow_bias: 0.0,
+            shadow_tint: Color::BLACK,
+            fade_shadows_with_intensity: false,
+            full_shadow_intensity: 1_000_000.0,
+            shadow_blur_world_radius: None,
+            stable_shadows: false,
+            shadow_cutoff_distance: None,
+            cone_shadow_culling: false,
             shadow_map_near_z: Self::DEFAULT_SHADOW_MAP_NEAR_Z,
             inner_angle: 0.0,
             outer_angle: core::f32::consts::FRAC_PI_4,
+            outer_angle_y: None,
             #[cfg(feature = "experimental_pbr_pcss")]
             soft_shadows_enabled: false,
         }
     }
 }
+
+/// The circular cone of a [`SpotLight`] in world space, for culling.
+///
+/// See [`SpotLight::influence_bounds`].
+#[derive(Clone, Copy, Debug)]
+pub struct SpotLightCone {
+    apex: Vec3A,
+    direction: Vec3A,
+    sin_angle: f32,
+    cos_angle: f32,
+}
+
+impl SpotLightCone {
+    /// Returns the cone of a spot light with the given transform and outer angle.
+    pub fn new(transform: &GlobalTransform, outer_angle: f32) -> Self {
+        let (sin_angle, cos_angle) = ops::sin_cos(outer_angle);
+        Self {
+            apex: transform.translation_vec3a(),
+            direction: Vec3A::from(*transform.forward()),
+            sin_angle,
+            cos_angle,
+        }
+    }
+
+    /// Returns false if an oriented bounding box lies entirely outside of the cone.
+    ///
+    /// The box is tested against the plane tangent to the cone along the side nearest to the
+    /// box's center, so boxes that straddle the apex may be kept even though they're outside.
+    pub fn intersects_obb(&self, aabb: &Aabb, world_from_local: &Affine3A) -> bool {
+        let to_center = world_from_local.transform_point3a(aabb.center) - self.apex;
+        let along_axis = to_center.dot(self.direction);
+        // If the center is on the axis of the cone, any side of the cone is nearest.
+        let away_from_axis = (to_center - self.direction * along_axis)
+            .try_normalize()
+            .unwrap_or_else(|| self.direction.any_orthonormal_vector());
+
+        // The cone lies entirely behind this plane.
+        let normal = away_from_axis * self.cos_angle - self.direction * self.sin_angle;
+        to_center.dot(normal) <= aabb.relative_radius(&normal, &world_from_local.matrix3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::f32::consts::{FRAC_PI_2, FRAC_PI_3, FRAC_PI_4, PI};
+
+    #[test]
+    fn equivalent_point_intensity_scales_by_cone_solid_angle() {
+        let point_intensity = |outer_angle| {
+            SpotLight {
+                intensity: 1000.0,
+                outer_angle,
+                ..Default::default()
+            }
+            .equivalent_point_intensity()
+        };
+
+        // A cone covering the full sphere emits as much light as a point light, and a cone
+        // covering a hemisphere emits half as much.
+        assert!((point_intensity(PI) - 1000.0).abs() < 1e-3);
+        assert!((point_intensity(FRAC_PI_2) - 500.0).abs() < 1e-3);
+        // A cone with a half-angle of 60° covers 2π(1 - cos(60°)) = π steradians, a quarter of
+        // the sphere.
+        assert!((point_intensity(FRAC_PI_3) - 250.0).abs() < 1e-3);
+        // A 90° cone has a half-angle of 45°, and covers (1 - cos(45°)) / 2 ≈ 14.6% of the sphere.
+        assert!((point_intensity(FRAC_PI_4) - 146.446_6).abs() < 1e-3);
+    }
+}
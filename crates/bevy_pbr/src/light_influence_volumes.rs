@@ -0,0 +1,303 @@
+//! Draws the influence of lights as translucent volumes, to help lay out
+//! lights in a level.
+
+use bevy_app::{App, Plugin, PostUpdate};
+use bevy_asset::{Assets, Handle};
+use bevy_color::{Color, ColorToComponents as _, LinearRgba};
+use bevy_ecs::{
+    change_detection::{DetectChanges, DetectChangesMut, Ref},
+    component::Component,
+    entity::{Entity, EntityHashMap},
+    query::{AnyOf, Has},
+    reflect::ReflectResource,
+    resource::Resource,
+    schedule::{common_conditions::*, IntoScheduleConfigs as _, SystemCondition as _},
+    system::{Commands, Local, Query, Res, ResMut},
+};
+use bevy_math::{
+    ops,
+    primitives::{Cone, Cylinder, Sphere},
+    Quat, Vec3,
+};
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+use bevy_render::{
+    alpha::AlphaMode,
+    mesh::{Mesh, Mesh3d},
+};
+use bevy_transform::{
+    components::{GlobalTransform, Transform},
+    TransformSystems,
+};
+
+use crate::{
+    light_consts, scaled_light_range, DirectionalLight, LightKind, MeshMaterial3d, NotShadowCaster,
+    NotShadowReceiver, PointLight, ScaleAffectsRange, SpotLight, StandardMaterial,
+};
+
+/// Adds support for drawing the influence of lights as translucent volumes.
+///
+/// The volumes are controlled by the [`DrawLightInfluenceVolumes`] resource.
+/// When that resource is absent, nothing is drawn.
+pub struct LightInfluenceVolumesPlugin;
+
+/// Insert this resource to draw the influence of every light as a translucent
+/// volume, and remove it to hide the volumes again.
+///
+/// Point lights are drawn as spheres whose radius is their
+/// [`range`](PointLight::range), spot lights as cones reaching out to their
+/// [`range`](SpotLight::range) at their outer angles, and directional lights
+/// as arrows pointing in the direction they shine in. Point lights are drawn in
+/// yellow, spot lights in cyan and directional lights in orange, and brighter
+/// lights are drawn more opaque.
+///
+/// This complements the wireframe light gizmos with solid volumes, which make
+/// it easier to see where the influence of lights overlaps. The volumes are
+/// regular [`Mesh3d`] entities with a [`LightInfluenceVolume`] component, so
+/// they show up in every camera, and don't cast or receive shadows.
+#[derive(Resource, Clone, Copy, Debug, Default, Reflect)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct DrawLightInfluenceVolumes;
+
+/// The influence volume of a light, drawn while the
+/// [`DrawLightInfluenceVolumes`] resource exists.
+#[derive(Component, Clone, Copy, Debug)]
+pub struct LightInfluenceVolume {
+    /// The light whose influence this volume shows.
+    pub light: Entity,
+    /// The type of the light.
+    pub kind: LightKind,
+}
+
+/// The length of the arrows drawn for directional lights.
+const DIRECTIONAL_LIGHT_ARROW_LENGTH: f32 = 2.0;
+
+/// The meshes of the influence volumes, shared between all lights of a type.
+pub struct LightInfluenceVolumeMeshes {
+    /// A sphere of radius 1.
+    sphere: Handle<Mesh>,
+    /// A cone with its tip at the origin and its base of radius 1 at -Z.
+    cone: Handle<Mesh>,
+    /// An arrow of length 1 from the origin toward -Z.
+    arrow: Handle<Mesh>,
+}
+
+impl LightInfluenceVolumeMeshes {
+    fn new(meshes: &mut Assets<Mesh>) -> Self {
+        // The primitives point along +Y, so they're turned to point along -Z.
+        let cone = Mesh::from(Cone::new(1.0, 1.0))
+            .translated_by(Vec3::Y * -0.5)
+            .rotated_by(Quat::from_rotation_x(core::f32::consts::FRAC_PI_2));
+        let mut arrow = Mesh::from(Cylinder::new(0.02, 0.75)).translated_by(Vec3::Y * 0.375);
+        arrow
+            .merge(&Mesh::from(Cone::new(0.08, 0.25)).translated_by(Vec3::Y * 0.875))
+            .expect("arrow meshes have the same attributes");
+        let arrow = arrow.rotated_by(Quat::from_rotation_x(-core::f32::consts::FRAC_PI_2));
+
+        Self {
+            sphere: meshes.add(Sphere::new(1.0)),
+            cone: meshes.add(cone),
+            arrow: meshes.add(arrow),
+        }
+    }
+}
+
+impl Plugin for LightInfluenceVolumesPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_type::<DrawLightInfluenceVolumes>()
+            .add_systems(
+                PostUpdate,
+                update_light_influence_volumes
+                    .after(TransformSystems::Propagate)
+                    .run_if(
+                        resource_exists::<DrawLightInfluenceVolumes>
+                            .or(any_with_component::<LightInfluenceVolume>),
+                    ),
+            );
+    }
+}
+
+/// Spawns, updates and despawns the [`LightInfluenceVolume`]s of lights,
+/// according to the [`DrawLightInfluenceVolumes`] resource.
+pub fn update_light_influence_volumes(
+    mut commands: Commands,
+    draw_volumes: Option<Res<DrawLightInfluenceVolumes>>,
+    lights: Query<(
+        Entity,
+        AnyOf<(Ref<PointLight>, Ref<SpotLight>, Ref<DirectionalLight>)>,
+        &GlobalTransform,
+        Has<ScaleAffectsRange>,
+    )>,
+    mut volumes: Query<(
+        Entity,
+        &LightInfluenceVolume,
+        &mut Transform,
+        &MeshMaterial3d<StandardMaterial>,
+    )>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut volume_meshes: Local<Option<LightInfluenceVolumeMeshes>>,
+) {
+    let mut light_volumes = EntityHashMap::default();
+    for (volume_entity, volume, _, _) in &volumes {
+        let current_kind = lights
+            .get(volume.light)
+            .ok()
+            .map(|(_, light, _, _)| light_kind(&light));
+        if draw_volumes.is_none() || current_kind != Some(volume.kind) {
+            commands.entity(volume_entity).despawn();
+        } else {
+            light_volumes.insert(volume.light, volume_entity);
+        }
+    }
+    if draw_volumes.is_none() {
+        return;
+    }
+
+    let volume_meshes =
+        volume_meshes.get_or_insert_with(|| LightInfluenceVolumeMeshes::new(&mut meshes));
+
+    for (entity, light, global_transform, scale_affects_range) in &lights {
+        let kind = light_kind(&light);
+        let (scale, color, intensity, light_changed) = match light {
+            (Some(point_light), _, _) => (
+                Vec3::splat(scaled_light_range(
+                    point_light.range,
+                    global_transform,
+                    scale_affects_range,
+                )),
+                point_light.color,
+                point_light.intensity,
+                point_light.is_changed(),
+            ),
+            (_, Some(spot_light), _) => (
+                spot_light_volume_scale(
+                    &spot_light,
+                    scaled_light_range(spot_light.range, global_transform, scale_affects_range),
+                ),
+                spot_light.color,
+                spot_light.intensity,
+                spot_light.is_changed(),
+            ),
+            (_, _, Some(directional_light)) => (
+                Vec3::splat(DIRECTIONAL_LIGHT_ARROW_LENGTH),
+                directional_light.color,
+                directional_light.illuminance,
+                directional_light.is_changed(),
+            ),
+            (None, None, None) => continue,
+        };
+
+        let (_, rotation, translation) = global_transform.to_scale_rotation_translation();
+        let transform = Transform {
+            translation,
+            rotation,
+            scale,
+        };
+        let base_color = light_influence_volume_color(kind, color, intensity);
+
+        let Some(&volume_entity) = light_volumes.get(&entity) else {
+            let mesh = match kind {
+                LightKind::Point => volume_meshes.sphere.clone(),
+                LightKind::Spot => volume_meshes.cone.clone(),
+                LightKind::Directional => volume_meshes.arrow.clone(),
+            };
+            let material = materials.add(StandardMaterial {
+                base_color,
+                alpha_mode: AlphaMode::Blend,
+                unlit: true,
+                double_sided: true,
+                cull_mode: None,
+                ..Default::default()
+            });
+            commands.spawn((
+                LightInfluenceVolume {
+                    light: entity,
+                    kind,
+                },
+                Mesh3d(mesh),
+                MeshMaterial3d(material),
+                transform,
+                NotShadowCaster,
+                NotShadowReceiver,
+            ));
+            continue;
+        };
+
+        let Ok((_, _, mut volume_transform, material)) = volumes.get_mut(volume_entity) else {
+            continue;
+        };
+        volume_transform.set_if_neq(transform);
+        if light_changed && let Some(material) = materials.get_mut(&material.0) {
+            material.base_color = base_color;
+        }
+    }
+}
+
+/// Returns the type of a light from the components of a
+/// [`update_light_influence_volumes`] query.
+fn light_kind<P, S, D>(light: &(Option<P>, Option<S>, Option<D>)) -> LightKind {
+    match light {
+        (Some(_), _, _) => LightKind::Point,
+        (_, Some(_), _) => LightKind::Spot,
+        _ => LightKind::Directional,
+    }
+}
+
+/// Returns the scale of the unit cone mesh that covers the influence of a spot
+/// light with the given range: the radii of the cone's base along the light's
+/// X and Y axes, and its length along Z.
+fn spot_light_volume_scale(spot_light: &SpotLight, range: f32) -> Vec3 {
+    // Cones wider than a hemisphere can't be drawn, so they're clamped just
+    // short of one.
+    let max_angle = core::f32::consts::FRAC_PI_2 - 0.01;
+    let (outer_angle_x, outer_angle_y) = spot_light.outer_angles();
+    Vec3::new(
+        ops::tan(outer_angle_x.min(max_angle)) * range,
+        ops::tan(outer_angle_y.min(max_angle)) * range,
+        range,
+    )
+}
+
+/// Returns the color of the influence volume of a light of the given type,
+/// color and brightness.
+///
+/// The volume is tinted by the type of the light, and lights that are much
+/// brighter than the default for their type are drawn up to three times as
+/// opaque as very dim ones.
+fn light_influence_volume_color(kind: LightKind, color: Color, intensity: f32) -> Color {
+    let (tint, reference_intensity) = match kind {
+        LightKind::Point => (LinearRgba::rgb(1.0, 0.85, 0.2), 1_000_000.0),
+        LightKind::Spot => (LinearRgba::rgb(0.2, 0.85, 1.0), 1_000_000.0),
+        LightKind::Directional => (
+            LinearRgba::rgb(1.0, 0.5, 0.1),
+            light_consts::lux::AMBIENT_DAYLIGHT,
+        ),
+    };
+    let brightness = 1.0 - ops::exp(-intensity.max(0.0) / reference_intensity);
+    let color = LinearRgba::from(color);
+    let mixed = (tint.to_vec3() * 0.75 + color.to_vec3() * 0.25).extend(0.1 + 0.2 * brightness);
+    Color::LinearRgba(LinearRgba::from_vec4(mixed))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spot_light_volumes_match_cone() {
+        let spot_light = SpotLight {
+            outer_angle: core::f32::consts::FRAC_PI_4,
+            ..Default::default()
+        };
+        let scale = spot_light_volume_scale(&spot_light, 10.0);
+        assert!((scale - Vec3::new(10.0, 10.0, 10.0)).abs().max_element() < 1e-4);
+
+        let elliptical = SpotLight {
+            outer_angle: core::f32::consts::FRAC_PI_4,
+            outer_angle_y: Some(ops::atan(0.5)),
+            ..Default::default()
+        };
+        let scale = spot_light_volume_scale(&elliptical, 4.0);
+        assert!((scale - Vec3::new(4.0, 2.0, 4.0)).abs().max_element() < 1e-4);
+    }
+}
@@ -1,9 +1,9 @@
 use super::{meshlet_mesh_manager::MeshletMeshManager, MeshletMesh, MeshletMesh3d};
 use crate::DUMMY_MESH_MATERIAL;
 use crate::{
-    meshlet::asset::MeshletAabb, MaterialBindingId, MeshFlags, MeshTransforms, MeshUniform,
-    NotShadowCaster, NotShadowReceiver, PreviousGlobalTransform, RenderMaterialBindings,
-    RenderMaterialInstances,
+    meshlet::asset::MeshletAabb, pack_shadow_layers, MaterialBindingId, MeshFlags, MeshTransforms,
+    MeshUniform, NoAmbientLight, NotShadowCaster, NotShadowReceiver, PreviousGlobalTransform,
+    RenderMaterialBindings, RenderMaterialInstances, ShadowCaster,
 };
 use bevy_asset::{AssetEvent, AssetServer, Assets, UntypedAssetId};
 use bevy_ecs::{
@@ -98,6 +98,7 @@ impl InstanceManager {
         render_material_bindings: &RenderMaterialBindings,
         not_shadow_receiver: bool,
         not_shadow_caster: bool,
+        no_ambient_light: bool,
     ) {
         // Build a MeshUniform for the instance
         let transform = transform.affine();
@@ -107,9 +108,16 @@ impl InstanceManager {
         } else {
             MeshFlags::SHADOW_RECEIVER
         };
+        if no_ambient_light {
+            flags |= MeshFlags::NO_AMBIENT_LIGHT;
+        }
         if transform.matrix3.determinant().is_sign_positive() {
             flags |= MeshFlags::SIGN_DETERMINANT_MODEL_3X3;
         }
+        let receiver_layers = pack_shadow_layers(render_layers.unwrap_or_default());
+        flags |= MeshFlags::from_bits_retain(
+            u32::from(receiver_layers) << MeshFlags::RECEIVER_LAYERS_SHIFT,
+        );
         let transforms = MeshTransforms {
             world_from_local: (&transform).into(),
             previous_world_from_local: (&previous_transform).into(),
@@ -205,6 +213,8 @@ pub fn extract_meshlet_mesh_entities(
                     Option<&RenderLayers>,
                     Has<NotShadowReceiver>,
                     Has<NotShadowCaster>,
+                    Option<&ShadowCaster>,
+                    Has<NoAmbientLight>,
                 )>,
                 Res<AssetServer>,
                 ResMut<Assets<MeshletMesh>>,
@@ -242,6 +252,8 @@ pub fn extract_meshlet_mesh_entities(
         render_layers,
         not_shadow_receiver,
         not_shadow_caster,
+        shadow_caster,
+        no_ambient_light,
     ) in &instances_query
     {
         // Skip instances with an unloaded MeshletMesh asset
@@ -268,7 +280,8 @@ pub fn extract_meshlet_mesh_entities(
             &mesh_material_ids,
             &render_material_bindings,
             not_shadow_receiver,
-            not_shadow_caster,
+            not_shadow_caster || shadow_caster.is_some_and(|shadow_caster| !shadow_caster.enabled),
+            no_ambient_light,
         );
     }
 }
@@ -121,6 +121,9 @@ pub fn prepare_material_meshlet_meshes_main_opaque_pass(
             ShadowFilteringMethod::Temporal => {
                 view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_TEMPORAL;
             }
+            ShadowFilteringMethod::Vogel { .. } => {
+                view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_VOGEL;
+            }
         }
 
         if !view.hdr {
@@ -305,6 +305,20 @@ pub struct StandardMaterial {
     #[cfg(feature = "pbr_transmission_textures")]
     pub thickness_texture: Option<Handle<Image>>,
 
+    /// The number of shadow map samples taken through the [`StandardMaterial::thickness`] of the
+    /// material for the shadows received by its diffuse transmission lobe.
+    ///
+    /// With a single sample (the default), the transmitted shadow is only sampled at the back of
+    /// the volume, which makes thick translucent objects look uniformly shadowed. More samples are
+    /// spread evenly through the thickness and averaged, so that the transmitted shadow accounts
+    /// for occlusion inside the volume, at the cost of one more shadow lookup per light for each
+    /// sample.
+    ///
+    /// **Note:** This only has an effect on meshes with the [`TransmittedShadowReceiver`]
+    /// component and a [`StandardMaterial::diffuse_transmission`] above `0.0`. Values below 1
+    /// are treated as 1.
+    pub transmission_shadow_samples: u32,
+
     /// The [index of refraction](https://en.wikipedia.org/wiki/Refractive_index) of the material.
     ///
     /// Defaults to 1.5.
@@ -856,6 +870,7 @@ impl Default for StandardMaterial {
             thickness_channel: UvChannel::Uv0,
             #[cfg(feature = "pbr_transmission_textures")]
             thickness_texture: None,
+            transmission_shadow_samples: 1,
             ior: 1.5,
             attenuation_color: Color::WHITE,
             attenuation_distance: f32::INFINITY,
@@ -1032,6 +1047,8 @@ pub struct StandardMaterialUniform {
     pub max_relief_mapping_search_steps: u32,
     /// ID for specifying which deferred lighting pass should be used for rendering this material, if any.
     pub deferred_lighting_pass_id: u32,
+    /// The number of transmitted shadow samples taken through the thickness of the material
+    pub transmission_shadow_samples: u32,
 }
 
 impl AsBindGroupShaderType<StandardMaterialUniform> for StandardMaterial {
@@ -1178,6 +1195,7 @@ impl AsBindGroupShaderType<StandardMaterialUniform> for StandardMaterial {
             lightmap_exposure: self.lightmap_exposure,
             max_relief_mapping_search_steps: self.parallax_mapping_method.max_steps(),
             deferred_lighting_pass_id: self.deferred_lighting_pass_id as u32,
+            transmission_shadow_samples: self.transmission_shadow_samples.max(1),
             uv_transform: self.uv_transform.into(),
         }
     }
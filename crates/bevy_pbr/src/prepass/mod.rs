@@ -8,7 +8,7 @@ use crate::{
     MaterialProperties, MeshLayouts, MeshPipeline, MeshPipelineKey, OpaqueRendererMethod,
     PreparedMaterial, PrepassDrawFunction, PrepassFragmentShader, PrepassVertexShader,
     RenderLightmaps, RenderMaterialInstances, RenderMeshInstanceFlags, RenderMeshInstances,
-    RenderPhaseType, SetMaterialBindGroup, SetMeshBindGroup, ShadowView,
+    RenderPhaseType, SetMaterialBindGroup, SetMeshBindGroup, ShadowDepthFormat, ShadowView,
 };
 use bevy_app::{App, Plugin, PreUpdate};
 use bevy_render::{
@@ -451,8 +451,18 @@ impl PrepassPipelineInternal {
         }
         // For directional light shadow map views, use unclipped depth via either the native GPU feature,
         // or emulated by setting depth in the fragment shader for GPUs that don't support it natively.
-        let emulate_unclipped_depth = mesh_key.contains(MeshPipelineKey::UNCLIPPED_DEPTH_ORTHO)
-            && !self.depth_clip_control_supported;
+        // Logarithmic shadow depth is written from the fragment shader too, so it takes the same
+        // path as the emulation.
+        let logarithmic_shadow_depth = mesh_key.contains(MeshPipelineKey::LOGARITHMIC_SHADOW_DEPTH);
+        let emulate_unclipped_depth = (mesh_key.contains(MeshPipelineKey::UNCLIPPED_DEPTH_ORTHO)
+            && !self.depth_clip_control_supported)
+            || logarithmic_shadow_depth;
+        if logarithmic_shadow_depth {
+            shader_defs.push("LOGARITHMIC_SHADOW_DEPTH".into());
+        }
+        if mesh_key.contains(MeshPipelineKey::DUAL_PARABOLOID_SHADOW) {
+            shader_defs.push("DUAL_PARABOLOID_SHADOW".into());
+        }
         if emulate_unclipped_depth {
             shader_defs.push("UNCLIPPED_DEPTH_ORTHO_EMULATION".into());
             // PERF: This line forces the "prepass fragment shader" to always run in
@@ -621,7 +631,11 @@ impl PrepassPipelineInternal {
                 conservative: false,
             },
             depth_stencil: Some(DepthStencilState {
-                format: CORE_3D_DEPTH_FORMAT,
+                format: if mesh_key.contains(MeshPipelineKey::SHADOW_DEPTH_16) {
+                    ShadowDepthFormat::Depth16.texture_format()
+                } else {
+                    CORE_3D_DEPTH_FORMAT
+                },
                 depth_write_enabled: true,
                 depth_compare: CompareFunction::GreaterEqual,
                 stencil: StencilState {
@@ -6,6 +6,7 @@
     skinning,
     morph,
     mesh_view_bindings::view,
+    utils,
     view_transformations::position_world_to_clip,
 }
 
@@ -13,6 +14,20 @@
 #import bevy_pbr::rgb9e5
 #endif
 
+#ifdef DUAL_PARABOLOID_SHADOW
+// Projects a world position onto the paraboloid covering the hemisphere in front of a point light
+// shadow view, storing the reverse-Z depth of the radial distance to the light. Positions behind
+// the light get a negative depth so that they're clipped.
+fn position_world_to_paraboloid_clip(world_pos: vec3<f32>) -> vec4<f32> {
+    let view_pos = (view.view_from_world * vec4(world_pos, 1.0)).xyz;
+    let distance = max(length(view_pos), 1e-6);
+    let direction = view_pos / distance;
+    let near = view.clip_from_view[3][2];
+    let depth = select(near / distance, -1.0, direction.z > 0.0);
+    return vec4(direction.xy / (1.0 - direction.z), depth, 1.0);
+}
+#endif  // DUAL_PARABOLOID_SHADOW
+
 #ifdef MORPH_TARGETS
 fn morph_vertex(vertex_in: Vertex) -> Vertex {
     var vertex = vertex_in;
@@ -81,7 +96,11 @@ fn vertex(vertex_no_morph: Vertex) -> VertexOutput {
 #endif // SKINNED
 
     out.world_position = mesh_functions::mesh_position_local_to_world(world_from_local, vec4<f32>(vertex.position, 1.0));
+#ifdef DUAL_PARABOLOID_SHADOW
+    out.position = position_world_to_paraboloid_clip(out.world_position.xyz);
+#else   // DUAL_PARABOLOID_SHADOW
     out.position = position_world_to_clip(out.world_position.xyz);
+#endif  // DUAL_PARABOLOID_SHADOW
 #ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
     out.unclipped_depth = out.position.z;
     out.position.z = min(out.position.z, 1.0); // Clamp depth to avoid clipping
@@ -188,7 +207,11 @@ fn fragment(in: VertexOutput) -> FragmentOutput {
 #endif
 
 #ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+#ifdef LOGARITHMIC_SHADOW_DEPTH
+    out.frag_depth = utils::logarithmic_shadow_depth(in.unclipped_depth);
+#else   // LOGARITHMIC_SHADOW_DEPTH
     out.frag_depth = in.unclipped_depth;
+#endif  // LOGARITHMIC_SHADOW_DEPTH
 #endif // UNCLIPPED_DEPTH_ORTHO_EMULATION
 
 #ifdef MOTION_VECTOR_PREPASS
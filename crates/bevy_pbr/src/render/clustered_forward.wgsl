@@ -14,7 +14,7 @@
 //
 // These offsets must be monotonically nondecreasing. That is, indices are
 // always sorted into the following order: point lights, spot lights, reflection
-// probes, irradiance volumes.
+// probes, irradiance volumes, decals, local ambient volumes.
 struct ClusterableObjectIndexRanges {
     // The offset of the index of the first point light.
     first_point_light_index_offset: u32,
@@ -28,6 +28,9 @@ struct ClusterableObjectIndexRanges {
     // terminates the list of reflection probes.
     first_irradiance_volume_index_offset: u32,
     first_decal_offset: u32,
+    // The offset of the index of the first local ambient volume, which also
+    // terminates the list of decals.
+    first_local_ambient_offset: u32,
     // One past the offset of the index of the final clusterable object for this
     // cluster.
     last_clusterable_object_index_offset: u32,
@@ -83,13 +86,15 @@ fn unpack_clusterable_object_index_ranges(cluster_index: u32) -> ClusterableObje
     let reflection_probe_offset = spot_light_offset + offset_and_counts_a.z;
     let irradiance_volume_offset = reflection_probe_offset + offset_and_counts_a.w;
     let decal_offset = irradiance_volume_offset + offset_and_counts_b.x;
-    let last_clusterable_offset = decal_offset + offset_and_counts_b.y;
+    let local_ambient_offset = decal_offset + offset_and_counts_b.y;
+    let last_clusterable_offset = local_ambient_offset + offset_and_counts_b.z;
     return ClusterableObjectIndexRanges(
         point_light_offset,
         spot_light_offset,
         reflection_probe_offset,
         irradiance_volume_offset,
         decal_offset,
+        local_ambient_offset,
         last_clusterable_offset
     );
 
@@ -104,16 +109,17 @@ fn unpack_clusterable_object_index_ranges(cluster_index: u32) -> ClusterableObje
         raw_offset_and_counts                                & ((1u << CLUSTER_COUNT_SIZE) - 1u),
     );
 
-    // We don't cluster reflection probes or irradiance volumes on this
-    // platform, as there's no room in the UBO. Thus, those offset ranges
-    // (corresponding to `offset_d` and `offset_e` above) are empty and are
-    // simply copies of `offset_c`.
+    // We don't cluster reflection probes, irradiance volumes, or local ambient
+    // volumes on this platform, as there's no room in the UBO. Thus, those
+    // offset ranges are empty and are simply copies of `offset_c`.
 
     let offset_a = offset_and_counts.x;
     let offset_b = offset_a + offset_and_counts.y;
     let offset_c = offset_b + offset_and_counts.z;
 
-    return ClusterableObjectIndexRanges(offset_a, offset_b, offset_c, offset_c, offset_c, offset_c);
+    return ClusterableObjectIndexRanges(
+        offset_a, offset_b, offset_c, offset_c, offset_c, offset_c, offset_c
+    );
 
 #endif  // AVAILABLE_STORAGE_BUFFER_BINDINGS >= 3
 }
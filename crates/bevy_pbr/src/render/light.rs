@@ -1,9 +1,10 @@
 use self::assign::ClusterableObjectType;
 use crate::*;
-use bevy_asset::UntypedAssetId;
-use bevy_color::ColorToComponents;
-use bevy_core_pipeline::core_3d::{Camera3d, CORE_3D_DEPTH_FORMAT};
+use bevy_asset::{AssetId, UntypedAssetId};
+use bevy_color::{Alpha, ColorToComponents};
+use bevy_core_pipeline::core_3d::Camera3d;
 use bevy_derive::{Deref, DerefMut};
+use bevy_diagnostic::FrameCount;
 use bevy_ecs::component::Tick;
 use bevy_ecs::system::SystemChangeTick;
 use bevy_ecs::{
@@ -11,6 +12,7 @@ use bevy_ecs::{
     prelude::*,
     system::lifetimeless::Read,
 };
+use bevy_image::Image;
 use bevy_math::{ops, Mat4, UVec4, Vec2, Vec3, Vec3Swizzles, Vec4, Vec4Swizzles};
 use bevy_platform::collections::{HashMap, HashSet};
 use bevy_platform::hash::FixedHasher;
@@ -21,7 +23,7 @@ use bevy_render::experimental::occlusion_culling::{
 use bevy_render::sync_world::MainEntityHashMap;
 use bevy_render::{
     batching::gpu_preprocessing::{GpuPreprocessingMode, GpuPreprocessingSupport},
-    camera::SortedCameras,
+    camera::{Exposure, ExtractedCamera, SortedCameras},
     mesh::allocator::MeshAllocator,
     view::{NoIndirectDrawing, RetainedViewEntity},
 };
@@ -61,8 +63,21 @@ pub struct ExtractedPointLight {
     pub shadows_enabled: bool,
     pub shadow_depth_bias: f32,
     pub shadow_normal_bias: f32,
+    /// The shadow tint, with the alpha replaced by the light's shadow strength.
+    pub shadow_tint: LinearRgba,
+    pub shadow_blur_world_radius: Option<f32>,
+    pub stable_shadows: bool,
     pub shadow_map_near_z: f32,
-    pub spot_light_angles: Option<(f32, f32)>,
+    /// Whether the shadow map is rendered with [`PointShadowTechnique::DualParaboloid`].
+    pub dual_paraboloid_shadows: bool,
+    /// The cube map that the shadow map is copied from instead of being rendered, see
+    /// [`BakedPointShadow`].
+    pub baked_shadow_map: Option<AssetId<Image>>,
+    /// The first 8 render layers that receive the light's shadows as a bitmask, see
+    /// [`ShadowLayers`].
+    pub shadow_layers: u8,
+    /// The inner angle and the outer angles along the X and Y axes of a spot light's cone.
+    pub spot_light_angles: Option<(f32, f32, f32)>,
     pub volumetric: bool,
     pub soft_shadows_enabled: bool,
     /// whether this point light contributes diffuse light to lightmapped meshes
@@ -81,10 +96,24 @@ pub struct ExtractedDirectionalLight {
     pub affects_lightmapped_mesh_diffuse: bool,
     pub shadow_depth_bias: f32,
     pub shadow_normal_bias: f32,
+    /// The world-space floor of the depth bias of each cascade, see
+    /// [`DirectionalLight::min_world_shadow_bias`].
+    pub min_world_shadow_bias: f32,
+    /// The shadow tint, with the alpha replaced by the light's shadow strength.
+    pub shadow_tint: LinearRgba,
+    pub shadow_blur_world_radius: Option<f32>,
+    pub stable_shadows: bool,
+    pub shadow_contact_darkening: f32,
     pub cascade_shadow_config: CascadeShadowConfig,
+    /// Whether the shadow maps should be rendered this frame, or reused from a previous frame
+    /// because of [`TemporalShadowUpdate`].
+    pub update_shadow_map: bool,
     pub cascades: EntityHashMap<Vec<Cascade>>,
     pub frusta: EntityHashMap<Vec<Frustum>>,
     pub render_layers: RenderLayers,
+    /// The first 8 render layers that receive the light's shadows as a bitmask, see
+    /// [`ShadowLayers`].
+    pub shadow_layers: u8,
     pub soft_shadow_size: Option<f32>,
     /// True if this light is using two-phase occlusion culling.
     pub occlusion_culling: bool,
@@ -98,16 +127,54 @@ bitflags::bitflags! {
         const SPOT_LIGHT_Y_NEGATIVE             = 1 << 1;
         const VOLUMETRIC                        = 1 << 2;
         const AFFECTS_LIGHTMAPPED_MESH_DIFFUSE  = 1 << 3;
+        const STABLE_SHADOWS                    = 1 << 4;
+        const DUAL_PARABOLOID_SHADOWS           = 1 << 5;
+        // Bits 8 to 15 hold the shadow layers, see `SHADOW_LAYERS_SHIFT`.
+        // The upper 16 bits hold the aspect ratio of elliptical spot lights, see
+        // `SPOT_LIGHT_ASPECT_RATIO_SHIFT`.
         const NONE                              = 0;
         const UNINITIALIZED                     = 0xFFFF;
     }
 }
 
+/// The offset of the [`ShadowLayers`] of a light in the [`PointLightFlags`] and
+/// [`DirectionalLightFlags`].
+const SHADOW_LAYERS_SHIFT: u32 = 8;
+
+/// Packs the first [`ShadowLayers::MAX_LAYERS`] layers of `layers` into a bitmask.
+///
+/// Lights without [`ShadowLayers`] use a full mask, which the shaders treat as shadowing every
+/// mesh.
+pub(crate) fn pack_shadow_layers(layers: &RenderLayers) -> u8 {
+    layers.bits().first().map_or(0, |&bits| bits as u8)
+}
+
+/// The offset of the aspect ratio of elliptical spot lights in the [`PointLightFlags`], stored as
+/// 8.8 fixed point. A value of zero means the cone is circular.
+const SPOT_LIGHT_ASPECT_RATIO_SHIFT: u32 = 16;
+
+/// Packs the ratio of the tangents of a spot light's outer angles along its Y and X axes into the
+/// upper bits of the [`PointLightFlags`].
+fn pack_spot_light_aspect_ratio(outer_angle_x: f32, outer_angle_y: f32) -> u32 {
+    if outer_angle_x == outer_angle_y {
+        return 0;
+    }
+    let aspect_ratio = ops::tan(outer_angle_y) / ops::tan(outer_angle_x).max(1e-4);
+    let packed = (aspect_ratio * 256.0).round().clamp(1.0, u16::MAX as f32) as u32;
+    packed << SPOT_LIGHT_ASPECT_RATIO_SHIFT
+}
+
 #[derive(Copy, Clone, ShaderType, Default, Debug)]
 pub struct GpuDirectionalCascade {
     clip_from_world: Mat4,
     texel_size: f32,
     far_bound: f32,
+    // The world-space depth bias, scaled to the texel size of this cascade
+    depth_bias: f32,
+    // Nonzero if the shadow map stores logarithmic rather than linear depth
+    logarithmic_depth: u32,
+    // The proportion of overlap between the next cascade and this one
+    overlap_proportion: f32,
 }
 
 #[derive(Copy, Clone, ShaderType, Default, Debug)]
@@ -116,13 +183,94 @@ pub struct GpuDirectionalLight {
     color: Vec4,
     dir_to_light: Vec3,
     flags: u32,
+    // Positive for the soft shadow size, or the negated world-space shadow blur radius
     soft_shadow_size: f32,
     shadow_depth_bias: f32,
     shadow_normal_bias: f32,
     num_cascades: u32,
-    cascades_overlap_proportion: f32,
     depth_texture_base_index: u32,
     decal_index: u32,
+    // The shadow tint, packed as RGBA8 in linear space, with the shadow strength in alpha
+    shadow_tint: u32,
+    shadow_contact_darkening: f32,
+}
+
+/// One shadow cascade of a directional light, as exposed to custom shaders by
+/// [`GpuViewShadowCascades`].
+///
+/// This matches the `ShadowCascade` struct in `bevy_pbr::mesh_view_types`.
+#[derive(Copy, Clone, ShaderType, Default, Debug, PartialEq)]
+pub struct GpuShadowCascade {
+    /// Transforms world-space positions into the clip space of the cascade. The shadow map
+    /// texture coordinates are `clip.xy * vec2(0.5, -0.5) + 0.5`, and `clip.z` is the depth.
+    pub clip_from_world: Mat4,
+    /// The world-space size of a texel of the cascade, see [`Cascade::texel_size`].
+    pub texel_size: f32,
+    /// The view-space distance from the camera at which the cascade ends, see
+    /// [`CascadeShadowConfig::bounds`].
+    pub far_bound: f32,
+}
+
+/// The shadow cascades of one directional light, as exposed to custom shaders by
+/// [`GpuViewShadowCascades`].
+///
+/// This matches the `DirectionalLightCascades` struct in `bevy_pbr::mesh_view_types`.
+#[derive(Copy, Clone, ShaderType, Default, Debug, PartialEq)]
+pub struct GpuDirectionalLightCascades {
+    /// The cascades, of which the first [`num_cascades`](Self::num_cascades) are valid.
+    pub cascades: [GpuShadowCascade; MAX_CASCADES_PER_LIGHT],
+    /// The number of cascades, which is zero if the light doesn't have shadows in this view.
+    pub num_cascades: u32,
+    /// The layer of [`ViewShadowBindings::directional_light_depth_texture`] that holds the first
+    /// cascade. The other cascades follow it.
+    pub depth_texture_base_index: u32,
+}
+
+impl GpuDirectionalLightCascades {
+    /// Creates the GPU data of the given `cascades` of a light, which end at the given
+    /// `far_bounds`, and are stored from the given layer of the shadow map texture on.
+    pub fn new(cascades: &[Cascade], far_bounds: &[f32], depth_texture_base_index: u32) -> Self {
+        let mut light_cascades = Self {
+            depth_texture_base_index,
+            ..Default::default()
+        };
+        for (gpu_cascade, (cascade, &far_bound)) in light_cascades
+            .cascades
+            .iter_mut()
+            .zip(cascades.iter().zip(far_bounds))
+        {
+            *gpu_cascade = GpuShadowCascade {
+                clip_from_world: cascade.clip_from_world,
+                texel_size: cascade.texel_size,
+                far_bound,
+            };
+            light_cascades.num_cascades += 1;
+        }
+        light_cascades
+    }
+}
+
+/// The shadow cascades of the directional lights of a view, for custom render nodes and shaders
+/// that sample directional light shadow maps themselves.
+///
+/// Bevy writes this for every view into [`LightMeta::view_shadow_cascades`], at the offset in
+/// the view's [`ViewShadowCascadesUniformOffset`]. Bind that buffer as a uniform with a dynamic
+/// offset, and the shadow maps from [`ViewShadowBindings::directional_light_depth_texture`],
+/// and declare the binding with the `ViewShadowCascades` struct in `bevy_pbr::mesh_view_types`:
+///
+/// ```wgsl
+/// #import bevy_pbr::mesh_view_types::ViewShadowCascades
+///
+/// @group(0) @binding(0) var<uniform> shadow_cascades: ViewShadowCascades;
+/// ```
+///
+/// The lights are in the same order as in the view's `Lights` uniform.
+#[derive(Copy, Clone, ShaderType, Default, Debug, PartialEq)]
+pub struct GpuViewShadowCascades {
+    /// The cascades of each light, of which the first [`n_lights`](Self::n_lights) are valid.
+    pub lights: [GpuDirectionalLightCascades; MAX_DIRECTIONAL_LIGHTS],
+    /// The number of directional lights that affect the view.
+    pub n_lights: u32,
 }
 
 // NOTE: These must match the bit flags in bevy_pbr/src/render/mesh_view_types.wgsl!
@@ -132,6 +280,8 @@ bitflags::bitflags! {
         const SHADOWS_ENABLED                   = 1 << 0;
         const VOLUMETRIC                        = 1 << 1;
         const AFFECTS_LIGHTMAPPED_MESH_DIFFUSE  = 1 << 2;
+        const STABLE_SHADOWS                    = 1 << 3;
+        // Bits 8 to 15 hold the shadow layers, see `SHADOW_LAYERS_SHIFT`.
         const NONE                              = 0;
         const UNINITIALIZED                     = 0xFFFF;
     }
@@ -140,7 +290,10 @@ bitflags::bitflags! {
 #[derive(Copy, Clone, Debug, ShaderType)]
 pub struct GpuLights {
     directional_lights: [GpuDirectionalLight; MAX_DIRECTIONAL_LIGHTS],
+    // the ambient color for surfaces facing up
     ambient_color: Vec4,
+    // the ambient color for surfaces facing down
+    ambient_ground_color: Vec4,
     // xyz are x/y/z cluster dimensions and w is the number of clusters
     cluster_dimensions: UVec4,
     // xy are vec2<f32>(cluster_dimensions.xy) / vec2<f32>(view.width, view.height)
@@ -151,6 +304,13 @@ pub struct GpuLights {
     // offset from spot light's light index to spot light's shadow map index
     spot_light_shadowmap_offset: i32,
     ambient_light_affects_lightmapped_meshes: u32,
+    // 1 / tan(fov / 2) for the field of view of point light cubemap faces, used to skip the
+    // overlapping border of each face when sampling
+    point_light_cube_face_scale: f32,
+    // the number of samples taken by `ShadowFilteringMethod::Vogel`
+    vogel_shadow_sample_count: u32,
+    // the frame index used by the noise of temporal shadow filtering
+    temporal_shadow_frame: u32,
 }
 
 // NOTE: When running bevy on Adreno GPU chipsets in WebGL, any value above 1 will result in a crash
@@ -217,11 +377,48 @@ impl FromWorld for ShadowSamplers {
     }
 }
 
+/// Returns the width and height of the faces of the point light shadow cubemap array, which is
+/// shared by the given point lights with shadows: the largest of their
+/// [`shadow_map_size`](PointLight::shadow_map_size)s, or [`PointLightShadowMap::size`] if that's
+/// larger.
+pub(crate) fn point_light_shadow_map_face_size<'a>(
+    point_light_shadow_map: &PointLightShadowMap,
+    shadowed_point_lights: impl IntoIterator<Item = &'a PointLight>,
+) -> usize {
+    shadowed_point_lights
+        .into_iter()
+        .filter_map(|point_light| point_light.shadow_map_size)
+        .fold(point_light_shadow_map.size, usize::max)
+}
+
+/// Returns the descriptor of a shadow map texture of the given size, in the given
+/// [`ShadowDepthFormat`].
+pub(crate) fn shadow_map_texture_descriptor(
+    label: &'static str,
+    size: Extent3d,
+    shadow_depth_format: ShadowDepthFormat,
+) -> TextureDescriptor<'static> {
+    TextureDescriptor {
+        size,
+        mip_level_count: 1,
+        sample_count: 1,
+        dimension: TextureDimension::D2,
+        format: shadow_depth_format.texture_format(),
+        label: Some(label),
+        usage: TextureUsages::RENDER_ATTACHMENT
+            | TextureUsages::TEXTURE_BINDING
+            | TextureUsages::COPY_SRC,
+        view_formats: &[],
+    }
+}
+
 pub fn extract_lights(
     mut commands: Commands,
     point_light_shadow_map: Extract<Res<PointLightShadowMap>>,
     directional_light_shadow_map: Extract<Res<DirectionalLightShadowMap>>,
+    frame_count: Extract<Res<FrameCount>>,
     global_visible_clusterable: Extract<Res<GlobalVisibleClusterableObjects>>,
+    camera_positions: Extract<Res<ActiveCameraPositions>>,
     previous_point_lights: Query<
         Entity,
         (
@@ -240,9 +437,13 @@ pub fn extract_lights(
             &PointLight,
             &CubemapVisibleEntities,
             &GlobalTransform,
+            &ShadowFrustaTransform,
             &ViewVisibility,
             &CubemapFrusta,
             Option<&VolumetricLight>,
+            Has<ScaleAffectsRange>,
+            Option<&BakedPointShadow>,
+            Option<&ShadowLayers>,
         )>,
     >,
     spot_lights: Extract<
@@ -252,9 +453,12 @@ pub fn extract_lights(
             &SpotLight,
             &VisibleMeshEntities,
             &GlobalTransform,
+            &ShadowFrustaTransform,
             &ViewVisibility,
             &Frustum,
             Option<&VolumetricLight>,
+            Has<ScaleAffectsRange>,
+            Option<&ShadowLayers>,
         )>,
     >,
     directional_lights: Extract<
@@ -265,13 +469,15 @@ pub fn extract_lights(
                 &DirectionalLight,
                 &CascadesVisibleEntities,
                 &Cascades,
-                &CascadeShadowConfig,
+                Ref<CascadeShadowConfig>,
                 &CascadesFrusta,
                 &GlobalTransform,
                 &ViewVisibility,
                 Option<&RenderLayers>,
                 Option<&VolumetricLight>,
                 Has<OcclusionCulling>,
+                Option<&TemporalShadowUpdate>,
+                Option<&ShadowLayers>,
             ),
             Without<SpotLight>,
         >,
@@ -279,11 +485,29 @@ pub fn extract_lights(
     mapper: Extract<Query<RenderEntity>>,
     mut previous_point_lights_len: Local<usize>,
     mut previous_spot_lights_len: Local<usize>,
+    mut previous_point_light_shadow_map_size: Local<usize>,
 ) {
+    // The faces of the point light shadow cubemap array have the largest size among the visible
+    // point lights with shadows.
+    let point_light_shadow_map_size = point_light_shadow_map_face_size(
+        &point_light_shadow_map,
+        global_visible_clusterable.iter().filter_map(|entity| {
+            let (_, _, point_light, _, _, _, view_visibility, ..) =
+                point_lights.get(*entity).ok()?;
+            (view_visibility.get() && point_light.shadows_enabled).then_some(point_light)
+        }),
+    );
+
     // NOTE: These shadow map resources are extracted here as they are used here too so this avoids
     // races between scheduling of ExtractResourceSystems and this system.
-    if point_light_shadow_map.is_changed() {
-        commands.insert_resource(point_light_shadow_map.clone());
+    if point_light_shadow_map.is_changed()
+        || *previous_point_light_shadow_map_size != point_light_shadow_map_size
+    {
+        *previous_point_light_shadow_map_size = point_light_shadow_map_size;
+        commands.insert_resource(PointLightShadowMap {
+            size: point_light_shadow_map_size,
+            ..point_light_shadow_map.clone()
+        });
     }
     if directional_light_shadow_map.is_changed() {
         commands.insert_resource(directional_light_shadow_map.clone());
@@ -306,12 +530,13 @@ pub fn extract_lights(
 
     // This is the point light shadow map texel size for one face of the cube as a distance of 1.0
     // world unit from the light.
-    // point_light_texel_size = 2.0 * 1.0 * tan(PI / 4.0) / cube face width in texels
-    // PI / 4.0 is half the cube face fov, tan(PI / 4.0) = 1.0, so this simplifies to:
+    // point_light_texel_size = 2.0 * 1.0 * tan(fov / 2.0) / cube face width in texels
+    // Without any fov padding, fov / 2.0 is PI / 4.0 and tan(PI / 4.0) = 1.0, so this simplifies to:
     // point_light_texel_size = 2.0 / cube face width in texels
     // NOTE: When using various PCF kernel sizes, this will need to be adjusted, according to:
     // https://catlikecoding.com/unity/tutorials/custom-srp/point-and-spot-shadows/
-    let point_light_texel_size = 2.0 / point_light_shadow_map.size as f32;
+    let point_light_texel_size = 2.0 * ops::tan(point_light_shadow_map.cube_face_fov() * 0.5)
+        / point_light_shadow_map_size as f32;
 
     let mut point_lights_values = Vec::with_capacity(*previous_point_lights_len);
     for entity in global_visible_clusterable.iter().copied() {
@@ -321,9 +546,13 @@ pub fn extract_lights(
             point_light,
             cubemap_visible_entities,
             transform,
+            frusta_transform,
             view_visibility,
             frusta,
             volumetric_light,
+            scale_affects_range,
+            baked_shadow,
+            shadow_layers,
         )) = point_lights.get(entity)
         else {
             continue;
@@ -331,6 +560,13 @@ pub fn extract_lights(
         if !view_visibility.get() {
             continue;
         }
+        // Render shadowed lights from the transform their frusta were computed from, so that the
+        // shadow projection matches the shadow caster culling.
+        let transform = if point_light.shadows_enabled {
+            &frusta_transform.0
+        } else {
+            transform
+        };
         let render_cubemap_visible_entities = RenderCubemapVisibleEntities {
             data: cubemap_visible_entities
                 .iter()
@@ -346,16 +582,32 @@ pub fn extract_lights(
             // for a point light. See https://google.github.io/filament/Filament.html#mjx-eqn-pointLightLuminousPower
             // for details.
             intensity: point_light.intensity / (4.0 * core::f32::consts::PI),
-            range: point_light.range,
+            range: scaled_light_range(point_light.range, transform, scale_affects_range),
             radius: point_light.radius,
             transform: *transform,
-            shadows_enabled: point_light.shadows_enabled,
-            shadow_depth_bias: point_light.shadow_depth_bias,
+            shadows_enabled: point_light.shadows_enabled
+                && camera_positions.within_shadow_cutoff_distance(
+                    point_light.shadow_cutoff_distance,
+                    transform.translation_vec3a(),
+                ),
+            shadow_depth_bias: point_light
+                .shadow_depth_bias
+                .max(point_light.min_world_shadow_bias),
             // The factor of SQRT_2 is for the worst-case diagonal offset
             shadow_normal_bias: point_light.shadow_normal_bias
                 * point_light_texel_size
                 * core::f32::consts::SQRT_2,
+            shadow_tint: LinearRgba::from(point_light.shadow_tint)
+                .with_alpha(point_light.shadow_strength()),
+            shadow_blur_world_radius: point_light.shadow_blur_world_radius,
+            stable_shadows: point_light.stable_shadows,
             shadow_map_near_z: point_light.shadow_map_near_z,
+            // Baked shadows are always cube maps.
+            dual_paraboloid_shadows: point_light.shadow_technique
+                == PointShadowTechnique::DualParaboloid
+                && baked_shadow.is_none(),
+            baked_shadow_map: baked_shadow.map(|baked_shadow| baked_shadow.cubemap.id()),
+            shadow_layers: shadow_layers.map_or(u8::MAX, |layers| pack_shadow_layers(&layers.0)),
             spot_light_angles: None,
             volumetric: volumetric_light.is_some(),
             affects_lightmapped_mesh_diffuse: point_light.affects_lightmapped_mesh_diffuse,
@@ -385,19 +637,27 @@ pub fn extract_lights(
             spot_light,
             visible_entities,
             transform,
+            frusta_transform,
             view_visibility,
             frustum,
             volumetric_light,
+            scale_affects_range,
+            shadow_layers,
         )) = spot_lights.get(entity)
         {
             if !view_visibility.get() {
                 continue;
             }
+            let transform = if spot_light.shadows_enabled {
+                &frusta_transform.0
+            } else {
+                transform
+            };
             let render_visible_entities =
                 create_render_visible_mesh_entities(&mapper, visible_entities);
 
-            let texel_size =
-                2.0 * ops::tan(spot_light.outer_angle) / directional_light_shadow_map.size as f32;
+            let texel_size = 2.0 * ops::tan(spot_light.max_outer_angle())
+                / directional_light_shadow_map.size as f32;
 
             spot_lights_values.push((
                 render_entity,
@@ -411,17 +671,34 @@ pub fn extract_lights(
                         // in both cases so that toggling between point light and spot light keeps lit areas lit equally,
                         // which seems least surprising for users
                         intensity: spot_light.intensity / (4.0 * core::f32::consts::PI),
-                        range: spot_light.range,
+                        range: scaled_light_range(spot_light.range, transform, scale_affects_range),
                         radius: spot_light.radius,
                         transform: *transform,
-                        shadows_enabled: spot_light.shadows_enabled,
-                        shadow_depth_bias: spot_light.shadow_depth_bias,
+                        shadows_enabled: spot_light.shadows_enabled
+                            && camera_positions.within_shadow_cutoff_distance(
+                                spot_light.shadow_cutoff_distance,
+                                transform.translation_vec3a(),
+                            ),
+                        shadow_depth_bias: spot_light
+                            .shadow_depth_bias
+                            .max(spot_light.min_world_shadow_bias),
                         // The factor of SQRT_2 is for the worst-case diagonal offset
                         shadow_normal_bias: spot_light.shadow_normal_bias
                             * texel_size
                             * core::f32::consts::SQRT_2,
+                        shadow_tint: LinearRgba::from(spot_light.shadow_tint)
+                            .with_alpha(spot_light.shadow_strength()),
+                        shadow_blur_world_radius: spot_light.shadow_blur_world_radius,
+                        stable_shadows: spot_light.stable_shadows,
                         shadow_map_near_z: spot_light.shadow_map_near_z,
-                        spot_light_angles: Some((spot_light.inner_angle, spot_light.outer_angle)),
+                        dual_paraboloid_shadows: false,
+                        baked_shadow_map: None,
+                        shadow_layers: shadow_layers
+                            .map_or(u8::MAX, |layers| pack_shadow_layers(&layers.0)),
+                        spot_light_angles: {
+                            let (outer_angle_x, outer_angle_y) = spot_light.outer_angles();
+                            Some((spot_light.inner_angle, outer_angle_x, outer_angle_y))
+                        },
                         volumetric: volumetric_light.is_some(),
                         affects_lightmapped_mesh_diffuse: spot_light
                             .affects_lightmapped_mesh_diffuse,
@@ -453,6 +730,8 @@ pub fn extract_lights(
         maybe_layers,
         volumetric_light,
         occlusion_culling,
+        temporal_shadow_update,
+        shadow_layers,
     ) in &directional_lights
     {
         if !view_visibility.get() {
@@ -514,10 +793,24 @@ pub fn extract_lights(
                     // The factor of SQRT_2 is for the worst-case diagonal offset
                     shadow_normal_bias: directional_light.shadow_normal_bias
                         * core::f32::consts::SQRT_2,
-                    cascade_shadow_config: cascade_config.clone(),
+                    min_world_shadow_bias: directional_light.min_world_shadow_bias,
+                    shadow_tint: LinearRgba::from(directional_light.shadow_tint)
+                        .with_alpha(directional_light.shadow_strength()),
+                    shadow_blur_world_radius: directional_light.shadow_blur_world_radius,
+                    stable_shadows: directional_light.stable_shadows,
+                    shadow_contact_darkening: directional_light.clamped_shadow_contact_darkening(),
+                    cascade_shadow_config: (*cascade_config).clone(),
+                    // A changed cascade config invalidates the shadow map, even on frames that
+                    // `TemporalShadowUpdate` would skip.
+                    update_shadow_map: cascade_config.is_changed()
+                        || temporal_shadow_update.is_none_or(|temporal_shadow_update| {
+                            temporal_shadow_update.updates_on_frame(frame_count.0)
+                        }),
                     cascades: extracted_cascades,
                     frusta: extracted_frusta,
                     render_layers: maybe_layers.unwrap_or_default().clone(),
+                    shadow_layers: shadow_layers
+                        .map_or(u8::MAX, |layers| pack_shadow_layers(&layers.0)),
                     occlusion_culling,
                 },
                 RenderCascadesVisibleEntities {
@@ -528,6 +821,52 @@ pub fn extract_lights(
     }
 }
 
+/// Extracts the [`ReservedShadowMaps`] of the [`ShadowPrewarm`] resource, if there is one.
+pub fn extract_shadow_prewarm(
+    mut commands: Commands,
+    shadow_prewarm: Extract<Option<Res<ShadowPrewarm>>>,
+    lights: Extract<
+        Query<(
+            AnyOf<(&PointLight, &SpotLight, &DirectionalLight)>,
+            Option<&CascadeShadowConfig>,
+        )>,
+    >,
+) {
+    match shadow_prewarm.as_deref() {
+        Some(shadow_prewarm) => {
+            commands.insert_resource(shadow_prewarm.reserved_shadow_maps(&lights));
+        }
+        None => commands.remove_resource::<ReservedShadowMaps>(),
+    }
+}
+
+/// Copies the [`SkipShadowRender`] resource to the render world, removing it from the render world
+/// when it's removed from the main world.
+pub fn extract_skip_shadow_render(
+    mut commands: Commands,
+    skip_shadow_render: Extract<Option<Res<SkipShadowRender>>>,
+) {
+    match skip_shadow_render.as_deref() {
+        Some(skip_shadow_render) => commands.insert_resource(*skip_shadow_render),
+        None => commands.remove_resource::<SkipShadowRender>(),
+    }
+}
+
+/// Packs a light's soft shadow size and world-space shadow blur radius into the single
+/// `soft_shadow_size` field of the GPU light.
+///
+/// Soft shadows take precedence and are stored as a positive size. Otherwise, the blur radius is
+/// stored negated, and zero means that neither is used.
+fn pack_soft_shadow_size(
+    soft_shadow_size: Option<f32>,
+    shadow_blur_world_radius: Option<f32>,
+) -> f32 {
+    match soft_shadow_size {
+        Some(soft_shadow_size) if soft_shadow_size > 0.0 => soft_shadow_size,
+        _ => -shadow_blur_world_radius.unwrap_or_default().max(0.0),
+    }
+}
+
 fn create_render_visible_mesh_entities(
     mapper: &Extract<Query<RenderEntity>>,
     visible_entities: &VisibleMeshEntities,
@@ -645,8 +984,20 @@ fn face_index_to_name(face_index: usize) -> &'static str {
 pub struct ShadowView {
     pub depth_attachment: DepthAttachment,
     pub pass_name: String,
+    /// The layer of the shadow map texture array that this view renders into.
+    pub array_layer: u32,
 }
 
+/// Marks a directional light cascade view whose shadow map stores logarithmic depth, see
+/// [`Cascade::logarithmic_depth`].
+#[derive(Component)]
+pub struct LogarithmicShadowDepth;
+
+/// Marks a point light view that renders a hemisphere of
+/// [`PointShadowTechnique::DualParaboloid`] shadows.
+#[derive(Component)]
+pub struct DualParaboloidShadowView;
+
 #[derive(Component)]
 pub struct ViewShadowBindings {
     pub point_light_depth_texture: Texture,
@@ -675,9 +1026,57 @@ pub struct ViewLightsUniformOffset {
     pub offset: u32,
 }
 
+/// The offset of the [`GpuViewShadowCascades`] of a view in
+/// [`LightMeta::view_shadow_cascades`].
+#[derive(Component)]
+pub struct ViewShadowCascadesUniformOffset {
+    pub offset: u32,
+}
+
 #[derive(Resource, Default)]
 pub struct LightMeta {
     pub view_gpu_lights: DynamicUniformBuffer<GpuLights>,
+    /// The shadow cascades of the directional lights of every view, see
+    /// [`GpuViewShadowCascades`].
+    pub view_shadow_cascades: DynamicUniformBuffer<GpuViewShadowCascades>,
+}
+
+/// The directional light shadow map texture, kept alive across frames.
+///
+/// On the frames that a light with a [`TemporalShadowUpdate`] skips, its cascades are sampled from
+/// what was rendered into the texture on an earlier frame. A [`TextureCache`] texture can't be
+/// relied on for that, as the cache may hand out a different texture with the same descriptor, or
+/// drop textures that go unused for a few frames.
+#[derive(Resource, Default)]
+pub struct DirectionalLightShadowMapTexture {
+    texture: Option<(TextureDescriptor<'static>, CachedTexture)>,
+}
+
+impl DirectionalLightShadowMapTexture {
+    /// Returns the texture matching `descriptor`, and whether it was created this frame, in which
+    /// case it doesn't hold any shadow maps rendered on earlier frames.
+    pub fn get(
+        &mut self,
+        render_device: &RenderDevice,
+        descriptor: TextureDescriptor<'static>,
+    ) -> (CachedTexture, bool) {
+        if let Some((_, texture)) = self
+            .texture
+            .as_ref()
+            .filter(|(cached_descriptor, _)| *cached_descriptor == descriptor)
+        {
+            return (texture.clone(), false);
+        }
+
+        let texture = render_device.create_texture(&descriptor);
+        let default_view = texture.create_view(&TextureViewDescriptor::default());
+        let texture = CachedTexture {
+            texture,
+            default_view,
+        };
+        self.texture = Some((descriptor, texture.clone()));
+        (texture, true)
+    }
 }
 
 #[derive(Component)]
@@ -738,9 +1137,11 @@ pub(crate) fn spot_light_world_from_view(transform: &GlobalTransform) -> Mat4 {
     )
 }
 
-pub(crate) fn spot_light_clip_from_view(angle: f32, near_z: f32) -> Mat4 {
-    // spot light projection FOV is 2x the angle from spot light center to outer edge
-    Mat4::perspective_infinite_reverse_rh(angle * 2.0, 1.0, near_z)
+pub(crate) fn spot_light_clip_from_view(angle_x: f32, angle_y: f32, near_z: f32) -> Mat4 {
+    // spot light projection FOV is 2x the angle from spot light center to outer edge. for
+    // elliptical spot lights, the aspect ratio stretches the X axis to cover `angle_x`
+    let aspect_ratio = ops::tan(angle_x) / ops::tan(angle_y);
+    Mat4::perspective_infinite_reverse_rh(angle_y * 2.0, aspect_ratio, near_z)
 }
 
 pub fn prepare_lights(
@@ -758,6 +1159,9 @@ pub fn prepare_lights(
             Option<&RenderLayers>,
             Has<NoIndirectDrawing>,
             Option<&AmbientLight>,
+            Option<&ShadowFilteringMethod>,
+            Option<&ViewCascadeShadowConfig>,
+            Option<&ExtractedCamera>,
         ),
         With<Camera3d>,
     >,
@@ -779,18 +1183,42 @@ pub fn prepare_lights(
     directional_lights: Query<(Entity, &MainEntity, &ExtractedDirectionalLight)>,
     mut light_view_entities: Query<&mut LightViewEntities>,
     sorted_cameras: Res<SortedCameras>,
-    (gpu_preprocessing_support, decals): (
+    (
+        gpu_preprocessing_support,
+        decals,
+        local_ambients,
+        reserved_shadow_maps,
+        temporal_shadow_seed,
+        frame_count,
+        skip_shadow_render,
+        shadow_depth_format,
+        mut directional_light_shadow_map_texture,
+    ): (
         Res<GpuPreprocessingSupport>,
         Option<Res<RenderClusteredDecals>>,
+        Query<(Entity, &LocalAmbient)>,
+        Option<Res<ReservedShadowMaps>>,
+        Option<Res<TemporalShadowSeed>>,
+        Res<FrameCount>,
+        Option<Res<SkipShadowRender>>,
+        Option<Res<ShadowDepthFormat>>,
+        ResMut<DirectionalLightShadowMapTexture>,
     ),
 ) {
+    let shadow_depth_format = shadow_depth_format
+        .map(|format| *format)
+        .unwrap_or_default();
     let views_iter = views.iter();
     let views_count = views_iter.len();
-    let Some(mut view_gpu_lights_writer) =
+    let light_meta = &mut *light_meta;
+    let (Some(mut view_gpu_lights_writer), Some(mut view_shadow_cascades_writer)) = (
         light_meta
             .view_gpu_lights
-            .get_writer(views_count, &render_device, &render_queue)
-    else {
+            .get_writer(views_count, &render_device, &render_queue),
+        light_meta
+            .view_shadow_cascades
+            .get_writer(views_count, &render_device, &render_queue),
+    ) else {
         return;
     };
 
@@ -832,14 +1260,19 @@ pub fn prepare_lights(
         *max_directional_lights_warning_emitted = true;
     }
 
+    let max_cascades = max_shadow_cascades(max_texture_array_layers as u32);
     if !*max_cascades_per_light_warning_emitted
-        && directional_lights
+        && let Some(num_cascades) = directional_lights
             .iter()
-            .any(|(_, _, light)| light.cascade_shadow_config.bounds.len() > MAX_CASCADES_PER_LIGHT)
+            .map(|(_, _, light)| light.cascade_shadow_config.bounds.len())
+            .find(|&num_cascades| num_cascades > max_cascades)
     {
         warn!(
-            "The number of cascades configured for a directional light exceeds the supported limit of {}.",
-            MAX_CASCADES_PER_LIGHT
+            "{}",
+            TooManyCascadesError {
+                num_cascades,
+                max_cascades,
+            }
         );
         *max_cascades_per_light_warning_emitted = true;
     }
@@ -937,9 +1370,13 @@ pub fn prepare_lights(
         {
             flags |= PointLightFlags::SHADOWS_ENABLED;
         }
+        // Without rendered shadow maps, shade the light as if nothing cast shadows.
+        if skip_shadow_render.is_some() {
+            flags.remove(PointLightFlags::SHADOWS_ENABLED);
+        }
 
         let cube_face_projection = Mat4::perspective_infinite_reverse_rh(
-            core::f32::consts::FRAC_PI_2,
+            point_light_shadow_map.cube_face_fov(),
             1.0,
             light.shadow_map_near_z,
         );
@@ -956,13 +1393,30 @@ pub fn prepare_lights(
             flags |= PointLightFlags::AFFECTS_LIGHTMAPPED_MESH_DIFFUSE;
         }
 
+        if light.stable_shadows {
+            flags |= PointLightFlags::STABLE_SHADOWS;
+        }
+
+        if light.dual_paraboloid_shadows {
+            flags |= PointLightFlags::DUAL_PARABOLOID_SHADOWS;
+        }
+
+        flags = PointLightFlags::from_bits_retain(
+            flags.bits() | (u32::from(light.shadow_layers) << SHADOW_LAYERS_SHIFT),
+        );
+
         let (light_custom_data, spot_light_tan_angle) = match light.spot_light_angles {
-            Some((inner, outer)) => {
+            Some((inner, outer, outer_y)) => {
                 let light_direction = light.transform.forward();
                 if light_direction.y.is_sign_negative() {
                     flags |= PointLightFlags::SPOT_LIGHT_Y_NEGATIVE;
                 }
 
+                // The falloff of elliptical spot lights is computed in a space where the cone is
+                // circular with the X angle, so the Y axis only needs the aspect ratio.
+                let aspect_ratio_bits = pack_spot_light_aspect_ratio(outer, outer_y);
+                flags = PointLightFlags::from_bits_retain(flags.bits() | aspect_ratio_bits);
+
                 let cos_outer = ops::cos(outer);
                 let spot_scale = 1.0 / f32::max(ops::cos(inner) - cos_outer, 1e-4);
                 let spot_offset = -cos_outer * spot_scale;
@@ -1007,16 +1461,29 @@ pub fn prepare_lights(
                 .and_then(|decals| decals.get(entity))
                 .and_then(|index| index.try_into().ok())
                 .unwrap_or(u32::MAX),
-            pad: 0.0,
-            soft_shadow_size: if light.soft_shadows_enabled {
-                light.radius
-            } else {
-                0.0
-            },
+            shadow_tint: light.shadow_tint.as_u32(),
+            soft_shadow_size: pack_soft_shadow_size(
+                light.soft_shadows_enabled.then_some(light.radius),
+                light.shadow_blur_world_radius,
+            ),
         });
         global_light_meta.entity_to_index.insert(entity, index);
     }
 
+    // Local ambient volumes follow the lights in the clusterable object list.
+    for (entity, local_ambient) in &local_ambients {
+        global_light_meta
+            .entity_to_index
+            .insert(entity, gpu_point_lights.len());
+        gpu_point_lights.push(GpuClusterableObject {
+            // For local ambients: the half extents of the bounds
+            light_custom_data: Vec3::from(local_ambient.bounds.half_extents).extend(0.0),
+            color_inverse_square_range: local_ambient.shader_color().extend(0.0),
+            position_radius: Vec3::from(local_ambient.bounds.center).extend(0.0),
+            ..default()
+        });
+    }
+
     // iterate the views once to find the maximum number of cascade shadowmaps we will need
     let mut num_directional_cascades_enabled = 0usize;
     for (
@@ -1027,6 +1494,9 @@ pub fn prepare_lights(
         maybe_layers,
         _no_indirect_drawing,
         _maybe_ambient_override,
+        _maybe_shadow_filtering_method,
+        maybe_view_cascade_shadow_config,
+        _maybe_extracted_camera,
     ) in sorted_cameras
         .0
         .iter()
@@ -1037,8 +1507,8 @@ pub fn prepare_lights(
 
         for (_light_entity, _, light) in directional_lights.iter() {
             if light.shadows_enabled && light.render_layers.intersects(render_layers) {
-                num_directional_cascades_for_this_view += light
-                    .cascade_shadow_config
+                num_directional_cascades_for_this_view += maybe_view_cascade_shadow_config
+                    .map_or(&light.cascade_shadow_config, |config| &config.0)
                     .bounds
                     .len()
                     .min(MAX_CASCADES_PER_LIGHT);
@@ -1059,25 +1529,43 @@ pub fn prepare_lights(
 
     live_shadow_mapping_lights.clear();
 
+    // Size the shadow map textures for the lights reserved by `ShadowPrewarm` too, so that they
+    // keep the same size, and are reused by the texture cache, when those lights appear.
+    let reserved_shadow_maps = reserved_shadow_maps.as_deref().copied().unwrap_or_default();
+    let reserved_point_light_shadow_maps = reserved_shadow_maps
+        .point_light_shadow_maps
+        .min(max_texture_cubes);
+    let point_light_shadow_map_cubes =
+        point_light_shadow_maps_count.max(reserved_point_light_shadow_maps);
+    let directional_light_shadow_map_layers =
+        num_directional_cascades_enabled + spot_light_shadow_maps_count;
+    let reserved_directional_light_shadow_map_layers = (reserved_shadow_maps
+        .directional_cascades
+        .max(num_directional_cascades_enabled)
+        + reserved_shadow_maps
+            .spot_light_shadow_maps
+            .max(spot_light_shadow_maps_count))
+    .min(max_texture_array_layers);
+    let directional_light_shadow_map_layers =
+        directional_light_shadow_map_layers.max(reserved_directional_light_shadow_map_layers);
+
     let mut point_light_depth_attachments = HashMap::<u32, DepthAttachment>::default();
     let mut directional_light_depth_attachments = HashMap::<u32, DepthAttachment>::default();
+    // The light and cascade last rendered into each layer of the directional light shadow map
+    // this frame, so that views with the same cascades, as with `SharedCascades`, render them once.
+    let mut rendered_directional_cascades = HashMap::<u32, (Entity, Mat4)>::default();
 
     let point_light_depth_texture = texture_cache.get(
         &render_device,
-        TextureDescriptor {
-            size: Extent3d {
+        shadow_map_texture_descriptor(
+            "point_light_shadow_map_texture",
+            Extent3d {
                 width: point_light_shadow_map.size as u32,
                 height: point_light_shadow_map.size as u32,
-                depth_or_array_layers: point_light_shadow_maps_count.max(1) as u32 * 6,
+                depth_or_array_layers: point_light_shadow_map_cubes.max(1) as u32 * 6,
             },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: CORE_3D_DEPTH_FORMAT,
-            label: Some("point_light_shadow_map_texture"),
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        },
+            shadow_depth_format,
+        ),
     );
 
     let point_light_depth_texture_view =
@@ -1110,27 +1598,21 @@ pub fn prepare_lights(
                 array_layer_count: None,
             });
 
-    let directional_light_depth_texture = texture_cache.get(
-        &render_device,
-        TextureDescriptor {
-            size: Extent3d {
-                width: (directional_light_shadow_map.size as u32)
-                    .min(render_device.limits().max_texture_dimension_2d),
-                height: (directional_light_shadow_map.size as u32)
-                    .min(render_device.limits().max_texture_dimension_2d),
-                depth_or_array_layers: (num_directional_cascades_enabled
-                    + spot_light_shadow_maps_count)
-                    .max(1) as u32,
-            },
-            mip_level_count: 1,
-            sample_count: 1,
-            dimension: TextureDimension::D2,
-            format: CORE_3D_DEPTH_FORMAT,
-            label: Some("directional_light_shadow_map_texture"),
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING,
-            view_formats: &[],
-        },
-    );
+    let (directional_light_depth_texture, directional_light_depth_texture_created) =
+        directional_light_shadow_map_texture.get(
+            &render_device,
+            shadow_map_texture_descriptor(
+                "directional_light_shadow_map_texture",
+                Extent3d {
+                    width: (directional_light_shadow_map.size as u32)
+                        .min(render_device.limits().max_texture_dimension_2d),
+                    height: (directional_light_shadow_map.size as u32)
+                        .min(render_device.limits().max_texture_dimension_2d),
+                    depth_or_array_layers: directional_light_shadow_map_layers.max(1) as u32,
+                },
+                shadow_depth_format,
+            ),
+        );
 
     let directional_light_depth_texture_view =
         directional_light_depth_texture
@@ -1165,6 +1647,9 @@ pub fn prepare_lights(
         maybe_layers,
         no_indirect_drawing,
         maybe_ambient_override,
+        maybe_shadow_filtering_method,
+        maybe_view_cascade_shadow_config,
+        maybe_extracted_camera,
     ) in sorted_cameras
         .0
         .iter()
@@ -1204,6 +1689,8 @@ pub fn prepare_lights(
         {
             num_directional_lights_for_this_view += 1;
 
+            let cascade_shadow_config = maybe_view_cascade_shadow_config
+                .map_or(&light.cascade_shadow_config, |config| &config.0);
             let mut flags = DirectionalLightFlags::NONE;
 
             // Lights are sorted, volumetric and shadow enabled lights are first
@@ -1217,8 +1704,7 @@ pub fn prepare_lights(
             // Shadow enabled lights are second
             let mut num_cascades = 0;
             if light.shadows_enabled {
-                let cascades = light
-                    .cascade_shadow_config
+                let cascades = cascade_shadow_config
                     .bounds
                     .len()
                     .min(MAX_CASCADES_PER_LIGHT);
@@ -1235,6 +1721,14 @@ pub fn prepare_lights(
                 flags |= DirectionalLightFlags::AFFECTS_LIGHTMAPPED_MESH_DIFFUSE;
             }
 
+            if light.stable_shadows {
+                flags |= DirectionalLightFlags::STABLE_SHADOWS;
+            }
+
+            flags = DirectionalLightFlags::from_bits_retain(
+                flags.bits() | (u32::from(light.shadow_layers) << SHADOW_LAYERS_SHIFT),
+            );
+
             gpu_directional_lights[index] = GpuDirectionalLight {
                 // Filled in later.
                 cascades: [GpuDirectionalCascade::default(); MAX_CASCADES_PER_LIGHT],
@@ -1244,25 +1738,31 @@ pub fn prepare_lights(
                 // direction is negated to be ready for N.L
                 dir_to_light: light.transform.back().into(),
                 flags: flags.bits(),
-                soft_shadow_size: light.soft_shadow_size.unwrap_or_default(),
+                soft_shadow_size: pack_soft_shadow_size(
+                    light.soft_shadow_size,
+                    light.shadow_blur_world_radius,
+                ),
                 shadow_depth_bias: light.shadow_depth_bias,
                 shadow_normal_bias: light.shadow_normal_bias,
                 num_cascades: num_cascades as u32,
-                cascades_overlap_proportion: light.cascade_shadow_config.overlap_proportion,
                 depth_texture_base_index: num_directional_cascades_enabled_for_this_view as u32,
                 decal_index: decals
                     .as_ref()
                     .and_then(|decals| decals.get(*light_entity))
                     .and_then(|index| index.try_into().ok())
                     .unwrap_or(u32::MAX),
+                shadow_tint: light.shadow_tint.as_u32(),
+                shadow_contact_darkening: light.shadow_contact_darkening,
             };
             num_directional_cascades_enabled_for_this_view += num_cascades;
         }
 
+        let exposure = maybe_extracted_camera
+            .map_or_else(|| Exposure::default().exposure(), |camera| camera.exposure);
         let mut gpu_lights = GpuLights {
             directional_lights: gpu_directional_lights,
-            ambient_color: Vec4::from_slice(&LinearRgba::from(ambient_light.color).to_f32_array())
-                * ambient_light.brightness,
+            ambient_color: ambient_light.shader_color(exposure),
+            ambient_ground_color: ambient_light.shader_ground_color(exposure),
             cluster_factors: Vec4::new(
                 clusters.dimensions.x as f32 / extracted_view.viewport.z as f32,
                 clusters.dimensions.y as f32 / extracted_view.viewport.w as f32,
@@ -1278,6 +1778,16 @@ pub fn prepare_lights(
                 - point_light_count as i32,
             ambient_light_affects_lightmapped_meshes: ambient_light.affects_lightmapped_meshes
                 as u32,
+            point_light_cube_face_scale: 1.0
+                / ops::tan(point_light_shadow_map.cube_face_fov() * 0.5),
+            vogel_shadow_sample_count: match maybe_shadow_filtering_method {
+                Some(ShadowFilteringMethod::Vogel { samples }) => (*samples).max(1),
+                _ => 0,
+            },
+            temporal_shadow_frame: temporal_shadow_frame(
+                temporal_shadow_seed.as_deref(),
+                frame_count.0,
+            ),
         };
 
         // TODO: this should select lights based on relevance to the view instead of the first ones that show up in a query
@@ -1312,7 +1822,7 @@ pub fn prepare_lights(
                 .or_insert_with(|| (0..6).map(|_| commands.spawn_empty().id()).collect());
 
             let cube_face_projection = Mat4::perspective_infinite_reverse_rh(
-                core::f32::consts::FRAC_PI_2,
+                point_light_shadow_map.cube_face_fov(),
                 1.0,
                 light.shadow_map_near_z,
             );
@@ -1323,6 +1833,11 @@ pub fn prepare_lights(
                 .zip(light_view_entities.iter().copied())
                 .enumerate()
             {
+                // Dual-paraboloid shadows only render one view per hemisphere.
+                if light.dual_paraboloid_shadows && !DUAL_PARABOLOID_FACES.contains(&face_index) {
+                    continue;
+                }
+
                 let mut first = false;
                 let base_array_layer = (light_index * 6 + face_index) as u32;
 
@@ -1364,6 +1879,7 @@ pub fn prepare_lights(
                             light_index,
                             face_index_to_name(face_index)
                         ),
+                        array_layer: base_array_layer,
                     },
                     ExtractedView {
                         retained_view_entity,
@@ -1389,10 +1905,20 @@ pub fn prepare_lights(
                 if !matches!(gpu_preprocessing_mode, GpuPreprocessingMode::Culling) {
                     commands.entity(view_light_entity).insert(NoIndirectDrawing);
                 }
+                if light.dual_paraboloid_shadows {
+                    commands
+                        .entity(view_light_entity)
+                        .insert(DualParaboloidShadowView);
+                } else {
+                    commands
+                        .entity(view_light_entity)
+                        .remove::<DualParaboloidShadowView>();
+                }
 
                 view_lights.push(view_light_entity);
 
-                if first {
+                // Baked shadow maps are copied by `prepare_baked_point_shadows` instead.
+                if first && skip_shadow_render.is_none() && light.baked_shadow_map.is_none() {
                     // Subsequent views with the same light entity will reuse the same shadow map
                     shadow_render_phases
                         .prepare_for_new_frame(retained_view_entity, gpu_preprocessing_mode);
@@ -1423,9 +1949,10 @@ pub fn prepare_lights(
             let spot_world_from_view = spot_light_world_from_view(&light.transform);
             let spot_world_from_view = spot_world_from_view.into();
 
-            let angle = light.spot_light_angles.expect("lights should be sorted so that \
-                [point_light_count..point_light_count + spot_light_shadow_maps_count] are spot lights").1;
-            let spot_projection = spot_light_clip_from_view(angle, light.shadow_map_near_z);
+            let (_, angle_x, angle_y) = light.spot_light_angles.expect("lights should be sorted so that \
+                [point_light_count..point_light_count + spot_light_shadow_maps_count] are spot lights");
+            let spot_projection =
+                spot_light_clip_from_view(angle_x, angle_y, light.shadow_map_near_z);
 
             let mut first = false;
             let base_array_layer = (num_directional_cascades_enabled + light_index) as u32;
@@ -1466,6 +1993,7 @@ pub fn prepare_lights(
                 ShadowView {
                     depth_attachment,
                     pass_name: format!("shadow pass spot light {light_index}"),
+                    array_layer: base_array_layer,
                 },
                 ExtractedView {
                     retained_view_entity,
@@ -1491,7 +2019,7 @@ pub fn prepare_lights(
 
             view_lights.push(view_light_entity);
 
-            if first {
+            if first && skip_shadow_render.is_none() {
                 // Subsequent views with the same light entity will reuse the same shadow map
                 shadow_render_phases
                     .prepare_for_new_frame(retained_view_entity, gpu_preprocessing_mode);
@@ -1513,6 +2041,10 @@ pub fn prepare_lights(
             }
         }
 
+        let mut view_shadow_cascades = GpuViewShadowCascades {
+            n_lights: gpu_lights.n_directional_lights,
+            ..Default::default()
+        };
         let mut directional_depth_texture_array_index = 0u32;
         for (light_index, &(light_entity, light_main_entity, light)) in directional_lights
             .iter()
@@ -1534,12 +2066,11 @@ pub fn prepare_lights(
                 continue;
             }
 
-            let cascades = light
-                .cascades
-                .get(&entity)
-                .unwrap()
-                .iter()
-                .take(MAX_CASCADES_PER_LIGHT);
+            let view_cascades = light.cascades.get(&entity).unwrap();
+            let first_cascade_texel_size = view_cascades
+                .first()
+                .map_or(1.0, |cascade| cascade.texel_size);
+            let cascades = view_cascades.iter().take(MAX_CASCADES_PER_LIGHT);
             let frusta = light
                 .frusta
                 .get(&entity)
@@ -1547,9 +2078,14 @@ pub fn prepare_lights(
                 .iter()
                 .take(MAX_CASCADES_PER_LIGHT);
 
-            let iter = cascades
-                .zip(frusta)
-                .zip(&light.cascade_shadow_config.bounds);
+            let cascade_shadow_config = maybe_view_cascade_shadow_config
+                .map_or(&light.cascade_shadow_config, |config| &config.0);
+            view_shadow_cascades.lights[light_index] = GpuDirectionalLightCascades::new(
+                view_cascades,
+                &cascade_shadow_config.bounds,
+                directional_depth_texture_array_index,
+            );
+            let iter = cascades.zip(frusta).zip(&cascade_shadow_config.bounds);
 
             let light_view_entities = light_view_entities.entry(entity).or_insert_with(|| {
                 (0..iter.len())
@@ -1570,8 +2106,31 @@ pub fn prepare_lights(
                         clip_from_world: cascade.clip_from_world,
                         texel_size: cascade.texel_size,
                         far_bound: *bound,
+                        depth_bias: cascade
+                            .world_depth_bias(light.shadow_depth_bias, first_cascade_texel_size)
+                            .max(light.min_world_shadow_bias),
+                        logarithmic_depth: cascade.logarithmic_depth as u32,
+                        overlap_proportion: cascade_shadow_config
+                            .boundary_overlap_proportion(cascade_index),
                     };
 
+                // If the shadow map isn't updated this frame, don't render the cascade. Shading
+                // samples what was rendered into this layer on a previous frame instead, unless
+                // the texture was just created and holds nothing yet.
+                if !light.update_shadow_map && !directional_light_depth_texture_created {
+                    directional_depth_texture_array_index += 1;
+                    live_shadow_mapping_lights.insert(RetainedViewEntity::new(
+                        *light_main_entity,
+                        Some(camera_main_entity.into()),
+                        cascade_index as u32,
+                    ));
+                    continue;
+                }
+
+                let base_array_layer = directional_depth_texture_array_index;
+                let already_rendered = rendered_directional_cascades
+                    .insert(base_array_layer, (light_entity, cascade.clip_from_world))
+                    == Some((light_entity, cascade.clip_from_world));
                 let depth_texture_view =
                     directional_light_depth_texture
                         .texture
@@ -1583,7 +2142,7 @@ pub fn prepare_lights(
                             aspect: TextureAspect::All,
                             base_mip_level: 0,
                             mip_level_count: None,
-                            base_array_layer: directional_depth_texture_array_index,
+                            base_array_layer,
                             array_layer_count: Some(1u32),
                         });
 
@@ -1611,6 +2170,7 @@ pub fn prepare_lights(
                         pass_name: format!(
                             "shadow pass directional light {light_index} cascade {cascade_index}"
                         ),
+                        array_layer: base_array_layer,
                     },
                     ExtractedView {
                         retained_view_entity,
@@ -1636,6 +2196,15 @@ pub fn prepare_lights(
                 if !matches!(gpu_preprocessing_mode, GpuPreprocessingMode::Culling) {
                     commands.entity(view_light_entity).insert(NoIndirectDrawing);
                 }
+                if cascade.logarithmic_depth {
+                    commands
+                        .entity(view_light_entity)
+                        .insert(LogarithmicShadowDepth);
+                } else {
+                    commands
+                        .entity(view_light_entity)
+                        .remove::<LogarithmicShadowDepth>();
+                }
 
                 view_lights.push(view_light_entity);
 
@@ -1652,12 +2221,27 @@ pub fn prepare_lights(
                 }
 
                 // Subsequent views with the same light entity will **NOT** reuse the same shadow map
-                // (Because the cascades are unique to each view)
+                // (Because the cascades are unique to each view), unless the cascades are the same,
+                // as with `SharedCascades`, and the layer still holds them.
                 // TODO: Implement GPU culling for shadow passes.
-                shadow_render_phases
-                    .prepare_for_new_frame(retained_view_entity, gpu_preprocessing_mode);
-                live_shadow_mapping_lights.insert(retained_view_entity);
+                if !already_rendered && skip_shadow_render.is_none() {
+                    shadow_render_phases
+                        .prepare_for_new_frame(retained_view_entity, gpu_preprocessing_mode);
+                    live_shadow_mapping_lights.insert(retained_view_entity);
+                }
+            }
+        }
+
+        // Without rendered shadow maps, shade the lights as if nothing cast shadows. The cascades
+        // are still prepared above, as they are when the shadow maps are rendered.
+        if skip_shadow_render.is_some() {
+            for gpu_light in &mut gpu_lights.directional_lights {
+                gpu_light.flags &= !DirectionalLightFlags::SHADOWS_ENABLED.bits();
             }
+            view_shadow_cascades = GpuViewShadowCascades {
+                n_lights: gpu_lights.n_directional_lights,
+                ..Default::default()
+            };
         }
 
         commands.entity(entity).insert((
@@ -1673,6 +2257,9 @@ pub fn prepare_lights(
             ViewLightsUniformOffset {
                 offset: view_gpu_lights_writer.write(&gpu_lights),
             },
+            ViewShadowCascadesUniformOffset {
+                offset: view_shadow_cascades_writer.write(&view_shadow_cascades),
+            },
         ));
 
         // Make a link from the camera to all shadow cascades with occlusion
@@ -1746,15 +2333,21 @@ pub struct SpecializedShadowMaterialViewPipelineCache {
 
 pub fn check_views_lights_need_specialization(
     view_lights: Query<&ViewLightEntities, With<ExtractedView>>,
-    view_light_entities: Query<(&LightEntity, &ExtractedView)>,
+    view_light_entities: Query<(
+        &LightEntity,
+        &ExtractedView,
+        Has<LogarithmicShadowDepth>,
+        Has<DualParaboloidShadowView>,
+    )>,
     shadow_render_phases: Res<ViewBinnedRenderPhases<Shadow>>,
+    shadow_depth_format: Option<Res<ShadowDepthFormat>>,
     mut light_key_cache: ResMut<LightKeyCache>,
     mut light_specialization_ticks: ResMut<LightSpecializationTicks>,
     ticks: SystemChangeTick,
 ) {
     for view_lights in &view_lights {
         for view_light_entity in view_lights.lights.iter().copied() {
-            let Ok((light_entity, extracted_view_light)) =
+            let Ok((light_entity, extracted_view_light, logarithmic_depth, dual_paraboloid)) =
                 view_light_entities.get(view_light_entity)
             else {
                 continue;
@@ -1766,6 +2359,14 @@ pub fn check_views_lights_need_specialization(
             let is_directional_light = matches!(light_entity, LightEntity::Directional { .. });
             let mut light_key = MeshPipelineKey::DEPTH_PREPASS;
             light_key.set(MeshPipelineKey::UNCLIPPED_DEPTH_ORTHO, is_directional_light);
+            light_key.set(MeshPipelineKey::LOGARITHMIC_SHADOW_DEPTH, logarithmic_depth);
+            light_key.set(MeshPipelineKey::DUAL_PARABOLOID_SHADOW, dual_paraboloid);
+            light_key.set(
+                MeshPipelineKey::SHADOW_DEPTH_16,
+                shadow_depth_format
+                    .as_deref()
+                    .is_some_and(|format| *format == ShadowDepthFormat::Depth16),
+            );
             if let Some(current_key) =
                 light_key_cache.get_mut(&extracted_view_light.retained_view_entity)
             {
@@ -2139,6 +2740,11 @@ impl PhaseItemBatchSetKey for ShadowBatchSetKey {
 }
 
 /// Data used to bin each object in the shadow map phase.
+///
+/// Shadow casters are binned by mesh, so all the visible casters of a light that share a mesh and
+/// a [`ShadowBatchSetKey`] are drawn together as a single instanced draw, however their entities
+/// are ordered in the light's visible entity lists. Casters with
+/// [`NoAutomaticBatching`](bevy_render::batching::NoAutomaticBatching) are drawn individually.
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct ShadowBinKey {
     /// The object.
@@ -2367,3 +2973,143 @@ impl ShadowPassNode {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_render::{camera::Camera, view::InheritedVisibility};
+
+    #[test]
+    fn elliptical_spot_light_projection() {
+        let near_z = SpotLight::DEFAULT_SHADOW_MAP_NEAR_Z;
+
+        // A circular cone scales both axes equally.
+        let circular = spot_light_clip_from_view(0.5, 0.5, near_z);
+        assert!((circular.x_axis.x - circular.y_axis.y).abs() < 1e-6);
+
+        // A cone that is wider along X than along Y scales X less, so that the projection covers
+        // the wider angle, and each axis maps the edge of its cone to the edge of the shadow map.
+        let (angle_x, angle_y) = (0.8, 0.3);
+        let elliptical = spot_light_clip_from_view(angle_x, angle_y, near_z);
+        assert!(elliptical.x_axis.x < elliptical.y_axis.y);
+        assert!((elliptical.x_axis.x * ops::tan(angle_x) - 1.0).abs() < 1e-5);
+        assert!((elliptical.y_axis.y * ops::tan(angle_y) - 1.0).abs() < 1e-5);
+
+        let spot_light = SpotLight {
+            outer_angle: angle_x,
+            outer_angle_y: Some(angle_y),
+            ..Default::default()
+        };
+        assert_eq!(spot_light.outer_angles(), (angle_x, angle_y));
+        assert_eq!(spot_light.max_outer_angle(), angle_x);
+    }
+
+    #[test]
+    fn point_light_shadow_map_size_overrides_resource() {
+        let shadow_map = PointLightShadowMap {
+            size: 512,
+            ..Default::default()
+        };
+        let fill_light = PointLight::default();
+        let hero_light = PointLight {
+            shadow_map_size: Some(2048),
+            ..Default::default()
+        };
+        let small_light = PointLight {
+            shadow_map_size: Some(128),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            point_light_shadow_map_face_size(&shadow_map, [&fill_light]),
+            512
+        );
+        assert_eq!(
+            point_light_shadow_map_face_size(&shadow_map, [&fill_light, &hero_light]),
+            2048
+        );
+        assert_eq!(
+            point_light_shadow_map_face_size(&shadow_map, [&small_light]),
+            512
+        );
+    }
+
+    #[test]
+    fn shadow_maps_use_requested_depth_format() {
+        let size = Extent3d {
+            width: 1024,
+            height: 1024,
+            depth_or_array_layers: 6,
+        };
+        let descriptor = |format| shadow_map_texture_descriptor("shadow_map", size, format);
+
+        assert_eq!(
+            descriptor(ShadowDepthFormat::default()).format,
+            TextureFormat::Depth32Float
+        );
+        assert_eq!(
+            descriptor(ShadowDepthFormat::Depth16).format,
+            TextureFormat::Depth16Unorm
+        );
+        assert_eq!(descriptor(ShadowDepthFormat::Depth16).size, size);
+    }
+
+    #[test]
+    fn view_shadow_cascades_match_cascades() {
+        use bevy_render::render_resource::encase::UniformBuffer;
+
+        bevy_tasks::ComputeTaskPool::get_or_init(bevy_tasks::TaskPool::default);
+
+        let mut world = World::new();
+        world.init_resource::<DirectionalLightShadowMap>();
+        world.init_resource::<DirectionalLightShadowWarningThreshold>();
+        let view = world
+            .spawn((
+                Camera::default(),
+                Projection::default(),
+                GlobalTransform::IDENTITY,
+            ))
+            .id();
+        let light = world
+            .spawn((
+                DirectionalLight {
+                    shadows_enabled: true,
+                    ..Default::default()
+                },
+                InheritedVisibility::VISIBLE,
+            ))
+            .id();
+        let build = world.register_system(build_directional_light_cascades);
+        world.run_system(build).unwrap();
+
+        let cascades = &world.get::<Cascades>(light).unwrap().cascades[&view];
+        let bounds = &world.get::<CascadeShadowConfig>(light).unwrap().bounds;
+        let mut view_shadow_cascades = GpuViewShadowCascades {
+            n_lights: 1,
+            ..Default::default()
+        };
+        view_shadow_cascades.lights[0] = GpuDirectionalLightCascades::new(cascades, bounds, 2);
+
+        let mut buffer = UniformBuffer::new(Vec::<u8>::new());
+        buffer.write(&view_shadow_cascades).unwrap();
+        let bytes = buffer.into_inner();
+        let read_f32 =
+            |offset: usize| f32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+        let read_u32 =
+            |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+        // Each cascade is a 64-byte matrix and two floats, padded to 80 bytes.
+        let num_cascades = cascades.len().min(MAX_CASCADES_PER_LIGHT);
+        assert!(num_cascades > 0);
+        for (index, cascade) in cascades.iter().take(num_cascades).enumerate() {
+            let offset = index * 80;
+            let clip_from_world: Vec<_> = (0..16).map(|i| read_f32(offset + i * 4)).collect();
+            assert_eq!(clip_from_world, cascade.clip_from_world.to_cols_array());
+            assert_eq!(read_f32(offset + 64), cascade.texel_size);
+            assert_eq!(read_f32(offset + 68), bounds[index]);
+        }
+        let cascades_size = MAX_CASCADES_PER_LIGHT * 80;
+        assert_eq!(read_u32(cascades_size), num_cascades as u32);
+        assert_eq!(read_u32(cascades_size + 4), 2);
+    }
+}
@@ -39,8 +39,8 @@ use bevy_render::{
     sync_world::MainEntityHashSet,
     texture::{DefaultImageSampler, GpuImage},
     view::{
-        self, NoFrustumCulling, NoIndirectDrawing, RenderVisibilityRanges, RetainedViewEntity,
-        ViewTarget, ViewUniformOffset, ViewVisibility, VisibilityRange,
+        self, NoFrustumCulling, NoIndirectDrawing, RenderLayers, RenderVisibilityRanges,
+        RetainedViewEntity, ViewTarget, ViewUniformOffset, ViewVisibility, VisibilityRange,
     },
     Extract,
 };
@@ -387,6 +387,9 @@ pub fn check_views_need_specialization(
             ShadowFilteringMethod::Temporal => {
                 view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_TEMPORAL;
             }
+            ShadowFilteringMethod::Vogel { .. } => {
+                view_key |= MeshPipelineKey::SHADOW_FILTER_METHOD_VOGEL;
+            }
         }
 
         if !view.hdr {
@@ -601,6 +604,13 @@ bitflags::bitflags! {
         ///
         /// This will be `u16::MAX` if this mesh has no LOD.
         const LOD_INDEX_MASK              = (1 << 16) - 1;
+        /// Bitmask for the first 8 render layers of this mesh, used to
+        /// check the [`ShadowLayers`] of lights.
+        const RECEIVER_LAYERS_MASK        = 0xFF << 16;
+        /// Disables ambient light for this mesh.
+        ///
+        /// This corresponds to the [`NoAmbientLight`] component.
+        const NO_AMBIENT_LIGHT            = 1 << 27;
         /// Disables frustum culling for this mesh.
         ///
         /// This corresponds to the
@@ -623,6 +633,8 @@ impl MeshFlags {
         no_frustum_culling: bool,
         not_shadow_receiver: bool,
         transmitted_receiver: bool,
+        no_ambient_light: bool,
+        render_layers: Option<&RenderLayers>,
     ) -> MeshFlags {
         let mut mesh_flags = if not_shadow_receiver {
             MeshFlags::empty()
@@ -635,6 +647,9 @@ impl MeshFlags {
         if transmitted_receiver {
             mesh_flags |= MeshFlags::TRANSMITTED_SHADOW_RECEIVER;
         }
+        if no_ambient_light {
+            mesh_flags |= MeshFlags::NO_AMBIENT_LIGHT;
+        }
         if transform.affine().matrix3.determinant().is_sign_positive() {
             mesh_flags |= MeshFlags::SIGN_DETERMINANT_MODEL_3X3;
         }
@@ -646,11 +661,29 @@ impl MeshFlags {
         mesh_flags |=
             MeshFlags::from_bits_retain((lod_index_bits as u32) << MeshFlags::LOD_INDEX_SHIFT);
 
+        let receiver_layers = pack_shadow_layers(render_layers.unwrap_or_default());
+        mesh_flags |= MeshFlags::from_bits_retain(
+            u32::from(receiver_layers) << MeshFlags::RECEIVER_LAYERS_SHIFT,
+        );
+
         mesh_flags
     }
 
+    /// Returns true if this mesh receives the shadows of a light with the given
+    /// shadow layers, as packed from its [`ShadowLayers`].
+    ///
+    /// This matches the check that the shaders make.
+    pub fn receives_shadows_from_layers(&self, shadow_layers: u8) -> bool {
+        let receiver_layers = ((self.bits() & MeshFlags::RECEIVER_LAYERS_MASK.bits())
+            >> MeshFlags::RECEIVER_LAYERS_SHIFT) as u8;
+        shadow_layers == u8::MAX || shadow_layers & receiver_layers != 0
+    }
+
     /// The first bit of the LOD index.
     pub const LOD_INDEX_SHIFT: u32 = 0;
+
+    /// The first bit of the receiver render layers.
+    pub const RECEIVER_LAYERS_SHIFT: u32 = 16;
 }
 
 bitflags::bitflags! {
@@ -1308,9 +1341,11 @@ pub fn extract_meshes_for_cpu_building(
             Has<NoFrustumCulling>,
             Has<NotShadowReceiver>,
             Has<TransmittedShadowReceiver>,
+            Has<NoAmbientLight>,
             Has<NotShadowCaster>,
             Has<NoAutomaticBatching>,
             Has<VisibilityRange>,
+            Option<&RenderLayers>,
         )>,
     >,
 ) {
@@ -1327,9 +1362,11 @@ pub fn extract_meshes_for_cpu_building(
             no_frustum_culling,
             not_shadow_receiver,
             transmitted_receiver,
+            no_ambient_light,
             not_shadow_caster,
             no_automatic_batching,
             visibility_range,
+            render_layers,
         )| {
             if !view_visibility.get() {
                 return;
@@ -1346,6 +1383,8 @@ pub fn extract_meshes_for_cpu_building(
                 no_frustum_culling,
                 not_shadow_receiver,
                 transmitted_receiver,
+                no_ambient_light,
+                render_layers,
             );
 
             let mesh_material = mesh_material_ids.mesh_material(MainEntity::from(entity));
@@ -1410,8 +1449,14 @@ type GpuMeshExtractionQuery = (
     Read<Mesh3d>,
     Option<Read<MeshTag>>,
     Has<NoFrustumCulling>,
-    Has<NotShadowReceiver>,
-    Has<TransmittedShadowReceiver>,
+    // How the mesh receives lighting, nested to stay within the `QueryData`
+    // tuple limit.
+    (
+        Has<NotShadowReceiver>,
+        Has<TransmittedShadowReceiver>,
+        Has<NoAmbientLight>,
+        Option<Read<RenderLayers>>,
+    ),
     Has<NotShadowCaster>,
     Has<NoAutomaticBatching>,
     Has<VisibilityRange>,
@@ -1442,10 +1487,12 @@ pub fn extract_meshes_for_gpu_building(
                 Changed<NoFrustumCulling>,
                 Changed<NotShadowReceiver>,
                 Changed<TransmittedShadowReceiver>,
+                Changed<NoAmbientLight>,
                 Changed<NotShadowCaster>,
                 Changed<NoAutomaticBatching>,
                 Changed<VisibilityRange>,
                 Changed<SkinnedMesh>,
+                Changed<RenderLayers>,
             )>,
         >,
     >,
@@ -1535,8 +1582,7 @@ fn extract_mesh_for_gpu_building(
         mesh,
         tag,
         no_frustum_culling,
-        not_shadow_receiver,
-        transmitted_receiver,
+        (not_shadow_receiver, transmitted_receiver, no_ambient_light, render_layers),
         not_shadow_caster,
         no_automatic_batching,
         visibility_range,
@@ -1562,6 +1608,8 @@ fn extract_mesh_for_gpu_building(
         no_frustum_culling,
         not_shadow_receiver,
         transmitted_receiver,
+        no_ambient_light,
+        render_layers,
     );
 
     let shared = RenderMeshInstanceShared::for_gpu_building(
@@ -2076,7 +2124,10 @@ bitflags::bitflags! {
         const HAS_PREVIOUS_MORPH                = 1 << 19;
         const OIT_ENABLED                       = 1 << 20;
         const DISTANCE_FOG                      = 1 << 21;
-        const LAST_FLAG                         = Self::DISTANCE_FOG.bits();
+        const LOGARITHMIC_SHADOW_DEPTH          = 1 << 22; // Writes logarithmic depth, for `Cascade::logarithmic_depth`
+        const DUAL_PARABOLOID_SHADOW            = 1 << 23; // Warps shadow casters onto a paraboloid, for `PointShadowTechnique::DualParaboloid`
+        const SHADOW_DEPTH_16                   = 1 << 24; // Renders into 16-bit shadow maps, for `ShadowDepthFormat::Depth16`
+        const LAST_FLAG                         = Self::SHADOW_DEPTH_16.bits();
 
         // Bitfields
         const MSAA_RESERVED_BITS                = Self::MSAA_MASK_BITS << Self::MSAA_SHIFT_BITS;
@@ -2099,6 +2150,7 @@ bitflags::bitflags! {
         const SHADOW_FILTER_METHOD_HARDWARE_2X2  = 0 << Self::SHADOW_FILTER_METHOD_SHIFT_BITS;
         const SHADOW_FILTER_METHOD_GAUSSIAN      = 1 << Self::SHADOW_FILTER_METHOD_SHIFT_BITS;
         const SHADOW_FILTER_METHOD_TEMPORAL      = 2 << Self::SHADOW_FILTER_METHOD_SHIFT_BITS;
+        const SHADOW_FILTER_METHOD_VOGEL         = 3 << Self::SHADOW_FILTER_METHOD_SHIFT_BITS;
         const VIEW_PROJECTION_RESERVED_BITS     = Self::VIEW_PROJECTION_MASK_BITS << Self::VIEW_PROJECTION_SHIFT_BITS;
         const VIEW_PROJECTION_NONSTANDARD       = 0 << Self::VIEW_PROJECTION_SHIFT_BITS;
         const VIEW_PROJECTION_PERSPECTIVE       = 1 << Self::VIEW_PROJECTION_SHIFT_BITS;
@@ -2522,6 +2574,8 @@ impl SpecializedMeshPipeline for MeshPipeline {
             shader_defs.push("SHADOW_FILTER_METHOD_GAUSSIAN".into());
         } else if shadow_filter_method == MeshPipelineKey::SHADOW_FILTER_METHOD_TEMPORAL {
             shader_defs.push("SHADOW_FILTER_METHOD_TEMPORAL".into());
+        } else if shadow_filter_method == MeshPipelineKey::SHADOW_FILTER_METHOD_VOGEL {
+            shader_defs.push("SHADOW_FILTER_METHOD_VOGEL".into());
         }
 
         let blur_quality =
@@ -3291,11 +3345,61 @@ impl<P: PhaseItem> RenderCommand<P> for DrawMesh {
 
 #[cfg(test)]
 mod tests {
-    use super::MeshPipelineKey;
+    use super::{MeshFlags, MeshPipelineKey};
+    use crate::pack_shadow_layers;
+    use bevy_render::view::RenderLayers;
+    use bevy_transform::components::GlobalTransform;
     #[test]
     fn mesh_key_msaa_samples() {
         for i in [1, 2, 4, 8, 16, 32, 64, 128] {
             assert_eq!(MeshPipelineKey::from_msaa_samples(i).msaa_samples(), i);
         }
     }
+
+    #[test]
+    fn no_ambient_light_sets_mesh_flag() {
+        let flags = |no_ambient_light| {
+            MeshFlags::from_components(
+                &GlobalTransform::IDENTITY,
+                None,
+                false,
+                false,
+                false,
+                no_ambient_light,
+                None,
+            )
+        };
+        assert!(flags(true).contains(MeshFlags::NO_AMBIENT_LIGHT));
+        assert!(!flags(false).contains(MeshFlags::NO_AMBIENT_LIGHT));
+        // The flag doesn't overlap the LOD index.
+        assert!(!MeshFlags::LOD_INDEX_MASK.intersects(MeshFlags::NO_AMBIENT_LIGHT));
+    }
+
+    #[test]
+    fn shadow_layers_exclude_receivers_on_other_layers() {
+        let flags = |render_layers: &RenderLayers| {
+            MeshFlags::from_components(
+                &GlobalTransform::IDENTITY,
+                None,
+                false,
+                false,
+                false,
+                false,
+                Some(render_layers),
+            )
+        };
+        let on_layer_1 = flags(&RenderLayers::layer(1));
+        let on_layer_2 = flags(&RenderLayers::layer(2));
+        let shadow_layers = pack_shadow_layers(&RenderLayers::layer(1));
+
+        // The receiver on the excluded layer is still lit, but doesn't receive the shadows.
+        assert!(on_layer_2.contains(MeshFlags::SHADOW_RECEIVER));
+        assert!(!on_layer_2.receives_shadows_from_layers(shadow_layers));
+        assert!(on_layer_1.receives_shadows_from_layers(shadow_layers));
+        // Lights without shadow layers shadow every layer, even the ones that aren't told apart.
+        assert!(on_layer_2.receives_shadows_from_layers(u8::MAX));
+        assert!(flags(&RenderLayers::layer(20)).receives_shadows_from_layers(u8::MAX));
+        // The layers don't overlap the LOD index.
+        assert!(!MeshFlags::LOD_INDEX_MASK.intersects(MeshFlags::RECEIVER_LAYERS_MASK));
+    }
 }
@@ -40,6 +40,9 @@ struct MorphWeights {
 
 // [2^0, 2^16)
 const MESH_FLAGS_VISIBILITY_RANGE_INDEX_BITS: u32     = (1u << 16u) - 1u;
+// [2^16, 2^24): the first 8 render layers of the mesh
+const MESH_FLAGS_RECEIVER_LAYERS_SHIFT: u32           = 16u;
+const MESH_FLAGS_NO_AMBIENT_LIGHT_BIT: u32            = 1u << 27u;
 const MESH_FLAGS_NO_FRUSTUM_CULLING_BIT: u32          = 1u << 28u;
 const MESH_FLAGS_SHADOW_RECEIVER_BIT: u32             = 1u << 29u;
 const MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT: u32 = 1u << 30u;
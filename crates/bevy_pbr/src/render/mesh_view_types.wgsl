@@ -3,6 +3,7 @@
 struct ClusterableObject {
     // For point lights: the lower-right 2x2 values of the projection matrix [2][2] [2][3] [3][2] [3][3]
     // For spot lights: the direction (x,z), spot_scale and spot_offset
+    // For local ambients: the half extents of the bounds
     light_custom_data: vec4<f32>,
     color_inverse_square_range: vec4<f32>,
     position_radius: vec4<f32>,
@@ -11,21 +12,36 @@ struct ClusterableObject {
     shadow_depth_bias: f32,
     shadow_normal_bias: f32,
     spot_light_tan_angle: f32,
+    // Positive for the soft shadow size, or the negated world-space shadow blur radius
     soft_shadow_size: f32,
     shadow_map_near_z: f32,
     decal_index: u32,
-    pad: f32,
+    // The shadow tint, packed as RGBA8 in linear space, with the shadow strength in alpha
+    shadow_tint: u32,
 };
 
 const POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT: u32                    = 1u << 0u;
 const POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE: u32                  = 1u << 1u;
 const POINT_LIGHT_FLAGS_VOLUMETRIC_BIT: u32                         = 1u << 2u;
 const POINT_LIGHT_FLAGS_AFFECTS_LIGHTMAPPED_MESH_DIFFUSE_BIT: u32   = 1u << 3u;
+const POINT_LIGHT_FLAGS_STABLE_SHADOWS_BIT: u32                     = 1u << 4u;
+const POINT_LIGHT_FLAGS_DUAL_PARABOLOID_SHADOWS_BIT: u32            = 1u << 5u;
+// Bits 8 to 15 hold the first 8 render layers that receive the light's shadows.
+const POINT_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT: u32                    = 8u;
+// The upper 16 bits hold the aspect ratio of elliptical spot lights as 8.8 fixed point, or zero
+// for circular spot lights.
+const POINT_LIGHT_FLAGS_SPOT_LIGHT_ASPECT_RATIO_SHIFT: u32          = 16u;
 
 struct DirectionalCascade {
     clip_from_world: mat4x4<f32>,
     texel_size: f32,
     far_bound: f32,
+    // The world-space depth bias, scaled to the texel size of this cascade
+    depth_bias: f32,
+    // Nonzero if the shadow map stores logarithmic rather than linear depth
+    logarithmic_depth: u32,
+    // The proportion of overlap between the next cascade and this one
+    overlap_proportion: f32,
 }
 
 struct DirectionalLight {
@@ -34,23 +50,52 @@ struct DirectionalLight {
     direction_to_light: vec3<f32>,
     // 'flags' is a bit field indicating various options. u32 is 32 bits so we have up to 32 options.
     flags: u32,
+    // Positive for the soft shadow size, or the negated world-space shadow blur radius
     soft_shadow_size: f32,
     shadow_depth_bias: f32,
     shadow_normal_bias: f32,
     num_cascades: u32,
-    cascades_overlap_proportion: f32,
     depth_texture_base_index: u32,
     decal_index: u32,
+    // The shadow tint, packed as RGBA8 in linear space, with the shadow strength in alpha
+    shadow_tint: u32,
+    shadow_contact_darkening: f32,
 };
 
 const DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT: u32                  = 1u << 0u;
 const DIRECTIONAL_LIGHT_FLAGS_VOLUMETRIC_BIT: u32                       = 1u << 1u;
 const DIRECTIONAL_LIGHT_FLAGS_AFFECTS_LIGHTMAPPED_MESH_DIFFUSE_BIT: u32 = 1u << 2u;
+const DIRECTIONAL_LIGHT_FLAGS_STABLE_SHADOWS_BIT: u32                  = 1u << 3u;
+// Bits 8 to 15 hold the first 8 render layers that receive the light's shadows.
+const DIRECTIONAL_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT: u32                  = 8u;
+
+// The shadow cascades of the directional lights of a view, for custom shaders that sample
+// directional light shadow maps themselves. See `GpuViewShadowCascades` in
+// bevy_pbr/src/render/light.rs.
+struct ShadowCascade {
+    clip_from_world: mat4x4<f32>,
+    texel_size: f32,
+    far_bound: f32,
+};
+
+struct DirectionalLightCascades {
+    cascades: array<ShadowCascade, #{MAX_CASCADES_PER_LIGHT}>,
+    num_cascades: u32,
+    depth_texture_base_index: u32,
+};
+
+struct ViewShadowCascades {
+    lights: array<DirectionalLightCascades, #{MAX_DIRECTIONAL_LIGHTS}u>,
+    n_lights: u32,
+};
 
 struct Lights {
     // NOTE: this array size must be kept in sync with the constants defined in bevy_pbr/src/render/light.rs
     directional_lights: array<DirectionalLight, #{MAX_DIRECTIONAL_LIGHTS}u>,
+    // The ambient color for surfaces facing up
     ambient_color: vec4<f32>,
+    // The ambient color for surfaces facing down
+    ambient_ground_color: vec4<f32>,
     // x/y/z dimensions and n_clusters in w
     cluster_dimensions: vec4<u32>,
     // xy are vec2<f32>(cluster_dimensions.xy) / vec2<f32>(view.width, view.height)
@@ -67,7 +112,12 @@ struct Lights {
     n_directional_lights: u32,
     spot_light_shadowmap_offset: i32,
     environment_map_smallest_specular_mip_level: u32,
-    environment_map_intensity: f32,
+    // 1 / tan(fov / 2) for the field of view of point light cubemap faces
+    point_light_cube_face_scale: f32,
+    // The number of samples taken by `SHADOW_FILTER_METHOD_VOGEL`
+    vogel_shadow_sample_count: u32,
+    // The frame index used by the noise of temporal shadow filtering
+    temporal_shadow_frame: u32,
 };
 
 struct Fog {
@@ -177,10 +227,14 @@ struct ClusteredDecal {
     local_from_world: mat4x4<f32>,
     image_index: i32,
     tag: u32,
-    pad_a: u32,
-    pad_b: u32,
+    // Only used by spot projectors; 0xFFFFFFFF if there's no texture
+    normal_map_index: u32,
+    metallic_roughness_index: u32,
 }
 
+const SPOT_PROJECTOR_DECAL_TAG: u32 = 1u;
+const IES_PROFILE_DECAL_TAG: u32 = 16u;
+
 struct ClusteredDecals {
     decals: array<ClusteredDecal>,
 }
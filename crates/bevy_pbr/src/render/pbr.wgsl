@@ -2,7 +2,7 @@
     pbr_types,
     pbr_functions::alpha_discard,
     pbr_fragment::pbr_input_from_standard_material,
-    decal::clustered::apply_decal_base_color,
+    decal::clustered::{apply_decal_base_color, apply_spot_projectors},
 }
 
 #ifdef PREPASS_PIPELINE
@@ -72,6 +72,9 @@ fn fragment(
         pbr_input.material.base_color
     );
 
+    // spot projectors
+    apply_spot_projectors(in.position.xy, &pbr_input);
+
 #ifdef PREPASS_PIPELINE
     // write the gbuffer, lighting pass id, and optionally normal and motion_vector textures
     let out = deferred_output(in, pbr_input);
@@ -1,8 +1,54 @@
 #define_import_path bevy_pbr::ambient
 
 #import bevy_pbr::{
+    clustered_forward,
+    clustered_forward::ClusterableObjectIndexRanges,
     lighting::{EnvBRDFApprox, F_AB},
-    mesh_view_bindings::lights,
+    mesh_view_bindings::{clusterable_objects, lights},
+}
+
+// Returns the ambient color at `world_position` for a surface with the normal
+// `world_normal`, blending the global ambient light with any `LocalAmbient`
+// volumes in the fragment's cluster that contain it.
+//
+// The global ambient light is blended from its ground color for normals facing
+// down to its sky color for normals facing up, which must be kept in sync with
+// `AmbientLight::color_for_normal`. Both colors are the same unless the
+// `AmbientLight` has a `sky_color` or `ground_color`.
+//
+// Each volume's weight fades from 1 in the central half of its bounds along each
+// axis to 0 at the bounds. Where the weights sum to less than 1, the global
+// ambient light makes up the remainder; otherwise, the volumes are averaged.
+//
+// This must be kept in sync with `LocalAmbient::weight_at` and
+// `blend_local_ambients`.
+fn ambient_color_at(
+    world_position: vec3<f32>,
+    world_normal: vec3<f32>,
+    clusterable_object_index_ranges: ptr<function, ClusterableObjectIndexRanges>,
+) -> vec3<f32> {
+    var total_weight = 0.0;
+    var local_color = vec3(0.0);
+    for (var i = (*clusterable_object_index_ranges).first_local_ambient_offset;
+            i < (*clusterable_object_index_ranges).last_clusterable_object_index_offset;
+            i = i + 1u) {
+        let local_ambient = &clusterable_objects.data[clustered_forward::get_clusterable_object_id(i)];
+        // The center is stored in `position_radius`, the half extents in
+        // `light_custom_data`, and the color in `color_inverse_square_range`.
+        let distance = abs(world_position - (*local_ambient).position_radius.xyz) /
+            max((*local_ambient).light_custom_data.xyz, vec3(1.1920929e-7));
+        let axis_weights = vec3(1.0) - smoothstep(vec3(0.5), vec3(1.0), distance);
+        let weight = axis_weights.x * axis_weights.y * axis_weights.z;
+        total_weight += weight;
+        local_color += (*local_ambient).color_inverse_square_range.rgb * weight;
+    }
+    let global_color = mix(
+        lights.ambient_ground_color.rgb,
+        lights.ambient_color.rgb,
+        world_normal.y * 0.5 + 0.5,
+    );
+    return (local_color + global_color * max(1.0 - total_weight, 0.0)) /
+        max(total_weight, 1.0);
 }
 
 // A precomputed `NdotV` is provided because it is computed regardless,
@@ -16,6 +62,7 @@ fn ambient_light(
     specular_color: vec3<f32>,
     perceptual_roughness: f32,
     occlusion: vec3<f32>,
+    clusterable_object_index_ranges: ptr<function, ClusterableObjectIndexRanges>,
 ) -> vec3<f32> {
     let diffuse_ambient = EnvBRDFApprox(diffuse_color, F_AB(1.0, NdotV));
     let specular_ambient = EnvBRDFApprox(specular_color, F_AB(perceptual_roughness, NdotV));
@@ -25,5 +72,7 @@ fn ambient_light(
     // See: https://google.github.io/filament/Filament.html#specularocclusion
     let specular_occlusion = saturate(dot(specular_color, vec3(50.0 * 0.33)));
 
-    return (diffuse_ambient + specular_ambient * specular_occlusion) * lights.ambient_color.rgb * occlusion;
+    return (diffuse_ambient + specular_ambient * specular_occlusion) *
+        ambient_color_at(world_position.xyz, world_normal, clusterable_object_index_ranges) *
+        occlusion;
 }
@@ -623,6 +623,14 @@ fn pbr_input_from_standard_material(
 #endif
         pbr_input.material.diffuse_transmission = diffuse_transmission;
 
+#ifdef BINDLESS
+        pbr_input.material.transmission_shadow_samples =
+            pbr_bindings::material_array[material_indices[slot].material].transmission_shadow_samples;
+#else   // BINDLESS
+        pbr_input.material.transmission_shadow_samples =
+            pbr_bindings::material.transmission_shadow_samples;
+#endif  // BINDLESS
+
         var diffuse_occlusion: vec3<f32> = vec3(1.0);
         var specular_occlusion: f32 = 1.0;
 #ifdef VERTEX_UVS
@@ -12,7 +12,10 @@
     shadows,
     ambient,
     irradiance_volume,
-    mesh_types::{MESH_FLAGS_SHADOW_RECEIVER_BIT, MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT},
+    mesh_types::{
+        MESH_FLAGS_NO_AMBIENT_LIGHT_BIT, MESH_FLAGS_RECEIVER_LAYERS_SHIFT,
+        MESH_FLAGS_SHADOW_RECEIVER_BIT, MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT,
+    },
 }
 #import bevy_render::maths::{E, powsafe}
 
@@ -58,6 +61,17 @@ const DITHER_THRESHOLD_MAP: vec4<u32> = vec4(
     0x050d070f
 );
 
+// Returns true if the shadow layers of a light, in the lowest 8 bits of
+// `light_shadow_layers`, include one of the first 8 render layers of a mesh.
+//
+// A light without `ShadowLayers` has all 8 bits set, which matches every mesh,
+// even meshes that are only on higher render layers.
+fn shadow_layers_match(light_shadow_layers: u32, mesh_flags: u32) -> bool {
+    let light_layers = light_shadow_layers & 0xffu;
+    let mesh_layers = (mesh_flags >> MESH_FLAGS_RECEIVER_LAYERS_SHIFT) & 0xffu;
+    return light_layers == 0xffu || (light_layers & mesh_layers) != 0u;
+}
+
 // Processes a visibility range dither value and discards the fragment if
 // needed.
 //
@@ -271,6 +285,20 @@ fn calculate_diffuse_color(
         (1.0 - diffuse_transmission);
 }
 
+// Returns the position of a transmitted shadow sample, the `sample_index`th of `sample_count`
+// samples spread evenly through the `thickness` of the material below `world_position`. The last
+// sample lies on the diffuse transmission lobe.
+fn transmitted_shadow_sample_position(
+    world_position: vec4<f32>,
+    world_normal: vec3<f32>,
+    thickness: f32,
+    sample_index: u32,
+    sample_count: u32,
+) -> vec4<f32> {
+    let depth = thickness * f32(sample_index) / f32(sample_count);
+    return world_position - vec4<f32>(world_normal, 0.0) * depth;
+}
+
 // Remapping [0,1] reflectance to F0
 // See https://google.github.io/filament/Filament.html#materialsystem/parameterization/remapping
 fn calculate_F0(base_color: vec3<f32>, metallic: f32, reflectance: vec3<f32>) -> vec3<f32> {
@@ -328,6 +356,10 @@ fn apply_pbr_lighting(
     // Calculate the world position of the second Lambertian lobe used for diffuse transmission, by subtracting material thickness
     let diffuse_transmissive_lobe_world_position = in.world_position - vec4<f32>(in.world_normal, 0.0) * thickness;
 
+    // Transmitted shadows are sampled at evenly spaced depths through the thickness of the
+    // material, the last one at the diffuse transmission lobe, and averaged.
+    let transmission_shadow_samples = max(in.material.transmission_shadow_samples, 1u);
+
     let F0 = calculate_F0(output_color.rgb, metallic, reflectance);
     let F_ab = lighting::F_AB(perceptual_roughness, NdotV);
 
@@ -418,12 +450,16 @@ fn apply_pbr_lighting(
 
         var shadow: f32 = 1.0;
         if ((in.flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u
-                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::clusterable_objects.data[light_id].flags >> mesh_view_types::POINT_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
             shadow = shadows::fetch_point_shadow(light_id, in.world_position, in.world_normal);
         }
 
         let light_contrib = lighting::point_light(light_id, &lighting_input, enable_diffuse, true);
-        direct_light += light_contrib * shadow;
+        direct_light += light_contrib * shadows::shadow_tint_factor(
+            shadow,
+            view_bindings::clusterable_objects.data[light_id].shadow_tint,
+        );
 
 #ifdef STANDARD_MATERIAL_DIFFUSE_TRANSMISSION
         // NOTE: We use the diffuse transmissive color, the second Lambertian lobe's calculated
@@ -437,13 +473,23 @@ fn apply_pbr_lighting(
         // F0 = vec3<f32>(0.0)
         var transmitted_shadow: f32 = 1.0;
         if ((in.flags & (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)) == (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)
-                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
-            transmitted_shadow = shadows::fetch_point_shadow(light_id, diffuse_transmissive_lobe_world_position, -in.world_normal);
+                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::clusterable_objects.data[light_id].flags >> mesh_view_types::POINT_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
+            transmitted_shadow = 0.0;
+            for (var sample_index = 1u; sample_index <= transmission_shadow_samples; sample_index += 1u) {
+                let sample_position = transmitted_shadow_sample_position(
+                    in.world_position, in.world_normal, thickness, sample_index, transmission_shadow_samples);
+                transmitted_shadow += shadows::fetch_point_shadow(light_id, sample_position, -in.world_normal);
+            }
+            transmitted_shadow /= f32(transmission_shadow_samples);
         }
 
         let transmitted_light_contrib =
             lighting::point_light(light_id, &transmissive_lighting_input, enable_diffuse, true);
-        transmitted_light += transmitted_light_contrib * transmitted_shadow;
+        transmitted_light += transmitted_light_contrib * shadows::shadow_tint_factor(
+            transmitted_shadow,
+            view_bindings::clusterable_objects.data[light_id].shadow_tint,
+        );
 #endif
     }
 
@@ -466,7 +512,8 @@ fn apply_pbr_lighting(
         var shadow: f32 = 1.0;
         if ((in.flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u
                 && (view_bindings::clusterable_objects.data[light_id].flags &
-                    mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+                    mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::clusterable_objects.data[light_id].flags >> mesh_view_types::POINT_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
             shadow = shadows::fetch_spot_shadow(
                 light_id,
                 in.world_position,
@@ -476,7 +523,10 @@ fn apply_pbr_lighting(
         }
 
         let light_contrib = lighting::spot_light(light_id, &lighting_input, enable_diffuse);
-        direct_light += light_contrib * shadow;
+        direct_light += light_contrib * shadows::shadow_tint_factor(
+            shadow,
+            view_bindings::clusterable_objects.data[light_id].shadow_tint,
+        );
 
 #ifdef STANDARD_MATERIAL_DIFFUSE_TRANSMISSION
         // NOTE: We use the diffuse transmissive color, the second Lambertian lobe's calculated
@@ -490,18 +540,28 @@ fn apply_pbr_lighting(
         // F0 = vec3<f32>(0.0)
         var transmitted_shadow: f32 = 1.0;
         if ((in.flags & (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)) == (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)
-                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
-            transmitted_shadow = shadows::fetch_spot_shadow(
-                light_id,
-                diffuse_transmissive_lobe_world_position,
-                -in.world_normal,
-                view_bindings::clusterable_objects.data[light_id].shadow_map_near_z,
-            );
+                && (view_bindings::clusterable_objects.data[light_id].flags & mesh_view_types::POINT_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::clusterable_objects.data[light_id].flags >> mesh_view_types::POINT_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
+            transmitted_shadow = 0.0;
+            for (var sample_index = 1u; sample_index <= transmission_shadow_samples; sample_index += 1u) {
+                let sample_position = transmitted_shadow_sample_position(
+                    in.world_position, in.world_normal, thickness, sample_index, transmission_shadow_samples);
+                transmitted_shadow += shadows::fetch_spot_shadow(
+                    light_id,
+                    sample_position,
+                    -in.world_normal,
+                    view_bindings::clusterable_objects.data[light_id].shadow_map_near_z,
+                );
+            }
+            transmitted_shadow /= f32(transmission_shadow_samples);
         }
 
         let transmitted_light_contrib =
             lighting::spot_light(light_id, &transmissive_lighting_input, enable_diffuse);
-        transmitted_light += transmitted_light_contrib * transmitted_shadow;
+        transmitted_light += transmitted_light_contrib * shadows::shadow_tint_factor(
+            transmitted_shadow,
+            view_bindings::clusterable_objects.data[light_id].shadow_tint,
+        );
 #endif
     }
 
@@ -525,7 +585,8 @@ fn apply_pbr_lighting(
 
         var shadow: f32 = 1.0;
         if ((in.flags & MESH_FLAGS_SHADOW_RECEIVER_BIT) != 0u
-                && (view_bindings::lights.directional_lights[i].flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
+                && (view_bindings::lights.directional_lights[i].flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::lights.directional_lights[i].flags >> mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
             shadow = shadows::fetch_directional_shadow(i, in.world_position, in.world_normal, view_z);
         }
 
@@ -534,7 +595,7 @@ fn apply_pbr_lighting(
 #ifdef DIRECTIONAL_LIGHT_SHADOW_MAP_DEBUG_CASCADES
         light_contrib = shadows::cascade_debug_visualization(light_contrib, i, view_z);
 #endif
-        direct_light += light_contrib * shadow;
+        direct_light += light_contrib * shadows::shadow_tint_factor(shadow, (*light).shadow_tint);
 
 #ifdef STANDARD_MATERIAL_DIFFUSE_TRANSMISSION
         // NOTE: We use the diffuse transmissive color, the second Lambertian lobe's calculated
@@ -548,13 +609,21 @@ fn apply_pbr_lighting(
         // F0 = vec3<f32>(0.0)
         var transmitted_shadow: f32 = 1.0;
         if ((in.flags & (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)) == (MESH_FLAGS_SHADOW_RECEIVER_BIT | MESH_FLAGS_TRANSMITTED_SHADOW_RECEIVER_BIT)
-                && (view_bindings::lights.directional_lights[i].flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u) {
-            transmitted_shadow = shadows::fetch_directional_shadow(i, diffuse_transmissive_lobe_world_position, -in.world_normal, view_z);
+                && (view_bindings::lights.directional_lights[i].flags & mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOWS_ENABLED_BIT) != 0u
+                && shadow_layers_match(view_bindings::lights.directional_lights[i].flags >> mesh_view_types::DIRECTIONAL_LIGHT_FLAGS_SHADOW_LAYERS_SHIFT, in.flags)) {
+            transmitted_shadow = 0.0;
+            for (var sample_index = 1u; sample_index <= transmission_shadow_samples; sample_index += 1u) {
+                let sample_position = transmitted_shadow_sample_position(
+                    in.world_position, in.world_normal, thickness, sample_index, transmission_shadow_samples);
+                transmitted_shadow += shadows::fetch_directional_shadow(i, sample_position, -in.world_normal, view_z);
+            }
+            transmitted_shadow /= f32(transmission_shadow_samples);
         }
 
         let transmitted_light_contrib =
             lighting::directional_light(i, &transmissive_lighting_input, enable_diffuse);
-        transmitted_light += transmitted_light_contrib * transmitted_shadow;
+        transmitted_light +=
+            transmitted_light_contrib * shadows::shadow_tint_factor(transmitted_shadow, (*light).shadow_tint);
 #endif
     }
 
@@ -567,7 +636,9 @@ fn apply_pbr_lighting(
     // NdotV = 1.0;
     // F0 = vec3<f32>(0.0)
     // diffuse_occlusion = vec3<f32>(1.0)
-    transmitted_light += ambient::ambient_light(diffuse_transmissive_lobe_world_position, -in.N, -in.V, 1.0, diffuse_transmissive_color, vec3<f32>(0.0), 1.0, vec3<f32>(1.0));
+    if ((in.flags & MESH_FLAGS_NO_AMBIENT_LIGHT_BIT) == 0u) {
+        transmitted_light += ambient::ambient_light(diffuse_transmissive_lobe_world_position, -in.N, -in.V, 1.0, diffuse_transmissive_color, vec3<f32>(0.0), 1.0, vec3<f32>(1.0), &clusterable_object_index_ranges);
+    }
 #endif
 
     // Diffuse indirect lighting can come from a variety of sources. The
@@ -641,8 +712,10 @@ fn apply_pbr_lighting(
 
 #endif  // ENVIRONMENT_MAP
 
-    // Ambient light (indirect)
-    indirect_light += ambient::ambient_light(in.world_position, in.N, in.V, NdotV, diffuse_color, F0, perceptual_roughness, diffuse_occlusion);
+    // Ambient light (indirect), unless the mesh opted out with `NoAmbientLight`
+    if ((in.flags & MESH_FLAGS_NO_AMBIENT_LIGHT_BIT) == 0u) {
+        indirect_light += ambient::ambient_light(in.world_position, in.N, in.V, NdotV, diffuse_color, F0, perceptual_roughness, diffuse_occlusion, &clusterable_object_index_ranges);
+    }
 
     // we'll use the specular component of the transmitted environment
     // light in the call to `specular_transmissive_light()` below
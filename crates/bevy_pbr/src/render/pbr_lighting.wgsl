@@ -1,7 +1,10 @@
 #define_import_path bevy_pbr::lighting
 
 #import bevy_pbr::{
-    mesh_view_types::POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE,
+    mesh_view_types::{
+        POINT_LIGHT_FLAGS_SPOT_LIGHT_ASPECT_RATIO_SHIFT, POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE,
+        SPOT_PROJECTOR_DECAL_TAG, IES_PROFILE_DECAL_TAG,
+    },
     mesh_view_bindings as view_bindings,
 }
 #import bevy_render::maths::PI
@@ -625,7 +628,10 @@ fn point_light(
     var texture_sample = 1f;
 
 #ifdef LIGHT_TEXTURES
-    if enable_texture && (*light).decal_index != 0xFFFFFFFFu {
+    if enable_texture && (*light).decal_index != 0xFFFFFFFFu &&
+            view_bindings::clustered_decals.decals[(*light).decal_index].tag == IES_PROFILE_DECAL_TAG {
+        texture_sample = ies_profile_intensity((*light).decal_index, P);
+    } else if enable_texture && (*light).decal_index != 0xFFFFFFFFu {
         let relative_position = (view_bindings::clustered_decals.decals[(*light).decal_index].local_from_world * vec4(P, 1.0)).xyz;
         let cubemap_type = view_bindings::clustered_decals.decals[(*light).decal_index].tag;
         let decal_uv = cubemap_uv(relative_position, cubemap_type);
@@ -644,6 +650,71 @@ fn point_light(
         (rangeAttenuation * derived_input.NdotL) * texture_sample;
 }
 
+#ifdef LIGHT_TEXTURES
+// Returns the relative intensity of the IES profile in the decal `decal_index` toward the
+// world-space position `P`.
+//
+// The vertical angle is measured from the light's local -Z axis, and the horizontal angle from its
+// local +X axis toward +Y, which must be kept in sync with `IesProfile::to_image`.
+fn ies_profile_intensity(decal_index: u32, P: vec3<f32>) -> f32 {
+    let decal = &view_bindings::clustered_decals.decals[decal_index];
+    let direction = normalize(((*decal).local_from_world * vec4(P, 1.0)).xyz);
+    let vertical_angle = acos(clamp(-direction.z, -1.0, 1.0));
+    var horizontal_angle = atan2(direction.y, direction.x);
+    if horizontal_angle < 0.0 {
+        horizontal_angle += 2.0 * PI;
+    }
+    let uv = vec2(horizontal_angle / (2.0 * PI), vertical_angle / PI);
+
+    return textureSampleLevel(
+        view_bindings::clustered_decal_textures[(*decal).image_index],
+        view_bindings::clustered_decal_sampler,
+        uv,
+        0.0
+    ).r;
+}
+#endif  // LIGHT_TEXTURES
+
+// Returns the ratio of the tangents of a spot light's outer angles along its Y and X axes, or 1.0
+// for circular spot lights.
+fn spot_light_aspect_ratio(flags: u32) -> f32 {
+    let packed = flags >> POINT_LIGHT_FLAGS_SPOT_LIGHT_ASPECT_RATIO_SHIFT;
+    if packed == 0u {
+        return 1.0;
+    }
+    return f32(packed) / 256.0;
+}
+
+// Returns the cosine of the angle between the spot light direction and the direction from the
+// light to a point, which drives the falloff of the spot light cone.
+//
+// For elliptical spot lights, the offset along the Y axis of the light's shadow map is divided by
+// the aspect ratio first, which maps the elliptical cone onto the circular cone of the X angle.
+fn spot_light_cone_cos(spot_dir: vec3<f32>, light_to_point: vec3<f32>, flags: u32) -> f32 {
+    let aspect_ratio = spot_light_aspect_ratio(flags);
+    if aspect_ratio == 1.0 {
+        // note we normalize here to get "l" from the filament listing. spot_dir is already
+        // normalized
+        return dot(spot_dir, normalize(light_to_point));
+    }
+
+    // the construction of the up and right vectors needs to precisely mirror the code
+    // in render/light.rs:spot_light_world_from_view
+    let fwd = -spot_dir;
+    var sign = -1.0;
+    if (fwd.z >= 0.0) {
+        sign = 1.0;
+    }
+    let a = -1.0 / (fwd.z + sign);
+    let b = fwd.x * fwd.y * a;
+    let up_dir = vec3<f32>(1.0 + sign * fwd.x * fwd.x * a, sign * b, -sign * fwd.x);
+    let right_dir = vec3<f32>(-b, -sign - fwd.y * fwd.y * a, fwd.y);
+    let light_inv_rot = mat3x3<f32>(right_dir, up_dir, fwd);
+
+    let local_position = (light_to_point * light_inv_rot) * vec3(1.0, 1.0 / aspect_ratio, 1.0);
+    return -local_position.z / length(local_position);
+}
+
 fn spot_light(
     light_id: u32,
     input: ptr<function, LightingInput>,
@@ -664,27 +735,34 @@ fn spot_light(
 
     // calculate attenuation based on filament formula https://google.github.io/filament/Filament.html#listing_glslpunctuallight
     // spot_scale and spot_offset have been precomputed
-    // note we normalize here to get "l" from the filament listing. spot_dir is already normalized
-    let cd = dot(-spot_dir, normalize(light_to_frag));
+    let cd = spot_light_cone_cos(spot_dir, -light_to_frag, (*light).flags);
     let attenuation = saturate(cd * (*light).light_custom_data.z + (*light).light_custom_data.w);
     let spot_attenuation = attenuation * attenuation;
 
     var texture_sample = 1f;
 
 #ifdef LIGHT_TEXTURES
-    if (*light).decal_index != 0xFFFFFFFFu {
+    // Spot projectors change the surface rather than the light, see `apply_spot_projectors`.
+    if (*light).decal_index != 0xFFFFFFFFu &&
+            view_bindings::clustered_decals.decals[(*light).decal_index].tag == IES_PROFILE_DECAL_TAG {
+        texture_sample = ies_profile_intensity((*light).decal_index, (*input).P);
+    } else if (*light).decal_index != 0xFFFFFFFFu &&
+            view_bindings::clustered_decals.decals[(*light).decal_index].tag != SPOT_PROJECTOR_DECAL_TAG {
         let local_position = (view_bindings::clustered_decals.decals[(*light).decal_index].local_from_world *
             vec4((*input).P, 1.0)).xyz;
         if local_position.z < 0.0 {
             let decal_uv = (local_position.xy / (local_position.z * (*light).spot_light_tan_angle)) * vec2(-0.5, 0.5) + 0.5;
             let image_index = view_bindings::clustered_decals.decals[(*light).decal_index].image_index;
 
-            texture_sample = textureSampleLevel(
+            // The alpha channel cuts out the texture, so that cookies with transparency work
+            // without having to bake it into the R channel.
+            let cookie = textureSampleLevel(
                 view_bindings::clustered_decal_textures[image_index],
                 view_bindings::clustered_decal_sampler,
                 decal_uv,
                 0.0
-            ).r;
+            );
+            texture_sample = cookie.r * cookie.a;
         }
     }
 #endif
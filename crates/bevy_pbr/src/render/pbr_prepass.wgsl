@@ -8,6 +8,7 @@
     prepass_io,
     mesh_bindings::mesh,
     mesh_view_bindings::view,
+    utils,
 }
 
 #import bevy_render::bindless::{bindless_samplers_filtering, bindless_textures_2d}
@@ -56,7 +57,11 @@ fn fragment(
     var out: prepass_io::FragmentOutput;
 
 #ifdef UNCLIPPED_DEPTH_ORTHO_EMULATION
+#ifdef LOGARITHMIC_SHADOW_DEPTH
+    out.frag_depth = utils::logarithmic_shadow_depth(in.unclipped_depth);
+#else   // LOGARITHMIC_SHADOW_DEPTH
     out.frag_depth = in.unclipped_depth;
+#endif  // LOGARITHMIC_SHADOW_DEPTH
 #endif // UNCLIPPED_DEPTH_ORTHO_EMULATION
 
 #ifdef NORMAL_PREPASS
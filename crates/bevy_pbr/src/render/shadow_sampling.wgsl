@@ -67,11 +67,47 @@ fn search_for_blockers_in_shadow_map_hardware(
 #endif  // WEBGL2
 }
 
+// Returns the depth of the nearest occluder in a directional light's shadow map, or 0.0 (infinitely
+// far from the light) if the depth can't be sampled.
+fn sample_shadow_map_depth(light_local: vec2<f32>, array_index: i32) -> f32 {
+#ifdef WEBGL2
+    return 0.0;
+#else   // WEBGL2
+
+#ifdef PCSS_SAMPLERS_AVAILABLE
+
+#ifdef NO_ARRAY_TEXTURES_SUPPORT
+    return textureSampleLevel(
+        view_bindings::directional_shadow_textures,
+        view_bindings::directional_shadow_textures_linear_sampler,
+        light_local,
+        0u,
+    );
+#else   // NO_ARRAY_TEXTURES_SUPPORT
+    return textureSampleLevel(
+        view_bindings::directional_shadow_textures,
+        view_bindings::directional_shadow_textures_linear_sampler,
+        light_local,
+        array_index,
+        0u,
+    );
+#endif  // NO_ARRAY_TEXTURES_SUPPORT
+
+#else   // PCSS_SAMPLERS_AVAILABLE
+    return 0.0;
+#endif  // PCSS_SAMPLERS_AVAILABLE
+
+#endif  // WEBGL2
+}
+
 // Numbers determined by trial and error that gave nice results.
 const SPOT_SHADOW_TEXEL_SIZE: f32 = 0.0134277345;
 const POINT_SHADOW_SCALE: f32 = 0.003;
 const POINT_SHADOW_TEMPORAL_OFFSET_SCALE: f32 = 0.5;
 
+// The angle between successive samples on a Vogel disk, pi * (3 - sqrt(5)).
+const GOLDEN_ANGLE: f32 = 2.3999632;
+
 // These are the standard MSAA sample point positions from D3D. They were chosen
 // to get a reasonable distribution that's not too regular.
 //
@@ -155,7 +191,7 @@ fn map(min1: f32, max1: f32, min2: f32, max2: f32, value: f32) -> f32 {
 // See: https://www.iryoku.com/next-generation-post-processing-in-call-of-duty-advanced-warfare/
 fn random_rotation_matrix(scale: vec2<f32>, temporal: bool) -> mat2x2<f32> {
     let random_angle = 2.0 * PI * interleaved_gradient_noise(
-        scale, select(1u, view_bindings::globals.frame_count, temporal));
+        scale, select(1u, view_bindings::lights.temporal_shadow_frame, temporal));
     let m = vec2(sin(random_angle), cos(random_angle));
     return mat2x2(
         m.y, -m.x,
@@ -174,6 +210,14 @@ fn calculate_uv_offset_scale_jimenez_fourteen(texel_size: f32, blur_size: f32) -
     return f * blur_size / (texel_size * shadow_map_size);
 }
 
+// Returns the `blur_size` that makes the Jimenez '14 and Vogel filters blur over
+// `texel_radius` shadow map texels. This undoes the fudge factor in
+// `calculate_uv_offset_scale_jimenez_fourteen`, so that the blur radius is exact.
+fn blur_size_for_texel_radius(texel_radius: f32, texel_size: f32) -> f32 {
+    let f = map(0.00390625, 0.022949219, 0.015, 0.035, texel_size);
+    return texel_radius * texel_size / f;
+}
+
 fn sample_shadow_map_jimenez_fourteen(
     light_local: vec2<f32>,
     depth: f32,
@@ -208,6 +252,41 @@ fn sample_shadow_map_jimenez_fourteen(
     return sum / 8.0;
 }
 
+// Returns the position of sample `sample_index` out of `sample_count` on a Vogel
+// disk of radius 1. Successive samples are rotated by the golden angle, with the
+// radius chosen so that the samples cover the disk with uniform density.
+//
+// See: https://www.gamedev.net/tutorials/programming/graphics/contact-hardening-soft-shadows-made-fast-r4906/
+fn vogel_disk_sample(sample_index: u32, sample_count: u32) -> vec2<f32> {
+    let r = sqrt((f32(sample_index) + 0.5) / f32(sample_count));
+    let theta = f32(sample_index) * GOLDEN_ANGLE;
+    return r * vec2(cos(theta), sin(theta));
+}
+
+// Like the Jimenez '14 filter above, but with a configurable number of samples
+// distributed on a Vogel disk instead of the fixed 8-sample spiral.
+fn sample_shadow_map_vogel(
+    light_local: vec2<f32>,
+    depth: f32,
+    array_index: i32,
+    texel_size: f32,
+    blur_size: f32,
+    temporal: bool,
+) -> f32 {
+    let shadow_map_size = vec2<f32>(textureDimensions(view_bindings::directional_shadow_textures));
+    let rotation_matrix = random_rotation_matrix(light_local * shadow_map_size, temporal);
+    let uv_offset_scale = calculate_uv_offset_scale_jimenez_fourteen(texel_size, blur_size);
+    let sample_count = max(view_bindings::lights.vogel_shadow_sample_count, 1u);
+
+    var sum = 0.0;
+    for (var i = 0u; i < sample_count; i += 1u) {
+        let sample_offset = (rotation_matrix * vogel_disk_sample(i, sample_count)) *
+            uv_offset_scale;
+        sum += sample_shadow_map_hardware(light_local + sample_offset, depth, array_index);
+    }
+    return sum / f32(sample_count);
+}
+
 // Performs the blocker search portion of percentage-closer soft shadows (PCSS).
 // This is the variation used for directional lights.
 //
@@ -254,11 +333,35 @@ fn search_for_blockers_in_shadow_map(
 }
 
 fn sample_shadow_map(light_local: vec2<f32>, depth: f32, array_index: i32, texel_size: f32) -> f32 {
+    return sample_shadow_map_blurred(light_local, depth, array_index, texel_size, 1.0, true);
+}
+
+// Like `sample_shadow_map`, but scales the size of the filter by `blur_size`.
+// The Gaussian and hardware 2x2 filters have a fixed size, so they ignore it.
+//
+// If `temporal` is false, as for lights with stable shadows, the sampling
+// pattern doesn't change from frame to frame.
+fn sample_shadow_map_blurred(
+    light_local: vec2<f32>,
+    depth: f32,
+    array_index: i32,
+    texel_size: f32,
+    blur_size: f32,
+    temporal: bool,
+) -> f32 {
 #ifdef SHADOW_FILTER_METHOD_GAUSSIAN
     return sample_shadow_map_castano_thirteen(light_local, depth, array_index);
 #else ifdef SHADOW_FILTER_METHOD_TEMPORAL
+    // Without temporal jitter, fall back to the Gaussian filter, unless a wider
+    // blur is needed, which only the Jimenez '14 filter supports.
+    if (!temporal && blur_size == 1.0) {
+        return sample_shadow_map_castano_thirteen(light_local, depth, array_index);
+    }
     return sample_shadow_map_jimenez_fourteen(
-        light_local, depth, array_index, texel_size, 1.0, true);
+        light_local, depth, array_index, texel_size, blur_size, temporal);
+#else ifdef SHADOW_FILTER_METHOD_VOGEL
+    return sample_shadow_map_vogel(
+        light_local, depth, array_index, texel_size, blur_size, temporal);
 #else ifdef SHADOW_FILTER_METHOD_HARDWARE_2X2
     return sample_shadow_map_hardware(light_local, depth, array_index);
 #else
@@ -287,6 +390,7 @@ fn sample_shadow_map_pcss(
     array_index: i32,
     texel_size: f32,
     light_size: f32,
+    temporal: bool,
 ) -> f32 {
     // Determine the average Z value of the closest blocker.
     let z_blocker = search_for_blockers_in_shadow_map(
@@ -302,7 +406,10 @@ fn sample_shadow_map_pcss(
     // provide better blurs.
 #ifdef SHADOW_FILTER_METHOD_TEMPORAL
     return sample_shadow_map_jimenez_fourteen(
-        light_local, depth, array_index, texel_size, blur_size, true);
+        light_local, depth, array_index, texel_size, blur_size, temporal);
+#else ifdef SHADOW_FILTER_METHOD_VOGEL
+    return sample_shadow_map_vogel(
+        light_local, depth, array_index, texel_size, blur_size, temporal);
 #else   // SHADOW_FILTER_METHOD_TEMPORAL
     return sample_shadow_map_jimenez_fourteen(
         light_local, depth, array_index, texel_size, blur_size, false);
@@ -514,18 +621,75 @@ fn sample_shadow_cubemap_jittered(
     return sum;
 }
 
+// The cubemap variant of `sample_shadow_map_vogel`, sampling the Vogel disk in a
+// 2D orthonormal basis along the principal light direction.
+fn sample_shadow_cubemap_vogel(
+    light_local: vec3<f32>,
+    depth: f32,
+    scale: f32,
+    distance_to_light: f32,
+    light_id: u32,
+    temporal: bool,
+) -> f32 {
+    // Create an orthonormal basis so we can apply a 2D sampling pattern to a
+    // cubemap.
+    var up = vec3(0.0, 1.0, 0.0);
+    if (dot(up, normalize(light_local)) > 0.99) {
+        up = vec3(1.0, 0.0, 0.0);   // Avoid creating a degenerate basis.
+    }
+    let basis = orthonormalize(light_local, up) * scale * distance_to_light;
+
+    let rotation_matrix = random_rotation_matrix(vec2(1.0), temporal);
+    let sample_count = max(view_bindings::lights.vogel_shadow_sample_count, 1u);
+    let coeff = 1.0 / f32(sample_count);
+
+    var sum: f32 = 0.0;
+    for (var i = 0u; i < sample_count; i += 1u) {
+        let sample_offset = rotation_matrix * vogel_disk_sample(i, sample_count) *
+            POINT_SHADOW_TEMPORAL_OFFSET_SCALE;
+        sum += sample_shadow_cubemap_at_offset(
+            sample_offset, coeff, basis[0], basis[1], light_local, depth, light_id);
+    }
+    return sum;
+}
+
 fn sample_shadow_cubemap(
     light_local: vec3<f32>,
     distance_to_light: f32,
     depth: f32,
     light_id: u32,
+) -> f32 {
+    return sample_shadow_cubemap_scaled(
+        light_local, distance_to_light, depth, light_id, POINT_SHADOW_SCALE, true);
+}
+
+// Like `sample_shadow_cubemap`, but with the size of the filter set by `scale`.
+// The filter covers `scale * distance_to_light` world units around the sample.
+//
+// If `temporal` is false, as for lights with stable shadows, the sampling
+// pattern doesn't change from frame to frame.
+fn sample_shadow_cubemap_scaled(
+    light_local: vec3<f32>,
+    distance_to_light: f32,
+    depth: f32,
+    light_id: u32,
+    scale: f32,
+    temporal: bool,
 ) -> f32 {
 #ifdef SHADOW_FILTER_METHOD_GAUSSIAN
     return sample_shadow_cubemap_gaussian(
-        light_local, depth, POINT_SHADOW_SCALE, distance_to_light, light_id);
+        light_local, depth, scale, distance_to_light, light_id);
 #else ifdef SHADOW_FILTER_METHOD_TEMPORAL
+    // Without temporal jitter, fall back to the Gaussian filter.
+    if (!temporal) {
+        return sample_shadow_cubemap_gaussian(
+            light_local, depth, scale, distance_to_light, light_id);
+    }
     return sample_shadow_cubemap_jittered(
-        light_local, depth, POINT_SHADOW_SCALE, distance_to_light, light_id, true);
+        light_local, depth, scale, distance_to_light, light_id, true);
+#else ifdef SHADOW_FILTER_METHOD_VOGEL
+    return sample_shadow_cubemap_vogel(
+        light_local, depth, scale, distance_to_light, light_id, temporal);
 #else ifdef SHADOW_FILTER_METHOD_HARDWARE_2X2
     return sample_shadow_cubemap_hardware(light_local, depth, light_id);
 #else
@@ -594,6 +758,7 @@ fn sample_shadow_cubemap_pcss(
     depth: f32,
     light_id: u32,
     light_size: f32,
+    temporal: bool,
 ) -> f32 {
     let z_blocker = search_for_blockers_in_shadow_cubemap(
         light_local, depth, light_size, distance_to_light, light_id);
@@ -603,7 +768,10 @@ fn sample_shadow_cubemap_pcss(
 
 #ifdef SHADOW_FILTER_METHOD_TEMPORAL
     return sample_shadow_cubemap_jittered(
-        light_local, depth, POINT_SHADOW_SCALE * blur_size, distance_to_light, light_id, true);
+        light_local, depth, POINT_SHADOW_SCALE * blur_size, distance_to_light, light_id, temporal);
+#else ifdef SHADOW_FILTER_METHOD_VOGEL
+    return sample_shadow_cubemap_vogel(
+        light_local, depth, POINT_SHADOW_SCALE * blur_size, distance_to_light, light_id, temporal);
 #else
     return sample_shadow_cubemap_jittered(
         light_local, depth, POINT_SHADOW_SCALE * blur_size, distance_to_light, light_id, false);
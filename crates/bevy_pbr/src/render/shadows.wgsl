@@ -1,11 +1,17 @@
 #define_import_path bevy_pbr::shadows
 
 #import bevy_pbr::{
-    mesh_view_types::POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE,
+    mesh_view_types::{
+        DIRECTIONAL_LIGHT_FLAGS_STABLE_SHADOWS_BIT, POINT_LIGHT_FLAGS_SPOT_LIGHT_Y_NEGATIVE,
+        POINT_LIGHT_FLAGS_STABLE_SHADOWS_BIT,
+    },
     mesh_view_bindings as view_bindings,
+    lighting::spot_light_aspect_ratio,
+    utils::{linear_shadow_depth, logarithmic_shadow_depth},
     shadow_sampling::{
-        SPOT_SHADOW_TEXEL_SIZE, sample_shadow_cubemap, sample_shadow_cubemap_pcss,
-        sample_shadow_map, sample_shadow_map_pcss,
+        POINT_SHADOW_SCALE, SPOT_SHADOW_TEXEL_SIZE, blur_size_for_texel_radius,
+        sample_shadow_cubemap_pcss, sample_shadow_cubemap_scaled, sample_shadow_map_blurred,
+        sample_shadow_map_depth, sample_shadow_map_pcss,
     }
 }
 
@@ -16,6 +22,15 @@
 
 const flip_z: vec3<f32> = vec3<f32>(1.0, 1.0, -1.0);
 
+// Returns the factor that a light's contribution is multiplied by, given the shadow factor and
+// the light's packed shadow tint. In shadow, this blends toward the tint rather than to black, so
+// with a black tint this is just the shadow factor. The alpha of the tint is the shadow strength,
+// which fades the whole shadow out for lights that fade their shadows with their intensity.
+fn shadow_tint_factor(shadow: f32, packed_shadow_tint: u32) -> vec3<f32> {
+    let shadow_tint = unpack4x8unorm(packed_shadow_tint);
+    return mix(vec3(1.0), mix(shadow_tint.rgb, vec3(1.0), shadow), shadow_tint.a);
+}
+
 fn fetch_point_shadow(light_id: u32, frag_position: vec4<f32>, surface_normal: vec3<f32>) -> f32 {
     let light = &view_bindings::clusterable_objects.data[light_id];
 
@@ -41,25 +56,61 @@ fn fetch_point_shadow(light_id: u32, frag_position: vec4<f32>, surface_normal: v
     // projection * vec4(0, 0, -major_axis_magnitude, 1.0)
     // and keeping only the terms that have any impact on the depth.
     // Projection-agnostic approach:
-    let zw = -major_axis_magnitude * (*light).light_custom_data.xy + (*light).light_custom_data.zw;
+    var zw = -major_axis_magnitude * (*light).light_custom_data.xy + (*light).light_custom_data.zw;
+
+    // If the cube faces were rendered with a field of view wider than 90 degrees, scale down the
+    // components of the lookup vector that aren't along the major axis, so that the lookup lands
+    // inside the face rather than in the border that overlaps the neighboring faces.
+    let cube_face_scale = view_bindings::lights.point_light_cube_face_scale;
+    var lookup_ls = select(
+        frag_ls * cube_face_scale,
+        frag_ls,
+        abs_position_ls == vec3(major_axis_magnitude),
+    );
+
+    // Dual-paraboloid shadows store each hemisphere in the -Z or +Z cube face, warped onto a
+    // paraboloid, with the radial distance to the light as depth. Map the fragment onto the same
+    // paraboloid; the result points at the right face and already accounts for the z flip below.
+    if (((*light).flags & POINT_LIGHT_FLAGS_DUAL_PARABOLOID_SHADOWS_BIT) != 0u) {
+        let distance = length(frag_ls);
+        let direction = frag_ls / max(distance, 1e-6);
+        lookup_ls = vec3(
+            direction.xy / (1.0 + abs(direction.z)),
+            select(1.0, -1.0, direction.z <= 0.0),
+        ) * flip_z;
+        zw = -distance * (*light).light_custom_data.xy + (*light).light_custom_data.zw;
+    }
     let depth = zw.x / zw.y;
 
+    // Lights with stable shadows don't use temporal jitter.
+    let temporal = ((*light).flags & POINT_LIGHT_FLAGS_STABLE_SHADOWS_BIT) == 0u;
+
     // If soft shadows are enabled, use the PCSS path. Cubemaps assume a
     // left-handed coordinate space, so we have to flip the z-axis when
     // sampling.
     if ((*light).soft_shadow_size > 0.0) {
         return sample_shadow_cubemap_pcss(
-            frag_ls * flip_z,
+            lookup_ls * flip_z,
             distance_to_light,
             depth,
             light_id,
             (*light).soft_shadow_size,
+            temporal,
         );
     }
 
+    // If the light has a world-space blur radius, it's stored negated. The filter
+    // covers `scale * distance_to_light` world units, so divide by the distance.
+    if ((*light).soft_shadow_size < 0.0) {
+        let scale = -(*light).soft_shadow_size / distance_to_light;
+        return sample_shadow_cubemap_scaled(
+            lookup_ls * flip_z, distance_to_light, depth, light_id, scale, temporal);
+    }
+
     // Do the lookup, using HW PCF and comparison. Cubemaps assume a left-handed
     // coordinate space, so we have to flip the z-axis when sampling.
-    return sample_shadow_cubemap(frag_ls * flip_z, distance_to_light, depth, light_id);
+    return sample_shadow_cubemap_scaled(
+        lookup_ls * flip_z, distance_to_light, depth, light_id, POINT_SHADOW_SCALE, temporal);
 }
 
 fn fetch_spot_shadow(
@@ -108,20 +159,45 @@ fn fetch_spot_shadow(
     // divide xy by perspective matrix "f" and by -projected.z (projected.z is -projection matrix's w)
     // to get ndc coordinates
     let f_div_minus_z = 1.0 / ((*light).spot_light_tan_angle * -projected_position.z);
-    let shadow_xy_ndc = projected_position.xy * f_div_minus_z;
+    // elliptical spot lights cover a wider or narrower angle along Y than along X
+    let aspect_ratio = vec2(1.0, spot_light_aspect_ratio((*light).flags));
+    let shadow_xy_ndc = projected_position.xy * f_div_minus_z / aspect_ratio;
     // convert to uv coordinates
     let shadow_uv = shadow_xy_ndc * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
 
     let depth = near_z / -projected_position.z;
 
+    // Lights with stable shadows don't use temporal jitter.
+    let temporal = ((*light).flags & POINT_LIGHT_FLAGS_STABLE_SHADOWS_BIT) == 0u;
+
     // If soft shadows are enabled, use the PCSS path.
     let array_index = i32(light_id) + view_bindings::lights.spot_light_shadowmap_offset;
     if ((*light).soft_shadow_size > 0.0) {
         return sample_shadow_map_pcss(
-            shadow_uv, depth, array_index, SPOT_SHADOW_TEXEL_SIZE, (*light).soft_shadow_size);
+            shadow_uv,
+            depth,
+            array_index,
+            SPOT_SHADOW_TEXEL_SIZE,
+            (*light).soft_shadow_size,
+            temporal,
+        );
     }
 
-    return sample_shadow_map(shadow_uv, depth, array_index, SPOT_SHADOW_TEXEL_SIZE);
+    // If the light has a world-space blur radius, it's stored negated. Convert it
+    // to texels using the size of a shadow map texel at the fragment's depth.
+    if ((*light).soft_shadow_size < 0.0) {
+        let shadow_map_size =
+            f32(textureDimensions(view_bindings::directional_shadow_textures).x);
+        let world_texel_size =
+            2.0 * (*light).spot_light_tan_angle * -projected_position.z / shadow_map_size;
+        let blur_size = blur_size_for_texel_radius(
+            -(*light).soft_shadow_size / world_texel_size, SPOT_SHADOW_TEXEL_SIZE);
+        return sample_shadow_map_blurred(
+            shadow_uv, depth, array_index, SPOT_SHADOW_TEXEL_SIZE, blur_size, temporal);
+    }
+
+    return sample_shadow_map_blurred(
+        shadow_uv, depth, array_index, SPOT_SHADOW_TEXEL_SIZE, 1.0, temporal);
 }
 
 fn get_cascade_index(light_id: u32, view_z: f32) -> u32 {
@@ -137,7 +213,8 @@ fn get_cascade_index(light_id: u32, view_z: f32) -> u32 {
 
 // Converts from world space to the uv position in the light's shadow map.
 //
-// The depth is stored in the return value's z coordinate. If the return value's
+// The depth is stored in the return value's z coordinate, remapped in the same way as the shadow
+// map if the cascade has logarithmic depth. If the return value's
 // w coordinate is 0.0, then we landed outside the shadow map entirely.
 fn world_to_directional_light_local(
     light_id: u32,
@@ -163,7 +240,10 @@ fn world_to_directional_light_local(
     let flip_correction = vec2<f32>(0.5, -0.5);
     let light_local = offset_position_ndc.xy * flip_correction + vec2<f32>(0.5, 0.5);
 
-    let depth = offset_position_ndc.z;
+    var depth = offset_position_ndc.z;
+    if ((*cascade).logarithmic_depth != 0u) {
+        depth = logarithmic_shadow_depth(depth);
+    }
 
     return vec4(light_local, depth, 1.0);
 }
@@ -179,7 +259,7 @@ fn sample_directional_cascade(
 
     // The normal bias is scaled to the texel size.
     let normal_offset = (*light).shadow_normal_bias * (*cascade).texel_size * surface_normal.xyz;
-    let depth_offset = (*light).shadow_depth_bias * (*light).direction_to_light.xyz;
+    let depth_offset = (*cascade).depth_bias * (*light).direction_to_light.xyz;
     let offset_position = vec4<f32>(frag_position.xyz + normal_offset + depth_offset, frag_position.w);
 
     let light_local = world_to_directional_light_local(light_id, cascade_index, offset_position);
@@ -190,30 +270,100 @@ fn sample_directional_cascade(
     let array_index = i32((*light).depth_texture_base_index + cascade_index);
     let texel_size = (*cascade).texel_size;
 
-    // If soft shadows are enabled, use the PCSS path.
+    // Lights with stable shadows don't use temporal jitter.
+    let temporal = ((*light).flags & DIRECTIONAL_LIGHT_FLAGS_STABLE_SHADOWS_BIT) == 0u;
+
+    var shadow: f32;
     if ((*light).soft_shadow_size > 0.0) {
-        return sample_shadow_map_pcss(
-            light_local.xy, light_local.z, array_index, texel_size, (*light).soft_shadow_size);
+        // If soft shadows are enabled, use the PCSS path.
+        shadow = sample_shadow_map_pcss(
+            light_local.xy,
+            light_local.z,
+            array_index,
+            texel_size,
+            (*light).soft_shadow_size,
+            temporal,
+        );
+    } else if ((*light).soft_shadow_size < 0.0) {
+        // If the light has a world-space blur radius, it's stored negated. Convert it
+        // to texels using the texel size of this cascade.
+        let blur_size =
+            blur_size_for_texel_radius(-(*light).soft_shadow_size / texel_size, texel_size);
+        shadow = sample_shadow_map_blurred(
+            light_local.xy, light_local.z, array_index, texel_size, blur_size, temporal);
+    } else {
+        shadow = sample_shadow_map_blurred(
+            light_local.xy, light_local.z, array_index, texel_size, 1.0, temporal);
     }
 
-    return sample_shadow_map(light_local.xy, light_local.z, array_index, texel_size);
+    if ((*light).shadow_contact_darkening > 0.0) {
+        shadow *= directional_contact_darkening(light_id, cascade_index, light_local, array_index);
+    }
+    return shadow;
+}
+
+// The distance between an occluder and a fragment, in texels of the cascade, over which contact
+// darkening fades out.
+const CONTACT_DARKENING_DISTANCE_TEXELS: f32 = 16.0;
+
+// Returns the factor that the shadow of a fragment is multiplied by to darken it when there's an
+// occluder just above it in the shadow map.
+fn directional_contact_darkening(
+    light_id: u32,
+    cascade_index: u32,
+    light_local: vec4<f32>,
+    array_index: i32,
+) -> f32 {
+    let light = &view_bindings::lights.directional_lights[light_id];
+    let cascade = &(*light).cascades[cascade_index];
+
+    // Reverse Z: occluders closer to the light have a greater depth.
+    var occluder_depth = sample_shadow_map_depth(light_local.xy, array_index);
+    if (occluder_depth <= light_local.z) {
+        return 1.0;
+    }
+
+    // Distances are measured in linear depth.
+    var receiver_depth = light_local.z;
+    if ((*cascade).logarithmic_depth != 0u) {
+        occluder_depth = linear_shadow_depth(occluder_depth);
+        receiver_depth = linear_shadow_depth(receiver_depth);
+    }
+
+    // The cascade projection is orthographic, so the depth changes by the length of the depth
+    // row of `clip_from_world` per world unit.
+    let clip_from_world = (*cascade).clip_from_world;
+    let depth_per_world_unit =
+        length(vec3(clip_from_world[0].z, clip_from_world[1].z, clip_from_world[2].z));
+    let occluder_distance = (occluder_depth - receiver_depth) / depth_per_world_unit;
+
+    let fade_distance = CONTACT_DARKENING_DISTANCE_TEXELS * (*cascade).texel_size;
+    let closeness = 1.0 - saturate(occluder_distance / fade_distance);
+    return 1.0 - (*light).shadow_contact_darkening * closeness;
 }
 
 fn fetch_directional_shadow(light_id: u32, frag_position: vec4<f32>, surface_normal: vec3<f32>, view_z: f32) -> f32 {
     let light = &view_bindings::lights.directional_lights[light_id];
-    let cascade_index = get_cascade_index(light_id, view_z);
+    var cascade_index = get_cascade_index(light_id, view_z);
 
     if (cascade_index >= (*light).num_cascades) {
         return 1.0;
     }
 
+    // With a `ShadowFocus`, the first cascade only covers part of its depth range, and the second
+    // cascade covers the rest.
+    if (cascade_index == 0u && (*light).num_cascades > 1u
+            && world_to_directional_light_local(light_id, 0u, frag_position).w == 0.0) {
+        cascade_index = 1u;
+    }
+
     var shadow = sample_directional_cascade(light_id, cascade_index, frag_position, surface_normal);
 
     // Blend with the next cascade, if there is one.
     let next_cascade_index = cascade_index + 1u;
     if (next_cascade_index < (*light).num_cascades) {
         let this_far_bound = (*light).cascades[cascade_index].far_bound;
-        let next_near_bound = (1.0 - (*light).cascades_overlap_proportion) * this_far_bound;
+        let next_near_bound = (1.0 - (*light).cascades[cascade_index].overlap_proportion) * this_far_bound;
         if (-view_z >= next_near_bound) {
             let next_shadow = sample_directional_cascade(light_id, next_cascade_index, frag_position, surface_normal);
             shadow = mix(shadow, next_shadow, (-view_z - next_near_bound) / (this_far_bound - next_near_bound));
@@ -86,3 +86,20 @@ const SPIRAL_OFFSET_4_ = vec2<f32>( 0.3536, -0.3536);
 const SPIRAL_OFFSET_5_ = vec2<f32>(-0.0000,  0.3750);
 const SPIRAL_OFFSET_6_ = vec2<f32>(-0.1768, -0.1768);
 const SPIRAL_OFFSET_7_ = vec2<f32>( 0.1250,  0.0000);
+
+// How strongly `logarithmic_shadow_depth` concentrates precision near the top of a cascade.
+// NOTE: This must match `LOGARITHMIC_SHADOW_DEPTH_SCALE` in `bevy_pbr/src/light/mod.rs`!
+const LOGARITHMIC_SHADOW_DEPTH_SCALE: f32 = 1000.0;
+
+// Remaps a linear reverse-Z cascade depth to the logarithmic distribution used by directional
+// light cascades with logarithmic depth. 0.0 and 1.0 stay in place.
+fn logarithmic_shadow_depth(linear_depth: f32) -> f32 {
+    return 1.0 - log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE * max(1.0 - linear_depth, 0.0))
+        / log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE);
+}
+
+// The inverse of `logarithmic_shadow_depth`.
+fn linear_shadow_depth(logarithmic_depth: f32) -> f32 {
+    let log_range = log2(1.0 + LOGARITHMIC_SHADOW_DEPTH_SCALE);
+    return 1.0 - (exp2((1.0 - logarithmic_depth) * log_range) - 1.0) / LOGARITHMIC_SHADOW_DEPTH_SCALE;
+}
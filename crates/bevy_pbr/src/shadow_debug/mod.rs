@@ -0,0 +1,415 @@
+//! A debug overlay that draws the contents of a single shadow map into a
+//! corner of the screen.
+
+use bevy_app::{App, Plugin};
+use bevy_asset::{embedded_asset, load_embedded_asset, Handle};
+use bevy_core_pipeline::{
+    core_3d::graph::{Core3d, Node3d},
+    FullscreenShader,
+};
+use bevy_derive::{Deref, DerefMut};
+use bevy_ecs::{
+    component::Component,
+    entity::Entity,
+    query::{QueryItem, With},
+    reflect::ReflectResource,
+    resource::Resource,
+    schedule::IntoScheduleConfigs as _,
+    system::{lifetimeless::Read, Commands, Query, Res, ResMut},
+    world::{FromWorld, World},
+};
+use bevy_image::BevyDefault as _;
+use bevy_math::Vec2;
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+use bevy_render::{
+    render_graph::{NodeRunError, RenderGraphApp, RenderGraphContext, ViewNode, ViewNodeRunner},
+    render_resource::{
+        binding_types, BindGroupEntries, BindGroupLayout, BindGroupLayoutEntries,
+        CachedRenderPipelineId, ColorTargetState, ColorWrites, DynamicUniformBuffer, FragmentState,
+        LoadOp, Operations, PipelineCache, RenderPassColorAttachment, RenderPassDescriptor,
+        RenderPipelineDescriptor, Shader, ShaderStages, ShaderType, SpecializedRenderPipeline,
+        SpecializedRenderPipelines, StoreOp, TextureFormat, TextureView,
+    },
+    renderer::{RenderContext, RenderDevice, RenderQueue},
+    sync_world::MainEntity,
+    view::{ExtractedView, ViewTarget},
+    Extract, ExtractSchedule, Render, RenderApp, RenderSystems,
+};
+use bevy_utils::prelude::default;
+
+use crate::{
+    graph::NodePbr, ExtractedDirectionalLight, ExtractedPointLight, LightEntity, ShadowView,
+    ViewLightEntities,
+};
+
+/// Adds support for drawing a shadow map on top of the rendered image, for
+/// debugging purposes.
+///
+/// The overlay is controlled by the [`ShadowMapDebugView`] resource. When that
+/// resource is absent, nothing is drawn.
+pub struct ShadowMapDebugPlugin;
+
+/// Selects the shadow map that [`ShadowMapDebugPlugin`] draws on screen.
+///
+/// Insert this resource to show the shadow map of `light` in a corner of every
+/// 3D camera, and remove it to hide the overlay again.
+///
+/// For directional lights, `cascade` is the index of the cascade to show. For
+/// point lights, it's the index of the cube map face. It's ignored for spot
+/// lights, which only have a single shadow map.
+///
+/// Directional light shadow maps are orthographic, so their depth is drawn
+/// as-is. Point and spot light shadow maps are perspective, so their depth is
+/// linearized and divided by the light's range before being drawn. In both
+/// cases, white is closer to the light.
+#[derive(Resource, Clone, Copy, Debug, Reflect)]
+#[reflect(Resource, Debug, Clone)]
+pub struct ShadowMapDebugView {
+    /// The light whose shadow map is shown.
+    pub light: Entity,
+    /// The cascade (for directional lights) or cube face (for point lights)
+    /// to show.
+    pub cascade: usize,
+    /// The corner of the screen that the shadow map is drawn in.
+    pub corner: ShadowMapDebugCorner,
+}
+
+/// The corner of the screen that a [`ShadowMapDebugView`] is drawn in.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+#[reflect(Debug, Default, PartialEq, Clone)]
+pub enum ShadowMapDebugCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    #[default]
+    BottomRight,
+}
+
+impl ShadowMapDebugView {
+    /// Shows the given cascade of a directional light, or face of a point light.
+    pub fn new(light: Entity, cascade: usize) -> Self {
+        Self {
+            light,
+            cascade,
+            corner: default(),
+        }
+    }
+
+    /// Returns this debug view, drawn in the given corner of the screen.
+    pub fn with_corner(mut self, corner: ShadowMapDebugCorner) -> Self {
+        self.corner = corner;
+        self
+    }
+}
+
+/// The render pipeline that draws the shadow map overlay.
+#[derive(Resource)]
+pub struct ShadowMapDebugPipeline {
+    bind_group_layout: BindGroupLayout,
+    fullscreen_shader: FullscreenShader,
+    fragment_shader: Handle<Shader>,
+}
+
+/// The pipeline key for [`ShadowMapDebugPipeline`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ShadowMapDebugPipelineKey {
+    is_hdr: bool,
+}
+
+/// The GPU-side settings for the shadow map overlay.
+#[derive(Clone, Copy, ShaderType)]
+pub struct ShadowMapDebugUniform {
+    /// The near plane of a perspective shadow map, or zero for orthographic
+    /// shadow maps.
+    near: f32,
+    /// The distance that maps to black, for perspective shadow maps.
+    far: f32,
+    // WebGL2 requires uniform structs to be 16-byte aligned.
+    _padding: Vec2,
+}
+
+/// Holds the uniforms for every view that draws the shadow map overlay.
+#[derive(Resource, Default, Deref, DerefMut)]
+pub struct ShadowMapDebugBuffer(pub DynamicUniformBuffer<ShadowMapDebugUniform>);
+
+/// Everything the [`ShadowMapDebugNode`] needs to draw the overlay for a view.
+#[derive(Component)]
+pub struct ViewShadowMapDebug {
+    pipeline_id: CachedRenderPipelineId,
+    shadow_map_view: TextureView,
+    uniform_offset: u32,
+    corner: ShadowMapDebugCorner,
+}
+
+/// The render graph node that draws the shadow map overlay.
+#[derive(Default)]
+pub struct ShadowMapDebugNode;
+
+impl Plugin for ShadowMapDebugPlugin {
+    fn build(&self, app: &mut App) {
+        embedded_asset!(app, "shadow_debug.wgsl");
+
+        app.register_type::<ShadowMapDebugView>();
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ShadowMapDebugBuffer>()
+            .add_systems(ExtractSchedule, extract_shadow_map_debug_view)
+            .add_systems(
+                Render,
+                prepare_shadow_map_debug.in_set(RenderSystems::PrepareResources),
+            )
+            .add_render_graph_node::<ViewNodeRunner<ShadowMapDebugNode>>(
+                Core3d,
+                NodePbr::ShadowMapDebug,
+            )
+            .add_render_graph_edges(
+                Core3d,
+                (
+                    Node3d::Tonemapping,
+                    NodePbr::ShadowMapDebug,
+                    Node3d::EndMainPassPostProcessing,
+                ),
+            );
+    }
+
+    fn finish(&self, app: &mut App) {
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+
+        render_app
+            .init_resource::<ShadowMapDebugPipeline>()
+            .init_resource::<SpecializedRenderPipelines<ShadowMapDebugPipeline>>();
+    }
+}
+
+impl FromWorld for ShadowMapDebugPipeline {
+    fn from_world(world: &mut World) -> Self {
+        let render_device = world.resource::<RenderDevice>();
+
+        let bind_group_layout = render_device.create_bind_group_layout(
+            "shadow map debug bind group layout",
+            &BindGroupLayoutEntries::sequential(
+                ShaderStages::FRAGMENT,
+                (
+                    binding_types::texture_depth_2d(),
+                    binding_types::uniform_buffer::<ShadowMapDebugUniform>(true),
+                ),
+            ),
+        );
+
+        Self {
+            bind_group_layout,
+            fullscreen_shader: world.resource::<FullscreenShader>().clone(),
+            fragment_shader: load_embedded_asset!(world, "shadow_debug.wgsl"),
+        }
+    }
+}
+
+impl SpecializedRenderPipeline for ShadowMapDebugPipeline {
+    type Key = ShadowMapDebugPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("shadow map debug pipeline".into()),
+            layout: vec![self.bind_group_layout.clone()],
+            vertex: self.fullscreen_shader.to_vertex_state(),
+            fragment: Some(FragmentState {
+                shader: self.fragment_shader.clone(),
+                shader_defs: vec![],
+                entry_point: "fragment".into(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.is_hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            push_constant_ranges: vec![],
+            primitive: default(),
+            depth_stencil: None,
+            multisample: default(),
+            zero_initialize_workgroup_memory: false,
+        }
+    }
+}
+
+/// Copies the [`ShadowMapDebugView`] resource to the render world, removing it
+/// from the render world when it's removed from the main world.
+pub fn extract_shadow_map_debug_view(
+    mut commands: Commands,
+    debug_view: Extract<Option<Res<ShadowMapDebugView>>>,
+) {
+    match debug_view.as_deref() {
+        Some(debug_view) => commands.insert_resource(*debug_view),
+        None => commands.remove_resource::<ShadowMapDebugView>(),
+    }
+}
+
+/// Finds the shadow map selected by [`ShadowMapDebugView`] for each view and
+/// prepares the overlay that draws it.
+pub fn prepare_shadow_map_debug(
+    mut commands: Commands,
+    debug_view: Option<Res<ShadowMapDebugView>>,
+    pipeline_cache: Res<PipelineCache>,
+    pipeline: Res<ShadowMapDebugPipeline>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<ShadowMapDebugPipeline>>,
+    mut buffer: ResMut<ShadowMapDebugBuffer>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    views: Query<(Entity, &ExtractedView, &ViewLightEntities)>,
+    view_lights: Query<(&LightEntity, &ShadowView)>,
+    point_lights: Query<(&MainEntity, &ExtractedPointLight)>,
+    directional_lights: Query<&MainEntity, With<ExtractedDirectionalLight>>,
+) {
+    for (view_entity, ..) in &views {
+        commands.entity(view_entity).remove::<ViewShadowMapDebug>();
+    }
+
+    let Some(debug_view) = debug_view else {
+        return;
+    };
+
+    let Some(mut writer) = buffer.get_writer(views.iter().len(), &render_device, &render_queue)
+    else {
+        return;
+    };
+
+    for (view_entity, extracted_view, view_light_entities) in &views {
+        let found = view_light_entities.lights.iter().find_map(|&view_light| {
+            let (light_entity, shadow_view) = view_lights.get(view_light).ok()?;
+            let uniform = match *light_entity {
+                LightEntity::Directional {
+                    light_entity,
+                    cascade_index,
+                } => {
+                    let main_entity = directional_lights.get(light_entity).ok()?;
+                    if main_entity.id() != debug_view.light || cascade_index != debug_view.cascade {
+                        return None;
+                    }
+                    ShadowMapDebugUniform {
+                        near: 0.0,
+                        far: 1.0,
+                        _padding: Vec2::ZERO,
+                    }
+                }
+                LightEntity::Point {
+                    light_entity,
+                    face_index,
+                } => {
+                    let (main_entity, light) = point_lights.get(light_entity).ok()?;
+                    if main_entity.id() != debug_view.light || face_index != debug_view.cascade {
+                        return None;
+                    }
+                    ShadowMapDebugUniform {
+                        near: light.shadow_map_near_z,
+                        far: light.range,
+                        _padding: Vec2::ZERO,
+                    }
+                }
+                LightEntity::Spot { light_entity } => {
+                    let (main_entity, light) = point_lights.get(light_entity).ok()?;
+                    if main_entity.id() != debug_view.light {
+                        return None;
+                    }
+                    ShadowMapDebugUniform {
+                        near: light.shadow_map_near_z,
+                        far: light.range,
+                        _padding: Vec2::ZERO,
+                    }
+                }
+            };
+            Some((shadow_view.depth_attachment.view.clone(), uniform))
+        });
+
+        let Some((shadow_map_view, uniform)) = found else {
+            continue;
+        };
+
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            ShadowMapDebugPipelineKey {
+                is_hdr: extracted_view.hdr,
+            },
+        );
+
+        commands.entity(view_entity).insert(ViewShadowMapDebug {
+            pipeline_id,
+            shadow_map_view,
+            uniform_offset: writer.write(&uniform),
+            corner: debug_view.corner,
+        });
+    }
+}
+
+impl ViewNode for ShadowMapDebugNode {
+    type ViewQuery = (Read<ViewTarget>, Read<ViewShadowMapDebug>);
+
+    fn run<'w>(
+        &self,
+        _: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        (view_target, shadow_map_debug): QueryItem<'w, '_, Self::ViewQuery>,
+        world: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let Some(render_pipeline) =
+            pipeline_cache.get_render_pipeline(shadow_map_debug.pipeline_id)
+        else {
+            return Ok(());
+        };
+
+        let pipeline = world.resource::<ShadowMapDebugPipeline>();
+        let Some(uniform_binding) = world.resource::<ShadowMapDebugBuffer>().binding() else {
+            return Ok(());
+        };
+
+        let bind_group = render_context.render_device().create_bind_group(
+            "shadow map debug bind group",
+            &pipeline.bind_group_layout,
+            &BindGroupEntries::sequential((&shadow_map_debug.shadow_map_view, uniform_binding)),
+        );
+
+        // Draw the shadow map as a square a third of the size of the smaller
+        // screen dimension.
+        let target_size = view_target.main_texture().size();
+        let size = (target_size.width.min(target_size.height) / 3) as f32;
+        let (width, height) = (target_size.width as f32, target_size.height as f32);
+        let (x, y) = match shadow_map_debug.corner {
+            ShadowMapDebugCorner::TopLeft => (0.0, 0.0),
+            ShadowMapDebugCorner::TopRight => (width - size, 0.0),
+            ShadowMapDebugCorner::BottomLeft => (0.0, height - size),
+            ShadowMapDebugCorner::BottomRight => (width - size, height - size),
+        };
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("shadow map debug pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: view_target.main_texture_view(),
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+
+        render_pass.set_render_pipeline(render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[shadow_map_debug.uniform_offset]);
+        render_pass.set_viewport(x, y, size, size, 0.0, 1.0);
+        render_pass.draw(0..3, 0..1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,34 @@
+// Draws a single shadow map on top of the rendered image, for debugging.
+
+#import bevy_core_pipeline::fullscreen_vertex_shader::FullscreenVertexOutput
+
+struct ShadowMapDebugSettings {
+    // The near plane of a perspective shadow map, or zero for orthographic
+    // shadow maps.
+    near: f32,
+    // The distance that maps to black, for perspective shadow maps.
+    far: f32,
+    // WebGL2 requires uniform structs to be 16-byte aligned.
+    _padding: vec2<f32>,
+}
+
+@group(0) @binding(0) var shadow_map: texture_depth_2d;
+@group(0) @binding(1) var<uniform> settings: ShadowMapDebugSettings;
+
+@fragment
+fn fragment(in: FullscreenVertexOutput) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(shadow_map));
+    let texel = vec2<i32>(clamp(in.uv * size, vec2(0.0), size - 1.0));
+    let depth = textureLoad(shadow_map, texel, 0);
+
+    // Shadow maps use reverse Z, so nearer surfaces have larger depth values.
+    var value = depth;
+    if (settings.near > 0.0) {
+        // Perspective shadow maps are infinite reverse-Z projections, so the
+        // view-space distance is `near / depth`.
+        let distance = settings.near / max(depth, 1e-7);
+        value = 1.0 - saturate(distance / settings.far);
+    }
+
+    return vec4(vec3(value), 1.0);
+}
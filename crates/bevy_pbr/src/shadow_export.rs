@@ -0,0 +1,280 @@
+//! Copies the shadow maps of lights into user-provided images, so that other
+//! passes and materials can sample them.
+
+use bevy_asset::Handle;
+use bevy_ecs::{
+    component::Component,
+    entity::{Entity, EntityHashSet},
+    query::QueryItem,
+    reflect::ReflectComponent,
+    system::{lifetimeless::Read, Commands, Local, Query, Res},
+    world::World,
+};
+use bevy_image::Image;
+use bevy_reflect::{std_traits::ReflectDefault, Reflect};
+use bevy_render::{
+    extract_component::ExtractComponent,
+    render_asset::RenderAssets,
+    render_graph::{NodeRunError, RenderGraphContext, ViewNode},
+    render_resource::{
+        Extent3d, Origin3d, TexelCopyTextureInfo, Texture, TextureAspect, TextureFormat,
+        TextureUsages,
+    },
+    renderer::RenderContext,
+    texture::GpuImage,
+};
+use bevy_utils::once;
+use tracing::warn;
+
+use crate::{LightEntity, ShadowView, ViewLightEntities, ViewShadowBindings};
+
+/// Copies the shadow map of the light this component is on into
+/// [`target`](Self::target) every frame, right after the shadow passes, so
+/// that other passes and materials can sample it.
+///
+/// The target must be a 2D image in the format of the shadow maps, which is
+/// `Depth32Float` unless a [`ShadowDepthFormat`](crate::ShadowDepthFormat)
+/// selects another, with the [`TextureUsages::COPY_DST`] usage, plus
+/// [`TextureUsages::TEXTURE_BINDING`] to sample it. It must be exactly the
+/// size of the light's shadow map: the
+/// [`PointLightShadowMap`](crate::PointLightShadowMap) size for point lights,
+/// and the [`DirectionalLightShadowMap`](crate::DirectionalLightShadowMap) size
+/// for spot and directional lights. Otherwise, a warning is logged and nothing
+/// is copied.
+///
+/// The copied depth follows the shadow map conventions. It's reverse Z, so
+/// nearer surfaces have larger values. Point and spot light shadow maps use an
+/// infinite perspective projection, so the distance from the light along the
+/// view direction is `shadow_map_near_z / depth`. Directional light shadow maps
+/// are orthographic, so the depth is linear across the depth range of the
+/// cascade, unless the cascade uses
+/// [`logarithmic_depth`](crate::CascadeShadowConfig::logarithmic_depth).
+///
+/// For directional lights, [`cascade`](Self::cascade) selects the cascade to
+/// copy, and for point lights it selects the cube map face. It's ignored for
+/// spot lights. If several cameras render the cascades of a directional light,
+/// the cascade of the first camera is copied. When the shadow map isn't
+/// rendered on a frame, for example because the light isn't visible, the
+/// target keeps its previous contents.
+#[derive(Component, Clone, Debug, Default, ExtractComponent, Reflect)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct ExportShadowMap {
+    /// The image that the shadow map is copied into.
+    pub target: Handle<Image>,
+    /// The cascade (for directional lights) or cube face (for point lights)
+    /// to copy.
+    pub cascade: usize,
+}
+
+/// The shadow map copies that the [`ShadowMapExportNode`] makes for a view.
+#[derive(Component, Default)]
+pub struct ViewShadowMapExports(Vec<ShadowMapExport>);
+
+/// A copy of one layer of a shadow map texture into an [`ExportShadowMap`]
+/// target.
+struct ShadowMapExport {
+    source: Texture,
+    array_layer: u32,
+    target: Texture,
+    size: Extent3d,
+}
+
+/// The render graph node that copies shadow maps into the targets of
+/// [`ExportShadowMap`] components.
+#[derive(Default)]
+pub struct ShadowMapExportNode;
+
+/// Finds the shadow views of the lights with an [`ExportShadowMap`] and
+/// prepares the copies of their shadow maps.
+///
+/// Each light is copied at most once, by the first view that renders its
+/// shadow map.
+pub fn prepare_shadow_map_exports(
+    mut commands: Commands,
+    images: Res<RenderAssets<GpuImage>>,
+    views: Query<(Entity, &ViewLightEntities, &ViewShadowBindings)>,
+    view_lights: Query<(&LightEntity, &ShadowView)>,
+    exports: Query<&ExportShadowMap>,
+    mut exported_lights: Local<EntityHashSet>,
+) {
+    exported_lights.clear();
+
+    for (view_entity, view_light_entities, shadow_bindings) in &views {
+        let mut view_exports = Vec::new();
+
+        for &view_light in &view_light_entities.lights {
+            let Ok((light_entity, shadow_view)) = view_lights.get(view_light) else {
+                continue;
+            };
+            let (light_entity, index, source) = match *light_entity {
+                LightEntity::Directional {
+                    light_entity,
+                    cascade_index,
+                } => (
+                    light_entity,
+                    Some(cascade_index),
+                    &shadow_bindings.directional_light_depth_texture,
+                ),
+                LightEntity::Point {
+                    light_entity,
+                    face_index,
+                } => (
+                    light_entity,
+                    Some(face_index),
+                    &shadow_bindings.point_light_depth_texture,
+                ),
+                LightEntity::Spot { light_entity } => (
+                    light_entity,
+                    None,
+                    &shadow_bindings.directional_light_depth_texture,
+                ),
+            };
+
+            let Ok(export) = exports.get(light_entity) else {
+                continue;
+            };
+            if index.is_some_and(|index| index != export.cascade)
+                || exported_lights.contains(&light_entity)
+            {
+                continue;
+            }
+            let Some(target) = images.get(&export.target) else {
+                continue;
+            };
+
+            let size = Extent3d {
+                width: source.width(),
+                height: source.height(),
+                depth_or_array_layers: 1,
+            };
+            if let Some(error) = shadow_map_export_error(
+                target.texture_format,
+                target.texture.usage(),
+                target.size,
+                source.format(),
+                size,
+            ) {
+                once!(warn!("Can't export a shadow map: {error}"));
+                continue;
+            }
+
+            exported_lights.insert(light_entity);
+            view_exports.push(ShadowMapExport {
+                source: source.clone(),
+                array_layer: shadow_view.array_layer,
+                target: target.texture.clone(),
+                size,
+            });
+        }
+
+        if view_exports.is_empty() {
+            commands
+                .entity(view_entity)
+                .remove::<ViewShadowMapExports>();
+        } else {
+            commands
+                .entity(view_entity)
+                .insert(ViewShadowMapExports(view_exports));
+        }
+    }
+}
+
+/// Returns why an image with the given format, usage and size can't receive a
+/// copy of a shadow map with the format `shadow_map_format` and size
+/// `shadow_map_size`, or [`None`] if it can.
+fn shadow_map_export_error(
+    format: TextureFormat,
+    usage: TextureUsages,
+    size: Extent3d,
+    shadow_map_format: TextureFormat,
+    shadow_map_size: Extent3d,
+) -> Option<String> {
+    if format != shadow_map_format {
+        Some(format!(
+            "the target has the format {format:?} instead of {shadow_map_format:?}"
+        ))
+    } else if !usage.contains(TextureUsages::COPY_DST) {
+        Some("the target doesn't have the `COPY_DST` usage".into())
+    } else if (size.width, size.height) != (shadow_map_size.width, shadow_map_size.height) {
+        Some(format!(
+            "the target is {}x{} instead of the shadow map size of {}x{}",
+            size.width, size.height, shadow_map_size.width, shadow_map_size.height
+        ))
+    } else {
+        None
+    }
+}
+
+impl ViewNode for ShadowMapExportNode {
+    type ViewQuery = Read<ViewShadowMapExports>;
+
+    fn run<'w>(
+        &self,
+        _: &mut RenderGraphContext,
+        render_context: &mut RenderContext<'w>,
+        exports: QueryItem<'w, '_, Self::ViewQuery>,
+        _: &'w World,
+    ) -> Result<(), NodeRunError> {
+        let command_encoder = render_context.command_encoder();
+        for export in &exports.0 {
+            command_encoder.copy_texture_to_texture(
+                TexelCopyTextureInfo {
+                    texture: &export.source,
+                    mip_level: 0,
+                    origin: Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: export.array_layer,
+                    },
+                    aspect: TextureAspect::All,
+                },
+                export.target.as_image_copy(),
+                export.size,
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shadow_map_export_requires_matching_target() {
+        let size = Extent3d {
+            width: 1024,
+            height: 1024,
+            depth_or_array_layers: 1,
+        };
+        let usage = TextureUsages::COPY_DST | TextureUsages::TEXTURE_BINDING;
+        let format = TextureFormat::Depth32Float;
+
+        assert_eq!(
+            shadow_map_export_error(format, usage, size, format, size),
+            None
+        );
+        assert!(
+            shadow_map_export_error(TextureFormat::R32Float, usage, size, format, size).is_some()
+        );
+        assert!(shadow_map_export_error(
+            format,
+            TextureUsages::TEXTURE_BINDING,
+            size,
+            format,
+            size
+        )
+        .is_some());
+        assert!(
+            shadow_map_export_error(format, usage, size, TextureFormat::Depth16Unorm, size)
+                .is_some()
+        );
+        let smaller = Extent3d {
+            width: 512,
+            height: 512,
+            depth_or_array_layers: 1,
+        };
+        assert!(shadow_map_export_error(format, usage, smaller, format, size).is_some());
+    }
+}
@@ -39,7 +39,11 @@
     position_view_to_world
 }
 #import bevy_pbr::clustered_forward as clustering
-#import bevy_pbr::lighting::getDistanceAttenuation;
+#import bevy_pbr::lighting::{
+    getDistanceAttenuation,
+    spot_light_aspect_ratio,
+    spot_light_cone_cos
+}
 
 // The GPU version of [`VolumetricFog`]. See the comments in
 // `volumetric_fog/mod.rs` for descriptions of the fields here.
@@ -360,8 +364,7 @@ fn fragment(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
 
                 // calculate attenuation based on filament formula https://google.github.io/filament/Filament.html#listing_glslpunctuallight
                 // spot_scale and spot_offset have been precomputed
-                // note we normalize here to get "l" from the filament listing. spot_dir is already normalized
-                let cd = dot(-spot_dir, normalize(light_to_frag));
+                let cd = spot_light_cone_cos(spot_dir, -light_to_frag, (*light).flags);
                 let attenuation = saturate(cd * (*light).light_custom_data.z + (*light).light_custom_data.w);
                 let spot_attenuation = attenuation * attenuation;
 
@@ -470,7 +473,9 @@ fn fetch_spot_shadow_without_normal(light_id: u32, frag_position: vec4<f32>) ->
     // divide xy by perspective matrix "f" and by -projected.z (projected.z is -projection matrix's w)
     // to get ndc coordinates
     let f_div_minus_z = 1.0 / ((*light).spot_light_tan_angle * -projected_position.z);
-    let shadow_xy_ndc = projected_position.xy * f_div_minus_z;
+    // elliptical spot lights cover a wider or narrower angle along Y than along X
+    let aspect_ratio = vec2(1.0, spot_light_aspect_ratio((*light).flags));
+    let shadow_xy_ndc = projected_position.xy * f_div_minus_z / aspect_ratio;
     // convert to uv coordinates
     let shadow_uv = shadow_xy_ndc * vec2<f32>(0.5, -0.5) + vec2<f32>(0.5, 0.5);
 
@@ -0,0 +1,119 @@
+//! Draws the range of every light as a translucent volume, which is useful for
+//! laying out lights in a level.
+
+use std::f32::consts::PI;
+
+use bevy::{pbr::DrawLightInfluenceVolumes, prelude::*};
+
+fn main() {
+    println!(
+        "Controls:
+    Space  - toggle the light influence volumes"
+    );
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .insert_resource(DrawLightInfluenceVolumes)
+        .add_systems(Startup, setup)
+        .add_systems(Update, (toggle_volumes, move_lights))
+        .run();
+}
+
+/// Marks the point lights that move back and forth, so that the volumes can be
+/// seen following them.
+#[derive(Component)]
+struct Moving;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(40.0, 40.0))),
+        MeshMaterial3d(materials.add(Color::WHITE)),
+    ));
+
+    let cube = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+    let material = materials.add(Color::srgb(0.8, 0.7, 0.6));
+    for x in -2..=2 {
+        commands.spawn((
+            Mesh3d(cube.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_xyz(x as f32 * 4.0, 0.5, 0.0),
+        ));
+    }
+
+    // A dim and a bright point light, which are drawn with different opacities.
+    for (x, intensity, range) in [(-6.0, 100_000.0, 3.0), (-2.0, 2_000_000.0, 5.0)] {
+        commands.spawn((
+            PointLight {
+                intensity,
+                range,
+                ..default()
+            },
+            Transform::from_xyz(x, 2.0, 3.0),
+            Moving,
+        ));
+    }
+
+    // A round and an elliptical spot light.
+    commands.spawn((
+        SpotLight {
+            intensity: 1_000_000.0,
+            range: 6.0,
+            outer_angle: PI / 6.0,
+            inner_angle: PI / 8.0,
+            ..default()
+        },
+        Transform::from_xyz(2.0, 5.0, 2.0).looking_at(Vec3::new(2.0, 0.0, 0.0), Vec3::Y),
+    ));
+    commands.spawn((
+        SpotLight {
+            intensity: 1_000_000.0,
+            range: 8.0,
+            outer_angle: PI / 4.0,
+            outer_angle_y: Some(PI / 12.0),
+            inner_angle: PI / 6.0,
+            ..default()
+        },
+        Transform::from_xyz(7.0, 5.0, 2.0).looking_at(Vec3::new(7.0, 0.0, -1.0), Vec3::Y),
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            illuminance: light_consts::lux::OVERCAST_DAY,
+            ..default()
+        },
+        Transform::from_xyz(0.0, 6.0, -4.0).with_rotation(Quat::from_euler(
+            EulerRot::ZYX,
+            0.0,
+            PI / 4.,
+            -PI / 4.,
+        )),
+    ));
+
+    commands.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(0.0, 10.0, 16.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
+    ));
+}
+
+fn toggle_volumes(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    volumes: Option<Res<DrawLightInfluenceVolumes>>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        if volumes.is_some() {
+            commands.remove_resource::<DrawLightInfluenceVolumes>();
+        } else {
+            commands.insert_resource(DrawLightInfluenceVolumes);
+        }
+    }
+}
+
+fn move_lights(time: Res<Time>, mut lights: Query<&mut Transform, With<Moving>>) {
+    for mut transform in &mut lights {
+        transform.translation.z = 3.0 * ops::sin(time.elapsed_secs());
+    }
+}
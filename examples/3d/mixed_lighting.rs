@@ -128,6 +128,7 @@ fn main() {
             color: ClearColor::default().0,
             brightness: 10000.0,
             affects_lightmapped_meshes: true,
+            ..default()
         })
         .init_resource::<AppStatus>()
         .add_event::<WidgetClickEvent<LightingMode>>()
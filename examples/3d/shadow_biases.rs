@@ -224,6 +224,11 @@ fn cycle_filter_methods(
                     ShadowFilteringMethod::Temporal
                 }
                 ShadowFilteringMethod::Temporal => {
+                    filter_method_string = "Vogel".to_string();
+                    // Use the same number of taps as `Gaussian` for comparison.
+                    ShadowFilteringMethod::Vogel { samples: 9 }
+                }
+                ShadowFilteringMethod::Vogel { .. } => {
                     filter_method_string = "Hardware2x2".to_string();
                     ShadowFilteringMethod::Hardware2x2
                 }
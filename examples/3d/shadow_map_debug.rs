@@ -0,0 +1,107 @@
+//! Draws the shadow map of a light in the corner of the screen, which is useful
+//! for debugging shadow artifacts.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    pbr::{CascadeShadowConfigBuilder, ShadowMapDebugView},
+    prelude::*,
+};
+
+fn main() {
+    println!(
+        "Controls:
+    Space  - toggle the shadow map overlay
+    1-4    - show the given shadow cascade"
+    );
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_systems(Startup, setup)
+        .add_systems(Update, update_debug_view)
+        .run();
+}
+
+/// The directional light whose shadow maps are shown.
+#[derive(Resource)]
+struct Sun(Entity);
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    let cube = meshes.add(Cuboid::new(1.0, 1.0, 1.0));
+    let material = materials.add(Color::srgb(0.8, 0.7, 0.6));
+
+    // A row of cubes stretching away from the camera, so that each cascade has
+    // something in it.
+    for i in 0..20 {
+        commands.spawn((
+            Mesh3d(cube.clone()),
+            MeshMaterial3d(material.clone()),
+            Transform::from_xyz(0.0, 0.5, -4.0 * i as f32),
+        ));
+    }
+
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(200.0, 200.0))),
+        MeshMaterial3d(materials.add(Color::WHITE)),
+    ));
+
+    let sun = commands
+        .spawn((
+            DirectionalLight {
+                illuminance: light_consts::lux::OVERCAST_DAY,
+                shadows_enabled: true,
+                ..default()
+            },
+            Transform::from_rotation(Quat::from_euler(EulerRot::ZYX, 0.0, PI / 4., -PI / 4.)),
+            CascadeShadowConfigBuilder {
+                num_cascades: 4,
+                first_cascade_far_bound: 5.0,
+                maximum_distance: 80.0,
+                ..default()
+            }
+            .build(),
+        ))
+        .id();
+
+    commands.insert_resource(Sun(sun));
+    commands.insert_resource(ShadowMapDebugView::new(sun, 0));
+
+    commands.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(4.0, 3.0, 6.0).looking_at(Vec3::new(0.0, 0.5, -10.0), Vec3::Y),
+    ));
+}
+
+fn update_debug_view(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    sun: Res<Sun>,
+    debug_view: Option<ResMut<ShadowMapDebugView>>,
+) {
+    if input.just_pressed(KeyCode::Space) {
+        if debug_view.is_some() {
+            commands.remove_resource::<ShadowMapDebugView>();
+        } else {
+            commands.insert_resource(ShadowMapDebugView::new(sun.0, 0));
+        }
+        return;
+    }
+
+    let Some(mut debug_view) = debug_view else {
+        return;
+    };
+
+    for (key, cascade) in [
+        (KeyCode::Digit1, 0),
+        (KeyCode::Digit2, 1),
+        (KeyCode::Digit3, 2),
+        (KeyCode::Digit4, 3),
+    ] {
+        if input.just_pressed(key) {
+            debug_view.cascade = cascade;
+        }
+    }
+}
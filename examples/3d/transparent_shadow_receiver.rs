@@ -0,0 +1,93 @@
+//! Shows shadows falling on a transparent surface, such as glass or water.
+//!
+//! Transparent materials sample the shadow maps in their forward pass just like
+//! opaque ones, and [`NotShadowReceiver`] opts them out.
+
+use std::f32::consts::PI;
+
+use bevy::{
+    pbr::{NotShadowCaster, NotShadowReceiver},
+    prelude::*,
+};
+
+fn main() {
+    println!(
+        "Controls:
+    Space  - toggle shadows on the glass pane"
+    );
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_systems(Startup, setup)
+        .add_systems(Update, toggle_shadow_receiver)
+        .run();
+}
+
+/// Marks the transparent pane that shadows fall across.
+#[derive(Component)]
+struct GlassPane;
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    // The ground.
+    commands.spawn((
+        Mesh3d(meshes.add(Plane3d::default().mesh().size(20.0, 20.0))),
+        MeshMaterial3d(materials.add(Color::srgb(0.3, 0.5, 0.3))),
+    ));
+
+    // A glass pane raised above the ground. It doesn't cast shadows itself, so
+    // that only the shadow of the cube falls across it.
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(4.0, 0.05, 4.0))),
+        MeshMaterial3d(materials.add(StandardMaterial {
+            base_color: Color::srgba(0.6, 0.8, 1.0, 0.4),
+            alpha_mode: AlphaMode::Blend,
+            perceptual_roughness: 0.1,
+            ..default()
+        })),
+        Transform::from_xyz(0.0, 1.0, 0.0),
+        NotShadowCaster,
+        GlassPane,
+    ));
+
+    // A cube casting a shadow across the pane.
+    commands.spawn((
+        Mesh3d(meshes.add(Cuboid::new(1.0, 1.0, 1.0))),
+        MeshMaterial3d(materials.add(Color::srgb(0.8, 0.7, 0.6))),
+        Transform::from_xyz(0.0, 3.0, 0.0),
+    ));
+
+    commands.spawn((
+        DirectionalLight {
+            illuminance: light_consts::lux::OVERCAST_DAY,
+            shadows_enabled: true,
+            ..default()
+        },
+        Transform::from_rotation(Quat::from_euler(EulerRot::ZYX, 0.0, PI / 8., -PI / 3.)),
+    ));
+
+    commands.spawn((
+        Camera3d::default(),
+        Transform::from_xyz(-3.0, 5.0, 7.0).looking_at(Vec3::new(0.0, 1.0, 0.0), Vec3::Y),
+    ));
+}
+
+fn toggle_shadow_receiver(
+    mut commands: Commands,
+    input: Res<ButtonInput<KeyCode>>,
+    panes: Query<(Entity, Has<NotShadowReceiver>), With<GlassPane>>,
+) {
+    if !input.just_pressed(KeyCode::Space) {
+        return;
+    }
+
+    for (entity, not_shadow_receiver) in &panes {
+        if not_shadow_receiver {
+            commands.entity(entity).remove::<NotShadowReceiver>();
+        } else {
+            commands.entity(entity).insert(NotShadowReceiver);
+        }
+    }
+}